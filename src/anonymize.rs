@@ -0,0 +1,144 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::random::Rng;
+use crate::stats;
+use crate::EducationData;
+
+// A shareable stand-in for the observations: a random subset of countries,
+// each value jittered by noise scaled to its series' spread, clamped to the
+// series' observed range and rounded to hundredths, optionally with
+// countries renamed. Whole countries are kept or dropped so each one's time
+// series stays intact, and the noise is small next to the differences
+// between countries, so the graph and its clusters come out much the same.
+pub struct Anonymizer {
+    // Share of countries kept, in (0, 1]
+    pub fraction: f64,
+    // Noise standard deviation as a multiple of the series' own
+    pub jitter: f64,
+    // Replace names with "Country 1", "Country 2", ... in a random order
+    pub pseudonyms: bool,
+}
+
+impl Default for Anonymizer {
+    fn default() -> Anonymizer {
+        Anonymizer {
+            fraction: 0.25,
+            jitter: 0.05,
+            pseudonyms: false,
+        }
+    }
+}
+
+struct Spread {
+    std_dev: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Anonymizer {
+    pub fn apply(&self, data: &[EducationData], rng: &mut Rng) -> Vec<EducationData> {
+        let mut countries: Vec<&str> = data
+            .iter()
+            .map(|record| record.country_or_area.as_str())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        rng.shuffle(&mut countries);
+        let keep = ((countries.len() as f64 * self.fraction).ceil() as usize).min(countries.len());
+        let names: BTreeMap<&str, String> = countries[..keep]
+            .iter()
+            .enumerate()
+            .map(|(index, &country)| {
+                let name = if self.pseudonyms {
+                    format!("Country {}", index + 1)
+                } else {
+                    country.to_string()
+                };
+                (country, name)
+            })
+            .collect();
+
+        let mut values: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+        for record in data {
+            if let Some(value) = record.value {
+                values.entry(&record.series).or_default().push(value);
+            }
+        }
+        let spreads: BTreeMap<&str, Spread> = values
+            .into_iter()
+            .map(|(series, values)| {
+                let spread = Spread {
+                    std_dev: stats::std_dev(&values).unwrap_or(0.0),
+                    min: values.iter().copied().fold(f64::INFINITY, f64::min),
+                    max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                };
+                (series, spread)
+            })
+            .collect();
+
+        data.iter()
+            .filter_map(|record| {
+                let name = names.get(record.country_or_area.as_str())?;
+                let value = record.value.map(|value| {
+                    let spread = &spreads[record.series.as_str()];
+                    let noisy = value + rng.normal() * self.jitter * spread.std_dev;
+                    // Extra digits would only make the noise obvious
+                    (noisy.clamp(spread.min, spread.max) * 100.0).round() / 100.0
+                });
+                Some(EducationData {
+                    country_or_area: name.clone(),
+                    year: record.year,
+                    indicator: record.indicator.clone(),
+                    series: record.series.clone(),
+                    value,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_sample_jitter_and_rename() {
+        let data: Vec<EducationData> = (0..20)
+            .flat_map(|country| {
+                (2010..2015).map(move |year| {
+                    let value = (year != 2012).then_some(country as f64 * 5.0);
+                    record(&format!("C{:02}", country), "primary", year, value)
+                })
+            })
+            .collect();
+        let anonymizer = Anonymizer {
+            pseudonyms: true,
+            ..Anonymizer::default()
+        };
+        let shared = anonymizer.apply(&data, &mut Rng::new(1));
+
+        // 5 of 20 countries, all of their years, renamed
+        assert_eq!(shared.len(), 25);
+        let names: BTreeSet<&str> = shared.iter().map(|r| r.country_or_area.as_str()).collect();
+        assert_eq!(names.len(), 5);
+        assert!(names.iter().all(|name| name.starts_with("Country ")));
+        // Missing values stay missing; others move a little and stay in range
+        assert_eq!(shared.iter().filter(|r| r.value.is_none()).count(), 5);
+        assert!(shared
+            .iter()
+            .filter_map(|r| r.value)
+            .all(|value| (0.0..=95.0).contains(&value)));
+        assert!(shared
+            .iter()
+            .filter_map(|r| r.value)
+            .any(|v| v.fract() != 0.0));
+
+        // The same seed gives the same output
+        let again = anonymizer.apply(&data, &mut Rng::new(1));
+        assert!(shared
+            .iter()
+            .zip(&again)
+            .all(|(a, b)| a.country_or_area == b.country_or_area && a.value == b.value));
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::cluster::Clustering;
+use crate::{EducationData, Graph};
+
+// Every pipeline stage can persist its output so later stages can be rerun on
+// their own. Artifacts are a small little-endian binary format: an 8-byte
+// magic tag identifying the kind of artifact, a format version, then the
+// payload.
+//
+// v2: clusterings record the node names they refer to.
+const FORMAT_VERSION: u32 = 2;
+const DATASET_MAGIC: &[u8; 8] = b"DS210DAT";
+const GRAPH_MAGIC: &[u8; 8] = b"DS210GRF";
+const CLUSTERS_MAGIC: &[u8; 8] = b"DS210CLU";
+const STORE_MAGIC: &[u8; 8] = b"DS210OBS";
+
+pub fn save_dataset(path: &str, data: &[EducationData]) -> io::Result<()> {
+    let mut writer = create(path, DATASET_MAGIC)?;
+    write_u64(&mut writer, data.len() as u64)?;
+    for record in data {
+        write_observation(&mut writer, record)?;
+    }
+    writer.flush()
+}
+
+pub fn load_dataset(path: &str) -> io::Result<Vec<EducationData>> {
+    let mut reader = open(path, DATASET_MAGIC, "dataset")?;
+    let count = read_u64(&mut reader)? as usize;
+
+    let mut data = Vec::with_capacity(count);
+    for _ in 0..count {
+        data.push(read_observation(&mut reader)?);
+    }
+
+    Ok(data)
+}
+
+// Observation stores are an append-only log: the header, then records until
+// the end of the file. Later records for the same key supersede earlier ones
+// (see `store::Store`).
+pub fn read_store(path: &str) -> io::Result<Vec<EducationData>> {
+    let mut reader = open(path, STORE_MAGIC, "observation store")?;
+    let mut data = Vec::new();
+    while !reader.fill_buf()?.is_empty() {
+        let record = read_observation(&mut reader).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}: truncated record after {} records ({})",
+                    path,
+                    data.len(),
+                    e
+                ),
+            )
+        })?;
+        data.push(record);
+    }
+    Ok(data)
+}
+
+// Append records to a store, creating it if needed.
+pub fn append_store(path: &str, records: &[&EducationData]) -> io::Result<()> {
+    let mut writer = if Path::new(path).exists() {
+        // Check it really is a store before appending to it
+        open(path, STORE_MAGIC, "observation store")?;
+        BufWriter::new(OpenOptions::new().append(true).open(path)?)
+    } else {
+        create(path, STORE_MAGIC)?
+    };
+    for record in records {
+        write_observation(&mut writer, record)?;
+    }
+    writer.flush()
+}
+
+// Replace a store's log with the given records, via a temporary file so an
+// interrupted rewrite leaves the old log intact.
+pub fn rewrite_store(path: &str, records: &[&EducationData]) -> io::Result<()> {
+    let temp_path = format!("{}.tmp", path);
+    let mut writer = create(&temp_path, STORE_MAGIC)?;
+    for record in records {
+        write_observation(&mut writer, record)?;
+    }
+    writer.flush()?;
+    drop(writer);
+    fs::rename(&temp_path, path)
+}
+
+fn write_observation(writer: &mut impl Write, record: &EducationData) -> io::Result<()> {
+    write_str(writer, &record.country_or_area)?;
+    writer.write_all(&record.year.to_le_bytes())?;
+    write_str(writer, &record.indicator)?;
+    write_str(writer, &record.series)?;
+    match record.value {
+        Some(value) => {
+            writer.write_all(&[1])?;
+            write_f64(writer, value)
+        }
+        None => writer.write_all(&[0]),
+    }
+}
+
+fn read_observation(reader: &mut impl Read) -> io::Result<EducationData> {
+    let country_or_area = read_str(reader)?;
+    let mut year = [0u8; 4];
+    reader.read_exact(&mut year)?;
+    let indicator = read_str(reader)?;
+    let series = read_str(reader)?;
+    let mut has_value = [0u8; 1];
+    reader.read_exact(&mut has_value)?;
+    let value = match has_value[0] {
+        0 => None,
+        _ => Some(read_f64(reader)?),
+    };
+
+    Ok(EducationData {
+        country_or_area,
+        year: u32::from_le_bytes(year),
+        indicator,
+        series,
+        value,
+    })
+}
+
+pub fn save_graph(path: &str, graph: &Graph) -> io::Result<()> {
+    let mut writer = create(path, GRAPH_MAGIC)?;
+    write_u64(&mut writer, graph.nodes.len() as u64)?;
+    for node in &graph.nodes {
+        write_str(&mut writer, node)?;
+    }
+    for row in &graph.adjacency_matrix {
+        for &weight in row {
+            write_f64(&mut writer, weight)?;
+        }
+    }
+    writer.flush()
+}
+
+pub fn load_graph(path: &str) -> io::Result<Graph> {
+    let mut reader = open(path, GRAPH_MAGIC, "graph")?;
+    let node_count = read_u64(&mut reader)? as usize;
+
+    let mut nodes = Vec::with_capacity(node_count);
+    for _ in 0..node_count {
+        nodes.push(read_str(&mut reader)?);
+    }
+
+    let mut adjacency_matrix = Vec::with_capacity(node_count);
+    for _ in 0..node_count {
+        let mut row = Vec::with_capacity(node_count);
+        for _ in 0..node_count {
+            row.push(read_f64(&mut reader)?);
+        }
+        adjacency_matrix.push(row);
+    }
+
+    Ok(Graph {
+        nodes,
+        adjacency_matrix,
+    })
+}
+
+// Clusterings store member names alongside the indices so they can be
+// reapplied to a graph built from a different snapshot.
+pub fn save_clusters(path: &str, clusters: &[Vec<usize>], graph: &Graph) -> io::Result<()> {
+    debug_assert!(clusters.validate(graph).is_ok());
+    let mut writer = create(path, CLUSTERS_MAGIC)?;
+    write_u64(&mut writer, clusters.len() as u64)?;
+    for cluster in clusters {
+        write_u64(&mut writer, cluster.len() as u64)?;
+        for &node_index in cluster {
+            write_str(&mut writer, &graph.nodes[node_index])?;
+        }
+    }
+    writer.flush()
+}
+
+// Load a clustering and map its members onto `graph` by name. Members the
+// graph does not have are dropped, but clusters keep their positions (even
+// when emptied) so cluster indices stay comparable across snapshots.
+pub fn load_clusters(path: &str, graph: &Graph) -> io::Result<Vec<Vec<usize>>> {
+    load_clusters_of(path, &graph.nodes)
+}
+
+// A clustering over any list of countries, such as the rows of a feature
+// matrix; members not among them are dropped.
+pub fn load_clusters_of(path: &str, nodes: &[String]) -> io::Result<Vec<Vec<usize>>> {
+    let mut reader = open(path, CLUSTERS_MAGIC, "clustering")?;
+    let cluster_count = read_u64(&mut reader)? as usize;
+    let node_indices: HashMap<&str, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (node.as_str(), index))
+        .collect();
+
+    let mut clusters = Vec::with_capacity(cluster_count);
+    for _ in 0..cluster_count {
+        let size = read_u64(&mut reader)? as usize;
+        let mut cluster = Vec::with_capacity(size);
+        for _ in 0..size {
+            let name = read_str(&mut reader)?;
+            if let Some(&node_index) = node_indices.get(name.as_str()) {
+                cluster.push(node_index);
+            }
+        }
+        clusters.push(cluster);
+    }
+
+    clusters
+        .validate_over(nodes)
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", path, error)))?;
+    Ok(clusters)
+}
+
+// Check whether a file starts with the dataset magic, so `build --from` can
+// accept either a cached dataset or a raw CSV.
+pub fn is_dataset(path: &str) -> bool {
+    has_magic(path, DATASET_MAGIC)
+}
+
+pub fn is_store(path: &str) -> bool {
+    has_magic(path, STORE_MAGIC)
+}
+
+fn has_magic(path: &str, expected: &[u8; 8]) -> bool {
+    let mut magic = [0u8; 8];
+    File::open(Path::new(path))
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .map(|_| &magic == expected)
+        .unwrap_or(false)
+}
+
+fn create(path: &str, magic: &[u8; 8]) -> io::Result<BufWriter<File>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(magic)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    Ok(writer)
+}
+
+fn open(path: &str, magic: &[u8; 8], kind: &str) -> io::Result<BufReader<File>> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut header = [0u8; 12];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| not_an_artifact(path, kind))?;
+    if &header[..8] != magic {
+        return Err(not_an_artifact(path, kind));
+    }
+
+    let version = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+    if version != FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} uses artifact format v{}, expected v{}",
+                path, version, FORMAT_VERSION
+            ),
+        ));
+    }
+
+    Ok(reader)
+}
+
+fn not_an_artifact(path: &str, kind: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} is not a {} artifact", path, kind),
+    )
+}
+
+fn write_u64(writer: &mut impl Write, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_f64(writer: &mut impl Write, value: f64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_str(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    write_u64(writer, value.len() as u64)?;
+    writer.write_all(value.as_bytes())
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+fn read_str(reader: &mut impl Read) -> io::Result<String> {
+    let len = read_u64(reader)? as usize;
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("ds210-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_dataset_round_trip() {
+        let path = temp_path("dataset.bin");
+        let data = vec![
+            record(
+                "France",
+                "Gross enrollment ratio - Primary (male)",
+                2015,
+                102.5,
+            ),
+            record(
+                "Kenya",
+                "Gross enrollment ratio - Primary (female)",
+                2010,
+                None,
+            ),
+        ];
+
+        save_dataset(&path, &data).unwrap();
+        assert!(is_dataset(&path));
+        let loaded = load_dataset(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].country_or_area, "France");
+        assert_eq!(loaded[0].value, Some(102.5));
+        assert_eq!(loaded[1].year, 2010);
+        assert_eq!(loaded[1].value, None);
+    }
+
+    #[test]
+    fn test_graph_and_clusters_round_trip() {
+        let graph_path = temp_path("graph.bin");
+        let clusters_path = temp_path("clusters.bin");
+        let graph = Graph {
+            nodes: vec!["USA".to_string(), "Canada".to_string()],
+            adjacency_matrix: vec![vec![1.0, 0.5], vec![0.5, 2.0]],
+        };
+        let clusters = vec![vec![1], vec![0]];
+
+        save_graph(&graph_path, &graph).unwrap();
+        save_clusters(&clusters_path, &clusters, &graph).unwrap();
+        let loaded_graph = load_graph(&graph_path).unwrap();
+        let loaded_clusters = load_clusters(&clusters_path, &loaded_graph).unwrap();
+
+        // A graph artifact must not be accepted where a clustering is expected
+        assert!(load_clusters(&graph_path, &graph).is_err());
+        std::fs::remove_file(&graph_path).unwrap();
+        std::fs::remove_file(&clusters_path).unwrap();
+
+        assert_eq!(loaded_graph.nodes, graph.nodes);
+        assert_eq!(loaded_graph.adjacency_matrix, graph.adjacency_matrix);
+        assert_eq!(loaded_clusters, clusters);
+    }
+
+    #[test]
+    fn test_clusters_map_onto_another_snapshot() {
+        let path = temp_path("snapshot-clusters.bin");
+        let before = Graph {
+            nodes: vec!["USA".to_string(), "Canada".to_string(), "Chad".to_string()],
+            adjacency_matrix: vec![vec![0.0; 3]; 3],
+        };
+        save_clusters(&path, &[vec![0, 1], vec![2]], &before).unwrap();
+
+        // Next year's graph lists nodes in another order and lacks Chad
+        let after = Graph {
+            nodes: vec!["Mali".to_string(), "Canada".to_string(), "USA".to_string()],
+            adjacency_matrix: vec![vec![0.0; 3]; 3],
+        };
+        let clusters = load_clusters(&path, &after).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(clusters, vec![vec![2, 1], vec![]]);
+    }
+}
@@ -0,0 +1,107 @@
+use std::fs;
+use std::io;
+
+use crate::hash::sha256_hex;
+use crate::server::Request;
+
+// API keys accepted by `ds210 serve --api-keys FILE`. The file holds one key
+// per line, either verbatim or as `sha256:<hex digest>` so the plain key
+// need not sit on the server; blank lines and `#` comments are skipped.
+// Only digests are kept in memory, and a presented key is checked by
+// hashing it, so the comparison time says nothing about the stored keys.
+#[derive(Debug, Default)]
+pub struct ApiKeys {
+    digests: Vec<String>,
+}
+
+impl ApiKeys {
+    pub fn load(path: &str) -> io::Result<ApiKeys> {
+        let keys = ApiKeys::parse(&fs::read_to_string(path)?)
+            .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", path, error)))?;
+        if keys.digests.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} holds no API keys", path),
+            ));
+        }
+        Ok(keys)
+    }
+
+    pub fn parse(text: &str) -> io::Result<ApiKeys> {
+        let mut digests = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let digest = match line.strip_prefix("sha256:") {
+                Some(hex) if hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    hex.to_ascii_lowercase()
+                }
+                Some(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("line {}: sha256: needs a 64-digit hex digest", number + 1),
+                    ))
+                }
+                None => digest(line),
+            };
+            digests.push(digest);
+        }
+        Ok(ApiKeys { digests })
+    }
+
+    // No keys configured: every request is let through.
+    pub fn is_open(&self) -> bool {
+        self.digests.is_empty()
+    }
+
+    // Whether the request carries an accepted key, as
+    // `Authorization: Bearer <key>` or `X-API-Key: <key>`.
+    pub fn allows(&self, request: &Request) -> bool {
+        if self.is_open() {
+            return true;
+        }
+        let bearer = request.header("authorization").and_then(|value| {
+            let (scheme, token) = value.split_once(' ')?;
+            scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+        });
+        bearer
+            .or_else(|| request.header("x-api-key"))
+            .is_some_and(|key| self.digests.contains(&digest(key)))
+    }
+}
+
+fn digest(key: &str) -> String {
+    sha256_hex(key.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn request(headers: &str) -> Request {
+        let raw = format!("GET /metrics HTTP/1.1\r\n{}\r\n", headers);
+        Request::read(Cursor::new(raw)).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_keys_and_headers() {
+        // The second key is "abc" as a digest
+        let keys = ApiKeys::parse(
+            "# analysts\nletmein\n\n\
+             sha256:BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD\n",
+        )
+        .unwrap();
+        assert!(keys.allows(&request("Authorization: Bearer letmein\r\n")));
+        assert!(keys.allows(&request("authorization: bearer abc\r\n")));
+        assert!(keys.allows(&request("X-API-Key: abc\r\n")));
+        assert!(!keys.allows(&request("Authorization: Basic abc\r\n")));
+        assert!(!keys.allows(&request("X-API-Key: letmeout\r\n")));
+        assert!(!keys.allows(&request("")));
+
+        assert!(ApiKeys::default().allows(&request("")));
+        assert!(ApiKeys::parse("sha256:abc\n").is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+
+use crate::table::Table;
+use crate::{EducationData, Graph};
+
+// Countries' quantile bin (0-based) on every series: 0 is the lowest
+// `1 / bins` of the countries reporting the series, `bins - 1` the highest.
+// Bins are robust to outliers and skew, and easy to read across series.
+pub struct BinTable {
+    pub countries: Vec<String>,
+    pub series: Vec<String>,
+    // `assigned[country][series]`, None where the country has no value
+    pub assigned: Vec<Vec<Option<usize>>>,
+}
+
+// Bin each country's value of each series, taken from `year` or, without
+// one, from the latest year the country reports the series.
+pub fn quantile_bins(data: &[EducationData], bins: usize, year: Option<u32>) -> BinTable {
+    let bins = bins.max(1);
+    // (year, value) of the chosen observation per (series, country)
+    let mut chosen: BTreeMap<&str, BTreeMap<&str, (u32, f64)>> = BTreeMap::new();
+    for record in data {
+        let Some(value) = record.value else { continue };
+        if year.is_some_and(|year| record.year != year) {
+            continue;
+        }
+        let slot = chosen
+            .entry(record.series.as_str())
+            .or_default()
+            .entry(record.country_or_area.as_str())
+            .or_insert((record.year, value));
+        if record.year >= slot.0 {
+            *slot = (record.year, value);
+        }
+    }
+
+    let mut countries: Vec<String> = chosen
+        .values()
+        .flat_map(|values| values.keys().map(|country| country.to_string()))
+        .collect();
+    countries.sort();
+    countries.dedup();
+    let country_index: BTreeMap<&str, usize> = countries
+        .iter()
+        .enumerate()
+        .map(|(index, country)| (country.as_str(), index))
+        .collect();
+
+    let mut assigned = vec![vec![None; chosen.len()]; countries.len()];
+    for (column, values) in chosen.values().enumerate() {
+        let all: Vec<f64> = values.values().map(|&(_, value)| value).collect();
+        for (country, &(_, value)) in values {
+            assigned[country_index[country]][column] = Some(bin_of(value, &all, bins));
+        }
+    }
+
+    BinTable {
+        countries,
+        series: chosen.keys().map(|series| series.to_string()).collect(),
+        assigned,
+    }
+}
+
+// Ties share a bin: the value's mid-rank decides where it falls.
+fn bin_of(value: f64, all: &[f64], bins: usize) -> usize {
+    let below = all.iter().filter(|&&other| other < value).count() as f64;
+    let tied = all.iter().filter(|&&other| other == value).count() as f64;
+    let position = (below + tied / 2.0) / all.len() as f64;
+    ((position * bins as f64) as usize).min(bins - 1)
+}
+
+impl BinTable {
+    // Country x series table of 1-based bins, blank where there is no value.
+    pub fn to_table(&self) -> Table {
+        let mut headers = vec!["country".to_string()];
+        headers.extend(self.series.iter().cloned());
+        let mut table = Table {
+            headers,
+            rows: Vec::new(),
+        };
+        for (country, bins) in self.countries.iter().zip(&self.assigned) {
+            let mut row = vec![country.clone()];
+            row.extend(
+                bins.iter()
+                    .map(|bin| bin.map_or_else(String::new, |bin| (bin + 1).to_string())),
+            );
+            table.push_row(row);
+        }
+        table
+    }
+
+    // Similarity graph: the share of commonly reported series on which two
+    // countries fall in the same bin, i.e. one minus the normalized Hamming
+    // distance between their bin profiles.
+    pub fn hamming_graph(&self) -> Graph {
+        let n = self.countries.len();
+        let adjacency_matrix = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| if i == j { 1.0 } else { self.similarity(i, j) })
+                    .collect()
+            })
+            .collect();
+        Graph {
+            nodes: self.countries.clone(),
+            adjacency_matrix,
+        }
+    }
+
+    fn similarity(&self, a: usize, b: usize) -> f64 {
+        let (mut compared, mut equal) = (0, 0);
+        for (x, y) in self.assigned[a].iter().zip(&self.assigned[b]) {
+            if let (Some(x), Some(y)) = (x, y) {
+                compared += 1;
+                equal += usize::from(x == y);
+            }
+        }
+        if compared == 0 {
+            0.0
+        } else {
+            equal as f64 / compared as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_quantile_bins_and_hamming_similarity() {
+        let data = vec![
+            record("Chad", "primary", 2015, 10.0),
+            record("Mali", "primary", 2015, 20.0),
+            record("Niger", "primary", 2015, 30.0),
+            record("Togo", "primary", 2010, 5.0),
+            record("Togo", "primary", 2015, 40.0),
+            record("Chad", "tertiary", 2015, 1.0),
+            record("Mali", "tertiary", 2015, 1.0),
+            record("Togo", "tertiary", 2015, 9.0),
+        ];
+        let binned = quantile_bins(&data, 2, None);
+        assert_eq!(binned.countries, ["Chad", "Mali", "Niger", "Togo"]);
+        assert_eq!(binned.series, ["primary", "tertiary"]);
+        // Togo's latest value counts; tied values share a bin
+        assert_eq!(binned.assigned[3], [Some(1), Some(1)]);
+        assert_eq!(binned.assigned[0], [Some(0), Some(0)]);
+        assert_eq!(binned.assigned[1], [Some(0), Some(0)]);
+        assert_eq!(binned.assigned[2], [Some(1), None]);
+
+        let table = binned.to_table();
+        assert_eq!(table.rows[2], ["Niger", "2", ""]);
+
+        let graph = binned.hamming_graph();
+        assert_eq!(graph.adjacency_matrix[0][1], 1.0);
+        assert_eq!(graph.adjacency_matrix[0][3], 0.0);
+        assert_eq!(graph.adjacency_matrix[2][3], 1.0);
+
+        // A fixed year only bins that year's values
+        let binned = quantile_bins(&data, 2, Some(2010));
+        assert_eq!(binned.countries, ["Togo"]);
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// Set by the Ctrl-C handler; every token observes it.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// How a (possibly long-running) command finished.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunStatus {
+    Completed,
+    Interrupted,
+    TimedOut,
+}
+
+impl RunStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RunStatus::Completed => "completed",
+            RunStatus::Interrupted => "interrupted",
+            RunStatus::TimedOut => "timed_out",
+        }
+    }
+
+    // Follow shell conventions: 130 for SIGINT, 124 like `timeout(1)`
+    pub fn exit_code(self) -> i32 {
+        match self {
+            RunStatus::Completed => 0,
+            RunStatus::Interrupted => 130,
+            RunStatus::TimedOut => 124,
+        }
+    }
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RunStatus::Completed => "completed",
+            RunStatus::Interrupted => "interrupted by Ctrl-C",
+            RunStatus::TimedOut => "timed out",
+        })
+    }
+}
+
+// Cooperative cancellation: long-running loops poll `status()` between
+// iterations and stop with whatever partial result they have.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> CancelToken {
+        self.deadline = Some(Instant::now() + timeout);
+        self
+    }
+
+    // `Completed` means "keep going"; anything else is a reason to stop.
+    pub fn status(&self) -> RunStatus {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            RunStatus::Interrupted
+        } else if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            RunStatus::TimedOut
+        } else {
+            RunStatus::Completed
+        }
+    }
+
+    pub fn should_stop(&self) -> bool {
+        self.status() != RunStatus::Completed
+    }
+}
+
+// Route Ctrl-C to the cancellation flag. A second Ctrl-C exits immediately,
+// for the case where a stage is not polling.
+pub fn install_interrupt_handler() {
+    #[cfg(unix)]
+    unix::install();
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::INTERRUPTED;
+    use std::sync::atomic::Ordering;
+
+    const SIGINT: i32 = 2;
+
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        fn _exit(status: i32) -> !;
+    }
+
+    extern "C" fn on_interrupt(_signum: i32) {
+        // Only async-signal-safe work here: an atomic swap and _exit
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            unsafe { _exit(130) }
+        }
+    }
+
+    pub fn install() {
+        unsafe {
+            signal(SIGINT, on_interrupt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeout_expires() {
+        let token = CancelToken::new().with_timeout(Duration::from_millis(0));
+        assert_eq!(token.status(), RunStatus::TimedOut);
+        assert!(token.clone().should_stop());
+
+        let token = CancelToken::new().with_timeout(Duration::from_secs(3600));
+        assert_eq!(token.status(), RunStatus::Completed);
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(RunStatus::Completed.exit_code(), 0);
+        assert_eq!(RunStatus::Interrupted.exit_code(), 130);
+        assert_eq!(RunStatus::TimedOut.exit_code(), 124);
+    }
+}
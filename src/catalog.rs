@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+use crate::csv;
+
+// What each series measures: its unit, a one-line description and whether
+// a higher value is the better outcome. Scores that combine series need the
+// direction (a high pupil-teacher ratio is bad, a high enrolment ratio
+// good), and reports use the unit and description to label values. A copy
+// for the UNESCO education series ships with the binary; a metadata CSV
+// with the header `series,unit,description,direction` adds to or overrides
+// it.
+const BUNDLED_CATALOG: &str = include_str!("../data/series.csv");
+const COLUMNS: &[&str] = &["series", "unit", "description", "direction"];
+
+pub const DIRECTIONS: &[&str] = &["higher", "lower", "neutral"];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Direction {
+    HigherIsBetter,
+    LowerIsBetter,
+    // A count or size, good or bad only in context
+    #[default]
+    Neutral,
+}
+
+impl FromStr for Direction {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Direction> {
+        match value {
+            "higher" => Ok(Direction::HigherIsBetter),
+            "lower" => Ok(Direction::LowerIsBetter),
+            "neutral" | "" => Ok(Direction::Neutral),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown direction `{}`; expected one of {}",
+                    other,
+                    DIRECTIONS.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SeriesInfo {
+    pub unit: String,
+    pub description: String,
+    pub direction: Direction,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SeriesCatalog {
+    entries: BTreeMap<String, SeriesInfo>,
+}
+
+impl SeriesCatalog {
+    pub fn bundled() -> SeriesCatalog {
+        SeriesCatalog::parse(BUNDLED_CATALOG, "bundled series catalog")
+            .expect("bundled series catalog is valid")
+    }
+
+    // The bundled catalog with the entries of `path` on top.
+    pub fn with_file(path: &str) -> io::Result<SeriesCatalog> {
+        let mut catalog = SeriesCatalog::bundled();
+        catalog.extend(SeriesCatalog::parse(&fs::read_to_string(path)?, path)?);
+        Ok(catalog)
+    }
+
+    pub fn parse(text: &str, file: &str) -> io::Result<SeriesCatalog> {
+        let mut records = csv::Records::new(text.lines().map(|line| Ok(line.to_string())));
+        match records.next().transpose()? {
+            Some(header)
+                if csv::split_record(&header)
+                    .iter()
+                    .map(|name| name.trim())
+                    .eq(COLUMNS.iter().copied()) => {}
+            _ => {
+                return Err(invalid_data(format!(
+                    "{} must start with the header {}",
+                    file,
+                    COLUMNS.join(",")
+                )))
+            }
+        }
+        let mut entries = BTreeMap::new();
+        while let Some(record) = records.next() {
+            let record = record?;
+            if record.trim().is_empty() {
+                continue;
+            }
+            let fields = csv::split_record(&record);
+            let [series, unit, description, direction] = &fields[..] else {
+                return Err(invalid_data(format!(
+                    "{} line {}: expected {} columns, found {}",
+                    file,
+                    records.line(),
+                    COLUMNS.len(),
+                    fields.len()
+                )));
+            };
+            let direction = direction
+                .trim()
+                .parse()
+                .map_err(|e| invalid_data(format!("{} line {}: {}", file, records.line(), e)))?;
+            entries.insert(
+                series.trim().to_string(),
+                SeriesInfo {
+                    unit: unit.trim().to_string(),
+                    description: description.trim().to_string(),
+                    direction,
+                },
+            );
+        }
+        Ok(SeriesCatalog { entries })
+    }
+
+    pub fn extend(&mut self, other: SeriesCatalog) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn get(&self, series: &str) -> Option<&SeriesInfo> {
+        self.entries.get(series)
+    }
+
+    // Neutral for series the catalog does not know.
+    pub fn direction(&self, series: &str) -> Direction {
+        self.get(series)
+            .map_or(Direction::Neutral, |info| info.direction)
+    }
+
+    // "Gross enrollment ratio - Primary (male) (%)", or the bare name when
+    // the unit is unknown or already part of it.
+    pub fn label(&self, series: &str) -> String {
+        match self.get(series) {
+            Some(info)
+                if !info.unit.is_empty() && !series.ends_with(&format!("({})", info.unit)) =>
+            {
+                format!("{} ({})", series, info.unit)
+            }
+            _ => series.to_string(),
+        }
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_lookup_and_overrides() {
+        let mut catalog = SeriesCatalog::bundled();
+        let primary = "Gross enrollment ratio - Primary (female)";
+        assert_eq!(catalog.direction(primary), Direction::HigherIsBetter);
+        assert_eq!(catalog.label(primary), format!("{} (%)", primary));
+        let students = "Students enrolled in primary education (thousands)";
+        assert_eq!(catalog.label(students), students);
+        assert_eq!(catalog.direction("Unknown series"), Direction::Neutral);
+
+        let file = "series,unit,description,direction\n\
+                    \"Pupil-teacher ratio - Primary\",pupils,\"Pupils, per teacher\",lower\n\
+                    Literacy rate,%,,higher\n";
+        catalog.extend(SeriesCatalog::parse(file, "catalog.csv").unwrap());
+        let ratio = catalog.get("Pupil-teacher ratio - Primary").unwrap();
+        assert_eq!(ratio.unit, "pupils");
+        assert_eq!(ratio.description, "Pupils, per teacher");
+        assert_eq!(ratio.direction, Direction::LowerIsBetter);
+        assert_eq!(
+            catalog.direction("Literacy rate"),
+            Direction::HigherIsBetter
+        );
+
+        let error = SeriesCatalog::parse("series,unit\n", "catalog.csv").unwrap_err();
+        assert!(error.to_string().contains("must start with the header"));
+        let error = SeriesCatalog::parse("series,unit,description,direction\na,b,c,up\n", "x.csv");
+        assert!(error.unwrap_err().to_string().contains("x.csv line 2"));
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::io;
+use std::str::FromStr;
+
+use crate::eigen;
+use crate::table::Table;
+use crate::Graph;
+
+// How central each country is in the graph, by several measures. All but
+// PageRank read the graph as undirected, weighting each pair by the mean of
+// its two directions; self-loops are ignored throughout.
+//
+//   degree       countries linked to, as a share of the others
+//   strength     summed weight of the links (weighted degree)
+//   closeness    how near the others are, a link of weight w being 1 / w
+//                long; scaled by the share reachable (Wasserman and Faust),
+//                so a country in a small component does not score high
+//   betweenness  share of shortest paths between other pairs that pass
+//                through the country (Brandes, 2001)
+//   pagerank     stationary probability of a random walk along the links as
+//                directed, restarting anywhere with probability 0.15
+//   eigenvector  the country's entry in the leading eigenvector of the
+//                weights: central when linked to central countries
+
+pub const CENTRALITIES: &[&str] = &[
+    "degree",
+    "strength",
+    "closeness",
+    "betweenness",
+    "pagerank",
+    "eigenvector",
+];
+
+const DAMPING: f64 = 0.85;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Measure {
+    Degree,
+    Strength,
+    Closeness,
+    Betweenness,
+    #[default]
+    PageRank,
+    Eigenvector,
+}
+
+impl FromStr for Measure {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Measure> {
+        match value {
+            "degree" => Ok(Measure::Degree),
+            "strength" => Ok(Measure::Strength),
+            "closeness" => Ok(Measure::Closeness),
+            "betweenness" => Ok(Measure::Betweenness),
+            "pagerank" => Ok(Measure::PageRank),
+            "eigenvector" => Ok(Measure::Eigenvector),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown centrality measure `{}`; expected one of {}",
+                    other,
+                    CENTRALITIES.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+// Every measure for every node, in node order.
+pub struct Centrality {
+    pub degree: Vec<f64>,
+    pub strength: Vec<f64>,
+    pub closeness: Vec<f64>,
+    pub betweenness: Vec<f64>,
+    pub pagerank: Vec<f64>,
+    pub eigenvector: Vec<f64>,
+}
+
+impl Centrality {
+    pub fn values(&self, measure: Measure) -> &[f64] {
+        match measure {
+            Measure::Degree => &self.degree,
+            Measure::Strength => &self.strength,
+            Measure::Closeness => &self.closeness,
+            Measure::Betweenness => &self.betweenness,
+            Measure::PageRank => &self.pagerank,
+            Measure::Eigenvector => &self.eigenvector,
+        }
+    }
+
+    // Nodes by `measure`, most central first, ties in node order.
+    pub fn ranked(&self, measure: Measure) -> Vec<usize> {
+        let values = self.values(measure);
+        let mut order: Vec<usize> = (0..values.len()).collect();
+        order.sort_by(|&a, &b| values[b].total_cmp(&values[a]).then(a.cmp(&b)));
+        order
+    }
+}
+
+pub fn centrality(graph: &Graph) -> Centrality {
+    let weights = undirected(graph);
+    let size = weights.len();
+    let others = size.saturating_sub(1).max(1) as f64;
+    let degree = weights
+        .iter()
+        .map(|row| row.iter().filter(|&&weight| weight > 0.0).count() as f64 / others)
+        .collect();
+    let strength = weights.iter().map(|row| row.iter().sum()).collect();
+    let (closeness, betweenness) = shortest_paths(&weights);
+    let eigenvector = match eigen::top_k(&weights, 1, eigen::Options::default()).pop() {
+        Some(pair) if pair.value > 0.0 => pair.vector.iter().map(|x| x.abs()).collect(),
+        _ => vec![0.0; size],
+    };
+    Centrality {
+        degree,
+        strength,
+        closeness,
+        betweenness,
+        pagerank: pagerank(graph),
+        eigenvector,
+    }
+}
+
+// Mean of both directions, without the diagonal or negative weights.
+pub fn undirected(graph: &Graph) -> Vec<Vec<f64>> {
+    let matrix = &graph.adjacency_matrix;
+    (0..matrix.len())
+        .map(|i| {
+            (0..matrix.len())
+                .map(|j| {
+                    let weight = (matrix[i][j] + matrix[j][i]) / 2.0;
+                    if i != j && weight > 0.0 {
+                        weight
+                    } else {
+                        0.0
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Closeness and betweenness, from one Dijkstra run per source that also
+// counts the shortest paths (Brandes' accumulation).
+fn shortest_paths(weights: &[Vec<f64>]) -> (Vec<f64>, Vec<f64>) {
+    let size = weights.len();
+    let mut closeness = vec![0.0; size];
+    let mut betweenness = vec![0.0; size];
+    for source in 0..size {
+        let mut distance = vec![f64::INFINITY; size];
+        let mut paths = vec![0.0; size];
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); size];
+        let mut done = vec![false; size];
+        // Nodes in the order their distance was settled
+        let mut settled = Vec::new();
+        distance[source] = 0.0;
+        paths[source] = 1.0;
+        while let Some(node) = (0..size)
+            .filter(|&node| !done[node] && distance[node].is_finite())
+            .min_by(|&a, &b| distance[a].total_cmp(&distance[b]))
+        {
+            done[node] = true;
+            settled.push(node);
+            for (next, &weight) in weights[node].iter().enumerate() {
+                if weight <= 0.0 || done[next] {
+                    continue;
+                }
+                let through = distance[node] + 1.0 / weight;
+                let tolerance = 1e-12 * through;
+                if through < distance[next] - tolerance {
+                    distance[next] = through;
+                    paths[next] = paths[node];
+                    predecessors[next] = vec![node];
+                } else if (through - distance[next]).abs() <= tolerance {
+                    paths[next] += paths[node];
+                    predecessors[next].push(node);
+                }
+            }
+        }
+
+        let reached = settled.len() - 1;
+        let total: f64 = settled.iter().map(|&node| distance[node]).sum();
+        if reached > 0 && total > 0.0 {
+            let share = reached as f64 / (size - 1) as f64;
+            closeness[source] = share * reached as f64 / total;
+        }
+        let mut dependency = vec![0.0; size];
+        for &node in settled.iter().rev() {
+            for &previous in &predecessors[node] {
+                dependency[previous] += paths[previous] / paths[node] * (1.0 + dependency[node]);
+            }
+            if node != source {
+                betweenness[node] += dependency[node];
+            }
+        }
+    }
+    // Each pair was counted from both ends; scale by the pairs of others
+    if size > 2 {
+        let pairs = ((size - 1) * (size - 2)) as f64;
+        for value in &mut betweenness {
+            *value /= pairs;
+        }
+    }
+    (closeness, betweenness)
+}
+
+// Power iteration on the damped walk; the walk leaves a node with no
+// outgoing weight to any node alike.
+fn pagerank(graph: &Graph) -> Vec<f64> {
+    let matrix = &graph.adjacency_matrix;
+    let size = matrix.len();
+    if size == 0 {
+        return Vec::new();
+    }
+    let outgoing = |i: usize, j: usize| {
+        if i != j && matrix[i][j] > 0.0 {
+            matrix[i][j]
+        } else {
+            0.0
+        }
+    };
+    let totals: Vec<f64> = (0..size)
+        .map(|i| (0..size).map(|j| outgoing(i, j)).sum())
+        .collect();
+    let mut rank = vec![1.0 / size as f64; size];
+    for _ in 0..1000 {
+        let dangling: f64 = (0..size)
+            .filter(|&i| totals[i] == 0.0)
+            .map(|i| rank[i])
+            .sum();
+        let base = (1.0 - DAMPING + DAMPING * dangling) / size as f64;
+        let next: Vec<f64> = (0..size)
+            .map(|j| {
+                base + DAMPING
+                    * (0..size)
+                        .filter(|&i| totals[i] > 0.0)
+                        .map(|i| rank[i] * outgoing(i, j) / totals[i])
+                        .sum::<f64>()
+            })
+            .collect();
+        let change: f64 = next.iter().zip(&rank).map(|(a, b)| (a - b).abs()).sum();
+        rank = next;
+        if change < 1e-12 {
+            break;
+        }
+    }
+    rank
+}
+
+// The `top` most central countries by `measure`, with every measure.
+pub fn centrality_table(
+    graph: &Graph,
+    centrality: &Centrality,
+    measure: Measure,
+    top: usize,
+    clusters: Option<&HashMap<String, String>>,
+) -> Table {
+    let mut headers = vec!["rank", "country"];
+    headers.extend(CENTRALITIES);
+    if clusters.is_some() {
+        headers.push("cluster");
+    }
+    let mut table = Table::new(&headers);
+    for (index, node) in centrality.ranked(measure).into_iter().take(top).enumerate() {
+        let country = &graph.nodes[node];
+        let mut row = vec![(index + 1).to_string(), country.clone()];
+        for name in CENTRALITIES {
+            let measure: Measure = name.parse().expect("CENTRALITIES are all measures");
+            row.push(format!("{:.4}", centrality.values(measure)[node]));
+        }
+        if let Some(clusters) = clusters {
+            row.push(
+                clusters
+                    .get(country)
+                    .cloned()
+                    .unwrap_or_else(|| "-".to_string()),
+            );
+        }
+        table.push_row(row);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(size: usize, edges: &[(usize, usize, f64)]) -> Graph {
+        let mut matrix = vec![vec![0.0; size]; size];
+        for &(i, j, weight) in edges {
+            matrix[i][j] = weight;
+            matrix[j][i] = weight;
+        }
+        Graph {
+            nodes: (0..size).map(|node| format!("n{}", node)).collect(),
+            adjacency_matrix: matrix,
+        }
+    }
+
+    #[test]
+    fn test_measures_on_a_star_and_a_path() {
+        let close = |actual: f64, expected: f64| (actual - expected).abs() < 1e-9;
+
+        // A star: the hub is first by every measure
+        let star = graph(5, &[(0, 1, 1.0), (0, 2, 1.0), (0, 3, 1.0), (0, 4, 1.0)]);
+        let result = centrality(&star);
+        for name in CENTRALITIES {
+            let measure: Measure = name.parse().unwrap();
+            assert_eq!(result.ranked(measure)[0], 0, "{}", name);
+        }
+        assert_eq!(result.degree[0], 1.0);
+        assert_eq!(result.degree[1], 0.25);
+        assert!(close(result.betweenness[0], 1.0));
+        assert_eq!(result.betweenness[1], 0.0);
+        assert!(close(result.pagerank.iter().sum(), 1.0));
+        assert!(close(result.eigenvector[0], 0.5f64.sqrt()));
+
+        // A path 0 - 1 - 2 and an isolated node; a weight of 2 is half as long
+        let path = graph(4, &[(0, 1, 1.0), (1, 2, 2.0)]);
+        let result = centrality(&path);
+        assert_eq!(result.strength, [1.0, 3.0, 2.0, 0.0]);
+        // Node 1 reaches 2 of 3 at total distance 1.5
+        assert!(close(result.closeness[1], 2.0 / 3.0 * 2.0 / 1.5));
+        assert!(close(result.closeness[0], 2.0 / 3.0 * 2.0 / 2.5));
+        assert_eq!(result.closeness[3], 0.0);
+        // One pair of the three others' pairs goes through node 1
+        assert!(close(result.betweenness[1], 1.0 / 3.0));
+        assert!(close(result.pagerank.iter().sum(), 1.0));
+
+        let table = centrality_table(&path, &result, Measure::Strength, 2, None);
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(table.rows[0][1], "n1");
+        assert!("bogus".parse::<Measure>().is_err());
+    }
+}
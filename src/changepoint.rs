@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+
+use crate::table::Table;
+use crate::EducationData;
+
+// Binary segmentation for shifts in the mean of a time series. A segment
+// costs its sum of squared deviations from its mean; a split is kept when it
+// lowers the cost by more than `penalty * sigma^2 * ln(n)`, the BIC-style
+// penalty for one extra parameter, where sigma is a robust estimate of the
+// noise from the median absolute first difference.
+pub struct Detector {
+    pub penalty: f64,
+    // Fewest points on either side of a break
+    pub min_segment: usize,
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        Detector {
+            penalty: 2.0,
+            min_segment: 2,
+        }
+    }
+}
+
+// A structural break between two consecutive observations.
+#[derive(Debug, PartialEq)]
+pub struct Changepoint {
+    pub country: String,
+    pub series: String,
+    // First year of the new regime
+    pub year: u32,
+    pub before: f64,
+    pub after: f64,
+}
+
+impl Detector {
+    // Indices at which new segments start, ascending.
+    pub fn detect(&self, values: &[f64]) -> Vec<usize> {
+        let min_segment = self.min_segment.max(1);
+        if values.len() < 2 * min_segment {
+            return Vec::new();
+        }
+        let sigma = noise_scale(values);
+        let threshold = self.penalty * sigma * sigma * (values.len() as f64).ln();
+
+        let mut breaks = Vec::new();
+        let mut pending = vec![(0, values.len())];
+        while let Some((start, end)) = pending.pop() {
+            if end - start < 2 * min_segment {
+                continue;
+            }
+            let whole = cost(&values[start..end]);
+            let best = (start + min_segment..=end - min_segment)
+                .map(|split| {
+                    let gain = whole - cost(&values[start..split]) - cost(&values[split..end]);
+                    (split, gain)
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((split, gain)) = best {
+                // Tiny gains are float noise on constant segments
+                if gain > threshold && gain > 1e-9 * whole.max(1.0) {
+                    breaks.push(split);
+                    pending.push((start, split));
+                    pending.push((split, end));
+                }
+            }
+        }
+        breaks.sort_unstable();
+        breaks
+    }
+
+    // Run the detector over every (country, series) history in year order.
+    // Missing values are skipped; a repeated year keeps its last value.
+    pub fn scan(&self, data: &[EducationData]) -> Vec<Changepoint> {
+        let mut histories: BTreeMap<(&str, &str), BTreeMap<u32, f64>> = BTreeMap::new();
+        for record in data {
+            if let Some(value) = record.value {
+                histories
+                    .entry((record.country_or_area.as_str(), record.series.as_str()))
+                    .or_default()
+                    .insert(record.year, value);
+            }
+        }
+
+        let mut changepoints = Vec::new();
+        for ((country, series), history) in histories {
+            let years: Vec<u32> = history.keys().copied().collect();
+            let values: Vec<f64> = history.values().copied().collect();
+            let breaks = self.detect(&values);
+            let bounds: Vec<usize> = std::iter::once(0)
+                .chain(breaks.iter().copied())
+                .chain(std::iter::once(values.len()))
+                .collect();
+            for window in bounds.windows(3) {
+                changepoints.push(Changepoint {
+                    country: country.to_string(),
+                    series: series.to_string(),
+                    year: years[window[1]],
+                    before: mean(&values[window[0]..window[1]]),
+                    after: mean(&values[window[1]..window[2]]),
+                });
+            }
+        }
+        changepoints
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn cost(values: &[f64]) -> f64 {
+    let mean = mean(values);
+    values.iter().map(|value| (value - mean).powi(2)).sum()
+}
+
+// MAD of first differences, scaled to a normal standard deviation (a mean
+// shift only disturbs one difference, so it barely moves the median).
+fn noise_scale(values: &[f64]) -> f64 {
+    let mut differences: Vec<f64> = values
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).abs())
+        .collect();
+    differences.sort_by(f64::total_cmp);
+    let median = differences[differences.len() / 2];
+    median / (0.6745 * std::f64::consts::SQRT_2)
+}
+
+pub fn changepoint_table(changepoints: &[Changepoint]) -> Table {
+    let mut table = Table::new(&["country", "series", "year", "before", "after", "shift"]);
+    for changepoint in changepoints {
+        table.push_row(vec![
+            changepoint.country.clone(),
+            changepoint.series.clone(),
+            changepoint.year.to_string(),
+            format!("{:.4}", changepoint.before),
+            format!("{:.4}", changepoint.after),
+            format!("{:+.4}", changepoint.after - changepoint.before),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    const FEMALE: &str = "Gross enrollment ratio - Primary (female)";
+
+    #[test]
+    fn test_detects_mean_shifts() {
+        let detector = Detector::default();
+        assert_eq!(
+            detector.detect(&[10.0, 11.0, 10.0, 11.0, 30.0, 31.0, 30.0, 31.0]),
+            [4]
+        );
+        assert_eq!(
+            detector.detect(&[1.0, 1.0, 5.0, 5.0, 5.0, 9.0, 9.0]),
+            [2, 5]
+        );
+        assert!(detector.detect(&[4.0, 4.0, 4.0, 4.0]).is_empty());
+        assert!(detector
+            .detect(&[10.0, 12.0, 9.0, 11.0, 10.0, 12.0])
+            .is_empty());
+    }
+
+    #[test]
+    fn test_scan_reports_break_years() {
+        let data: Vec<EducationData> = [(2005, 40.0), (2010, 41.0), (2015, 70.0), (2020, 71.0)]
+            .into_iter()
+            .map(|(year, value)| record("Chad", FEMALE, year, value))
+            .collect();
+        let changepoints = Detector::default().scan(&data);
+        assert_eq!(changepoints.len(), 1);
+        assert_eq!(changepoints[0].year, 2015);
+        assert_eq!(
+            (changepoints[0].before, changepoints[0].after),
+            (40.5, 70.5)
+        );
+    }
+}
@@ -0,0 +1,422 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::stats;
+use crate::EducationData;
+
+// Canvas layout, in SVG user units.
+const MARGIN_LEFT: f64 = 80.0;
+const MARGIN_RIGHT: f64 = 30.0;
+const MARGIN_TOP: f64 = 50.0;
+const MARGIN_BOTTOM: f64 = 110.0;
+const PLOT_HEIGHT: f64 = 320.0;
+const SLOT_WIDTH: f64 = 90.0;
+const BOX_WIDTH: f64 = 44.0;
+const CELL_SIZE: f64 = 18.0;
+
+// Five-number summary of a box plot, with whiskers reaching the furthest
+// values within 1.5 IQR of the box and anything beyond drawn as outliers.
+#[derive(Debug, PartialEq)]
+pub struct BoxStats {
+    pub low: f64,
+    pub q1: f64,
+    pub median: f64,
+    pub q3: f64,
+    pub high: f64,
+    pub outliers: Vec<f64>,
+    pub count: usize,
+}
+
+pub fn box_stats(values: &[f64]) -> Option<BoxStats> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let q1 = stats::quantile(&sorted, 0.25)?;
+    let median = stats::quantile(&sorted, 0.5)?;
+    let q3 = stats::quantile(&sorted, 0.75)?;
+    let fence = 1.5 * (q3 - q1);
+    let inside = |value: &&f64| **value >= q1 - fence && **value <= q3 + fence;
+    Some(BoxStats {
+        low: sorted.iter().find(inside).copied().unwrap_or(q1),
+        q1,
+        median,
+        q3,
+        high: sorted.iter().rev().find(inside).copied().unwrap_or(q3),
+        outliers: sorted
+            .iter()
+            .filter(|value| !inside(value))
+            .copied()
+            .collect(),
+        count: sorted.len(),
+    })
+}
+
+// Each country's value of `series`, taken from `year` or, without one, from
+// the latest year the country reports it.
+pub fn country_values(
+    data: &[EducationData],
+    series: &str,
+    year: Option<u32>,
+) -> BTreeMap<String, f64> {
+    let mut chosen: BTreeMap<&str, (u32, f64)> = BTreeMap::new();
+    for record in data {
+        let Some(value) = record.value else { continue };
+        if record.series != series || year.is_some_and(|year| record.year != year) {
+            continue;
+        }
+        let slot = chosen
+            .entry(record.country_or_area.as_str())
+            .or_insert((record.year, value));
+        if record.year >= slot.0 {
+            *slot = (record.year, value);
+        }
+    }
+    chosen
+        .into_iter()
+        .map(|(country, (_, value))| (country.to_string(), value))
+        .collect()
+}
+
+// One box per group, side by side, sharing a value axis. Groups without
+// values keep their slot and label so cluster positions stay stable.
+pub fn write_box_plot(
+    writer: &mut dyn Write,
+    title: &str,
+    labels: &[String],
+    groups: &[Vec<f64>],
+) -> io::Result<()> {
+    let boxes: Vec<Option<BoxStats>> = groups.iter().map(|values| box_stats(values)).collect();
+    let (min, max) = groups
+        .iter()
+        .flatten()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &value| {
+            (min.min(value), max.max(value))
+        });
+    let ticks = if min.is_finite() {
+        nice_ticks(min, max)
+    } else {
+        vec![0.0, 1.0]
+    };
+    let (axis_min, axis_max) = (ticks[0], ticks[ticks.len() - 1]);
+    let y = |value: f64| MARGIN_TOP + PLOT_HEIGHT * (axis_max - value) / (axis_max - axis_min);
+
+    let width = MARGIN_LEFT + MARGIN_RIGHT + SLOT_WIDTH * groups.len().max(1) as f64;
+    let height = MARGIN_TOP + PLOT_HEIGHT + MARGIN_BOTTOM;
+    writeln!(
+        writer,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"12\">",
+        w = width,
+        h = height
+    )?;
+    writeln!(
+        writer,
+        "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>",
+        width, height
+    )?;
+    writeln!(
+        writer,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" font-size=\"15\">{}</text>",
+        width / 2.0,
+        MARGIN_TOP / 2.0 + 5.0,
+        xml_escape(title)
+    )?;
+
+    // Value axis with horizontal grid lines
+    let right = width - MARGIN_RIGHT;
+    for &tick in &ticks {
+        writeln!(
+            writer,
+            "<line x1=\"{:.1}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\" stroke=\"#ddd\"/>",
+            MARGIN_LEFT,
+            right,
+            y = y(tick)
+        )?;
+        writeln!(
+            writer,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>",
+            MARGIN_LEFT - 8.0,
+            y(tick) + 4.0,
+            format_tick(tick)
+        )?;
+    }
+    writeln!(
+        writer,
+        "<line x1=\"{x:.1}\" y1=\"{:.1}\" x2=\"{x:.1}\" y2=\"{:.1}\" stroke=\"black\"/>",
+        MARGIN_TOP,
+        MARGIN_TOP + PLOT_HEIGHT,
+        x = MARGIN_LEFT
+    )?;
+
+    for (slot, (label, summary)) in labels.iter().zip(&boxes).enumerate() {
+        let center = MARGIN_LEFT + SLOT_WIDTH * (slot as f64 + 0.5);
+        if let Some(summary) = summary {
+            write_box(writer, center, summary, &y)?;
+        }
+        let count = summary.as_ref().map_or(0, |summary| summary.count);
+        let label_y = MARGIN_TOP + PLOT_HEIGHT + 16.0;
+        writeln!(
+            writer,
+            "<text x=\"{x:.1}\" y=\"{y:.1}\" text-anchor=\"end\" \
+             transform=\"rotate(-35 {x:.1} {y:.1})\">{} (n={})</text>",
+            xml_escape(label),
+            count,
+            x = center + 4.0,
+            y = label_y
+        )?;
+    }
+    writeln!(writer, "</svg>")
+}
+
+fn write_box(
+    writer: &mut dyn Write,
+    center: f64,
+    summary: &BoxStats,
+    y: &dyn Fn(f64) -> f64,
+) -> io::Result<()> {
+    let left = center - BOX_WIDTH / 2.0;
+    let cap = BOX_WIDTH / 4.0;
+    // Whisker, its caps, then the box and median on top
+    writeln!(
+        writer,
+        "<line x1=\"{x:.1}\" y1=\"{:.1}\" x2=\"{x:.1}\" y2=\"{:.1}\" stroke=\"black\"/>",
+        y(summary.low),
+        y(summary.high),
+        x = center
+    )?;
+    for end in [summary.low, summary.high] {
+        writeln!(
+            writer,
+            "<line x1=\"{:.1}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\" stroke=\"black\"/>",
+            center - cap,
+            center + cap,
+            y = y(end)
+        )?;
+    }
+    writeln!(
+        writer,
+        "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" \
+         fill=\"#9ecae1\" stroke=\"black\"/>",
+        left,
+        y(summary.q3),
+        BOX_WIDTH,
+        (y(summary.q1) - y(summary.q3)).max(1.0)
+    )?;
+    writeln!(
+        writer,
+        "<line x1=\"{:.1}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\" \
+         stroke=\"black\" stroke-width=\"2\"/>",
+        left,
+        left + BOX_WIDTH,
+        y = y(summary.median)
+    )?;
+    for &outlier in &summary.outliers {
+        writeln!(
+            writer,
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"none\" stroke=\"black\"/>",
+            center,
+            y(outlier)
+        )?;
+    }
+    Ok(())
+}
+
+// A grid of shares between 0 and 1, one row per `rows` label and one column
+// per `columns` label, shaded from white (0) to dark blue (1). Each cell's
+// tooltip gives its row, column and percentage.
+pub fn write_heatmap(
+    writer: &mut dyn Write,
+    title: &str,
+    rows: &[String],
+    columns: &[String],
+    cells: &[Vec<f64>],
+) -> io::Result<()> {
+    let longest = |labels: &[String]| {
+        labels
+            .iter()
+            .map(|label| label.chars().count())
+            .max()
+            .unwrap_or(0)
+    };
+    let left = 20.0 + 7.0 * longest(rows) as f64;
+    // Column labels are rotated, so their length sets the top margin
+    let label_length = longest(columns).min(60) as f64;
+    let top = MARGIN_TOP + 6.0 * label_length;
+    // ... and lean to the right past the last column
+    let width = left + CELL_SIZE * columns.len() as f64 + MARGIN_RIGHT + 3.0 * label_length;
+    let height = top + CELL_SIZE * rows.len() as f64 + 60.0;
+    writeln!(
+        writer,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"11\">",
+        w = width,
+        h = height
+    )?;
+    writeln!(
+        writer,
+        "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>",
+        width, height
+    )?;
+    writeln!(
+        writer,
+        "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"15\">{}</text>",
+        20.0,
+        MARGIN_TOP / 2.0 + 5.0,
+        xml_escape(title)
+    )?;
+    for (column, label) in columns.iter().enumerate() {
+        let x = left + CELL_SIZE * (column as f64 + 0.5);
+        writeln!(
+            writer,
+            "<text x=\"{x:.1}\" y=\"{y:.1}\" transform=\"rotate(-60 {x:.1} {y:.1})\">{}</text>",
+            xml_escape(label),
+            x = x + 3.0,
+            y = top - 6.0
+        )?;
+    }
+    for (row, (label, values)) in rows.iter().zip(cells).enumerate() {
+        let y = top + CELL_SIZE * row as f64;
+        writeln!(
+            writer,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>",
+            left - 6.0,
+            y + CELL_SIZE * 0.7,
+            xml_escape(label)
+        )?;
+        for (column, &value) in values.iter().enumerate() {
+            writeln!(
+                writer,
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{s}\" height=\"{s}\" fill=\"{}\" \
+                 stroke=\"white\"><title>{}, {}: {:.0}%</title></rect>",
+                left + CELL_SIZE * column as f64,
+                y,
+                shade(value),
+                xml_escape(label),
+                xml_escape(&columns[column]),
+                100.0 * value,
+                s = CELL_SIZE
+            )?;
+        }
+    }
+
+    // Legend: the scale from 0% to 100% below the grid
+    let legend_y = top + CELL_SIZE * rows.len() as f64 + 20.0;
+    for step in 0..=10 {
+        writeln!(
+            writer,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"12\" height=\"12\" fill=\"{}\"/>",
+            left + 12.0 * step as f64,
+            legend_y,
+            shade(step as f64 / 10.0)
+        )?;
+    }
+    writeln!(
+        writer,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">0%</text>\
+         <text x=\"{:.1}\" y=\"{:.1}\">100%</text>",
+        left - 4.0,
+        legend_y + 10.0,
+        left + 136.0,
+        legend_y + 10.0
+    )?;
+    writeln!(writer, "</svg>")
+}
+
+// White at 0 to #08519c at 1.
+fn shade(value: f64) -> String {
+    let value = if value.is_finite() {
+        value.clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let channel = |full: f64| (255.0 + (full - 255.0) * value).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(8.0),
+        channel(81.0),
+        channel(156.0)
+    )
+}
+
+// Evenly spaced round tick values (steps of 1, 2 or 5 times a power of ten)
+// covering [min, max].
+fn nice_ticks(min: f64, max: f64) -> Vec<f64> {
+    let (min, max) = if min == max {
+        (min - 1.0, max + 1.0)
+    } else {
+        (min, max)
+    };
+    let rough = (max - min) / 5.0;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|factor| factor * magnitude)
+        .find(|&step| step >= rough)
+        .unwrap_or(10.0 * magnitude);
+    let first = (min / step).floor() as i64;
+    let last = (max / step).ceil() as i64;
+    (first..=last).map(|index| index as f64 * step).collect()
+}
+
+fn format_tick(value: f64) -> String {
+    let text = format!("{:.4}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_box_stats_and_svg() {
+        let summary = box_stats(&[5.0, 1.0, 3.0, 2.0, 4.0, 40.0]).unwrap();
+        assert_eq!((summary.q1, summary.median, summary.q3), (2.25, 3.5, 4.75));
+        assert_eq!((summary.low, summary.high), (1.0, 5.0));
+        assert_eq!(summary.outliers, [40.0]);
+        assert!(box_stats(&[]).is_none());
+        assert_eq!(nice_ticks(3.0, 47.0), [0.0, 10.0, 20.0, 30.0, 40.0, 50.0]);
+
+        let mut svg = Vec::new();
+        write_box_plot(
+            &mut svg,
+            "Gross enrolment <primary>",
+            &["0 (Chad)".to_string(), "1 (Togo)".to_string()],
+            &[vec![1.0, 2.0, 3.0], Vec::new()],
+        )
+        .unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.starts_with("<svg ") && svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains("Gross enrolment &lt;primary&gt;"));
+        assert!(svg.contains("1 (Togo) (n=0)"));
+        assert_eq!(svg.matches("<rect ").count(), 2);
+
+        let mut svg = Vec::new();
+        let rows = ["Chad".to_string(), "Mali".to_string()];
+        let columns = ["primary".to_string()];
+        write_heatmap(
+            &mut svg,
+            "Completeness",
+            &rows,
+            &columns,
+            &[vec![0.0], vec![1.0]],
+        )
+        .unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.contains("fill=\"#ffffff\" stroke=\"white\"><title>Chad, primary: 0%</title>"));
+        assert!(
+            svg.contains("fill=\"#08519c\" stroke=\"white\"><title>Mali, primary: 100%</title>")
+        );
+        assert_eq!(shade(0.5), "#84a8ce");
+    }
+}
@@ -0,0 +1,2293 @@
+use std::collections::HashMap;
+use std::io;
+
+use crate::centrality::CENTRALITIES;
+use crate::cluster::{ALGORITHMS, LINKAGES};
+use crate::commands::{REPORT_FORMATS, SUMMARY_FORMATS};
+use crate::config::{Config, Value};
+use crate::dendrogram::DENDROGRAM_FORMATS;
+use crate::features::SCALINGS;
+use crate::granger::CORRECTIONS;
+use crate::graph::SIMILARITIES;
+use crate::impute::IMPUTATIONS;
+use crate::kmeans::SEEDINGS;
+use crate::names::{AGGREGATE_POLICIES, DISSOLVED_POLICIES};
+use crate::ordering::ORDERS;
+use crate::paths::LENGTHS;
+use crate::pipeline;
+use crate::pivot::{AGGREGATES, DIMENSIONS, MEASURES};
+use crate::preset::{self, PRESETS};
+use crate::quality::SELECTIONS;
+use crate::stability::SPLITS;
+use crate::stats::TIES;
+use crate::symmetry::SYMMETRIES;
+
+// Declarative description of a subcommand. The parser and the help output are
+// both driven from these tables so they cannot drift apart.
+pub struct Command {
+    pub name: &'static str,
+    pub about: &'static str,
+    pub args: &'static [Arg],
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    Flag,
+    Option,
+    Positional,
+}
+
+pub struct Arg {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub value_name: &'static str,
+    pub help: &'static str,
+    pub required: bool,
+    // When non-empty, the only accepted values (also offered by completions)
+    pub possible_values: &'static [&'static str],
+}
+
+impl Arg {
+    pub const fn flag(name: &'static str, help: &'static str) -> Arg {
+        Arg {
+            name,
+            kind: ArgKind::Flag,
+            value_name: "",
+            help,
+            required: false,
+            possible_values: &[],
+        }
+    }
+
+    pub const fn option(name: &'static str, value_name: &'static str, help: &'static str) -> Arg {
+        Arg {
+            name,
+            kind: ArgKind::Option,
+            value_name,
+            help,
+            required: false,
+            possible_values: &[],
+        }
+    }
+
+    pub const fn positional(
+        name: &'static str,
+        value_name: &'static str,
+        help: &'static str,
+    ) -> Arg {
+        Arg {
+            name,
+            kind: ArgKind::Positional,
+            value_name,
+            help,
+            required: true,
+            possible_values: &[],
+        }
+    }
+
+    pub const fn required(mut self) -> Arg {
+        self.required = true;
+        self
+    }
+
+    pub const fn possible_values(mut self, values: &'static [&'static str]) -> Arg {
+        self.possible_values = values;
+        self
+    }
+
+    // Whether the value names a file, so completions can offer paths
+    pub fn takes_path(&self) -> bool {
+        self.value_name == "PATH"
+    }
+}
+
+pub const BIN_NAME: &str = "ds210";
+
+// Flags every command takes, before the command name or among its options.
+pub const GLOBAL_FLAGS: &[Arg] = &[Arg::flag(
+    "quiet",
+    "Print nothing but one JSON result on stdout (status, exit code, files written, output)",
+)];
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "run",
+        about: "Run the whole pipeline (load, build, cluster, export) in one go",
+        args: &[
+            Arg::option(
+                "input",
+                "PATH",
+                "Education CSV to analyze (a path, http:// URL or demo://education)",
+            ),
+            Arg::flag(
+                "demo",
+                "Analyze the built-in sample (same as --input demo://education)",
+            ),
+            Arg::option("skip-rows", "N", "Ignore N title rows before the header"),
+            Arg::option(
+                "header-rows",
+                "N",
+                "Number of stacked header rows to merge (default: 1)",
+            ),
+            Arg::flag(
+                "strict",
+                "Fail on the first malformed row instead of skipping it and reporting at the end",
+            ),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "years",
+                "RANGE",
+                "Keep only these years, e.g. 2010..2020 (both included), 2010.. or 2015",
+            ),
+            Arg::option(
+                "series",
+                "LIST",
+                "Keep only these comma-separated series, matched exactly",
+            ),
+            Arg::option(
+                "indicator",
+                "LIST",
+                "Keep only these comma-separated indicator codes, e.g. T07",
+            ),
+            Arg::option(
+                "aggregates",
+                "HOW",
+                "Keep regions and other aggregates such as \"Total, all countries or areas\" among the countries, drop them, or keep only them (default: include)",
+            )
+            .possible_values(AGGREGATE_POLICIES),
+            Arg::flag(
+                "iso-codes",
+                "Name countries by their ISO 3166-1 alpha-3 code",
+            ),
+            Arg::flag(
+                "reconcile-names",
+                "Merge countries listed under former names or other spellings (Swaziland, Eswatini) into one",
+            ),
+            Arg::option(
+                "dissolved",
+                "HOW",
+                "Keep dissolved states such as Serbia and Montenegro as countries, merge them into their successor, or drop them (default: keep)",
+            )
+            .possible_values(DISSOLVED_POLICIES),
+            Arg::option(
+                "sample",
+                "F",
+                "Keep a random share F of each country's observations, for a quick approximate run",
+            ),
+            Arg::option("seed", "N", "Seed for --sample (default: 0)"),
+            Arg::option(
+                "impute",
+                "HOW",
+                "Fill missing values with the series' mean or median, the country's last earlier value or zero, or drop them (default: leave missing)",
+            )
+            .possible_values(IMPUTATIONS),
+            Arg::option(
+                "trend",
+                "SPAN",
+                "Build from each series' LOWESS trend over this fraction of its years, e.g. 0.5",
+            ),
+            Arg::option(
+                "transforms",
+                "PATH",
+                "Config whose [transform.<group>] tables transform series values, e.g. transform = [\"log\"]",
+            ),
+            Arg::option(
+                "pipeline",
+                "PATH",
+                "Fill in the options not given from a pipeline.toml",
+            ),
+            Arg::option(
+                "preset",
+                "NAME",
+                "Fill in the options not given from a bundled combination",
+            )
+            .possible_values(PRESETS),
+            Arg::option(
+                "similarity",
+                "METRIC",
+                "Weight edges by how alike countries' latest series values are (default: cosine)",
+            )
+            .possible_values(SIMILARITIES),
+            Arg::option(
+                "ties",
+                "HOW",
+                "How --similarity spearman ranks tied values (default: average)",
+            )
+            .possible_values(TIES),
+            Arg::flag(
+                "value-graph",
+                "Legacy: weight every link of a country by the sum of its values instead of a similarity",
+            ),
+            Arg::option(
+                "normalize",
+                "SCALING",
+                "Scale each series (z-score, robust z-score, min-max or rank) before --similarity",
+            )
+            .possible_values(SCALINGS),
+            Arg::option(
+                "cache-dir",
+                "DIR",
+                "Keep --similarity graphs in DIR and reuse them for the same data and metric",
+            ),
+            Arg::option(
+                "symmetric",
+                "MODE",
+                "Make the matrix symmetric from the mean or max of both directions (default: none)",
+            )
+            .possible_values(SYMMETRIES),
+            Arg::flag(
+                "no-self-loops",
+                "Zero the diagonal so no country is linked to itself",
+            ),
+            Arg::option(
+                "min-weight",
+                "W",
+                "Drop edges lighter than W, e.g. to cluster only strong links",
+            ),
+            Arg::flag(
+                "sparse",
+                "Keep only the --similarity pairs of at least --min-weight as they are computed",
+            ),
+            Arg::option(
+                "top-k",
+                "K",
+                "Keep only each country's K strongest links (a link either end keeps stays)",
+            ),
+            Arg::option(
+                "algo",
+                "NAME",
+                "Clustering algorithm (default: agglomerative)",
+            )
+            .possible_values(ALGORITHMS),
+            Arg::option(
+                "clusters",
+                "N",
+                "Stop merging at N clusters (default: about the square root of the node count)",
+            ),
+            Arg::option(
+                "cutoff",
+                "W",
+                "Instead stop once no two clusters average an edge weight of W or more",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the cluster report to a file instead of stdout",
+            ),
+            Arg::option(
+                "out-dir",
+                "DIR",
+                "Write the report, its manifest, the graph and the run's history entry into a new run directory under DIR",
+            ),
+            Arg::option(
+                "run-id",
+                "ID",
+                "Name the --out-dir run directory (default: the start time in UTC)",
+            ),
+            Arg::option(
+                "format",
+                "FORMAT",
+                "Write the report as text, an HTML page with a drawing of the graph, JSON, one CSV row per country, or the graph as GraphViz DOT or GEXF",
+            )
+            .possible_values(REPORT_FORMATS),
+            Arg::option(
+                "timeout",
+                "SECONDS",
+                "Stop early with partial results after this long",
+            ),
+            Arg::option(
+                "threads",
+                "N",
+                "Threads for the similarity graph and clustering (default: one per core)",
+            ),
+            Arg::option(
+                "chunk-size",
+                "ROWS",
+                "Rows each thread takes at a time (default: 16)",
+            ),
+            Arg::option(
+                "order",
+                "KEY",
+                "Order nodes and cluster members (default: name)",
+            )
+            .possible_values(ORDERS),
+            Arg::option(
+                "history",
+                "PATH",
+                "Append the parameters and each country's cluster to this JSON Lines log",
+            ),
+            Arg::option(
+                "dump-cleaned",
+                "PATH",
+                "Debugging: write the observations after filtering, imputation and transforms as CSV",
+            ),
+            Arg::option(
+                "dump-features",
+                "PATH",
+                "Debugging: write the country x series matrix --similarity compares as CSV",
+            ),
+            Arg::option(
+                "dump-distances",
+                "PATH",
+                "Debugging: write the graph's weights, as the clustering gets them, as CSV",
+            ),
+        ],
+    },
+    Command {
+        name: "load",
+        about: "Parse an education CSV and cache the cleaned observations",
+        args: &[
+            Arg::option("input", "PATH", "Education CSV to load").required(),
+            Arg::option("skip-rows", "N", "Ignore N title rows before the header"),
+            Arg::option(
+                "header-rows",
+                "N",
+                "Number of stacked header rows to merge (default: 1)",
+            ),
+            Arg::flag(
+                "strict",
+                "Fail on the first malformed row instead of skipping it and reporting at the end",
+            ),
+            Arg::option(
+                "year-columns",
+                "PATTERN",
+                "Unpivot a wide CSV whose year columns match PATTERN (`#` = digit, `*` = any), e.g. ####",
+            ),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "save",
+                "PATH",
+                "Where to write the cleaned dataset artifact",
+            )
+            .required(),
+        ],
+    },
+    Command {
+        name: "fetch",
+        about: "Download several tables at once into a directory",
+        args: &[
+            Arg::option(
+                "inputs",
+                "LIST",
+                "Comma-separated locations (paths, http:// or s3:// URLs) to download",
+            )
+            .required(),
+            Arg::option("out-dir", "DIR", "Directory to save them in (created if missing)")
+                .required(),
+            Arg::option(
+                "concurrency",
+                "N",
+                "Tables downloaded at the same time (default: 4)",
+            ),
+            Arg::option(
+                "retries",
+                "N",
+                "Retry a failed or truncated http:// download N times (default: 3)",
+            ),
+            Arg::option(
+                "backoff",
+                "SECONDS",
+                "Wait before the first retry, doubling each time (default: 1)",
+            ),
+            Arg::option(
+                "rate",
+                "N",
+                "Send at most N http:// requests a second across all downloads; 0 for no limit (default: 2)",
+            ),
+            Arg::option(
+                "sha256",
+                "HEX",
+                "Refuse the (single) download unless it has this SHA-256",
+            ),
+            Arg::option(
+                "lock",
+                "PATH",
+                "Lockfile of SHA-256 digests: refuse downloads that differ, record new ones",
+            ),
+        ],
+    },
+    Command {
+        name: "ingest",
+        about: "Add a CSV to an observation store, updating revised observations",
+        args: &[
+            Arg::option("store", "PATH", "Observation store (created if missing)").required(),
+            Arg::option("input", "PATH", "Education CSV to ingest").required(),
+            Arg::option("skip-rows", "N", "Ignore N title rows before the header"),
+            Arg::option(
+                "header-rows",
+                "N",
+                "Number of stacked header rows to merge (default: 1)",
+            ),
+            Arg::flag(
+                "strict",
+                "Fail on the first malformed row instead of skipping it and reporting at the end",
+            ),
+            Arg::option(
+                "year-columns",
+                "PATTERN",
+                "Unpivot a wide CSV whose year columns match PATTERN (`#` = digit, `*` = any), e.g. ####",
+            ),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::flag("compact", "Rewrite the store without superseded records"),
+        ],
+    },
+    Command {
+        name: "build",
+        about: "Construct the country graph from a cleaned dataset (or a raw CSV)",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "years",
+                "RANGE",
+                "Keep only these years, e.g. 2010..2020 (both included), 2010.. or 2015",
+            ),
+            Arg::option(
+                "series",
+                "LIST",
+                "Keep only these comma-separated series, matched exactly",
+            ),
+            Arg::option(
+                "indicator",
+                "LIST",
+                "Keep only these comma-separated indicator codes, e.g. T07",
+            ),
+            Arg::option(
+                "aggregates",
+                "HOW",
+                "Keep regions and other aggregates such as \"Total, all countries or areas\" among the countries, drop them, or keep only them (default: include)",
+            )
+            .possible_values(AGGREGATE_POLICIES),
+            Arg::flag(
+                "iso-codes",
+                "Name countries by their ISO 3166-1 alpha-3 code",
+            ),
+            Arg::flag(
+                "reconcile-names",
+                "Merge countries listed under former names or other spellings (Swaziland, Eswatini) into one",
+            ),
+            Arg::option(
+                "dissolved",
+                "HOW",
+                "Keep dissolved states such as Serbia and Montenegro as countries, merge them into their successor, or drop them (default: keep)",
+            )
+            .possible_values(DISSOLVED_POLICIES),
+            Arg::option(
+                "sample",
+                "F",
+                "Keep a random share F of each country's observations, for a quick approximate run",
+            ),
+            Arg::option("seed", "N", "Seed for --sample (default: 0)"),
+            Arg::option(
+                "impute",
+                "HOW",
+                "Fill missing values with the series' mean or median, the country's last earlier value or zero, or drop them (default: leave missing)",
+            )
+            .possible_values(IMPUTATIONS),
+            Arg::option(
+                "trend",
+                "SPAN",
+                "Build from each series' LOWESS trend over this fraction of its years, e.g. 0.5",
+            ),
+            Arg::option(
+                "transforms",
+                "PATH",
+                "Config whose [transform.<group>] tables transform series values, e.g. transform = [\"log\"]",
+            ),
+            Arg::option(
+                "preset",
+                "NAME",
+                "Fill in the options not given from a bundled combination",
+            )
+            .possible_values(PRESETS),
+            Arg::option(
+                "similarity",
+                "METRIC",
+                "Weight edges by how alike countries' latest series values are (default: cosine)",
+            )
+            .possible_values(SIMILARITIES),
+            Arg::option(
+                "ties",
+                "HOW",
+                "How --similarity spearman ranks tied values (default: average)",
+            )
+            .possible_values(TIES),
+            Arg::flag(
+                "value-graph",
+                "Legacy: weight every link of a country by the sum of its values instead of a similarity",
+            ),
+            Arg::option(
+                "normalize",
+                "SCALING",
+                "Scale each series (z-score, robust z-score, min-max or rank) before --similarity",
+            )
+            .possible_values(SCALINGS),
+            Arg::option(
+                "cache-dir",
+                "DIR",
+                "Keep --similarity graphs in DIR and reuse them for the same data and metric",
+            ),
+            Arg::option(
+                "hamming-bins",
+                "N",
+                "Link countries by how often they share a quantile bin (of N) across series",
+            ),
+            Arg::option(
+                "symmetric",
+                "MODE",
+                "Make the matrix symmetric from the mean or max of both directions (default: none)",
+            )
+            .possible_values(SYMMETRIES),
+            Arg::flag(
+                "no-self-loops",
+                "Zero the diagonal so no country is linked to itself",
+            ),
+            Arg::option(
+                "min-weight",
+                "W",
+                "Drop edges lighter than W, e.g. to cluster only strong links",
+            ),
+            Arg::flag(
+                "sparse",
+                "Keep only the --similarity pairs of at least --min-weight as they are computed",
+            ),
+            Arg::option(
+                "top-k",
+                "K",
+                "Keep only each country's K strongest links (a link either end keeps stays)",
+            ),
+            Arg::option("save", "PATH", "Where to write the graph artifact").required(),
+            Arg::flag(
+                "profile",
+                "Report each series' records, missing values, variance and edge weight in the value graph",
+            ),
+            Arg::option(
+                "threads",
+                "N",
+                "Threads for the pairwise similarities (default: one per core)",
+            ),
+            Arg::option(
+                "chunk-size",
+                "ROWS",
+                "Rows each thread takes at a time (default: 16)",
+            ),
+            Arg::option(
+                "dump-cleaned",
+                "PATH",
+                "Debugging: write the observations after filtering, imputation and transforms as CSV",
+            ),
+            Arg::option(
+                "dump-features",
+                "PATH",
+                "Debugging: write the country x series matrix --similarity compares as CSV",
+            ),
+            Arg::option(
+                "dump-distances",
+                "PATH",
+                "Debugging: write the graph's weights, as saved, as CSV",
+            ),
+        ],
+    },
+    Command {
+        name: "cluster",
+        about: "Cluster a cached graph",
+        args: &[
+            Arg::option("graph", "PATH", "Graph artifact produced by `build`").required(),
+            Arg::option("save", "PATH", "Where to write the clustering artifact").required(),
+            Arg::option(
+                "preset",
+                "NAME",
+                "Fill in the options not given from a bundled combination",
+            )
+            .possible_values(PRESETS),
+            Arg::option(
+                "algo",
+                "NAME",
+                "Clustering algorithm (default: agglomerative)",
+            )
+            .possible_values(ALGORITHMS),
+            Arg::option(
+                "clusters",
+                "N",
+                "Stop merging at N clusters, or `auto` to pick N by modularity (default: about the square root of the node count)",
+            ),
+            Arg::option(
+                "max-clusters",
+                "N",
+                "Most clusters tried by `--clusters auto` (default: 10)",
+            ),
+            Arg::option(
+                "select",
+                "HOW",
+                "How `--clusters auto` picks N: best modularity or the elbow (default: best)",
+            )
+            .possible_values(SELECTIONS),
+            Arg::option(
+                "cutoff",
+                "W",
+                "Instead stop once no two clusters average an edge weight of W or more",
+            ),
+            Arg::option(
+                "init",
+                "PATH",
+                "Start from a previous clustering artifact (warm start)",
+            ),
+            Arg::option(
+                "align",
+                "PATH",
+                "Renumber clusters to match a previous clustering artifact",
+            ),
+            Arg::option(
+                "countries",
+                "LIST",
+                "Cluster only these comma-separated countries, leaving the rest unassigned",
+            ),
+            Arg::flag(
+                "components",
+                "Cluster each connected component on its own (--clusters then applies to each)",
+            ),
+            Arg::option(
+                "threads",
+                "N",
+                "Threads for clustering (default: one per core)",
+            ),
+            Arg::option(
+                "chunk-size",
+                "ROWS",
+                "Rows each thread takes at a time (default: 16)",
+            ),
+            Arg::option(
+                "history",
+                "PATH",
+                "Append the parameters and each country's cluster to this JSON Lines log",
+            ),
+            Arg::option(
+                "timeout",
+                "SECONDS",
+                "Stop early and save the clusters merged so far",
+            ),
+        ],
+    },
+    Command {
+        name: "dendrogram",
+        about: "Merge a cached graph down to one cluster and export the dendrogram",
+        args: &[
+            Arg::option("graph", "PATH", "Graph artifact produced by `build`").required(),
+            Arg::option(
+                "linkage",
+                "HOW",
+                "How alike two clusters are: their mean, heaviest or lightest weight (default: average)",
+            )
+            .possible_values(LINKAGES),
+            Arg::option("format", "FORMAT", "Newick or nested JSON (default: newick)")
+                .possible_values(DENDROGRAM_FORMATS),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the dendrogram to a file instead of stdout",
+            ),
+            Arg::option(
+                "save",
+                "PATH",
+                "Also cut the tree into a clustering artifact",
+            ),
+            Arg::option(
+                "clusters",
+                "N",
+                "Cut --save at N clusters (default: about the square root of the node count)",
+            ),
+            Arg::option(
+                "cutoff",
+                "W",
+                "Instead cut --save below the merges at a similarity of W",
+            ),
+            Arg::option(
+                "threads",
+                "N",
+                "Threads for the merging (default: one per core)",
+            ),
+            Arg::option(
+                "chunk-size",
+                "ROWS",
+                "Rows each thread takes at a time (default: 16)",
+            ),
+        ],
+    },
+    Command {
+        name: "history",
+        about: "Show how clusterings logged with --history changed from run to run",
+        args: &[
+            Arg::option("log", "PATH", "History log written by `run` or `cluster`").required(),
+            Arg::option(
+                "country",
+                "NAME",
+                "Follow one country: its cluster and cluster-mates in every run",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the table as CSV instead of printing it",
+            ),
+        ],
+    },
+    Command {
+        name: "analyze",
+        about: "Print summary statistics for a cached graph and clustering",
+        args: &[
+            Arg::option("graph", "PATH", "Graph artifact produced by `build`").required(),
+            Arg::option(
+                "clusters",
+                "PATH",
+                "Clustering artifact produced by `cluster`",
+            ),
+            Arg::option(
+                "format",
+                "FORMAT",
+                "Write the summary as text or as one JSON object (default: text)",
+            )
+            .possible_values(SUMMARY_FORMATS),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the summary to PATH instead of printing it",
+            ),
+        ],
+    },
+    Command {
+        name: "weights",
+        about: "Show a histogram of a cached graph's edge weights and suggest a --min-weight",
+        args: &[
+            Arg::option("graph", "PATH", "Graph artifact produced by `build`").required(),
+            Arg::option("bins", "N", "Number of equal-width bins (default: 20)"),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the bins as CSV instead of printing the histogram",
+            ),
+        ],
+    },
+    Command {
+        name: "centrality",
+        about: "List the most central countries of a cached graph",
+        args: &[
+            Arg::option("graph", "PATH", "Graph artifact produced by `build`").required(),
+            Arg::option(
+                "measure",
+                "NAME",
+                "Measure to rank countries by (default: pagerank)",
+            )
+            .possible_values(CENTRALITIES),
+            Arg::option("top", "N", "Show the N most central countries (default: 10)"),
+            Arg::option(
+                "clusters",
+                "PATH",
+                "Clustering artifact over the graph, to show each country's cluster",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write every measure for every country as CSV instead of printing the top N",
+            ),
+        ],
+    },
+    Command {
+        name: "path",
+        about: "Find the shortest path between two countries of a cached graph",
+        args: &[
+            Arg::positional("from", "FROM", "Country the path starts from").required(),
+            Arg::positional("to", "TO", "Country the path leads to").required(),
+            Arg::option("graph", "PATH", "Graph artifact produced by `build`").required(),
+            Arg::option(
+                "length",
+                "HOW",
+                "How long a link of weight w is: 1/w, 1-w, -ln w or one hop whatever the weight (default: inverse)",
+            )
+            .possible_values(LENGTHS),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the path as CSV instead of a table",
+            ),
+        ],
+    },
+    Command {
+        name: "similar",
+        about: "List the countries most like one country, by edge weight or feature distance",
+        args: &[
+            Arg::positional("country", "COUNTRY", "Country to find neighbors of").required(),
+            Arg::option(
+                "graph",
+                "PATH",
+                "Graph artifact produced by `build`; neighbors by edge weight",
+            ),
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file; neighbors by distance between latest series values",
+            ),
+            Arg::option(
+                "where",
+                "EXPR",
+                "With --from, keep only observations matching EXPR",
+            ),
+            Arg::option(
+                "normalize",
+                "SCALING",
+                "With --from, scale each series before measuring distances",
+            )
+            .possible_values(SCALINGS),
+            Arg::option("k", "N", "Number of neighbors, also -k (default: 10)"),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the neighbors as CSV instead of a table",
+            ),
+        ],
+    },
+    Command {
+        name: "export",
+        about: "Write the cluster report for a cached graph and clustering",
+        args: &[
+            Arg::option("graph", "PATH", "Graph artifact produced by `build`").required(),
+            Arg::option(
+                "clusters",
+                "PATH",
+                "Clustering artifact produced by `cluster`",
+            )
+            .required(),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the report to a file instead of stdout",
+            ),
+            Arg::flag(
+                "html",
+                "Write an HTML page with a cluster table and a drawing of the graph",
+            ),
+            Arg::option(
+                "format",
+                "FORMAT",
+                "Write the report as text, HTML, JSON, one CSV row per country, or the graph as GraphViz DOT or GEXF",
+            )
+            .possible_values(REPORT_FORMATS),
+            Arg::option(
+                "order",
+                "KEY",
+                "Order nodes and cluster members (default: name)",
+            )
+            .possible_values(ORDERS),
+        ],
+    },
+    Command {
+        name: "export-countries",
+        about: "Write one JSON file per country, for building country profile pages",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("graph", "PATH", "Graph artifact built from the same data").required(),
+            Arg::option(
+                "clusters",
+                "PATH",
+                "Clustering artifact over the graph, to record each country's cluster",
+            ),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "neighbors",
+                "N",
+                "Nearest neighbours listed per country (default: 5)",
+            ),
+            Arg::option(
+                "out-dir",
+                "DIR",
+                "Directory for the country files and index.json (created if missing)",
+            )
+            .required(),
+        ],
+    },
+    Command {
+        name: "pivot",
+        about: "Tabulate observations into a pivot table (rows x columns)",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("rows", "FIELD", "Field to group rows by (default: country)")
+                .possible_values(DIMENSIONS),
+            Arg::option("cols", "FIELD", "Field to group columns by (default: year)")
+                .possible_values(DIMENSIONS),
+            Arg::option("values", "FIELD", "Field to aggregate (default: value)")
+                .possible_values(MEASURES),
+            Arg::option("agg", "FUNC", "How to combine values in a cell (default: mean)")
+                .possible_values(AGGREGATES),
+            Arg::option("series", "NAME", "Only tabulate this series"),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the table as CSV instead of printing it",
+            ),
+        ],
+    },
+    Command {
+        name: "rank",
+        about: "Rank countries on a series with percentiles, deltas and clusters",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("series", "NAME", "Series to rank countries on").required(),
+            Arg::option("year", "YEAR", "Year to rank (default: the latest reported)"),
+            Arg::option(
+                "ties",
+                "HOW",
+                "How tied values share a rank (default: min, as in 1, 2, 2, 4)",
+            )
+            .possible_values(TIES),
+            Arg::option(
+                "catalog",
+                "PATH",
+                "Series metadata CSV (series,unit,description,direction) over the bundled one",
+            ),
+            Arg::option("graph", "PATH", "Graph artifact, to show cluster membership"),
+            Arg::option(
+                "clusters",
+                "PATH",
+                "Clustering artifact, to show cluster membership",
+            ),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the ranking as CSV instead of printing it",
+            ),
+        ],
+    },
+    Command {
+        name: "inequality",
+        about: "Track Gini and coefficient of variation across countries over time",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("series", "NAME", "Only measure this series"),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::flag(
+                "by-year",
+                "List the metrics for every series and year instead of the trends",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the table as CSV instead of printing it",
+            ),
+        ],
+    },
+    Command {
+        name: "convergence",
+        about: "Test whether countries converge on a series (beta and sigma convergence)",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("series", "NAME", "Series to analyze").required(),
+            Arg::option("start", "YEAR", "Initial year (default: the first reported)"),
+            Arg::option("end", "YEAR", "Final year (default: the last reported)"),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the report to PATH instead of printing it",
+            ),
+        ],
+    },
+    Command {
+        name: "top-movers",
+        about: "List the countries whose neighbours or cluster changed most between two year windows",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("before", "RANGE", "Earlier window of years, e.g. 2000..2009").required(),
+            Arg::option("after", "RANGE", "Later window of years, e.g. 2010..2019").required(),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "similarity",
+                "METRIC",
+                "How alike countries' scaled window means are (default: cosine)",
+            )
+            .possible_values(SIMILARITIES),
+            Arg::option(
+                "algo",
+                "NAME",
+                "Clustering algorithm for each window (default: agglomerative)",
+            )
+            .possible_values(ALGORITHMS),
+            Arg::option(
+                "neighbors",
+                "K",
+                "Nearest neighbours compared per country (default: 5)",
+            ),
+            Arg::option("top", "N", "Show the N countries that changed most (default: 10)"),
+            Arg::option(
+                "threads",
+                "N",
+                "Threads for the windows' graphs and clustering (default: one per core)",
+            ),
+            Arg::option(
+                "chunk-size",
+                "ROWS",
+                "Rows each thread takes at a time (default: 16)",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write every country as CSV instead of printing the top N",
+            ),
+        ],
+    },
+    Command {
+        name: "stability",
+        about: "Cluster two halves of the observations apart and report how well they agree (ARI)",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option(
+                "split",
+                "HOW",
+                "Even against odd years, or each country's records halved at random (default: parity)",
+            )
+            .possible_values(SPLITS),
+            Arg::option("seed", "N", "Seed for --split random (default: 0)"),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "similarity",
+                "METRIC",
+                "How alike countries are within each half (default: cosine)",
+            )
+            .possible_values(SIMILARITIES),
+            Arg::option(
+                "ties",
+                "HOW",
+                "How --similarity spearman ranks tied values (default: average)",
+            )
+            .possible_values(TIES),
+            Arg::flag(
+                "value-graph",
+                "Legacy: weight every link of a country by the sum of its values instead of a similarity",
+            ),
+            Arg::option(
+                "algo",
+                "NAME",
+                "Clustering algorithm for each half (default: agglomerative)",
+            )
+            .possible_values(ALGORITHMS),
+            Arg::option(
+                "clusters",
+                "N",
+                "Stop merging at N clusters (default: about the square root of the node count)",
+            ),
+            Arg::option(
+                "cutoff",
+                "W",
+                "Instead stop once no two clusters average an edge weight of W or more",
+            ),
+            Arg::option(
+                "threads",
+                "N",
+                "Threads for the similarity graphs and clustering (default: one per core)",
+            ),
+            Arg::option(
+                "chunk-size",
+                "ROWS",
+                "Rows each thread takes at a time (default: 16)",
+            ),
+            Arg::option("output", "PATH", "Write the summary as CSV instead of a table"),
+        ],
+    },
+    Command {
+        name: "temporal",
+        about: "Build and cluster one similarity graph per year (or bucket of years) and report how each country changes",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("bucket", "YEARS", "Years per snapshot (default: 1)"),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "aggregates",
+                "HOW",
+                "Keep regions and other aggregates such as \"Total, all countries or areas\" among the countries, drop them, or keep only them (default: include)",
+            )
+            .possible_values(AGGREGATE_POLICIES),
+            Arg::flag(
+                "iso-codes",
+                "Name countries by their ISO 3166-1 alpha-3 code",
+            ),
+            Arg::flag(
+                "reconcile-names",
+                "Merge countries listed under former names or other spellings (Swaziland, Eswatini) into one",
+            ),
+            Arg::option(
+                "dissolved",
+                "HOW",
+                "Keep dissolved states such as Serbia and Montenegro as countries, merge them into their successor, or drop them (default: keep)",
+            )
+            .possible_values(DISSOLVED_POLICIES),
+            Arg::option(
+                "similarity",
+                "METRIC",
+                "How alike countries are within a snapshot (default: cosine)",
+            )
+            .possible_values(SIMILARITIES),
+            Arg::option(
+                "ties",
+                "HOW",
+                "How --similarity spearman ranks tied values (default: average)",
+            )
+            .possible_values(TIES),
+            Arg::option(
+                "algo",
+                "NAME",
+                "Clustering algorithm for each snapshot (default: agglomerative)",
+            )
+            .possible_values(ALGORITHMS),
+            Arg::option(
+                "clusters",
+                "N",
+                "Stop merging at N clusters (default: about the square root of the node count)",
+            ),
+            Arg::option(
+                "cutoff",
+                "W",
+                "Instead stop once no two clusters average an edge weight of W or more",
+            ),
+            Arg::option(
+                "threads",
+                "N",
+                "Threads for the similarity graphs and clustering (default: one per core)",
+            ),
+            Arg::option(
+                "chunk-size",
+                "ROWS",
+                "Rows each thread takes at a time (default: 16)",
+            ),
+            Arg::option(
+                "save-dir",
+                "DIR",
+                "Also save each snapshot as a graph artifact, e.g. DIR/2015.graph",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the changes as CSV instead of a table",
+            ),
+        ],
+    },
+    Command {
+        name: "changepoints",
+        about: "Flag structural breaks in each country's series over time",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("series", "NAME", "Only scan this series"),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "penalty",
+                "FACTOR",
+                "Higher values flag fewer breaks (default: 2, a BIC-style penalty)",
+            ),
+            Arg::option(
+                "min-segment",
+                "N",
+                "Fewest observations on either side of a break (default: 2)",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the breaks as CSV instead of printing them",
+            ),
+        ],
+    },
+    Command {
+        name: "leadlag",
+        about: "Find countries whose trajectory on a series precedes others' by k years",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("series", "NAME", "Series to compare countries on").required(),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option("max-lag", "YEARS", "Longest lead to test (default: 5)"),
+            Arg::option(
+                "min-points",
+                "N",
+                "Fewest year pairs behind a correlation (default: 3)",
+            ),
+            Arg::option(
+                "min-correlation",
+                "R",
+                "Weakest lagged correlation reported (default: 0.8)",
+            ),
+            Arg::option(
+                "save",
+                "PATH",
+                "Also write the directed lead-lag graph as a graph artifact",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the edges as CSV instead of printing them",
+            ),
+        ],
+    },
+    Command {
+        name: "granger",
+        about: "Weight directed edges by whether one country's past predicts another's",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("series", "NAME", "Series to test").required(),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option("lag", "YEARS", "How far back the predictors reach (default: 1)"),
+            Arg::option(
+                "min-points",
+                "N",
+                "Fewest usable years per test (default: 6)",
+            ),
+            Arg::option(
+                "alpha",
+                "P",
+                "Significance level after correction (default: 0.05)",
+            ),
+            Arg::option(
+                "correction",
+                "METHOD",
+                "Multiple-testing correction (default: bh)",
+            )
+            .possible_values(CORRECTIONS),
+            Arg::option(
+                "save",
+                "PATH",
+                "Also write the directed graph, weighted by F statistic",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the edges as CSV instead of printing them",
+            ),
+        ],
+    },
+    Command {
+        name: "bins",
+        about: "Tabulate each country's quantile bin on every series",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("bins", "N", "Number of quantile bins (default: 4)"),
+            Arg::option(
+                "year",
+                "YEAR",
+                "Bin this year's values (default: each country's latest)",
+            ),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the table as CSV instead of printing it",
+            ),
+        ],
+    },
+    Command {
+        name: "kmeans",
+        about: "Group countries by k-means over their series-by-year values",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option(
+                "k",
+                "N",
+                "Number of clusters, or `auto` to sweep 1 to --k-max (default: 3)",
+            ),
+            Arg::option("k-max", "N", "Largest k tried by `--k auto` (default: 10)"),
+            Arg::option(
+                "select",
+                "HOW",
+                "How `--k auto` picks k: best silhouette or the elbow of the inertia (default: best)",
+            )
+            .possible_values(SELECTIONS),
+            Arg::option(
+                "max-iterations",
+                "N",
+                "Stop after N passes even if countries still move (default: 100)",
+            ),
+            Arg::option(
+                "seeding",
+                "METHOD",
+                "How to pick the first centroids (default: kmeans++)",
+            )
+            .possible_values(SEEDINGS),
+            Arg::option(
+                "init",
+                "PATH",
+                "Start from the clusters of a clustering artifact instead, taking k from it",
+            ),
+            Arg::option("seed", "N", "Seed for the initial centroids (default: 0)"),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the assignments as CSV instead of printing them",
+            ),
+            Arg::option(
+                "centroids",
+                "PATH",
+                "Also write each cluster's centroid as a CSV row",
+            ),
+            Arg::option(
+                "threads",
+                "N",
+                "Threads for assigning countries to centroids (default: one per core)",
+            ),
+            Arg::option(
+                "chunk-size",
+                "ROWS",
+                "Rows each thread takes at a time (default: 16)",
+            ),
+            Arg::option(
+                "timeout",
+                "SECONDS",
+                "Stop early with the assignments of the last pass",
+            ),
+        ],
+    },
+    Command {
+        name: "composite",
+        about: "Score countries on all series at once, inverting those where lower is better",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("year", "YEAR", "Score this year (default: each country's latest values)"),
+            Arg::option(
+                "catalog",
+                "PATH",
+                "Series metadata CSV (series,unit,description,direction) over the bundled one",
+            ),
+            Arg::option(
+                "invert",
+                "LIST",
+                "Comma-separated series to treat as lower-is-better, whatever the catalog says",
+            ),
+            Arg::option(
+                "keep",
+                "LIST",
+                "Comma-separated series to score as they are, whatever the catalog says",
+            ),
+            Arg::option(
+                "scaling",
+                "MODE",
+                "Scale series by mean and standard deviation, by median and MAD clipped at 3, to 0..1 by range, or by rank (default: standard)",
+            )
+            .possible_values(SCALINGS),
+            Arg::option("graph", "PATH", "Graph artifact, to characterize clusters by score"),
+            Arg::option(
+                "clusters",
+                "PATH",
+                "Clustering artifact, to characterize clusters by score",
+            ),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the scores as CSV instead of printing them",
+            ),
+        ],
+    },
+    Command {
+        name: "completeness",
+        about: "Show how many of each series' years every country reports, as CSV or a heatmap",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the country x series matrix as CSV instead of printing it",
+            ),
+            Arg::option("svg", "PATH", "Also draw the matrix as an SVG heatmap"),
+        ],
+    },
+    Command {
+        name: "chart",
+        about: "Draw an SVG box plot of a series for each cluster",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("graph", "PATH", "Graph artifact the clustering was run on").required(),
+            Arg::option("clusters", "PATH", "Clustering artifact to group countries by").required(),
+            Arg::option("series", "NAME", "Series to plot").required(),
+            Arg::option(
+                "year",
+                "YEAR",
+                "Plot this year's values (default: each country's latest)",
+            ),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option("output", "PATH", "SVG file to write").required(),
+        ],
+    },
+    Command {
+        name: "trajectories",
+        about: "Export each cluster's mean of every series year by year as tidy CSV",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option("graph", "PATH", "Graph artifact the clustering was run on").required(),
+            Arg::option("clusters", "PATH", "Clustering artifact to group countries by").required(),
+            Arg::option("series", "NAME", "Only export this series"),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the trajectories as CSV instead of printing them",
+            ),
+        ],
+    },
+    Command {
+        name: "arrays",
+        about: "Export the feature, distance and adjacency matrices for NumPy or MATLAB",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option(
+                "year",
+                "YEAR",
+                "Use this year's values (default: each country's latest)",
+            ),
+            Arg::option(
+                "graph",
+                "PATH",
+                "Graph artifact whose adjacency, transition and normalized Laplacian matrices to include",
+            ),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "An .npz archive, a .mat file, or an .npy file for the features (other arrays go beside it)",
+            )
+            .required(),
+            Arg::option(
+                "threads",
+                "N",
+                "Threads for the pairwise distances (default: one per core)",
+            ),
+            Arg::option(
+                "chunk-size",
+                "ROWS",
+                "Rows each thread takes at a time (default: 16)",
+            ),
+        ],
+    },
+    Command {
+        name: "serve",
+        about: "Serve the analysis engine as a JSON API over HTTP",
+        args: &[
+            Arg::option(
+                "listen",
+                "ADDR",
+                "Address to listen on (default: 127.0.0.1:8210)",
+            ),
+            Arg::option(
+                "cache-dir",
+                "DIR",
+                "Keep results here as MessagePack so they outlive the server",
+            ),
+            Arg::option(
+                "memory-limit",
+                "MB",
+                "Evict the least recently used named datasets to stay under this",
+            ),
+            Arg::option(
+                "api-keys",
+                "FILE",
+                "Require one of these keys (one per line, or sha256:<hex>) as a bearer token",
+            ),
+            Arg::option(
+                "workers",
+                "N",
+                "Threads running queued /jobs analyses (default: 2)",
+            ),
+            Arg::option(
+                "connections",
+                "N",
+                "Connections served at once, as many more waiting; the rest get 503 (default: 16)",
+            ),
+        ],
+    },
+    Command {
+        name: "diff-data",
+        about: "Report observations added, removed or changed between two releases",
+        args: &[
+            Arg::positional(
+                "old",
+                "OLD",
+                "Earlier release: dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::positional("new", "NEW", "Later release, in any of the same forms").required(),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "tolerance",
+                "DELTA",
+                "Value changes no larger than this are not material (default: 0)",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the differences as CSV instead of a table",
+            ),
+            Arg::option(
+                "rerun",
+                "PATH",
+                "If anything material changed, cluster the new release and write the report here",
+            ),
+            Arg::option(
+                "threads",
+                "N",
+                "Threads for the --rerun graph and clustering (default: one per core)",
+            ),
+            Arg::option(
+                "chunk-size",
+                "ROWS",
+                "Rows each thread takes at a time (default: 16)",
+            ),
+        ],
+    },
+    Command {
+        name: "compare-graphs",
+        about: "Measure how far apart two graphs over the same countries are",
+        args: &[
+            Arg::positional("first", "FIRST", "Graph artifact produced by `build`").required(),
+            Arg::positional("second", "SECOND", "Graph artifact to compare it with").required(),
+            Arg::option(
+                "threshold",
+                "W",
+                "Count pairs weighing more than W as edges for the Jaccard index (default: 0)",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the measures as CSV instead of a table",
+            ),
+        ],
+    },
+    Command {
+        name: "anonymize",
+        about: "Write a down-sampled, jittered copy of the observations to share as a fixture",
+        args: &[
+            Arg::option(
+                "from",
+                "PATH",
+                "Cleaned dataset artifact, observation store or CSV file",
+            )
+            .required(),
+            Arg::option(
+                "where",
+                "EXPR",
+                "Keep only observations matching EXPR, e.g. 'year >= 2015 and series ~ \"enrol\"'",
+            ),
+            Arg::option(
+                "fraction",
+                "F",
+                "Share of countries to keep, all years of each (default: 0.25)",
+            ),
+            Arg::option(
+                "jitter",
+                "F",
+                "Noise as a multiple of each series' standard deviation (default: 0.05)",
+            ),
+            Arg::option("seed", "N", "Seed for the sample and the noise (default: 0)"),
+            Arg::flag("pseudonyms", "Rename countries to Country 1, Country 2, ..."),
+            Arg::option("output", "PATH", "Where to write the CSV").required(),
+        ],
+    },
+    Command {
+        name: "reference",
+        about: "Show, look up or update the ISO/M49 country reference tables",
+        args: &[
+            Arg::option(
+                "lookup",
+                "QUERY",
+                "Find a country by M49 code, ISO alpha-3 code or name",
+            ),
+            Arg::option(
+                "classify",
+                "PATH",
+                "List each country name in a dataset, store or CSV as a country (with its codes), an aggregate or unknown",
+            ),
+            Arg::option(
+                "install",
+                "PATH",
+                "Directory with countries.tsv, regions.tsv and optionally aliases.tsv to cache",
+            ),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the lookup result, or the --classify table as CSV, to PATH instead of printing it",
+            ),
+        ],
+    },
+    Command {
+        name: "sweep",
+        about: "Run the pipeline over a grid of parameters and rank the results",
+        args: &[
+            Arg::option(
+                "config",
+                "PATH",
+                "Sweep config with `input` and a [grid] table",
+            )
+            .required(),
+            Arg::option(
+                "output",
+                "PATH",
+                "Write the ranked results as CSV instead of a table",
+            ),
+            Arg::option(
+                "timeout",
+                "SECONDS",
+                "Stop early and rank the combinations finished so far",
+            ),
+            Arg::option(
+                "threads",
+                "N",
+                "Threads for each combination's graph and clustering (default: one per core)",
+            ),
+            Arg::option(
+                "chunk-size",
+                "ROWS",
+                "Rows each thread takes at a time (default: 16)",
+            ),
+            Arg::flag("progress", "Report each finished combination on stderr"),
+        ],
+    },
+    Command {
+        name: "completions",
+        about: "Print a shell completion script to stdout",
+        args: &[
+            Arg::positional("shell", "SHELL", "Shell to generate completions for")
+                .possible_values(&["bash", "zsh", "fish"]),
+        ],
+    },
+];
+
+// Result of parsing the command line for one subcommand.
+pub struct Matches {
+    pub command: &'static Command,
+    values: HashMap<&'static str, String>,
+}
+
+impl Matches {
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|value| value.as_str())
+    }
+
+    // Only valid for arguments declared as required; the parser has already
+    // rejected command lines where they are missing.
+    pub fn required(&self, name: &str) -> &str {
+        self.value(name)
+            .unwrap_or_else(|| panic!("argument --{} is not declared as required", name))
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    pub fn parse_value<T: std::str::FromStr>(&self, name: &str) -> io::Result<Option<T>> {
+        match self.value(name) {
+            Some(raw) => raw
+                .parse()
+                .map(Some)
+                .map_err(|_| invalid_input(format!("invalid value {:?} for --{}", raw, name))),
+            None => Ok(None),
+        }
+    }
+}
+
+pub enum Parsed {
+    Run(Matches),
+    Help(String),
+}
+
+pub fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+pub fn find_command(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+// Take out `--quiet`, which applies to every command and may come before
+// the command or among its options, but is left alone as the value of an
+// option (`--run-id --quiet`). Returns the rest and whether it was given.
+pub fn strip_quiet(args: &[String]) -> (Vec<String>, bool) {
+    let mut rest = Vec::with_capacity(args.len());
+    let mut quiet = false;
+    let mut command = None;
+    let mut value_next = false;
+    for arg in args {
+        if value_next {
+            value_next = false;
+        } else if arg == "--quiet" {
+            quiet = true;
+            continue;
+        } else if let Some(command) = command {
+            value_next = takes_value(command, arg);
+        } else if !arg.starts_with('-') {
+            command = find_command(arg);
+        }
+        rest.push(arg.clone());
+    }
+    (rest, quiet)
+}
+
+// Whether `token` is an option of `command` whose value is the next
+// argument, as `parse_command` reads them.
+fn takes_value(command: &Command, token: &str) -> bool {
+    let short = token
+        .strip_prefix('-')
+        .filter(|name| name.len() == 1 && name.chars().all(|c| c.is_ascii_alphabetic()));
+    token
+        .strip_prefix("--")
+        .or(short)
+        .filter(|name| !name.contains('='))
+        .and_then(|name| command.args.iter().find(|arg| arg.name == name))
+        .is_some_and(|arg| arg.kind == ArgKind::Option)
+}
+
+// Parse the arguments following the program name. An empty command line maps
+// to `run` so the binary keeps working the way it did before subcommands.
+pub fn parse(args: &[String]) -> io::Result<Parsed> {
+    let (command, rest) = match args.first().map(|arg| arg.as_str()) {
+        None => (
+            find_command("run").expect("run command is declared"),
+            &args[..0],
+        ),
+        Some("help") | Some("--help") | Some("-h") => {
+            return Ok(Parsed::Help(
+                match args.get(1).and_then(|name| find_command(name)) {
+                    Some(command) => command_usage(command),
+                    None => overview(),
+                },
+            ));
+        }
+        Some("--version") | Some("-V") => {
+            return Ok(Parsed::Help(format!(
+                "{} {}",
+                BIN_NAME,
+                env!("CARGO_PKG_VERSION")
+            )));
+        }
+        Some(name) => match find_command(name) {
+            Some(command) => (command, &args[1..]),
+            None => {
+                return Err(invalid_input(format!(
+                    "unknown subcommand {:?}; run `{} help` for a list",
+                    name, BIN_NAME
+                )))
+            }
+        },
+    };
+
+    parse_command(command, rest)
+}
+
+fn parse_command(command: &'static Command, args: &[String]) -> io::Result<Parsed> {
+    let mut values = HashMap::new();
+    let mut positionals = command
+        .args
+        .iter()
+        .filter(|arg| arg.kind == ArgKind::Positional);
+
+    let mut iter = args.iter();
+    while let Some(token) = iter.next() {
+        if token == "--help" || token == "-h" {
+            return Ok(Parsed::Help(command_usage(command)));
+        }
+
+        // `-k` stands for an option named by the single letter
+        let short = token
+            .strip_prefix('-')
+            .filter(|name| name.len() == 1 && name.chars().all(|c| c.is_ascii_alphabetic()));
+        let (arg, value) = match token.strip_prefix("--").or(short) {
+            Some(long) => {
+                // Support both `--name value` and `--name=value`
+                let (name, inline_value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let arg = command
+                    .args
+                    .iter()
+                    .find(|arg| arg.name == name && arg.kind != ArgKind::Positional)
+                    .ok_or_else(|| {
+                        invalid_input(format!("unknown option --{} for `{}`", name, command.name))
+                    })?;
+                let value = match (arg.kind, inline_value) {
+                    (ArgKind::Flag, Some(_)) => {
+                        return Err(invalid_input(format!("--{} does not take a value", name)))
+                    }
+                    // Flags are stored as present/absent
+                    (ArgKind::Flag, None) => "true".to_string(),
+                    (_, Some(value)) => value,
+                    (_, None) => iter
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| invalid_input(format!("--{} expects a value", name)))?,
+                };
+                (arg, value)
+            }
+            None => {
+                let arg = positionals.next().ok_or_else(|| {
+                    invalid_input(format!(
+                        "unexpected argument {:?} for `{}`",
+                        token, command.name
+                    ))
+                })?;
+                (arg, token.clone())
+            }
+        };
+
+        if !arg.possible_values.is_empty() && !arg.possible_values.contains(&value.as_str()) {
+            return Err(invalid_input(format!(
+                "invalid value {:?} for <{}>; expected one of: {}",
+                value,
+                arg.value_name,
+                arg.possible_values.join(", ")
+            )));
+        }
+        values.insert(arg.name, value);
+    }
+
+    // Then the pipeline file's, then the preset's
+    if let Some(path) = values.get("pipeline") {
+        let config = Config::load(path)?;
+        let mut filled = Vec::new();
+        for (name, value) in pipeline::options(&config)? {
+            let arg = command
+                .args
+                .iter()
+                .find(|arg| arg.name == name)
+                .expect("pipeline keys name options of the command");
+            if values.contains_key(name) {
+                continue;
+            }
+            let invalid = || {
+                invalid_input(format!(
+                    "{}: invalid value {} for `{}`",
+                    path, value, arg.name
+                ))
+            };
+            match (arg.kind, value) {
+                (ArgKind::Flag, Value::Boolean(true)) => filled.push((name, "true".to_string())),
+                (ArgKind::Flag, Value::Boolean(false)) => {}
+                (ArgKind::Flag, _) | (_, Value::Boolean(_)) | (_, Value::Table(_)) => {
+                    return Err(invalid())
+                }
+                (_, value) => {
+                    let value = pipeline::option_value(value);
+                    if !arg.possible_values.is_empty()
+                        && !arg.possible_values.contains(&value.as_str())
+                    {
+                        return Err(invalid());
+                    }
+                    filled.push((name, value));
+                }
+            }
+        }
+        values.extend(filled);
+    }
+    // Options given on the command line win over the preset's
+    if let Some(preset) = values.get("preset").and_then(|name| preset::find(name)) {
+        let given = |name: &str| values.contains_key(name);
+        let filled: Vec<(&'static str, String)> = preset
+            .options
+            .iter()
+            .filter(|(name, _)| !given(name) && !preset::replaced(name, &given))
+            .filter_map(|(name, value)| {
+                let arg = command.args.iter().find(|arg| arg.name == *name)?;
+                Some((arg.name, value.to_string()))
+            })
+            .collect();
+        values.extend(filled);
+    }
+
+    for arg in command.args {
+        if arg.required && !values.contains_key(arg.name) {
+            return Err(invalid_input(match arg.kind {
+                ArgKind::Positional => format!("`{}` expects <{}>", command.name, arg.value_name),
+                _ => format!("`{}` requires --{}", command.name, arg.name),
+            }));
+        }
+    }
+
+    Ok(Parsed::Run(Matches { command, values }))
+}
+
+fn overview() -> String {
+    let mut text = format!("Usage: {} <COMMAND> [OPTIONS]\n\nCommands:\n", BIN_NAME);
+    let width = COMMANDS
+        .iter()
+        .map(|command| command.name.len())
+        .max()
+        .unwrap_or(0);
+    for command in COMMANDS {
+        text.push_str(&format!(
+            "  {:<width$} {}\n",
+            command.name,
+            command.about,
+            width = width
+        ));
+    }
+    text.push_str("\nGlobal options:\n");
+    for flag in GLOBAL_FLAGS {
+        text.push_str(&format!("  --{}  {}\n", flag.name, flag.help));
+    }
+    text.push_str(
+        "\nEnvironment:\n  DS210_NOTIFY_URL   POST each output's manifest to this http:// URL\n  \
+         DS210_DONE_MARKER  When set, write <output>.done once an output is complete\n",
+    );
+    text.push_str(&format!(
+        "\nRun `{} help <COMMAND>` for the options of a command.",
+        BIN_NAME
+    ));
+    text
+}
+
+pub fn command_usage(command: &Command) -> String {
+    let mut text = format!("{}\n\nUsage: {} {}", command.about, BIN_NAME, command.name);
+    for arg in command.args {
+        if arg.kind == ArgKind::Positional {
+            text.push_str(&format!(" <{}>", arg.value_name));
+        }
+    }
+    text.push_str(" [OPTIONS]\n\nOptions:\n");
+    for arg in command.args {
+        let spec = match arg.kind {
+            ArgKind::Flag => format!("--{}", arg.name),
+            ArgKind::Positional => format!("<{}>", arg.value_name),
+            ArgKind::Option => format!("--{} <{}>", arg.name, arg.value_name),
+        };
+        let required = if arg.required && arg.kind == ArgKind::Option {
+            " (required)"
+        } else {
+            ""
+        };
+        let choices = if arg.possible_values.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", arg.possible_values.join(", "))
+        };
+        text.push_str(&format!(
+            "  {:<28} {}{}{}\n",
+            spec, arg.help, choices, required
+        ));
+    }
+    text.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_subcommand_options() {
+        // Both `--name value` and `--name=value` are accepted
+        let parsed = parse(&args("build --from cleaned.bin --save=graph.bin")).unwrap();
+        let matches = match parsed {
+            Parsed::Run(matches) => matches,
+            Parsed::Help(_) => panic!("expected a subcommand"),
+        };
+
+        assert_eq!(matches.command.name, "build");
+        assert_eq!(matches.required("from"), "cleaned.bin");
+        assert_eq!(matches.value("save"), Some("graph.bin"));
+    }
+
+    #[test]
+    fn test_single_letter_options_take_one_dash() {
+        match parse(&args("similar Indonesia --graph g.bin -k 3")).unwrap() {
+            Parsed::Run(matches) => {
+                assert_eq!(matches.required("country"), "Indonesia");
+                assert_eq!(matches.value("k"), Some("3"));
+            }
+            Parsed::Help(_) => panic!("expected a subcommand"),
+        }
+        assert!(parse(&args("similar Indonesia -x 3")).is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_missing_and_unknown_options() {
+        assert!(parse(&args("cluster --save clusters.bin")).is_err());
+        assert!(parse(&args("load --input a.csv --save a.bin --bogus")).is_err());
+        assert!(parse(&args("frobnicate")).is_err());
+    }
+
+    #[test]
+    fn test_preset_fills_in_options_not_given() {
+        let value = |line: &str, name: &str| match parse(&args(line)).unwrap() {
+            Parsed::Run(matches) => matches.value(name).map(str::to_string),
+            Parsed::Help(_) => panic!("expected a command"),
+        };
+        let line = "run --demo --preset quick";
+        assert_eq!(value(line, "algo").as_deref(), Some("louvain"));
+        assert_eq!(value(line, "similarity").as_deref(), Some("cosine"));
+        let line = "run --demo --preset quick --algo agglomerative --min-weight=0.8";
+        assert_eq!(value(line, "algo").as_deref(), Some("agglomerative"));
+        assert_eq!(value(line, "min-weight").as_deref(), Some("0.8"));
+        // Only the options a command takes, and none a given option replaces
+        let line = "cluster --graph g.bin --save c.bin --preset quick";
+        assert_eq!(value(line, "algo").as_deref(), Some("louvain"));
+        let line = "build --from a.bin --save g.bin --preset quick --hamming-bins 4";
+        assert_eq!(value(line, "similarity"), None);
+        assert!(parse(&args("run --demo --preset fastest")).is_err());
+    }
+
+    #[test]
+    fn test_pipeline_file_fills_in_options_not_given() {
+        let path = std::env::temp_dir().join(format!("ds210-pipeline-{}.toml", std::process::id()));
+        let path = path.to_str().unwrap();
+        let parsed = |line: &str, toml: &str| {
+            std::fs::write(path, toml).unwrap();
+            parse(&args(&format!("run --pipeline {} {}", path, line)))
+        };
+        let toml = "preset = \"quick\"\n[input]\ndemo = true\n[graph]\nmin_weight = 0.7\n[cluster]\nalgo = \"agglomerative\"\n";
+        match parsed("--algo louvain", toml).unwrap() {
+            Parsed::Run(matches) => {
+                assert!(matches.flag("demo"));
+                assert_eq!(matches.value("min-weight"), Some("0.7"));
+                assert_eq!(matches.value("algo"), Some("louvain"));
+                // The preset fills in what neither sets
+                assert_eq!(matches.value("similarity"), Some("cosine"));
+            }
+            Parsed::Help(_) => panic!("expected a command"),
+        }
+        assert!(parsed("", "[cluster]\nalgo = \"kmeans\"\n").is_err());
+        assert!(parsed("", "[input]\nstrict = 1\n").is_err());
+        assert!(parsed("", "[graph]\nclusters = 3\n").is_err());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_flags_take_no_value() {
+        match parse(&args("sweep --config s.toml --progress")).unwrap() {
+            Parsed::Run(matches) => {
+                assert!(matches.flag("progress"));
+                assert!(matches.value("timeout").is_none());
+            }
+            Parsed::Help(_) => panic!("expected a subcommand"),
+        }
+        assert!(parse(&args("sweep --config s.toml --progress=yes")).is_err());
+    }
+
+    #[test]
+    fn test_quiet_is_taken_out_where_an_option_goes() {
+        let (rest, quiet) = strip_quiet(&args("--quiet run --demo --quiet -k 3"));
+        assert!(quiet);
+        assert_eq!(rest, args("run --demo -k 3"));
+        // The value of an option stays, in either form
+        let (rest, quiet) = strip_quiet(&args("run --run-id --quiet --output=--quiet"));
+        assert!(!quiet);
+        assert_eq!(rest, args("run --run-id --quiet --output=--quiet"));
+    }
+
+    #[test]
+    fn test_threads_come_with_chunk_size() {
+        for command in COMMANDS {
+            let has = |name: &str| command.args.iter().any(|arg| arg.name == name);
+            assert_eq!(has("threads"), has("chunk-size"), "{}", command.name);
+        }
+    }
+
+    #[test]
+    fn test_positional_possible_values() {
+        match parse(&args("completions zsh")).unwrap() {
+            Parsed::Run(matches) => assert_eq!(matches.required("shell"), "zsh"),
+            Parsed::Help(_) => panic!("expected a subcommand"),
+        }
+        assert!(parse(&args("completions powershell")).is_err());
+        assert!(parse(&args("completions")).is_err());
+    }
+
+    #[test]
+    fn test_empty_command_line_runs_pipeline() {
+        match parse(&[]).unwrap() {
+            Parsed::Run(matches) => assert_eq!(matches.command.name, "run"),
+            Parsed::Help(_) => panic!("expected the run command"),
+        }
+    }
+}
@@ -0,0 +1,763 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::graph::Graph;
+use crate::json::Json;
+use crate::labels;
+use crate::louvain;
+use crate::observer::{Control, Iteration, IterationObserver, Unobserved};
+use crate::parallel::Parallelism;
+use crate::table::Table;
+
+pub const ALGORITHMS: &[&str] = &["agglomerative", "louvain", "passthrough"];
+pub const LINKAGES: &[&str] = &["average", "single", "complete"];
+
+// The clustering algorithms `run --algo` and `cluster --algo` choose from.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Algorithm {
+    // `cluster_graph`: average-linkage merging of the warm start's clusters,
+    // or of single nodes
+    #[default]
+    Agglomerative,
+    // Modularity-maximizing communities (`louvain`); the number of clusters
+    // is its own, so `Stop` does not apply
+    Louvain,
+    // Keeps a warm start as it is, or finds no clusters
+    Passthrough,
+}
+
+impl FromStr for Algorithm {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Algorithm> {
+        match value {
+            "agglomerative" => Ok(Algorithm::Agglomerative),
+            "louvain" => Ok(Algorithm::Louvain),
+            "passthrough" => Ok(Algorithm::Passthrough),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown clustering algorithm `{}`; expected one of {}",
+                    other,
+                    ALGORITHMS.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+impl Algorithm {
+    // Agglomerative merging spreads each round's search over `parallelism`,
+    // and Louvain the scoring of each sweep's moves.
+    pub fn cluster(
+        &self,
+        graph: &Graph,
+        initial: Option<&[Vec<usize>]>,
+        stop: Stop,
+        parallelism: Parallelism,
+    ) -> Vec<Vec<usize>> {
+        self.cluster_observed(graph, initial, stop, parallelism, &mut Unobserved)
+    }
+
+    // The same, telling `observer` of every merge or Louvain pass. When it
+    // asks to stop (a --timeout, Ctrl-C), the clusters found so far are
+    // returned: for agglomerative merging, those of the merges made.
+    pub fn cluster_observed(
+        &self,
+        graph: &Graph,
+        initial: Option<&[Vec<usize>]>,
+        stop: Stop,
+        parallelism: Parallelism,
+        observer: &mut dyn IterationObserver,
+    ) -> Vec<Vec<usize>> {
+        let clusters = match self {
+            Algorithm::Agglomerative => agglomerative(graph, initial, stop, parallelism, observer),
+            Algorithm::Louvain => {
+                louvain::louvain(graph, initial, parallelism, observer).communities
+            }
+            Algorithm::Passthrough => initial.map(<[Vec<usize>]>::to_vec).unwrap_or_default(),
+        };
+        debug_assert!(clusters.validate(graph).is_ok());
+        clusters
+    }
+
+    // Cluster the subgraph on `nodes` alone (a connected component, or a
+    // chosen set of countries), with clusters given as nodes of the whole
+    // graph. A warm start is cut down to the same nodes.
+    pub fn cluster_subgraph(
+        &self,
+        graph: &Graph,
+        nodes: &[usize],
+        initial: Option<&[Vec<usize>]>,
+        stop: Stop,
+        parallelism: Parallelism,
+        observer: &mut dyn IterationObserver,
+    ) -> Vec<Vec<usize>> {
+        let subgraph = graph.subgraph(nodes);
+        let local = |node: &usize| nodes.iter().position(|member| member == node);
+        let initial: Option<Vec<Vec<usize>>> = initial.map(|initial| {
+            initial
+                .iter()
+                .map(|cluster| cluster.iter().filter_map(local).collect::<Vec<_>>())
+                .filter(|cluster| !cluster.is_empty())
+                .collect()
+        });
+        self.cluster_observed(&subgraph, initial.as_deref(), stop, parallelism, observer)
+            .into_iter()
+            .map(|cluster| cluster.into_iter().map(|node| nodes[node]).collect())
+            .collect()
+    }
+}
+
+// When agglomerative clustering stops merging.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Stop {
+    // At about sqrt(nodes) clusters
+    #[default]
+    Auto,
+    // At this many clusters (or as many as the warm start has, if fewer)
+    Clusters(usize),
+    // Once no two clusters are linked by an average weight of at least this
+    Cutoff(f64),
+}
+
+// Agglomerative clustering with the default stopping rule, on every core.
+pub fn cluster_graph(graph: &Graph, initial: Option<&[Vec<usize>]>) -> Vec<Vec<usize>> {
+    Algorithm::Agglomerative.cluster(graph, initial, Stop::Auto, Parallelism::default())
+}
+
+// Average-linkage agglomerative clustering. The matrix is made symmetric by
+// averaging both directions, the diagonal is ignored, and the two clusters
+// with the highest mean weight between their members are merged until
+// `stop` says otherwise. Merging starts from the warm start's clusters,
+// with every node they leave out on its own; ties go to the earliest pair.
+// The starting totals and each round's search for the best pair are split
+// by rows over `parallelism`, with the same merges for any setting. The
+// observer hears the similarity of every merge and may stop the merging.
+pub fn agglomerative(
+    graph: &Graph,
+    initial: Option<&[Vec<usize>]>,
+    stop: Stop,
+    parallelism: Parallelism,
+    observer: &mut dyn IterationObserver,
+) -> Vec<Vec<usize>> {
+    agglomerate(
+        graph,
+        initial,
+        stop,
+        Linkage::Average,
+        parallelism,
+        observer,
+    )
+    .0
+}
+
+// How alike two clusters are, from the weights between their members.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Linkage {
+    // The mean weight
+    #[default]
+    Average,
+    // The heaviest weight, chaining clusters through their closest members
+    Single,
+    // The lightest weight, so every member is at least that alike
+    Complete,
+}
+
+impl FromStr for Linkage {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Linkage> {
+        match value {
+            "average" => Ok(Linkage::Average),
+            "single" => Ok(Linkage::Single),
+            "complete" => Ok(Linkage::Complete),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown linkage `{}`; expected one of {}",
+                    other,
+                    LINKAGES.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+impl Linkage {
+    // The link kept between two clusters, from the weights between their
+    // members: the total for average linkage, else the linkage itself.
+    fn link(&self, weights: impl Iterator<Item = f64>) -> f64 {
+        match self {
+            Linkage::Average => weights.sum(),
+            Linkage::Single => weights.fold(f64::NEG_INFINITY, f64::max),
+            Linkage::Complete => weights.fold(f64::INFINITY, f64::min),
+        }
+    }
+
+    // The link of a merged cluster from the links of its two parts, by the
+    // Lance-Williams update.
+    fn combine(&self, a: f64, b: f64) -> f64 {
+        match self {
+            Linkage::Average => a + b,
+            Linkage::Single => a.max(b),
+            Linkage::Complete => a.min(b),
+        }
+    }
+
+    fn similarity(&self, link: f64, sizes: (usize, usize)) -> f64 {
+        match self {
+            Linkage::Average => link / (sizes.0 * sizes.1) as f64,
+            Linkage::Single | Linkage::Complete => link,
+        }
+    }
+}
+
+// One step of agglomerative merging: the two clusters joined, numbered as
+// the leaves are and then one past them for each merge in order, and how
+// alike they were.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Merge {
+    pub clusters: [usize; 2],
+    pub similarity: f64,
+    // Nodes in the merged cluster
+    pub size: usize,
+}
+
+// Merging run to a single cluster, for dendrograms: the leaves are the
+// graph's nodes, in order, and cutting the tree after any number of merges
+// gives the clustering that stopping there would have.
+pub struct MergeTree {
+    pub leaves: usize,
+    pub linkage: Linkage,
+    pub merges: Vec<Merge>,
+}
+
+pub fn merge_tree(graph: &Graph, linkage: Linkage, parallelism: Parallelism) -> MergeTree {
+    let (_, merges) = agglomerate(
+        graph,
+        None,
+        Stop::Clusters(1),
+        linkage,
+        parallelism,
+        &mut Unobserved,
+    );
+    MergeTree {
+        leaves: graph.nodes.len(),
+        linkage,
+        merges,
+    }
+}
+
+impl MergeTree {
+    // The clusters after the merges `stop` allows, as `agglomerative` would
+    // have stopped: each sorted, in order of their first member.
+    pub fn cut(&self, stop: Stop) -> Vec<Vec<usize>> {
+        let keep = match stop {
+            Stop::Auto => ((self.leaves as f64).sqrt().round() as usize).max(1),
+            Stop::Clusters(count) => count.max(1),
+            Stop::Cutoff(cutoff) => {
+                let merged = self
+                    .merges
+                    .iter()
+                    .take_while(|merge| merge.similarity >= cutoff)
+                    .count();
+                self.leaves - merged
+            }
+        };
+        let mut members: Vec<Vec<usize>> = (0..self.leaves).map(|leaf| vec![leaf]).collect();
+        for merge in &self.merges[..self.leaves.saturating_sub(keep)] {
+            let [a, b] = merge.clusters.map(|id| std::mem::take(&mut members[id]));
+            let mut joined = [a, b].concat();
+            joined.sort_unstable();
+            members.push(joined);
+        }
+        let mut clusters: Vec<Vec<usize>> = members
+            .into_iter()
+            .filter(|cluster| !cluster.is_empty())
+            .collect();
+        clusters.sort_unstable_by_key(|cluster| cluster[0]);
+        clusters
+    }
+}
+
+// Agglomerative merging under any linkage, returning the clusters where it
+// stopped and the merges on the way.
+fn agglomerate(
+    graph: &Graph,
+    initial: Option<&[Vec<usize>]>,
+    stop: Stop,
+    linkage: Linkage,
+    parallelism: Parallelism,
+    observer: &mut dyn IterationObserver,
+) -> (Vec<Vec<usize>>, Vec<Merge>) {
+    let matrix = &graph.adjacency_matrix;
+    let node_count = graph.nodes.len();
+    let mut clusters: Vec<Vec<usize>> = initial
+        .unwrap_or_default()
+        .iter()
+        .filter(|cluster| !cluster.is_empty())
+        .cloned()
+        .collect();
+    let mut placed = vec![false; node_count];
+    for &node in clusters.iter().flatten() {
+        placed[node] = true;
+    }
+    clusters.extend(
+        (0..node_count)
+            .filter(|&node| !placed[node])
+            .map(|node| vec![node]),
+    );
+    let leaves = clusters.len();
+    let mut ids: Vec<usize> = (0..leaves).collect();
+    let mut merges = Vec::new();
+
+    let target = match stop {
+        Stop::Auto => ((node_count as f64).sqrt().round() as usize).max(1),
+        Stop::Clusters(count) => count.max(1),
+        Stop::Cutoff(_) => 1,
+    };
+    // `links[a][b]`: the link between the members of a and b
+    let weight = |i: usize, j: usize| (matrix[i][j] + matrix[j][i]) / 2.0;
+    let mut links: Vec<Vec<f64>> = parallelism.map_rows(clusters.len(), |a| {
+        clusters
+            .iter()
+            .map(|b| {
+                linkage.link(
+                    clusters[a]
+                        .iter()
+                        .flat_map(|&i| b.iter().map(move |&j| weight(i, j))),
+                )
+            })
+            .collect()
+    });
+
+    while clusters.len() > target {
+        // The best partner of each cluster among those after it, then the
+        // best of those in order, so ties still go to the earliest pair
+        let best_after = parallelism.map_rows(clusters.len(), |a| {
+            let mut best: Option<(usize, f64)> = None;
+            for b in a + 1..clusters.len() {
+                let similarity =
+                    linkage.similarity(links[a][b], (clusters[a].len(), clusters[b].len()));
+                if best.is_none_or(|(_, best)| similarity > best) {
+                    best = Some((b, similarity));
+                }
+            }
+            best
+        });
+        let mut best: Option<(usize, usize, f64)> = None;
+        for (a, row_best) in best_after.into_iter().enumerate() {
+            if let Some((b, similarity)) = row_best {
+                if best.is_none_or(|(_, _, best)| similarity > best) {
+                    best = Some((a, b, similarity));
+                }
+            }
+        }
+        let Some((a, b, similarity)) = best else {
+            break;
+        };
+        if let Stop::Cutoff(cutoff) = stop {
+            if similarity < cutoff {
+                break;
+            }
+        }
+
+        let merged = clusters.remove(b);
+        clusters[a].extend(merged);
+        clusters[a].sort_unstable();
+        merges.push(Merge {
+            clusters: [ids[a], ids.remove(b)],
+            similarity,
+            size: clusters[a].len(),
+        });
+        ids[a] = leaves + merges.len() - 1;
+        let control = observer.on_iteration(&Iteration {
+            stage: "agglomerative",
+            iteration: merges.len(),
+            total: match stop {
+                Stop::Cutoff(_) => None,
+                _ => Some(leaves.saturating_sub(target)),
+            },
+            objective: similarity,
+        });
+        let row = links.remove(b);
+        for (c, links_c) in links.iter_mut().enumerate() {
+            let from_b = links_c.remove(b);
+            if c != a {
+                links_c[a] = linkage.combine(links_c[a], from_b);
+            }
+        }
+        for (c, &link) in row.iter().enumerate().filter(|&(c, _)| c != b) {
+            let c = if c > b { c - 1 } else { c };
+            if c != a {
+                links[a][c] = linkage.combine(links[a][c], link);
+            }
+        }
+        if let Control::Stop(_) = control {
+            break;
+        }
+    }
+    (clusters, merges)
+}
+
+pub fn print_clusters(
+    writer: &mut dyn Write,
+    clusters: &[Vec<usize>],
+    graph: &Graph,
+) -> io::Result<()> {
+    debug_assert!(clusters.validate(graph).is_ok());
+    // Print the clusters, each named after its medoid
+    let labels = labels::cluster_labels(graph, clusters);
+    for (cluster_index, (cluster, label)) in clusters.iter().zip(&labels).enumerate() {
+        writeln!(writer, "Cluster {} ({}):", cluster_index, label)?;
+        for &node_index in cluster {
+            writeln!(writer, "  - {}", graph.nodes[node_index])?;
+        }
+    }
+
+    // Print the adjacency matrix for debugging and visualization
+    writeln!(writer, "\nAdjacency Matrix:")?;
+    for row in &graph.adjacency_matrix {
+        writeln!(writer, "{:?}", row)?;
+    }
+
+    Ok(())
+}
+
+// The clusters as JSON for scripts: node names, each cluster's label and
+// members, their weighted modularity (`louvain::modularity`), every non-zero
+// edge weight by node name and, when given, the
+// objective traces of the run that found them (`ObjectiveTrace::to_json`).
+pub fn write_clusters_json(
+    writer: &mut dyn Write,
+    clusters: &[Vec<usize>],
+    graph: &Graph,
+    convergence: Option<&Json>,
+) -> io::Result<()> {
+    debug_assert!(clusters.validate(graph).is_ok());
+    let labels = labels::cluster_labels(graph, clusters);
+    let names = |members: &[usize]| {
+        Json::Array(
+            members
+                .iter()
+                .map(|&node| Json::from(graph.nodes[node].as_str()))
+                .collect(),
+        )
+    };
+    let all: Vec<usize> = (0..graph.nodes.len()).collect();
+    let clusters_json = clusters
+        .iter()
+        .zip(&labels)
+        .enumerate()
+        .map(|(index, (cluster, label))| {
+            Json::object()
+                .with("id", index)
+                .with("label", label.as_str())
+                .with("members", names(cluster))
+        })
+        .collect();
+    let mut edges = Vec::new();
+    for (from, row) in graph.adjacency_matrix.iter().enumerate() {
+        for (to, &weight) in row.iter().enumerate() {
+            if weight != 0.0 {
+                edges.push(
+                    Json::object()
+                        .with("source", graph.nodes[from].as_str())
+                        .with("target", graph.nodes[to].as_str())
+                        .with("weight", weight),
+                );
+            }
+        }
+    }
+    let document = Json::object()
+        .with("nodes", names(&all))
+        .with("clusters", Json::Array(clusters_json))
+        .with("modularity", louvain::modularity(graph, clusters))
+        .with("edges", Json::Array(edges));
+    let document = match convergence {
+        Some(convergence) => document.with("convergence", convergence.clone()),
+        None => document,
+    };
+    writeln!(writer, "{}", document.to_pretty_string())
+}
+
+// One `country,cluster,label` row per node, in node order; nodes outside
+// every cluster have empty cluster cells.
+pub fn write_clusters_csv(
+    writer: &mut dyn Write,
+    clusters: &[Vec<usize>],
+    graph: &Graph,
+) -> io::Result<()> {
+    debug_assert!(clusters.validate(graph).is_ok());
+    let labels = labels::cluster_labels(graph, clusters);
+    let mut membership = vec![None; graph.nodes.len()];
+    for (index, cluster) in clusters.iter().enumerate() {
+        for &node in cluster {
+            membership[node] = Some(index);
+        }
+    }
+    let mut table = Table::new(&["country", "cluster", "label"]);
+    for (node, cluster) in membership.into_iter().enumerate() {
+        table.push_row(vec![
+            graph.nodes[node].clone(),
+            cluster.map_or_else(String::new, |index| index.to_string()),
+            cluster.map_or_else(String::new, |index| labels[index].clone()),
+        ]);
+    }
+    table.write_csv(writer)
+}
+
+// Checks on a clustering (clusters of node indices) against the graph it
+// partitions. Everything that prints, labels or saves clusters indexes
+// `graph.nodes` with the members, so a bad index would panic there; loaded
+// clusterings are validated up front, and freshly computed ones are
+// debug-asserted where they are produced and consumed.
+pub trait Clustering {
+    // Every member must be one of `nodes`, and no node may appear more than
+    // once, in the same cluster or in two.
+    fn validate_over(&self, nodes: &[String]) -> io::Result<()>;
+
+    // The same over the nodes of `graph`.
+    fn validate(&self, graph: &Graph) -> io::Result<()> {
+        self.validate_over(&graph.nodes)
+    }
+}
+
+impl Clustering for [Vec<usize>] {
+    fn validate_over(&self, nodes: &[String]) -> io::Result<()> {
+        let node_count = nodes.len();
+        let mut cluster_of = vec![None; node_count];
+        for (cluster_index, cluster) in self.iter().enumerate() {
+            for &node_index in cluster {
+                if node_index >= node_count {
+                    return Err(invalid_data(format!(
+                        "cluster {} refers to node {}, but the graph has {} nodes",
+                        cluster_index, node_index, node_count
+                    )));
+                }
+                if let Some(first) = cluster_of[node_index].replace(cluster_index) {
+                    return Err(invalid_data(format!(
+                        "node {} appears in cluster {} and again in cluster {}",
+                        nodes[node_index], first, cluster_index
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // What `func` writes, as a string
+    fn capture_output<F>(func: F) -> String
+    where
+        F: FnOnce(&mut dyn Write),
+    {
+        let mut buffer = Cursor::new(Vec::new());
+        func(&mut buffer);
+        String::from_utf8(buffer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_print_clusters() {
+        // Define nodes
+        let nodes = vec!["USA".to_string(), "Canada".to_string()];
+
+        // Define clusters
+        let clusters = vec![
+            vec![0], // Cluster containing USA
+            vec![1], // Cluster containing Canada
+        ];
+
+        // Define an adjacency matrix for the graph
+        let adjacency_matrix = vec![
+            vec![1.0, 0.5], // USA to USA and USA to Canada
+            vec![0.5, 2.0], // Canada to USA and Canada to Canada
+        ];
+
+        // Create a graph struct with nodes and adjacency matrix
+        let graph = Graph {
+            nodes: nodes.clone(),
+            adjacency_matrix: adjacency_matrix.clone(),
+        };
+
+        // Capture the output of the print_clusters function
+        let output = capture_output(|writer| print_clusters(writer, &clusters, &graph).unwrap());
+
+        // Clean up the captured output to remove extra newlines
+        let cleaned_output = output.trim_end().to_string();
+
+        // Assert expected output
+        let expected_output = "Cluster 0 (USA):\n  - USA\nCluster 1 (Canada):\n  - Canada\n\nAdjacency Matrix:\n[1.0, 0.5]\n[0.5, 2.0]";
+        assert_eq!(cleaned_output, expected_output);
+    }
+
+    #[test]
+    fn test_json_and_csv_reports() {
+        let graph = Graph {
+            nodes: vec!["USA".to_string(), "Canada".to_string(), "Mali".to_string()],
+            adjacency_matrix: vec![
+                vec![0.0, 0.5, 0.0],
+                vec![0.5, 0.0, 0.0],
+                vec![0.0, 0.0, 0.0],
+            ],
+        };
+        let clusters = vec![vec![1, 0]];
+        let json =
+            capture_output(|writer| write_clusters_json(writer, &clusters, &graph, None).unwrap());
+        let document = Json::parse(&json).unwrap();
+        assert_eq!(document.get("convergence"), None);
+        // All the weight is inside the cluster, as chance would put it
+        assert_eq!(document.get("modularity").unwrap().as_f64(), Some(0.0));
+        assert_eq!(document.get("nodes").unwrap().as_array().unwrap().len(), 3);
+        let cluster = &document.get("clusters").unwrap().as_array().unwrap()[0];
+        assert_eq!(cluster.get("label").unwrap().as_str(), Some("Canada"));
+        let edges = document.get("edges").unwrap().as_array().unwrap();
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0].get("target").unwrap().as_str(), Some("Canada"));
+        assert_eq!(edges[0].get("weight").unwrap().as_f64(), Some(0.5));
+
+        let csv = capture_output(|writer| write_clusters_csv(writer, &clusters, &graph).unwrap());
+        assert_eq!(
+            csv,
+            "country,cluster,label\nUSA,0,Canada\nCanada,0,Canada\nMali,,\n"
+        );
+    }
+
+    #[test]
+    fn test_validate_members() {
+        let graph = Graph {
+            nodes: vec!["Chad".to_string(), "Mali".to_string(), "Niger".to_string()],
+            adjacency_matrix: vec![vec![0.0; 3]; 3],
+        };
+        assert!([vec![0, 2], vec![1]].validate(&graph).is_ok());
+        assert!([vec![0], vec![]].validate(&graph).is_ok());
+
+        let error = [vec![0, 3]].validate(&graph).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "cluster 0 refers to node 3, but the graph has 3 nodes"
+        );
+        let error = [vec![0, 1], vec![2, 1]].validate(&graph).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "node Mali appears in cluster 0 and again in cluster 1"
+        );
+        assert!([vec![2, 2]].validate(&graph).is_err());
+    }
+
+    #[test]
+    fn test_agglomerative_merges_the_closest_pair() {
+        // Averaged both ways: Chad-Mali 250, Chad-Niger 350, Mali-Niger 300
+        let graph = Graph {
+            nodes: vec!["Chad".to_string(), "Mali".to_string(), "Niger".to_string()],
+            adjacency_matrix: vec![
+                vec![300.5, 100.5, 100.5],
+                vec![400.0, 400.0, 0.0],
+                vec![600.0, 600.0, 600.0],
+            ],
+        };
+        assert_eq!(cluster_graph(&graph, None), [vec![0, 2], vec![1]]);
+        let merge = |stop| {
+            agglomerative(
+                &graph,
+                None,
+                stop,
+                Parallelism::sequential(),
+                &mut Unobserved,
+            )
+        };
+        assert_eq!(merge(Stop::Clusters(1)), [vec![0, 1, 2]]);
+        assert_eq!(merge(Stop::Clusters(5)), [vec![0], vec![1], vec![2]]);
+        // {Chad, Niger} to Mali averages (250 + 300) / 2
+        assert_eq!(merge(Stop::Cutoff(300.0)), [vec![0, 2], vec![1]]);
+        assert_eq!(merge(Stop::Cutoff(275.0)), [vec![0, 1, 2]]);
+
+        // A warm start is merged further, never split; Niger joins it alone
+        let warm = [vec![1, 0], vec![]];
+        assert_eq!(
+            agglomerative(
+                &graph,
+                Some(&warm),
+                Stop::Clusters(2),
+                Parallelism::sequential(),
+                &mut Unobserved
+            ),
+            [vec![1, 0], vec![2]]
+        );
+        let passthrough = Algorithm::Passthrough.cluster(
+            &graph,
+            Some(&warm),
+            Stop::Auto,
+            Parallelism::sequential(),
+        );
+        assert_eq!(passthrough, warm);
+        assert_eq!("louvain".parse::<Algorithm>().unwrap(), Algorithm::Louvain);
+        assert!("kmeans".parse::<Algorithm>().is_err());
+
+        // Mali and Niger on their own merge, with the warm start cut to Mali
+        let sub = Algorithm::Agglomerative.cluster_subgraph(
+            &graph,
+            &[1, 2],
+            Some(&warm),
+            Stop::Clusters(1),
+            Parallelism::sequential(),
+            &mut Unobserved,
+        );
+        assert_eq!(sub, [vec![1, 2]]);
+        let kept = Algorithm::Passthrough.cluster_subgraph(
+            &graph,
+            &[2, 1],
+            Some(&warm),
+            Stop::Auto,
+            Parallelism::sequential(),
+            &mut Unobserved,
+        );
+        assert_eq!(kept, [vec![1]]);
+
+        // Timed out, merging stops after its first merge
+        let mut cancel = crate::cancel::CancelToken::new().with_timeout(std::time::Duration::ZERO);
+        let partial = Algorithm::Agglomerative.cluster_observed(
+            &graph,
+            None,
+            Stop::Clusters(1),
+            Parallelism::sequential(),
+            &mut cancel,
+        );
+        assert_eq!(partial, [vec![0, 2], vec![1]]);
+
+        // Split over threads, the merges come out the same, ties included
+        let nodes = 40;
+        let graph = Graph {
+            nodes: (0..nodes).map(|node| node.to_string()).collect(),
+            adjacency_matrix: (0..nodes)
+                .map(|i| (0..nodes).map(|j| ((i * j) % 7) as f64).collect())
+                .collect(),
+        };
+        let threaded = Parallelism {
+            threads: 4,
+            chunk_size: 3,
+        };
+        for stop in [Stop::Auto, Stop::Clusters(3), Stop::Cutoff(3.0)] {
+            assert_eq!(
+                agglomerative(&graph, None, stop, threaded, &mut Unobserved),
+                agglomerative(
+                    &graph,
+                    None,
+                    stop,
+                    Parallelism::sequential(),
+                    &mut Unobserved
+                )
+            );
+        }
+    }
+}
@@ -0,0 +1,2911 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::anonymize::Anonymizer;
+use crate::auth::ApiKeys;
+use crate::binning;
+use crate::cancel::{CancelToken, RunStatus};
+use crate::catalog::SeriesCatalog;
+use crate::centrality::{self, Measure};
+use crate::changepoint::{self, Detector};
+use crate::chart;
+use crate::cli::{invalid_input, Matches};
+use crate::cluster::{self, Algorithm, Linkage, Stop};
+use crate::completeness;
+use crate::completions;
+use crate::composite;
+use crate::config::Config;
+use crate::console::{self, note};
+use crate::convergence;
+use crate::country_export;
+use crate::csv;
+use crate::data::{self, ParseMode};
+use crate::datadiff;
+use crate::dendrogram;
+use crate::dump;
+use crate::eigen;
+use crate::engine::Engine;
+use crate::features::{self, Scaling};
+use crate::fetch;
+use crate::filter::{self, DataFilter, Filter};
+use crate::granger::{self, GrangerTest};
+use crate::graph::{
+    construct_similarity_graph_with, similarity_graph_of, sparse_similarity_graph_of,
+};
+use crate::graphcompare;
+use crate::history;
+use crate::http;
+use crate::impute::Imputation;
+use crate::inequality;
+use crate::jobs::JobQueue;
+use crate::json::Json;
+use crate::kmeans;
+use crate::labels;
+use crate::leadlag::{self, History, LeadLag};
+use crate::louvain;
+use crate::manifest::{self, Manifest};
+use crate::mat;
+use crate::matrix::{self, MatrixBackend};
+use crate::movers;
+use crate::names::{self, Names};
+use crate::notebook;
+use crate::notify::Notifier;
+use crate::npy;
+use crate::observer::{IterationObserver, ObjectiveTrace, ProgressPrinter, Unobserved};
+use crate::ordering::{self, NodeOrder};
+use crate::parallel::Parallelism;
+use crate::paths::{self, Length};
+use crate::pivot::{self as crosstab, PivotSpec};
+use crate::profile;
+use crate::quality::{self, Selection};
+use crate::random::Rng;
+use crate::rank as ranking;
+use crate::reference::{self, ReferenceData};
+use crate::registry::Registry;
+use crate::rundir::{self, RunDir};
+use crate::sample;
+use crate::server::{self, Service};
+use crate::similar;
+use crate::similarity_cache;
+use crate::source;
+use crate::stability::{self, Split};
+use crate::stats::{self, Ties};
+use crate::store::Store;
+use crate::sweep::{self as grid_search, SweepPlan};
+use crate::symmetry::GraphPolicy;
+use crate::table;
+use crate::temporal;
+use crate::trajectory;
+use crate::transform::{TransformPlan, Transforms};
+use crate::trend;
+use crate::unpivot::{self, YearPattern};
+use crate::weights;
+use crate::{
+    artifact, construct_value_graph, construct_value_graph_from_records, load_and_preprocess_data,
+    print_clusters, EducationData, Graph, SimilarityMetric,
+};
+
+pub const REPORT_FORMATS: &[&str] = &["text", "html", "json", "csv", "dot", "gexf"];
+pub const SUMMARY_FORMATS: &[&str] = &["text", "json"];
+
+// Dispatch a parsed command line to the stage it names. Commands that can
+// be cancelled report whether they ran to completion.
+pub fn run(matches: &Matches) -> io::Result<RunStatus> {
+    match matches.command.name {
+        "run" => return run_pipeline(matches),
+        "sweep" => return sweep(matches),
+        "load" => load(matches)?,
+        "fetch" => fetch_tables(matches)?,
+        "ingest" => ingest(matches)?,
+        "build" => build(matches)?,
+        "cluster" => return cluster(matches),
+        "dendrogram" => dendrogram(matches)?,
+        "history" => cluster_history(matches)?,
+        "analyze" => analyze(matches)?,
+        "weights" => weight_histogram(matches)?,
+        "centrality" => central_countries(matches)?,
+        "path" => country_path(matches)?,
+        "similar" => similar_countries(matches)?,
+        "export" => export(matches)?,
+        "export-countries" => export_countries(matches)?,
+        "pivot" => pivot(matches)?,
+        "rank" => rank(matches)?,
+        "inequality" => measure_inequality(matches)?,
+        "convergence" => test_convergence(matches)?,
+        "top-movers" => top_movers(matches)?,
+        "stability" => split_stability(matches)?,
+        "temporal" => temporal_changes(matches)?,
+        "changepoints" => changepoints(matches)?,
+        "leadlag" => lead_lag(matches)?,
+        "granger" => granger_edges(matches)?,
+        "bins" => bins(matches)?,
+        "kmeans" => return kmeans(matches),
+        "composite" => composite(matches)?,
+        "completeness" => data_completeness(matches)?,
+        "chart" => chart(matches)?,
+        "trajectories" => cluster_trajectories(matches)?,
+        "arrays" => arrays(matches)?,
+        "serve" => serve(matches)?,
+        "diff-data" => diff_data(matches)?,
+        "compare-graphs" => compare_graphs(matches)?,
+        "anonymize" => anonymize(matches)?,
+        "reference" => reference(matches)?,
+        "completions" => {
+            let mut output = console::stdout();
+            completions::write_completions(&mut output, matches.required("shell"))?
+        }
+        other => unreachable!("subcommand {} has no handler", other),
+    }
+    Ok(RunStatus::Completed)
+}
+
+fn run_pipeline(matches: &Matches) -> io::Result<RunStatus> {
+    let mut cancel = cancel_token(matches)?;
+    let input = match (matches.value("input"), matches.flag("demo")) {
+        (Some(_), true) => {
+            return Err(invalid_input(
+                "--demo and --input cannot be combined".to_string(),
+            ))
+        }
+        (Some(input), false) => input,
+        (None, true) => source::DEMO_LOCATION,
+        (None, false) => {
+            return Err(invalid_input(
+                "`run` needs --input PATH, or --demo for the built-in sample".to_string(),
+            ))
+        }
+    };
+    if matches.value("out-dir").is_some() && matches.value("output").is_some() {
+        return Err(invalid_input(
+            "--out-dir puts the report in the run directory and cannot be combined with --output"
+                .to_string(),
+        ));
+    }
+    if matches.value("run-id").is_some() && matches.value("out-dir").is_none() {
+        return Err(invalid_input(
+            "--run-id names a run directory and needs --out-dir".to_string(),
+        ));
+    }
+    let mut manifest = start_manifest(matches);
+    if let Some(path) = matches.value("pipeline") {
+        manifest.input(path)?;
+    }
+    let input = source::open_location(input)?;
+    manifest.input_source(input.as_ref())?;
+
+    // A cancelled run stops reading and clustering, and reports the clusters
+    // found by then.
+    let layout = csv_layout(matches)?;
+    let filter = observation_filter(matches)?;
+    let mode = parse_mode(matches);
+    let selection = data_filter(matches)?;
+    let mut names = country_names(matches)?;
+    let policy = graph_policy(matches)?;
+    let similarity = similarity_metric(matches)?;
+    let parallelism = parallelism(matches)?;
+    // Merging two names' records needs them all at hand
+    let whole_data = ["transforms", "trend", "sample", "impute", "dump-cleaned"]
+        .iter()
+        .any(|name| matches.value(name).is_some())
+        || names
+            .as_ref()
+            .is_some_and(|names| names.options().merges_records());
+    let mut graph = if similarity.is_some() || whole_data {
+        let mut data = manifest.time("load", || {
+            data::load_with_mode(input.as_ref(), &layout, mode)
+        })?;
+        apply_filter(filter.as_ref(), &mut data);
+        selection.apply(&mut data);
+        if let Some(names) = names.as_mut() {
+            names.apply_all(&mut data);
+        }
+        sample_observations(matches, &mut manifest, &mut data)?;
+        impute_values(matches, &mut data)?;
+        transform_values(matches, &mut manifest, &mut data)?;
+        smooth_to_trend(matches, &mut data)?;
+        dump_cleaned(matches, &data)?;
+        match similarity {
+            Some(metric) => manifest.time("build", || {
+                similarity_graph(matches, &data, metric, parallelism)
+            })?,
+            None => manifest.time("build", || construct_value_graph(&data)),
+        }
+    } else {
+        // The value graph takes each record once, in order, so the rows go
+        // from the input straight into it instead of being held first (and
+        // a cancelled run stops reading)
+        let mut reader = data::EducationDataReader::open(input.as_ref(), &layout, mode)?;
+        let records = reader
+            .by_ref()
+            .take_while(|_| !cancel.should_stop())
+            .filter(|record| match record {
+                Ok(record) => {
+                    filter.as_ref().is_none_or(|filter| filter.matches(record))
+                        && selection.matches(record)
+                }
+                Err(_) => true,
+            })
+            .filter_map(|record| match (record, names.as_mut()) {
+                (Ok(mut record), Some(names)) => names.apply(&mut record).then_some(Ok(record)),
+                (record, _) => Some(record),
+            });
+        let graph = manifest.time("load and build", || {
+            construct_value_graph_from_records(records)
+        })?;
+        data::report_problems(input.location(), reader.problems());
+        graph
+    };
+    report_names(names.as_ref());
+    policy.apply(&mut graph);
+    dump_distances(matches, &graph)?;
+    let algorithm: Algorithm = matches.parse_value("algo")?.unwrap_or_default();
+    let stop = stop(matches)?;
+    let mut trace = ObjectiveTrace::new();
+    let clusters = manifest.time("cluster", || {
+        let mut observers: Vec<&mut dyn IterationObserver> = vec![&mut cancel, &mut trace];
+        algorithm.cluster_observed(&graph, None, stop, parallelism, &mut observers)
+    });
+    manifest.set_convergence(&trace);
+    let convergence = trace.to_json();
+    let status = stopped_early(&mut manifest, cancel.status());
+    if let Some(path) = matches.value("history") {
+        history::append_run(path, &manifest, &graph, &clusters)?;
+    }
+
+    let (graph, clusters) = ordering::ordered(&graph, &clusters, node_order(matches)?);
+    let title = format!("Clusters of {}", input.location());
+    let Some(root) = matches.value("out-dir") else {
+        let mut output = open_output(matches.value("output"))?;
+        write_report(
+            &mut output,
+            matches.value("format"),
+            &title,
+            &graph,
+            &clusters,
+            Some(&convergence),
+        )?;
+        output.flush()?;
+        write_manifest(&manifest, matches.value("output"))?;
+        return Ok(status);
+    };
+
+    let run = RunDir::create(Path::new(root), matches.value("run-id"), SystemTime::now())?;
+    let format = matches.value("format").unwrap_or("text");
+    let report = run.file(rundir::report_file(format));
+    let mut output = open_output(Some(&report))?;
+    write_report(
+        &mut output,
+        Some(format),
+        &title,
+        &graph,
+        &clusters,
+        Some(&convergence),
+    )?;
+    output.flush()?;
+    fs::write(run.file("graph.dot"), graph.to_dot(Some(&clusters)))?;
+    history::append_run(&run.file("history.jsonl"), &manifest, &graph, &clusters)?;
+    write_manifest(&manifest, Some(&report))?;
+    note!("Run {} written to {}", run.id, run.path.display());
+    Ok(status)
+}
+
+fn load(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    let data = read_input(matches, &mut manifest)?;
+    artifact::save_dataset(matches.required("save"), &data)?;
+    note!(
+        "Loaded {} records into {}",
+        data.len(),
+        matches.required("save")
+    );
+    write_manifest(&manifest, Some(matches.required("save")))
+}
+
+fn fetch_tables(matches: &Matches) -> io::Result<()> {
+    let locations: Vec<String> = matches
+        .required("inputs")
+        .split(',')
+        .map(str::trim)
+        .filter(|location| !location.is_empty())
+        .map(str::to_string)
+        .collect();
+    let concurrency = matches.parse_value::<usize>("concurrency")?.unwrap_or(4);
+    if concurrency == 0 {
+        return Err(invalid_input(
+            "--concurrency must be at least 1".to_string(),
+        ));
+    }
+    let retries = matches.parse_value::<u32>("retries")?.unwrap_or(3);
+    let backoff = matches.parse_value::<f64>("backoff")?.unwrap_or(1.0);
+    let rate = matches.parse_value::<f64>("rate")?.unwrap_or(2.0);
+    if !backoff.is_finite() || backoff < 0.0 || !rate.is_finite() || rate < 0.0 {
+        return Err(invalid_input(
+            "--backoff and --rate must be non-negative numbers".to_string(),
+        ));
+    }
+    let retry = http::Retry {
+        attempts: retries.saturating_add(1),
+        backoff: Duration::from_secs_f64(backoff),
+    };
+    let client = http::Client::new(retry, Some(rate));
+    let expected = matches.value("sha256");
+    if expected.is_some_and(|digest| !fetch::is_digest(digest)) {
+        return Err(invalid_input(
+            "--sha256 needs a 64-digit hex digest".to_string(),
+        ));
+    }
+    if expected.is_some() && locations.len() != 1 {
+        return Err(invalid_input(
+            "--sha256 checks a single input; use --lock for several".to_string(),
+        ));
+    }
+    let mut lock = match matches.value("lock") {
+        Some(path) => Some(fetch::Lockfile::load(path)?),
+        None => None,
+    };
+    let names = fetch::file_names(&locations)?;
+    let dir = Path::new(matches.required("out-dir"));
+    fs::create_dir_all(dir)?;
+
+    // Save what arrived and checks out even when other tables failed, then
+    // report those. Files are written beside their final name and renamed,
+    // so an interrupted save never leaves a partial table in place.
+    let mut failed = 0;
+    let fetched = fetch::fetch_all(&locations, concurrency, &client);
+    for ((location, name), bytes) in locations.iter().zip(&names).zip(fetched) {
+        let locked = lock.as_ref().and_then(|lock| lock.digest(location));
+        match bytes.and_then(|bytes| {
+            let digest = fetch::verify(location, &bytes, expected.or(locked))?;
+            let path = dir.join(name);
+            let partial = dir.join(format!("{}.part", name));
+            fs::write(&partial, &bytes)?;
+            fs::rename(&partial, &path)?;
+            Ok((path, bytes.len(), digest))
+        }) {
+            Ok((path, size, digest)) => {
+                note!(
+                    "Fetched {} ({} bytes, SHA-256 {}) to {}",
+                    location,
+                    size,
+                    digest,
+                    path.display()
+                );
+                if let Some(lock) = &mut lock {
+                    lock.record(location, &digest);
+                }
+            }
+            Err(error) => {
+                note!("Could not fetch {}: {}", location, error);
+                failed += 1;
+            }
+        }
+    }
+    if let (Some(lock), Some(path)) = (&lock, matches.value("lock")) {
+        lock.save(path)?;
+    }
+    if failed > 0 {
+        return Err(io::Error::other(format!(
+            "{} of {} tables could not be fetched",
+            failed,
+            locations.len()
+        )));
+    }
+    Ok(())
+}
+
+fn ingest(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    let data = read_input(matches, &mut manifest)?;
+
+    let path = matches.required("store");
+    let mut store = manifest.time("open", || Store::open(path))?;
+    let summary = manifest.time("ingest", || store.ingest(data))?;
+    note!(
+        "Ingested into {}: {} added, {} updated, {} unchanged ({} observations)",
+        path,
+        summary.added,
+        summary.updated,
+        summary.unchanged,
+        store.len()
+    );
+    if matches.flag("compact") {
+        let reclaimed = manifest.time("compact", || store.compact())?;
+        note!(
+            "Compacted {}, dropping {} superseded records",
+            path,
+            reclaimed
+        );
+    }
+    write_manifest(&manifest, Some(path))
+}
+
+// Load the `--input` of `load`/`ingest`, honouring the CSV layout options.
+fn read_input(matches: &Matches, manifest: &mut Manifest) -> io::Result<Vec<EducationData>> {
+    let input = source::open_location(matches.required("input"))?;
+    manifest.input_source(input.as_ref())?;
+
+    let layout = csv_layout(matches)?;
+    let filter = observation_filter(matches)?;
+    let mode = parse_mode(matches);
+    let mut data = match matches.value("year-columns") {
+        Some(_) if mode == ParseMode::Strict => Err(invalid_input(
+            "--strict checks the long layout and cannot be combined with --year-columns"
+                .to_string(),
+        )),
+        // Wide layout: one column per year, melted into long records
+        Some(pattern) => {
+            let pattern = YearPattern::parse(pattern)?;
+            manifest.time("load", || {
+                unpivot::load_wide(input.as_ref(), &layout, &pattern)
+            })
+        }
+        None => manifest.time("load", || {
+            data::load_with_mode(input.as_ref(), &layout, mode)
+        }),
+    }?;
+    apply_filter(filter.as_ref(), &mut data);
+    Ok(data)
+}
+
+fn build(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    data_filter(matches)?.apply(&mut data);
+    if let Some(mut names) = country_names(matches)? {
+        names.apply_all(&mut data);
+        report_names(Some(&names));
+    }
+    sample_observations(matches, &mut manifest, &mut data)?;
+    impute_values(matches, &mut data)?;
+    transform_values(matches, &mut manifest, &mut data)?;
+    smooth_to_trend(matches, &mut data)?;
+    dump_cleaned(matches, &data)?;
+    let hamming_bins = bin_count(matches, "hamming-bins")?;
+    let given_similarity = matches.value("similarity").is_some();
+    if matches.flag("profile") && (hamming_bins.is_some() || given_similarity) {
+        return Err(invalid_input(
+            "--profile reports on the value graph and cannot be combined with --hamming-bins or --similarity"
+                .to_string(),
+        ));
+    }
+    if hamming_bins.is_some() && given_similarity {
+        return Err(invalid_input(
+            "--hamming-bins and --similarity are different graphs; pick one".to_string(),
+        ));
+    }
+    let similarity = similarity_metric(matches)?;
+    let policy = graph_policy(matches)?;
+    let parallelism = parallelism(matches)?;
+    let mut graph = if let Some(bins) = hamming_bins {
+        manifest.time("build", || {
+            binning::quantile_bins(&data, bins, None).hamming_graph()
+        })
+    } else if matches.flag("profile") {
+        let (graph, profiles) = manifest.time("build", || profile::profile_series(&data));
+        note!("Series contributions:");
+        profile::profile_table(&profiles).write_text(&mut console::stderr())?;
+        graph
+    } else if let Some(metric) = similarity {
+        manifest.time("build", || {
+            similarity_graph(matches, &data, metric, parallelism)
+        })?
+    } else {
+        manifest.time("build", || construct_value_graph(&data))
+    };
+    policy.apply(&mut graph);
+    dump_distances(matches, &graph)?;
+    artifact::save_graph(matches.required("save"), &graph)?;
+    note!(
+        "Built a graph with {} nodes into {}",
+        graph.nodes.len(),
+        matches.required("save")
+    );
+    write_manifest(&manifest, Some(matches.required("save")))
+}
+
+fn cluster(matches: &Matches) -> io::Result<RunStatus> {
+    let mut cancel = cancel_token(matches)?;
+    let mut manifest = start_manifest(matches);
+    manifest.input(matches.required("graph"))?;
+
+    let graph = artifact::load_graph(matches.required("graph"))?;
+    // Warm start from a previous run, e.g. the clustering of an earlier year
+    let initial = match matches.value("init") {
+        Some(path) => {
+            manifest.input(path)?;
+            Some(artifact::load_clusters(path, &graph)?)
+        }
+        None => None,
+    };
+    let algorithm: Algorithm = matches.parse_value("algo")?.unwrap_or_default();
+    let pieces = graph_pieces(matches, &graph)?;
+    let parallelism = parallelism(matches)?;
+    let cluster_with = |stop: Stop, observer: &mut dyn IterationObserver| match &pieces {
+        Some(pieces) => pieces
+            .iter()
+            .flat_map(|nodes| {
+                algorithm.cluster_subgraph(
+                    &graph,
+                    nodes,
+                    initial.as_deref(),
+                    stop,
+                    parallelism,
+                    observer,
+                )
+            })
+            .collect(),
+        None => algorithm.cluster_observed(&graph, initial.as_deref(), stop, parallelism, observer),
+    };
+    let stop = if matches.value("clusters") == Some("auto") {
+        if algorithm != Algorithm::Agglomerative || matches.value("cutoff").is_some() {
+            return Err(invalid_input(
+                "--clusters auto sweeps agglomerative merging and cannot be combined with --cutoff"
+                    .to_string(),
+            ));
+        }
+        // Every number of clusters up to --max-clusters, scored by modularity
+        let most = matches.parse_value::<usize>("max-clusters")?.unwrap_or(10);
+        let curve: Vec<(usize, f64)> = manifest.time("sweep", || {
+            (2..=most.min(graph.nodes.len()))
+                .map(|k| {
+                    let clusters: Vec<Vec<usize>> =
+                        cluster_with(Stop::Clusters(k), &mut Unobserved);
+                    (k, louvain::modularity(&graph, &clusters))
+                })
+                .collect()
+        });
+        let selection: Selection = matches.parse_value("select")?.unwrap_or_default();
+        let Some(chosen) = selection.choose(&curve) else {
+            return Err(invalid_input(
+                "--clusters auto needs a graph of at least 2 countries".to_string(),
+            ));
+        };
+        quality::sweep_table("modularity", &curve, Some(chosen))
+            .write_text(&mut console::stderr())?;
+        Stop::Clusters(chosen)
+    } else {
+        stop(matches)?
+    };
+    let mut trace = ObjectiveTrace::new();
+    let mut clusters = manifest.time("cluster", || {
+        let mut observers: Vec<&mut dyn IterationObserver> = vec![&mut cancel, &mut trace];
+        cluster_with(stop, &mut observers)
+    });
+    manifest.set_convergence(&trace);
+    let status = stopped_early(&mut manifest, cancel.status());
+    // Keep "Cluster 3" meaning the same thing as in the previous run
+    if let Some(path) = matches.value("align") {
+        manifest.input(path)?;
+        let previous = artifact::load_clusters(path, &graph)?;
+        clusters = labels::align_to_previous(&previous, clusters);
+    }
+    artifact::save_clusters(matches.required("save"), &clusters, &graph)?;
+    if let Some(path) = matches.value("history") {
+        history::append_run(path, &manifest, &graph, &clusters)?;
+    }
+    note!(
+        "Found {} clusters (modularity {:.4}), saved to {}",
+        clusters.len(),
+        louvain::modularity(&graph, &clusters),
+        matches.required("save")
+    );
+    write_manifest(&manifest, Some(matches.required("save")))?;
+    Ok(status)
+}
+
+// The parts of the graph `cluster` clusters apart: the `--countries` given,
+// each connected component (`--components`, of those countries if both are
+// given), or None to cluster the whole graph at once.
+fn dendrogram(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input(matches.required("graph"))?;
+    let graph = artifact::load_graph(matches.required("graph"))?;
+    if graph.nodes.is_empty() {
+        return Err(invalid_input("the graph has no nodes to merge".to_string()));
+    }
+    let cut = ["clusters", "cutoff"]
+        .iter()
+        .any(|name| matches.value(name).is_some());
+    if cut && matches.value("save").is_none() {
+        return Err(invalid_input(
+            "--clusters and --cutoff cut the tree for --save".to_string(),
+        ));
+    }
+    let stop = stop(matches)?;
+    let linkage: Linkage = matches.parse_value("linkage")?.unwrap_or_default();
+    let format: dendrogram::Format = matches.parse_value("format")?.unwrap_or_default();
+    let parallelism = parallelism(matches)?;
+    let tree = manifest.time("merge", || {
+        cluster::merge_tree(&graph, linkage, parallelism)
+    });
+
+    let mut output = open_output(matches.value("output"))?;
+    match format {
+        dendrogram::Format::Newick => {
+            output.write_all(dendrogram::to_newick(&tree, &graph).as_bytes())?
+        }
+        dendrogram::Format::Json => writeln!(
+            output,
+            "{}",
+            dendrogram::to_json(&tree, &graph).to_pretty_string()
+        )?,
+    }
+    output.flush()?;
+    write_manifest(&manifest, matches.value("output"))?;
+
+    if let Some(path) = matches.value("save") {
+        let clusters = tree.cut(stop);
+        artifact::save_clusters(path, &clusters, &graph)?;
+        note!("Cut {} clusters, saved to {}", clusters.len(), path);
+        write_manifest(&manifest, Some(path))?;
+    }
+    Ok(())
+}
+
+fn graph_pieces(matches: &Matches, graph: &Graph) -> io::Result<Option<Vec<Vec<usize>>>> {
+    let chosen = match matches.value("countries") {
+        Some(list) => {
+            let mut nodes = Vec::new();
+            for country in list
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+            {
+                let node = graph
+                    .nodes
+                    .iter()
+                    .position(|name| name == country)
+                    .ok_or_else(|| invalid_input(format!("{} is not in the graph", country)))?;
+                if !nodes.contains(&node) {
+                    nodes.push(node);
+                }
+            }
+            Some(nodes)
+        }
+        None => None,
+    };
+    if !matches.flag("components") {
+        return Ok(chosen.map(|nodes| vec![nodes]));
+    }
+    Ok(Some(match chosen {
+        Some(nodes) => {
+            let subgraph = graph.subgraph(&nodes);
+            subgraph
+                .connected_components()
+                .into_iter()
+                .map(|component| component.into_iter().map(|node| nodes[node]).collect())
+                .collect()
+        }
+        None => graph.connected_components(),
+    }))
+}
+
+fn cluster_history(matches: &Matches) -> io::Result<()> {
+    let entries = history::read_history(matches.required("log"))?;
+    let table = match matches.value("country") {
+        Some(country) => {
+            if entries
+                .iter()
+                .all(|entry| entry.cluster_of(country).is_none())
+            {
+                return Err(invalid_input(format!(
+                    "{} is in none of the {} logged runs",
+                    country,
+                    entries.len()
+                )));
+            }
+            history::country_table(&entries, country)
+        }
+        None => history::runs_table(&entries),
+    };
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()
+        }
+        None => table.write_text(&mut console::stdout()),
+    }
+}
+
+fn analyze(matches: &Matches) -> io::Result<()> {
+    let graph = artifact::load_graph(matches.required("graph"))?;
+    let clusters = match matches.value("clusters") {
+        Some(path) => Some(artifact::load_clusters(path, &graph)?),
+        None => None,
+    };
+
+    let mut output = open_output(matches.value("output"))?;
+    match matches.value("format") {
+        Some("json") => writeln!(
+            output,
+            "{}",
+            summary_json(&graph, clusters.as_deref()).to_pretty_string()
+        )?,
+        _ => print_summary(&mut output, &graph, clusters.as_deref())?,
+    }
+    output.flush()
+}
+
+fn weight_histogram(matches: &Matches) -> io::Result<()> {
+    let graph = artifact::load_graph(matches.required("graph"))?;
+    let bins = matches.parse_value::<usize>("bins")?.unwrap_or(20);
+    if bins == 0 {
+        return Err(invalid_input("--bins must be at least 1".to_string()));
+    }
+    let edge_weights = matrix::storage(&graph.adjacency_matrix).edge_weights();
+    let histogram = weights::Histogram::new(&edge_weights, bins);
+
+    match matches.value("output") {
+        Some(path) => {
+            let mut manifest = start_manifest(matches);
+            manifest.input(matches.required("graph"))?;
+            let mut output = open_output(Some(path))?;
+            histogram.table().write_csv(&mut output)?;
+            output.flush()?;
+            if let Some(threshold) = weights::suggest_threshold(&edge_weights) {
+                note!("Suggested --min-weight {:.4}", threshold);
+            }
+            note!("Wrote {} bins to {}", bins, path);
+            write_manifest(&manifest, Some(path))
+        }
+        None => weights::write_histogram(&mut console::stdout(), &histogram, &edge_weights),
+    }
+}
+
+fn central_countries(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input(matches.required("graph"))?;
+    let graph = artifact::load_graph(matches.required("graph"))?;
+    let memberships = match matches.value("clusters") {
+        Some(path) => {
+            manifest.input(path)?;
+            let clusters = artifact::load_clusters(path, &graph)?;
+            Some(cluster_memberships(&graph, &clusters))
+        }
+        None => None,
+    };
+    let measure: Measure = matches.parse_value("measure")?.unwrap_or_default();
+    let top = matches.parse_value::<usize>("top")?.unwrap_or(10);
+    let scores = manifest.time("centrality", || centrality::centrality(&graph));
+
+    match matches.value("output") {
+        Some(path) => {
+            let table = centrality::centrality_table(
+                &graph,
+                &scores,
+                measure,
+                graph.nodes.len(),
+                memberships.as_ref(),
+            );
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!("Scored {} countries into {}", graph.nodes.len(), path);
+            write_manifest(&manifest, Some(path))
+        }
+        None => {
+            let table =
+                centrality::centrality_table(&graph, &scores, measure, top, memberships.as_ref());
+            table.write_text(&mut console::stdout())
+        }
+    }
+}
+
+fn country_path(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input(matches.required("graph"))?;
+    let graph = artifact::load_graph(matches.required("graph"))?;
+    let node = |name: &str| {
+        graph
+            .nodes
+            .iter()
+            .position(|node| node == name)
+            .ok_or_else(|| invalid_input(format!("{} is not in the graph", name)))
+    };
+    let (from, to) = (
+        node(matches.required("from"))?,
+        node(matches.required("to"))?,
+    );
+    let length: Length = matches.parse_value("length")?.unwrap_or_default();
+    let Some(path) = manifest.time("path", || paths::shortest_path(&graph, from, to, length))
+    else {
+        return Err(invalid_input(format!(
+            "no path links {} and {} in the graph",
+            graph.nodes[from], graph.nodes[to]
+        )));
+    };
+
+    let table = paths::path_table(&graph, &path, length);
+    match matches.value("output") {
+        Some(output_path) => {
+            let mut output = open_output(Some(output_path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!("Wrote the path to {}", output_path);
+            write_manifest(&manifest, Some(output_path))?;
+        }
+        None => {
+            let mut output = console::stdout();
+            table.write_text(&mut output)?;
+            writeln!(
+                output,
+                "Total cost {:.4} over {} links",
+                path.cost,
+                path.nodes.len() - 1
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn similar_countries(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    let country = matches.required("country");
+    let k = matches.parse_value::<usize>("k")?.unwrap_or(10);
+    let with_features = ["where", "normalize"]
+        .iter()
+        .any(|name| matches.value(name).is_some());
+    let (neighbors, score) = match (matches.value("graph"), matches.value("from")) {
+        (Some(path), None) if !with_features => {
+            manifest.input(path)?;
+            let graph = artifact::load_graph(path)?;
+            let node = graph
+                .nodes
+                .iter()
+                .position(|node| node == country)
+                .ok_or_else(|| invalid_input(format!("{} is not in the graph", country)))?;
+            (similar::by_weight(&graph, node, k), "weight")
+        }
+        (None, Some(from)) => {
+            manifest.input_source(source::open_location(from)?.as_ref())?;
+            let filter = observation_filter(matches)?;
+            let mut data = manifest.time("load", || load_data(from))?;
+            apply_filter(filter.as_ref(), &mut data);
+            let mut features = features::feature_matrix(&data, None);
+            if let Some(scaling) = matches.parse_value::<Scaling>("normalize")? {
+                features = features.standardized(scaling);
+            }
+            let row = features
+                .countries
+                .iter()
+                .position(|name| name == country)
+                .ok_or_else(|| invalid_input(format!("{} reports no values", country)))?;
+            (similar::by_distance(&features, row, k), "distance")
+        }
+        (Some(_), None) => {
+            return Err(invalid_input(
+                "--where and --normalize apply to the features of --from".to_string(),
+            ))
+        }
+        _ => return Err(invalid_input(
+            "`similar` needs either --graph PATH (edge weights) or --from PATH (feature distances)"
+                .to_string(),
+        )),
+    };
+
+    let table = similar::neighbors_table(&neighbors, score);
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!(
+                "Wrote {} neighbors of {} to {}",
+                neighbors.len(),
+                country,
+                path
+            );
+            write_manifest(&manifest, Some(path))?;
+        }
+        None => table.write_text(&mut console::stdout())?,
+    }
+    Ok(())
+}
+
+fn export(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input(matches.required("graph"))?;
+    manifest.input(matches.required("clusters"))?;
+
+    let graph = artifact::load_graph(matches.required("graph"))?;
+    let clusters = artifact::load_clusters(matches.required("clusters"), &graph)?;
+
+    let mut output = open_output(matches.value("output"))?;
+    let (graph, clusters) = ordering::ordered(&graph, &clusters, node_order(matches)?);
+    let format = match (matches.flag("html"), matches.value("format")) {
+        (true, Some(format)) if format != "html" => {
+            return Err(invalid_input(format!(
+                "--html and --format {} cannot be combined",
+                format
+            )))
+        }
+        (true, _) => Some("html"),
+        (false, format) => format,
+    };
+    let title = format!("Clusters of {}", matches.required("graph"));
+    let convergence = manifest::recorded_convergence(matches.required("clusters"));
+    write_report(
+        &mut output,
+        format,
+        &title,
+        &graph,
+        &clusters,
+        convergence.as_ref(),
+    )?;
+    output.flush()?;
+    write_manifest(&manifest, matches.value("output"))
+}
+
+// The cluster report of `run` and `export` in one of REPORT_FORMATS: text,
+// an HTML page, JSON or per-country CSV for scripts, or the graph itself as
+// DOT or GEXF coloured by cluster.
+fn export_countries(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+    manifest.input(matches.required("graph"))?;
+    let graph = artifact::load_graph(matches.required("graph"))?;
+    let clusters = match matches.value("clusters") {
+        Some(path) => {
+            manifest.input(path)?;
+            Some(artifact::load_clusters(path, &graph)?)
+        }
+        None => None,
+    };
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    let neighbours = matches.parse_value::<usize>("neighbors")?.unwrap_or(5);
+
+    let documents = manifest.time("profiles", || {
+        country_export::country_documents(&data, &graph, clusters.as_deref(), neighbours)
+    });
+    let dir = Path::new(matches.required("out-dir"));
+    let written = country_export::write_country_files(dir, &documents)?;
+    note!("Wrote {} country files to {}", written, dir.display());
+    let index = dir.join("index.json");
+    write_manifest(&manifest, index.to_str())
+}
+
+fn write_report(
+    output: &mut dyn Write,
+    format: Option<&str>,
+    title: &str,
+    graph: &Graph,
+    clusters: &[Vec<usize>],
+    convergence: Option<&Json>,
+) -> io::Result<()> {
+    match format.unwrap_or("text") {
+        "html" => output.write_all(notebook::html_report(title, graph, clusters).as_bytes()),
+        "dot" => output.write_all(graph.to_dot(Some(clusters)).as_bytes()),
+        "gexf" => output.write_all(graph.to_gexf(Some(clusters)).as_bytes()),
+        "json" => cluster::write_clusters_json(output, clusters, graph, convergence),
+        "csv" => cluster::write_clusters_csv(output, clusters, graph),
+        _ => print_clusters(output, clusters, graph),
+    }
+}
+
+fn pivot(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+
+    let spec = PivotSpec {
+        rows: matches
+            .parse_value("rows")?
+            .unwrap_or(crosstab::Dimension::Country),
+        cols: matches
+            .parse_value("cols")?
+            .unwrap_or(crosstab::Dimension::Year),
+        values: matches.parse_value("values")?.unwrap_or_default(),
+        agg: matches.parse_value("agg")?.unwrap_or_default(),
+    };
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    if let Some(series) = matches.value("series") {
+        data.retain(|record| record.series == series);
+        if data.is_empty() {
+            return Err(invalid_input(format!(
+                "no observations of series {:?}",
+                series
+            )));
+        }
+    }
+    let table = manifest.time("pivot", || crosstab::pivot(&data, &spec));
+
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!(
+                "Wrote a {}x{} pivot table to {}",
+                table.rows.len(),
+                table.headers.len() - 1,
+                path
+            );
+            write_manifest(&manifest, Some(path))
+        }
+        None => table.write_text(&mut console::stdout()),
+    }
+}
+
+fn rank(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+
+    // Cluster membership needs both the graph and the clustering over it
+    let memberships = match (matches.value("graph"), matches.value("clusters")) {
+        (Some(graph_path), Some(clusters_path)) => {
+            manifest.input(graph_path)?;
+            manifest.input(clusters_path)?;
+            let graph = artifact::load_graph(graph_path)?;
+            let clusters = artifact::load_clusters(clusters_path, &graph)?;
+            Some(cluster_memberships(&graph, &clusters))
+        }
+        (None, None) => None,
+        _ => {
+            return Err(invalid_input(
+                "--graph and --clusters must be given together".to_string(),
+            ))
+        }
+    };
+
+    let series = matches.required("series");
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    let year = match matches.parse_value("year")? {
+        Some(year) => year,
+        None => ranking::latest_year(&data, series)
+            .ok_or_else(|| invalid_input(format!("no observations of series {:?}", series)))?,
+    };
+    let ties = matches.parse_value("ties")?.unwrap_or(Ties::Min);
+    let ranked = manifest.time("rank", || {
+        ranking::rank_countries(&data, series, year, ties)
+    });
+    if ranked.is_empty() {
+        return Err(invalid_input(format!(
+            "no country reports {:?} in {}",
+            series, year
+        )));
+    }
+    let table = ranking::rank_table(&ranked, memberships.as_ref());
+
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!("Ranked {} countries ({}) into {}", ranked.len(), year, path);
+            write_manifest(&manifest, Some(path))
+        }
+        None => {
+            let catalog = series_catalog(matches)?;
+            note!("{} in {}:", catalog.label(series), year);
+            if let Some(info) = catalog
+                .get(series)
+                .filter(|info| !info.description.is_empty())
+            {
+                note!("  {}", info.description);
+            }
+            table.write_text(&mut console::stdout())
+        }
+    }
+}
+
+// Each clustered country's cluster, labelled as in the cluster report.
+fn cluster_memberships(graph: &Graph, clusters: &[Vec<usize>]) -> HashMap<String, String> {
+    let labels = labels::cluster_labels(graph, clusters);
+    let mut memberships = HashMap::new();
+    for (cluster_index, (cluster, label)) in clusters.iter().zip(&labels).enumerate() {
+        for &node in cluster {
+            memberships.insert(
+                graph.nodes[node].clone(),
+                format!("{} ({})", cluster_index, label),
+            );
+        }
+    }
+    memberships
+}
+
+fn measure_inequality(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    if let Some(series) = matches.value("series") {
+        data.retain(|record| record.series == series);
+        if data.is_empty() {
+            return Err(invalid_input(format!(
+                "no observations of series {:?}",
+                series
+            )));
+        }
+    }
+    let years = manifest.time("measure", || inequality::by_year(&data));
+    let table = if matches.flag("by-year") {
+        inequality::by_year_table(&years)
+    } else {
+        inequality::trend_table(&inequality::trends(&years))
+    };
+
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!("Wrote {} rows to {}", table.rows.len(), path);
+            write_manifest(&manifest, Some(path))
+        }
+        None => table.write_text(&mut console::stdout()),
+    }
+}
+
+fn test_convergence(matches: &Matches) -> io::Result<()> {
+    let series = matches.required("series");
+    let filter = observation_filter(matches)?;
+    let mut data = load_data(matches.required("from"))?;
+    apply_filter(filter.as_ref(), &mut data);
+    data.retain(|record| record.series == series);
+    if data.is_empty() {
+        return Err(invalid_input(format!(
+            "no observations of series {:?}",
+            series
+        )));
+    }
+
+    let beta = convergence::beta_convergence(
+        &data,
+        matches.parse_value("start")?,
+        matches.parse_value("end")?,
+    )?;
+    let sigma = convergence::sigma_convergence(&data);
+    let mut output = open_output(matches.value("output"))?;
+    convergence::write_report(&mut output, series, &beta, &sigma)?;
+    output.flush()
+}
+
+fn top_movers(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+
+    let before = filter::parse_years(matches.required("before"))?;
+    let after = filter::parse_years(matches.required("after"))?;
+    let mut options = movers::Options::default();
+    if let Some(metric) = matches.parse_value("similarity")? {
+        options.metric = metric;
+    }
+    if let Some(algorithm) = matches.parse_value("algo")? {
+        options.algorithm = algorithm;
+    }
+    if let Some(neighbours) = matches.parse_value("neighbors")? {
+        options.neighbours = neighbours;
+    }
+    options.parallelism = parallelism(matches)?;
+    let top = matches.parse_value::<usize>("top")?.unwrap_or(10);
+
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    let found = manifest.time("compare", || {
+        movers::top_movers(&data, &before, &after, options)
+    });
+    if found.len() < 2 {
+        return Err(invalid_input(format!(
+            "only {} countries report in both windows; nothing to compare",
+            found.len()
+        )));
+    }
+
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            movers::movers_table(&found, found.len()).write_csv(&mut output)?;
+            output.flush()?;
+            note!("Compared {} countries into {}", found.len(), path);
+            write_manifest(&manifest, Some(path))
+        }
+        None => movers::movers_table(&found, top).write_text(&mut console::stdout()),
+    }
+}
+
+fn split_stability(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+
+    let split: Split = matches.parse_value("split")?.unwrap_or_default();
+    let seed = matches.parse_value::<u64>("seed")?.unwrap_or(0);
+    manifest.set_seed(seed);
+    let options = stability::Options {
+        metric: similarity_metric(matches)?,
+        algorithm: matches.parse_value("algo")?.unwrap_or_default(),
+        stop: stop(matches)?,
+        parallelism: parallelism(matches)?,
+    };
+
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    let halves = split.split(data, &mut Rng::new(seed));
+    let found = manifest.time("cluster", || stability::split_stability(halves, options));
+    if found.compared < 2 {
+        return Err(invalid_input(format!(
+            "only {} countries are clustered in both halves; nothing to compare",
+            found.compared
+        )));
+    }
+
+    let table = stability::stability_table(matches.value("split").unwrap_or("parity"), &found);
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!("Compared {} countries into {}", found.compared, path);
+            write_manifest(&manifest, Some(path))
+        }
+        None => table.write_text(&mut console::stdout()),
+    }
+}
+
+fn temporal_changes(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+
+    let bucket = matches.parse_value::<u32>("bucket")?.unwrap_or(1);
+    if bucket == 0 {
+        return Err(invalid_input("--bucket must be at least 1".to_string()));
+    }
+    let options = temporal::Options {
+        metric: similarity_metric(matches)?.unwrap_or_default(),
+        bucket,
+        algorithm: matches.parse_value("algo")?.unwrap_or_default(),
+        stop: stop(matches)?,
+        parallelism: parallelism(matches)?,
+    };
+
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    if let Some(mut names) = country_names(matches)? {
+        names.apply_all(&mut data);
+        report_names(Some(&names));
+    }
+    let snapshots = manifest.time("build and cluster", || temporal::snapshots(data, options));
+    if snapshots.len() < 2 {
+        return Err(invalid_input(format!(
+            "the observations make {} snapshot(s); at least 2 are needed to compare",
+            snapshots.len()
+        )));
+    }
+    temporal::snapshots_table(&snapshots).write_text(&mut console::stderr())?;
+    if let Some(dir) = matches.value("save-dir") {
+        fs::create_dir_all(dir)?;
+        for snapshot in &snapshots {
+            let path = Path::new(dir).join(format!("{}.graph", snapshot.period()));
+            let path = path.to_string_lossy();
+            artifact::save_graph(&path, &snapshot.graph)?;
+            write_manifest(&manifest, Some(&path))?;
+        }
+        note!("Saved {} snapshot graphs to {}", snapshots.len(), dir);
+    }
+
+    let changes = temporal::changes(&snapshots);
+    let table = temporal::changes_table(&changes);
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!("Wrote {} changes to {}", changes.len(), path);
+            write_manifest(&manifest, Some(path))
+        }
+        None => table.write_text(&mut console::stdout()),
+    }
+}
+
+fn changepoints(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+
+    let mut detector = Detector::default();
+    if let Some(penalty) = matches.parse_value::<f64>("penalty")? {
+        if !penalty.is_finite() || penalty < 0.0 {
+            return Err(invalid_input(format!(
+                "--penalty must be a non-negative number, got {}",
+                penalty
+            )));
+        }
+        detector.penalty = penalty;
+    }
+    if let Some(min_segment) = matches.parse_value("min-segment")? {
+        detector.min_segment = min_segment;
+    }
+
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    if let Some(series) = matches.value("series") {
+        data.retain(|record| record.series == series);
+    }
+    let found = manifest.time("detect", || detector.scan(&data));
+    let table = changepoint::changepoint_table(&found);
+
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!("Wrote {} changepoints to {}", found.len(), path);
+            write_manifest(&manifest, Some(path))
+        }
+        None => table.write_text(&mut console::stdout()),
+    }
+}
+
+fn lead_lag(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+
+    let mut analysis = LeadLag::default();
+    if let Some(max_lag) = matches.parse_value("max-lag")? {
+        analysis.max_lag = max_lag;
+    }
+    if let Some(min_points) = matches.parse_value("min-points")? {
+        analysis.min_points = min_points;
+    }
+    if let Some(min_correlation) = matches.parse_value("min-correlation")? {
+        analysis.min_correlation = min_correlation;
+    }
+
+    let (nodes, histories) = load_histories(matches, &mut manifest)?;
+    let edges = manifest.time("correlate", || analysis.edges(&histories));
+
+    if let Some(path) = matches.value("save") {
+        let graph = leadlag::directed_graph(nodes.clone(), &edges);
+        artifact::save_graph(path, &graph)?;
+        note!(
+            "Saved a directed graph with {} edges to {}",
+            edges.len(),
+            path
+        );
+        write_manifest(&manifest, Some(path))?;
+    }
+    let table = leadlag::edge_table(&nodes, &edges);
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!("Wrote {} lead-lag edges to {}", edges.len(), path);
+            write_manifest(&manifest, Some(path))
+        }
+        None => table.write_text(&mut console::stdout()),
+    }
+}
+
+fn granger_edges(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+
+    let mut test = GrangerTest::default();
+    if let Some(lag) = matches.parse_value::<u32>("lag")? {
+        if lag == 0 {
+            return Err(invalid_input("--lag must be at least 1".to_string()));
+        }
+        test.lag = lag;
+    }
+    if let Some(min_points) = matches.parse_value("min-points")? {
+        test.min_points = min_points;
+    }
+    if let Some(alpha) = matches.parse_value("alpha")? {
+        test.alpha = alpha;
+    }
+    if let Some(correction) = matches.parse_value("correction")? {
+        test.correction = correction;
+    }
+
+    let (nodes, histories) = load_histories(matches, &mut manifest)?;
+    let edges = manifest.time("test", || test.edges(&histories));
+
+    if let Some(path) = matches.value("save") {
+        let graph = granger::directed_graph(nodes.clone(), &edges);
+        artifact::save_graph(path, &graph)?;
+        note!(
+            "Saved a directed graph with {} edges to {}",
+            edges.len(),
+            path
+        );
+        write_manifest(&manifest, Some(path))?;
+    }
+    let table = granger::edge_table(&nodes, &edges);
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!("Wrote {} significant edges to {}", edges.len(), path);
+            write_manifest(&manifest, Some(path))
+        }
+        None => table.write_text(&mut console::stdout()),
+    }
+}
+
+// Each country's history of `--series`, for the directed (temporal) graphs.
+fn load_histories(
+    matches: &Matches,
+    manifest: &mut Manifest,
+) -> io::Result<(Vec<String>, Vec<History>)> {
+    let series = matches.required("series");
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    data.retain(|record| record.series == series);
+    if data.is_empty() {
+        return Err(invalid_input(format!(
+            "no observations of series {:?}",
+            series
+        )));
+    }
+    Ok(leadlag::histories(&data))
+}
+
+fn bins(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+
+    let bins = bin_count(matches, "bins")?.unwrap_or(4);
+    let year = matches.parse_value("year")?;
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    let binned = manifest.time("bin", || binning::quantile_bins(&data, bins, year));
+    let table = binned.to_table();
+
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!(
+                "Wrote {} countries x {} series in {} bins to {}",
+                binned.countries.len(),
+                binned.series.len(),
+                bins,
+                path
+            );
+            write_manifest(&manifest, Some(path))
+        }
+        None => table.write_text(&mut console::stdout()),
+    }
+}
+
+fn bin_count(matches: &Matches, name: &str) -> io::Result<Option<usize>> {
+    match matches.parse_value::<usize>(name)? {
+        Some(bins) if bins < 2 => Err(invalid_input(format!(
+            "--{} needs at least 2 bins, got {}",
+            name, bins
+        ))),
+        bins => Ok(bins),
+    }
+}
+
+fn kmeans(matches: &Matches) -> io::Result<RunStatus> {
+    let mut cancel = cancel_token(matches)?;
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+
+    // `--k auto` picks k once the features are known
+    let auto = matches.value("k") == Some("auto");
+    let mut options = kmeans::Options {
+        k: match auto {
+            true => 3,
+            false => matches.parse_value("k")?.unwrap_or(3),
+        },
+        max_iterations: matches.parse_value("max-iterations")?.unwrap_or(100),
+        seeding: matches.parse_value("seeding")?.unwrap_or_default(),
+        seed: matches.parse_value("seed")?.unwrap_or(0),
+        parallelism: parallelism(matches)?,
+    };
+    if options.k == 0 {
+        return Err(invalid_input("--k must be at least 1".to_string()));
+    }
+    if matches.value("init").is_some() && matches.value("k").is_some() {
+        return Err(invalid_input(
+            "--init starts from its own clusters and cannot be combined with --k".to_string(),
+        ));
+    }
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    let features = kmeans::country_features(&data);
+    if features.countries.is_empty() {
+        return Err(invalid_input("no observations to cluster".to_string()));
+    }
+    // Warm start from a clustering of the same countries, e.g. last year's
+    let initial = match matches.value("init") {
+        Some(path) => {
+            manifest.input(path)?;
+            Some(artifact::load_clusters_of(path, &features.countries)?)
+        }
+        None => None,
+    };
+    let distance = |i: usize, j: usize| {
+        kmeans::squared_distance(&features.values[i], &features.values[j]).sqrt()
+    };
+    if auto {
+        let most = matches.parse_value::<usize>("k-max")?.unwrap_or(10);
+        let selection: Selection = matches.parse_value("select")?.unwrap_or_default();
+        let runs: Vec<kmeans::KMeans> = manifest.time("sweep", || {
+            (1..=most.min(features.countries.len()))
+                .map(|k| {
+                    let options = kmeans::Options { k, ..options };
+                    kmeans::kmeans(&features.values, options, None, &mut Unobserved)
+                })
+                .collect()
+        });
+        // The best silhouette, or the elbow of the inertia (which only falls)
+        let (measure, curve): (&str, Vec<(usize, f64)>) = match selection {
+            Selection::Best => (
+                "silhouette",
+                runs.iter()
+                    .map(|run| {
+                        let score = quality::silhouette(&run.assignments, distance);
+                        (run.centroids.len(), score.unwrap_or(f64::NAN))
+                    })
+                    .collect(),
+            ),
+            Selection::Elbow => (
+                "inertia",
+                runs.iter()
+                    .map(|run| (run.centroids.len(), run.inertia))
+                    .collect(),
+            ),
+        };
+        let chosen = selection.choose(&curve).unwrap_or(1);
+        quality::sweep_table(measure, &curve, Some(chosen)).write_text(&mut console::stderr())?;
+        options.k = chosen;
+    }
+    let mut trace = ObjectiveTrace::new();
+    let result = manifest.time("kmeans", || {
+        let mut observers: Vec<&mut dyn IterationObserver> = vec![&mut cancel, &mut trace];
+        kmeans::kmeans(
+            &features.values,
+            options,
+            initial.as_deref(),
+            &mut observers,
+        )
+    });
+    manifest.set_convergence(&trace);
+    let status = stopped_early(&mut manifest, result.status);
+    let silhouette = quality::silhouette(&result.assignments, distance);
+
+    let mut table = table::Table::new(&["country", "cluster", "distance"]);
+    for ((country, row), &cluster) in features
+        .countries
+        .iter()
+        .zip(&features.values)
+        .zip(&result.assignments)
+    {
+        let distance = kmeans::squared_distance(row, &result.centroids[cluster]).sqrt();
+        table.push_row(vec![
+            country.clone(),
+            cluster.to_string(),
+            format!("{:.4}", distance),
+        ]);
+    }
+    note!(
+        "{} countries in {} clusters after {} iterations, inertia {:.4}, silhouette {}",
+        features.countries.len(),
+        result.centroids.len(),
+        result.iterations,
+        result.inertia,
+        silhouette.map_or_else(|| "NA".to_string(), |score| format!("{:.4}", score))
+    );
+    if let Some(path) = matches.value("centroids") {
+        let mut headers = vec!["cluster"];
+        headers.extend(features.series.iter().map(String::as_str));
+        let mut centroids = table::Table::new(&headers);
+        for (cluster, centroid) in result.centroids.iter().enumerate() {
+            let mut row = vec![cluster.to_string()];
+            row.extend(centroid.iter().map(|value| value.to_string()));
+            centroids.push_row(row);
+        }
+        let mut output = open_output(Some(path))?;
+        centroids.write_csv(&mut output)?;
+        output.flush()?;
+        write_manifest(&manifest, Some(path))?;
+    }
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            write_manifest(&manifest, Some(path))?;
+        }
+        None => table.write_text(&mut console::stdout())?,
+    }
+    Ok(status)
+}
+
+fn composite(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+    let memberships = match (matches.value("graph"), matches.value("clusters")) {
+        (Some(graph_path), Some(clusters_path)) => {
+            manifest.input(graph_path)?;
+            manifest.input(clusters_path)?;
+            let graph = artifact::load_graph(graph_path)?;
+            let clusters = artifact::load_clusters(clusters_path, &graph)?;
+            Some(cluster_memberships(&graph, &clusters))
+        }
+        (None, None) => None,
+        _ => {
+            return Err(invalid_input(
+                "--graph and --clusters must be given together".to_string(),
+            ))
+        }
+    };
+
+    let catalog = series_catalog(matches)?;
+    let list = |name: &str| -> Vec<String> {
+        matches
+            .value(name)
+            .map(|list| {
+                list.split(',')
+                    .map(|series| series.trim().to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let (invert, keep) = (list("invert"), list("keep"));
+    if let Some(series) = invert.iter().find(|series| keep.contains(series)) {
+        return Err(invalid_input(format!(
+            "{:?} is in both --invert and --keep",
+            series
+        )));
+    }
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    let features = features::feature_matrix(&data, matches.parse_value("year")?);
+    let signs = composite::orientations(&features.series, &catalog, &invert, &keep)?;
+    let scaling = matches.parse_value("scaling")?.unwrap_or_default();
+    let scores = manifest.time("score", || {
+        composite::composite_scores(&features, &signs, scaling)
+    });
+    if scores.is_empty() {
+        return Err(invalid_input("no observations to score".to_string()));
+    }
+    let inverted: Vec<&str> = features
+        .series
+        .iter()
+        .zip(&signs)
+        .filter(|(_, &sign)| sign < 0.0)
+        .map(|(series, _)| series.as_str())
+        .collect();
+    note!(
+        "Scored {} countries on {} series; inverted (lower is better): {}",
+        scores.len(),
+        features.series.len(),
+        if inverted.is_empty() {
+            "none".to_string()
+        } else {
+            inverted.join(", ")
+        }
+    );
+    let table = composite::score_table(&scores, memberships.as_ref());
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            write_manifest(&manifest, Some(path))
+        }
+        None => {
+            let mut output = console::stdout();
+            table.write_text(&mut output)?;
+            if let Some(memberships) = &memberships {
+                writeln!(output)?;
+                composite::cluster_table(&scores, memberships).write_text(&mut output)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn data_completeness(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    if data.is_empty() {
+        return Err(invalid_input("no observations to measure".to_string()));
+    }
+    let result = manifest.time("completeness", || completeness::completeness(&data));
+    let overall = result.overall();
+    let thinnest = (0..overall.len())
+        .min_by(|&a, &b| overall[a].total_cmp(&overall[b]))
+        .expect("there is at least one country");
+    note!(
+        "{} countries x {} series, {:.1}% reported overall; thinnest: {} ({:.1}%)",
+        result.countries.len(),
+        result.series.len(),
+        100.0 * overall.iter().sum::<f64>() / overall.len() as f64,
+        result.countries[thinnest],
+        100.0 * overall[thinnest]
+    );
+    if let Some(path) = matches.value("svg") {
+        let mut output = open_output(Some(path))?;
+        chart::write_heatmap(
+            &mut output,
+            &format!("Share of years reported, {}", matches.required("from")),
+            &result.countries,
+            &result.series,
+            &result.fractions,
+        )?;
+        output.flush()?;
+        note!("Drew the heatmap into {}", path);
+    }
+    let table = result.table();
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            write_manifest(&manifest, Some(path))
+        }
+        None => table.write_text(&mut console::stdout()),
+    }
+}
+
+fn chart(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+    let graph_path = matches.required("graph");
+    let clusters_path = matches.required("clusters");
+    manifest.input(graph_path)?;
+    manifest.input(clusters_path)?;
+    let graph = artifact::load_graph(graph_path)?;
+    let clusters = artifact::load_clusters(clusters_path, &graph)?;
+
+    let series = matches.required("series");
+    let year = matches.parse_value("year")?;
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    let values = chart::country_values(&data, series, year);
+    if values.is_empty() {
+        return Err(invalid_input(format!(
+            "no observations of series {:?}",
+            series
+        )));
+    }
+
+    let groups: Vec<Vec<f64>> = clusters
+        .iter()
+        .map(|cluster| {
+            cluster
+                .iter()
+                .filter_map(|&node| values.get(&graph.nodes[node]).copied())
+                .collect()
+        })
+        .collect();
+    let labels: Vec<String> = labels::cluster_labels(&graph, &clusters)
+        .into_iter()
+        .enumerate()
+        .map(|(index, label)| format!("{} ({})", index, label))
+        .collect();
+    let title = match year {
+        Some(year) => format!("{} ({})", series, year),
+        None => format!("{} (latest)", series),
+    };
+
+    let path = matches.required("output");
+    let mut output = open_output(Some(path))?;
+    chart::write_box_plot(&mut output, &title, &labels, &groups)?;
+    output.flush()?;
+    let plotted: usize = groups.iter().map(Vec::len).sum();
+    note!(
+        "Plotted {} countries in {} clusters to {}",
+        plotted,
+        clusters.len(),
+        path
+    );
+    write_manifest(&manifest, Some(path))
+}
+
+// Write the features, distances and (with --graph) adjacency matrix. A .mat
+// file carries the row and column names as cell arrays; for NumPy they go
+// to a `<stem>.names.json` sidecar.
+fn cluster_trajectories(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+    let graph_path = matches.required("graph");
+    let clusters_path = matches.required("clusters");
+    manifest.input(graph_path)?;
+    manifest.input(clusters_path)?;
+    let graph = artifact::load_graph(graph_path)?;
+    let clusters = artifact::load_clusters(clusters_path, &graph)?;
+
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    if let Some(series) = matches.value("series") {
+        data.retain(|record| record.series == series);
+    }
+    let points = manifest.time("trajectories", || {
+        trajectory::cluster_trajectories(&data, &graph, &clusters)
+    });
+    let table = trajectory::trajectory_table(&graph, &clusters, &points);
+
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!(
+                "Wrote {} points for {} clusters to {}",
+                points.len(),
+                clusters.len(),
+                path
+            );
+            write_manifest(&manifest, Some(path))
+        }
+        None => table.write_text(&mut console::stdout()),
+    }
+}
+
+fn arrays(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+
+    let path = Path::new(matches.required("output"));
+    let format = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .filter(|extension| ["npz", "npy", "mat"].contains(extension))
+        .ok_or_else(|| {
+            invalid_input(format!(
+                "--output must end in .npz, .npy or .mat, got {}",
+                path.display()
+            ))
+        })?;
+    let graph = match matches.value("graph") {
+        Some(graph_path) => {
+            manifest.input(graph_path)?;
+            Some(artifact::load_graph(graph_path)?)
+        }
+        None => None,
+    };
+
+    let year = matches.parse_value("year")?;
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    let matrix = features::feature_matrix(&data, year);
+    if matrix.countries.is_empty() {
+        return Err(invalid_input("no observations to export".to_string()));
+    }
+    let parallelism = parallelism(matches)?;
+    let distances = manifest.time("distances", || matrix.distances(parallelism));
+    let (transition, laplacian) = match &graph {
+        Some(graph) => (graph.row_stochastic(), graph.normalized_laplacian()),
+        None => (Vec::new(), Vec::new()),
+    };
+
+    let mut written = vec![path.to_path_buf()];
+    if format == "mat" {
+        let mut variables = vec![
+            ("features", mat::Variable::Matrix(&matrix.values)),
+            ("distances", mat::Variable::Matrix(&distances)),
+            ("countries", mat::Variable::Strings(&matrix.countries)),
+            ("series", mat::Variable::Strings(&matrix.series)),
+        ];
+        if let Some(graph) = &graph {
+            variables.push(("adjacency", mat::Variable::Matrix(&graph.adjacency_matrix)));
+            variables.push(("transition", mat::Variable::Matrix(&transition)));
+            variables.push(("laplacian", mat::Variable::Matrix(&laplacian)));
+            variables.push(("nodes", mat::Variable::Strings(&graph.nodes)));
+        }
+        let mut output = open_output(path.to_str())?;
+        mat::write_mat(&mut output, &variables)?;
+        output.flush()?;
+    } else {
+        let mut arrays = vec![
+            ("features", npy::matrix_bytes(&matrix.values)),
+            ("distances", npy::matrix_bytes(&distances)),
+        ];
+        if let Some(graph) = &graph {
+            arrays.push(("adjacency", npy::matrix_bytes(&graph.adjacency_matrix)));
+            arrays.push(("transition", npy::matrix_bytes(&transition)));
+            arrays.push(("laplacian", npy::matrix_bytes(&laplacian)));
+        }
+        if format == "npz" {
+            let mut output = open_output(path.to_str())?;
+            npy::write_npz(&mut output, &arrays)?;
+            output.flush()?;
+        } else {
+            // The features take the given path, the rest sit beside it
+            for (name, bytes) in arrays {
+                let array_path = if name == "features" {
+                    path.to_path_buf()
+                } else {
+                    path.with_extension(format!("{}.npy", name))
+                };
+                std::fs::write(&array_path, bytes)?;
+                if name != "features" {
+                    written.push(array_path);
+                }
+            }
+        }
+
+        let names_path = path.with_extension("names.json");
+        let names = |names: &[String]| Json::Array(names.iter().cloned().map(Json::from).collect());
+        let mut sidecar = Json::object()
+            .with("countries", names(&matrix.countries))
+            .with("series", names(&matrix.series));
+        if let Some(graph) = &graph {
+            sidecar = sidecar.with("nodes", names(&graph.nodes));
+        }
+        std::fs::write(&names_path, sidecar.to_pretty_string() + "\n")?;
+        written.push(names_path);
+    }
+
+    note!(
+        "Exported {} countries x {} series to {}",
+        matrix.countries.len(),
+        matrix.series.len(),
+        written
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    write_manifest(&manifest, path.to_str())
+}
+
+fn serve(matches: &Matches) -> io::Result<()> {
+    let address = matches.value("listen").unwrap_or("127.0.0.1:8210");
+    let listener = TcpListener::bind(address)?;
+    let local = listener.local_addr()?;
+    let keys = match matches.value("api-keys") {
+        Some(path) => ApiKeys::load(path)?,
+        None => ApiKeys::default(),
+    };
+    if keys.is_open() && !local.ip().is_loopback() {
+        note!(
+            "Warning: {} is reachable beyond this machine and no --api-keys are required",
+            local
+        );
+    }
+    note!(
+        "Serving the analysis API on http://{}/v1/ (metrics at /metrics)",
+        local
+    );
+    let mut engine = match matches.value("cache-dir") {
+        Some(dir) => Engine::with_cache(Path::new(dir))?,
+        None => Engine::default(),
+    };
+    if let Some(megabytes) = matches.parse_value::<usize>("memory-limit")? {
+        engine.registry = Registry::with_budget(megabytes << 20);
+    }
+    let workers = matches.parse_value::<usize>("workers")?.unwrap_or(2);
+    if workers == 0 {
+        return Err(invalid_input("--workers must be at least 1".to_string()));
+    }
+    let connections = matches.parse_value::<usize>("connections")?.unwrap_or(16);
+    if connections == 0 {
+        return Err(invalid_input(
+            "--connections must be at least 1".to_string(),
+        ));
+    }
+    let service = Service {
+        engine: Arc::new(Mutex::new(engine)),
+        keys,
+        jobs: Arc::new(JobQueue::with_webhook(Notifier::from_env().webhook)),
+    };
+    service.jobs.start_workers(workers, &service.engine);
+    server::serve(listener, Arc::new(service), connections)
+}
+
+fn compare_graphs(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    let (first_path, second_path) = (matches.required("first"), matches.required("second"));
+    manifest.input(first_path)?;
+    manifest.input(second_path)?;
+    let threshold = matches.parse_value::<f64>("threshold")?.unwrap_or(0.0);
+    if !threshold.is_finite() {
+        return Err(invalid_input(format!(
+            "--threshold must be a number, got {}",
+            threshold
+        )));
+    }
+    let first = artifact::load_graph(first_path)?;
+    let second = artifact::load_graph(second_path)?;
+    let comparison = manifest.time("compare", || {
+        graphcompare::compare_graphs(&first, &second, threshold)
+    });
+    if comparison.shared < 2 {
+        note!(
+            "Warning: the graphs share {} countries; there is nothing to compare",
+            comparison.shared
+        );
+    }
+
+    let table = graphcompare::comparison_table(&comparison);
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!("Wrote the comparison to {}", path);
+            write_manifest(&manifest, Some(path))?;
+        }
+        None => table.write_text(&mut console::stdout())?,
+    }
+    Ok(())
+}
+
+fn diff_data(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    let (old_path, new_path) = (matches.required("old"), matches.required("new"));
+    manifest.input_source(source::open_location(old_path)?.as_ref())?;
+    manifest.input_source(source::open_location(new_path)?.as_ref())?;
+    let tolerance = matches.parse_value::<f64>("tolerance")?.unwrap_or(0.0);
+    if !tolerance.is_finite() || tolerance < 0.0 {
+        return Err(invalid_input(format!(
+            "--tolerance must be a non-negative number, got {}",
+            tolerance
+        )));
+    }
+
+    let filter = observation_filter(matches)?;
+    let mut old = manifest.time("load", || load_data(old_path))?;
+    let mut new = manifest.time("load", || load_data(new_path))?;
+    apply_filter(filter.as_ref(), &mut old);
+    apply_filter(filter.as_ref(), &mut new);
+    let diff = manifest.time("diff", || datadiff::diff_data(&old, &new));
+    let material = diff.material_changes(tolerance);
+    note!(
+        "{} added, {} removed, {} changed, {} unchanged; {} material",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len(),
+        diff.unchanged,
+        material
+    );
+
+    let table = diff.to_table();
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            write_manifest(&manifest, Some(path))?;
+        }
+        None => table.write_text(&mut console::stdout())?,
+    }
+
+    let Some(path) = matches.value("rerun") else {
+        return Ok(());
+    };
+    if material == 0 {
+        note!("No material changes; not re-running the analysis");
+        return Ok(());
+    }
+    let parallelism = parallelism(matches)?;
+    let graph = manifest.time("build", || {
+        construct_similarity_graph_with(&new, SimilarityMetric::default(), parallelism)
+    });
+    let clusters = manifest.time("cluster", || {
+        Algorithm::Agglomerative.cluster(&graph, None, Stop::Auto, parallelism)
+    });
+    let (graph, clusters) = ordering::ordered(&graph, &clusters, NodeOrder::default());
+    let mut output = open_output(Some(path))?;
+    print_clusters(&mut output, &clusters, &graph)?;
+    output.flush()?;
+    note!("Re-ran the analysis on {} into {}", new_path, path);
+    write_manifest(&manifest, Some(path))
+}
+
+fn anonymize(matches: &Matches) -> io::Result<()> {
+    let mut manifest = start_manifest(matches);
+    manifest.input_source(source::open_location(matches.required("from"))?.as_ref())?;
+    let mut anonymizer = Anonymizer {
+        pseudonyms: matches.flag("pseudonyms"),
+        ..Anonymizer::default()
+    };
+    if let Some(fraction) = matches.parse_value::<f64>("fraction")? {
+        if !(fraction > 0.0 && fraction <= 1.0) {
+            return Err(invalid_input(format!(
+                "--fraction must be in (0, 1], got {}",
+                fraction
+            )));
+        }
+        anonymizer.fraction = fraction;
+    }
+    if let Some(jitter) = matches.parse_value::<f64>("jitter")? {
+        if !jitter.is_finite() || jitter < 0.0 {
+            return Err(invalid_input(format!(
+                "--jitter must be a non-negative number, got {}",
+                jitter
+            )));
+        }
+        anonymizer.jitter = jitter;
+    }
+    let seed = matches.parse_value::<u64>("seed")?.unwrap_or(0);
+    manifest.set_seed(seed);
+
+    let filter = observation_filter(matches)?;
+    let mut data = manifest.time("load", || load_data(matches.required("from")))?;
+    apply_filter(filter.as_ref(), &mut data);
+    let shared = manifest.time("anonymize", || anonymizer.apply(&data, &mut Rng::new(seed)));
+
+    // The long layout the loader reads back, header row included
+    let path = matches.required("output");
+    let mut output = open_output(Some(path))?;
+    dump::write_observations(&mut output, &shared)?;
+    output.flush()?;
+    note!(
+        "Wrote {} of {} observations to {}",
+        shared.len(),
+        data.len(),
+        path
+    );
+    write_manifest(&manifest, Some(path))
+}
+
+fn reference(matches: &Matches) -> io::Result<()> {
+    if let Some(from) = matches.value("install") {
+        let dir = reference::install(Path::new(from))?;
+        note!("Installed reference tables into {}", dir.display());
+    }
+
+    let data = ReferenceData::load()?;
+    if let Some(from) = matches.value("classify") {
+        let observations = load_data(from)?;
+        let seen: BTreeSet<&str> = observations
+            .iter()
+            .map(|record| record.country_or_area.as_str())
+            .collect();
+        let table = names::names_table(&data, seen);
+        return match matches.value("output") {
+            Some(path) => {
+                let mut output = open_output(Some(path))?;
+                table.write_csv(&mut output)?;
+                output.flush()?;
+                note!("Wrote the names of {} to {}", from, path);
+                console::record_output(path);
+                Ok(())
+            }
+            None => table.write_text(&mut console::stdout()),
+        };
+    }
+    let mut output = open_output(matches.value("output"))?;
+    match matches.value("lookup") {
+        Some(query) => {
+            let country = data.country(query).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no country matches {:?}", query),
+                )
+            })?;
+            let regions: Vec<&str> = data
+                .region_path(country.region)
+                .iter()
+                .map(|region| region.name.as_str())
+                .collect();
+            writeln!(
+                output,
+                "{} ({}, M49 {:03}): {}",
+                country.name,
+                country.iso3,
+                country.m49,
+                regions.join(" > ")
+            )?;
+        }
+        None => writeln!(
+            output,
+            "Reference data: {} ({} countries, {} regions)",
+            data.source,
+            data.countries.len(),
+            data.regions.len()
+        )?,
+    }
+    output.flush()
+}
+
+fn sweep(matches: &Matches) -> io::Result<RunStatus> {
+    let mut cancel = cancel_token(matches)?;
+    let config = Config::load(matches.required("config"))?;
+    let plan = SweepPlan::from_config(&config)?;
+    let parallelism = parallelism(matches)?;
+    let input = config.get_str("input")?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "sweep config needs an `input` path",
+        )
+    })?;
+
+    let mut manifest = start_manifest(matches);
+    manifest.input(matches.required("config"))?;
+    manifest.input_source(source::open_location(input)?.as_ref())?;
+    manifest.parameter("input", input);
+    manifest.parameter("rank_by", plan.rank_by.as_str());
+    for (name, values) in &plan.grid {
+        let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        manifest.parameter(&format!("grid.{}", name), values);
+    }
+
+    let mut trace = ObjectiveTrace::new();
+    let mut progress = ProgressPrinter::new(console::stderr());
+    let mut observers: Vec<&mut dyn IterationObserver> = vec![&mut cancel, &mut trace];
+    if matches.flag("progress") {
+        observers.push(&mut progress);
+    }
+
+    let transforms = TransformPlan::from_config(&config, &Transforms::default())?;
+    let mut data = manifest.time("load", || load_data(input))?;
+    apply_transforms(&transforms, &mut data);
+    let (runs, status) = manifest.time("sweep", || {
+        grid_search::run_sweep(&data, &plan, parallelism, &mut observers)
+    })?;
+    drop(observers);
+    manifest.set_status(status);
+    manifest.set_convergence(&trace);
+    if status != RunStatus::Completed {
+        let total = plan.combinations().len();
+        note!(
+            "Sweep stopped early ({}); ranking {} of {} combinations",
+            status,
+            runs.len(),
+            total
+        );
+    }
+    let table = grid_search::results_table(&plan, &runs);
+
+    match matches.value("output") {
+        Some(path) => {
+            let mut output = open_output(Some(path))?;
+            table.write_csv(&mut output)?;
+            output.flush()?;
+            note!("Wrote {} sweep results to {}", runs.len(), path);
+            write_manifest(&manifest, Some(path))?;
+        }
+        None => table.write_text(&mut console::stdout())?,
+    }
+    Ok(status)
+}
+
+// `--similarity`, with `--ties` for the rank correlation: the metric of the
+// similarity graph, cosine unless given, or None for `--value-graph`.
+fn similarity_metric(matches: &Matches) -> io::Result<Option<SimilarityMetric>> {
+    let similarity = matches.parse_value::<SimilarityMetric>("similarity")?;
+    let ties = matches.parse_value::<Ties>("ties")?;
+    if matches.flag("value-graph") {
+        if similarity.is_some() || ties.is_some() {
+            return Err(invalid_input(
+                "--value-graph sums values and cannot be combined with --similarity or --ties"
+                    .to_string(),
+            ));
+        }
+        if matches.value("normalize").is_some() {
+            return Err(invalid_input(
+                "--normalize scales the features of a similarity graph, not --value-graph"
+                    .to_string(),
+            ));
+        }
+        if matches.value("dump-features").is_some() {
+            return Err(invalid_input(
+                "--dump-features writes the features of a similarity graph, not --value-graph"
+                    .to_string(),
+            ));
+        }
+        if matches.flag("sparse") {
+            return Err(invalid_input(
+                "--sparse builds a similarity graph, not --value-graph".to_string(),
+            ));
+        }
+        return Ok(None);
+    }
+    match (similarity.unwrap_or_default(), ties) {
+        (SimilarityMetric::Spearman(_), Some(ties)) => Ok(Some(SimilarityMetric::Spearman(ties))),
+        (_, Some(_)) => Err(invalid_input(
+            "--ties applies only to --similarity spearman".to_string(),
+        )),
+        (metric, None) => Ok(Some(metric)),
+    }
+}
+
+fn csv_layout(matches: &Matches) -> io::Result<csv::Layout> {
+    let mut layout = csv::Layout::default();
+    if let Some(skip_rows) = matches.parse_value("skip-rows")? {
+        layout.skip_rows = skip_rows;
+    }
+    if let Some(header_rows) = matches.parse_value("header-rows")? {
+        layout.header_rows = header_rows;
+    }
+    Ok(layout)
+}
+
+fn parse_mode(matches: &Matches) -> ParseMode {
+    if matches.flag("strict") {
+        ParseMode::Strict
+    } else {
+        ParseMode::Lenient
+    }
+}
+
+// Parse `--where` up front so a bad expression fails before any loading.
+fn observation_filter(matches: &Matches) -> io::Result<Option<Filter>> {
+    matches.value("where").map(Filter::parse).transpose()
+}
+
+// `--years`, `--series` and `--indicator`, on top of any `--where`.
+fn data_filter(matches: &Matches) -> io::Result<DataFilter> {
+    let mut filter = DataFilter::new();
+    if let Some(years) = matches.value("years") {
+        filter = filter.years(filter::parse_years(years)?);
+    }
+    let list = |name: &str| {
+        matches
+            .value(name)
+            .into_iter()
+            .flat_map(|list| list.split(','))
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+    };
+    for series in list("series") {
+        filter = filter.series(series);
+    }
+    for indicator in list("indicator") {
+        filter = filter.indicator(indicator);
+    }
+    Ok(filter)
+}
+
+fn apply_filter(filter: Option<&Filter>, data: &mut Vec<EducationData>) {
+    if let Some(filter) = filter {
+        data.retain(|record| filter.matches(record));
+    }
+}
+
+// `--transforms PATH`: the value transforms of the config's series groups.
+fn transform_values(
+    matches: &Matches,
+    manifest: &mut Manifest,
+    data: &mut [EducationData],
+) -> io::Result<()> {
+    match matches.value("transforms") {
+        Some(path) => {
+            manifest.input(path)?;
+            apply_transforms(&TransformPlan::load(path, &Transforms::default())?, data);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+fn apply_transforms(plan: &TransformPlan, data: &mut [EducationData]) {
+    let dropped = plan.apply(&Transforms::default(), data);
+    if dropped > 0 {
+        note!(
+            "Warning: {} values were outside their transform's domain and are treated as missing",
+            dropped
+        );
+    }
+}
+
+// `--trend SPAN`: compare countries on their smoothed series rather than
+// the raw, noisier observations.
+fn smooth_to_trend(matches: &Matches, data: &mut [EducationData]) -> io::Result<()> {
+    if let Some(span) = matches.value("trend") {
+        trend::smooth_observations(data, trend::parse_span(span)?);
+    }
+    Ok(())
+}
+
+// `--impute HOW`: fill in (or drop) the missing values.
+fn impute_values(matches: &Matches, data: &mut Vec<EducationData>) -> io::Result<()> {
+    if let Some(imputation) = matches.parse_value::<Imputation>("impute")? {
+        let changed = imputation.apply(data);
+        note!(
+            "Imputed {} missing values (--impute {})",
+            changed,
+            matches.required("impute")
+        );
+    }
+    Ok(())
+}
+
+// `--sample F --seed N`: a share of each country's observations.
+fn sample_observations(
+    matches: &Matches,
+    manifest: &mut Manifest,
+    data: &mut Vec<EducationData>,
+) -> io::Result<()> {
+    let Some(fraction) = matches.parse_value::<f64>("sample")? else {
+        return Ok(());
+    };
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        return Err(invalid_input(format!(
+            "--sample must be in (0, 1], got {}",
+            fraction
+        )));
+    }
+    let seed = matches.parse_value::<u64>("seed")?.unwrap_or(0);
+    manifest.set_seed(seed);
+    let total = data.len();
+    let kept = sample::sample_by_country(data, fraction, &mut Rng::new(seed));
+    note!(
+        "Sampled {} of {} observations (--sample {}, --seed {})",
+        kept,
+        total,
+        fraction,
+        seed
+    );
+    Ok(())
+}
+
+fn node_order(matches: &Matches) -> io::Result<NodeOrder> {
+    Ok(matches.parse_value("order")?.unwrap_or_default())
+}
+
+// Build the cancellation token for a command: Ctrl-C plus optional --timeout.
+fn cancel_token(matches: &Matches) -> io::Result<CancelToken> {
+    let mut token = CancelToken::new();
+    if let Some(seconds) = matches.parse_value::<f64>("timeout")? {
+        if !seconds.is_finite() || seconds <= 0.0 {
+            return Err(invalid_input(format!(
+                "--timeout must be a positive number of seconds, got {}",
+                seconds
+            )));
+        }
+        token = token.with_timeout(Duration::from_secs_f64(seconds));
+    }
+    Ok(token)
+}
+
+// Mark a run that was stopped (a --timeout, Ctrl-C) before it finished, so
+// that the partial results written next are recognizable as such.
+fn stopped_early(manifest: &mut Manifest, status: RunStatus) -> RunStatus {
+    if status != RunStatus::Completed {
+        manifest.set_status(status);
+        note!(
+            "Stopped early ({}); writing the results found so far",
+            status
+        );
+    }
+    status
+}
+
+// What the shared pipeline options stand for when left unset. Options whose
+// absence means "none" (no --min-weight, no --output) and counts whose
+// default differs between commands are not listed.
+const OPTION_DEFAULTS: &[(&str, &str)] = &[
+    ("algo", "agglomerative"),
+    ("linkage", "average"),
+    ("seed", "0"),
+    ("seeding", "kmeans++"),
+    ("select", "best"),
+    ("similarity", "cosine"),
+    ("symmetric", "none"),
+    ("threshold", "0"),
+];
+
+// Start a manifest that records every option the command runs with: those
+// given on the command line or filled in from a pipeline file or preset,
+// then the defaults of the options left unset.
+fn start_manifest(matches: &Matches) -> Manifest {
+    let mut manifest = Manifest::new(matches.command.name);
+    for arg in matches.command.args {
+        if let Some(value) = matches.value(arg.name) {
+            manifest.parameter(arg.name, value);
+        }
+    }
+    let unset = |name: &str| {
+        matches.command.args.iter().any(|arg| arg.name == name) && matches.value(name).is_none()
+    };
+    for &(name, value) in OPTION_DEFAULTS {
+        // A value graph has no similarity
+        if unset(name) && !(name == "similarity" && matches.flag("value-graph")) {
+            manifest.parameter(name, value);
+        }
+    }
+    if unset("ties") && matches.value("similarity") == Some("spearman") {
+        manifest.parameter("ties", "average");
+    }
+    let parallelism = Parallelism::default();
+    if unset("threads") {
+        manifest.parameter("threads", parallelism.threads);
+    }
+    if unset("chunk-size") {
+        manifest.parameter("chunk-size", parallelism.chunk_size);
+    }
+    manifest
+}
+
+// Outputs written to a file get a manifest alongside, and the completion
+// notices configured in the environment; stdout output gets neither.
+fn write_manifest(manifest: &Manifest, output_path: Option<&str>) -> io::Result<()> {
+    if let Some(path) = output_path {
+        console::record_output(path);
+        let written = manifest.write_for(path)?;
+        Notifier::from_env().completed(path, &written)?;
+    }
+    Ok(())
+}
+
+// `build --from` takes a cached dataset from `load`, an observation store
+// from `ingest` or a raw CSV.
+pub fn load_data(location: &str) -> io::Result<Vec<EducationData>> {
+    if artifact::is_dataset(location) {
+        artifact::load_dataset(location)
+    } else if artifact::is_store(location) {
+        Ok(Store::open(location)?.into_observations())
+    } else {
+        let input = source::open_location(location)?;
+        load_and_preprocess_data(input.as_ref(), &csv::Layout::default())
+    }
+}
+
+// The similarity graph of the features, each series scaled by any
+// `--normalize`, through the `--cache-dir` cache if there is one. With
+// `--sparse` only the pairs of at least `--min-weight` are kept as they
+// are computed.
+fn similarity_graph(
+    matches: &Matches,
+    data: &[EducationData],
+    metric: SimilarityMetric,
+    parallelism: Parallelism,
+) -> io::Result<Graph> {
+    let mut features = features::feature_matrix(data, None);
+    if let Some(scaling) = matches.parse_value::<Scaling>("normalize")? {
+        features = features.standardized(scaling);
+    }
+    dump_intermediate(matches, "dump-features", "features", |writer| {
+        dump::write_features(writer, &features)
+    })?;
+    if matches.flag("sparse") {
+        let Some(min_weight) = matches.parse_value("min-weight")? else {
+            return Err(invalid_input(
+                "--sparse keeps the pairs of at least --min-weight; give one".to_string(),
+            ));
+        };
+        if matches.value("cache-dir").is_some() {
+            return Err(invalid_input(
+                "--cache-dir keeps whole similarity graphs, not --sparse ones".to_string(),
+            ));
+        }
+        let graph = sparse_similarity_graph_of(features, metric, min_weight, parallelism);
+        return Ok(graph.to_dense());
+    }
+    let Some(dir) = matches.value("cache-dir") else {
+        return Ok(similarity_graph_of(features, metric, parallelism));
+    };
+    let cached =
+        similarity_cache::cached_similarity_graph(Path::new(dir), features, metric, parallelism)?;
+    if cached.reused {
+        note!(
+            "Reused the similarities cached in {}",
+            cached.path.display()
+        );
+    } else {
+        note!("Cached the similarities in {}", cached.path.display());
+    }
+    Ok(cached.graph)
+}
+
+// `--aggregates`, `--iso-codes`, `--reconcile-names` and `--dissolved`,
+// None when they leave the names as they are.
+fn country_names(matches: &Matches) -> io::Result<Option<Names>> {
+    let options = names::Options {
+        aggregates: matches.parse_value("aggregates")?.unwrap_or_default(),
+        iso_codes: matches.flag("iso-codes"),
+        reconcile: matches.flag("reconcile-names"),
+        dissolved: matches.parse_value("dissolved")?.unwrap_or_default(),
+    };
+    if !options.changes_names() {
+        return Ok(None);
+    }
+    Ok(Some(Names::new(ReferenceData::load()?, options)))
+}
+
+fn report_names(names: Option<&Names>) {
+    let Some(names) = names else { return };
+    for (name, reference) in names.reconciled() {
+        note!("Reconciled {} with {}", name, reference);
+    }
+    for (state, successor) in names.merged() {
+        note!("Merged {} into its successor {}", state, successor);
+    }
+    if !names.unknown().is_empty() {
+        let unknown: Vec<&str> = names.unknown().iter().map(String::as_str).collect();
+        note!(
+            "Warning: kept {} names the reference tables do not know: {}",
+            unknown.len(),
+            unknown.join(", ")
+        );
+    }
+}
+
+fn graph_policy(matches: &Matches) -> io::Result<GraphPolicy> {
+    let top_k = matches.parse_value::<usize>("top-k")?;
+    if top_k == Some(0) {
+        return Err(invalid_input("--top-k must be at least 1".to_string()));
+    }
+    Ok(GraphPolicy {
+        self_loops: !matches.flag("no-self-loops"),
+        symmetry: matches.parse_value("symmetric")?.unwrap_or_default(),
+        min_weight: matches.parse_value("min-weight")?,
+        top_k,
+    })
+}
+
+// `--dump-cleaned`: the observations the graph is built from.
+fn dump_cleaned(matches: &Matches, data: &[EducationData]) -> io::Result<()> {
+    dump_intermediate(matches, "dump-cleaned", "cleaned observations", |writer| {
+        dump::write_observations(writer, data)
+    })
+}
+
+// `--dump-distances`: the weights the clustering is given.
+fn dump_distances(matches: &Matches, graph: &Graph) -> io::Result<()> {
+    dump_intermediate(matches, "dump-distances", "graph weights", |writer| {
+        dump::write_distances(writer, graph)
+    })
+}
+
+fn dump_intermediate(
+    matches: &Matches,
+    name: &str,
+    what: &str,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let Some(path) = matches.value(name) else {
+        return Ok(());
+    };
+    let mut output = open_output(Some(path))?;
+    write(&mut output)?;
+    output.flush()?;
+    console::record_output(path);
+    note!("Wrote the {} to {}", what, path);
+    Ok(())
+}
+
+fn stop(matches: &Matches) -> io::Result<Stop> {
+    match (
+        matches.parse_value("clusters")?,
+        matches.parse_value("cutoff")?,
+    ) {
+        (Some(_), Some(_)) => Err(invalid_input(
+            "--clusters and --cutoff cannot be combined".to_string(),
+        )),
+        (Some(0), None) => Err(invalid_input("--clusters must be at least 1".to_string())),
+        (Some(count), None) => Ok(Stop::Clusters(count)),
+        (None, Some(cutoff)) => Ok(Stop::Cutoff(cutoff)),
+        (None, None) => Ok(Stop::Auto),
+    }
+}
+
+fn series_catalog(matches: &Matches) -> io::Result<SeriesCatalog> {
+    match matches.value("catalog") {
+        Some(path) => SeriesCatalog::with_file(path),
+        None => Ok(SeriesCatalog::bundled()),
+    }
+}
+
+fn parallelism(matches: &Matches) -> io::Result<Parallelism> {
+    Parallelism::new(
+        matches.parse_value("threads")?,
+        matches.parse_value("chunk-size")?,
+    )
+}
+
+fn open_output(path: Option<&str>) -> io::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(console::stdout()),
+    })
+}
+
+// Print node/edge counts, the edge weight range and, when a clustering is
+// given, the cluster sizes.
+fn print_summary(
+    writer: &mut dyn Write,
+    graph: &Graph,
+    clusters: Option<&[Vec<usize>]>,
+) -> io::Result<()> {
+    let node_count = graph.nodes.len();
+    let weights = matrix::storage(&graph.adjacency_matrix).edge_weights();
+
+    writeln!(writer, "Nodes: {}", node_count)?;
+    writeln!(writer, "Edges: {}", weights.len())?;
+    if node_count > 1 {
+        let possible = (node_count * (node_count - 1)) as f64;
+        writeln!(writer, "Density: {:.4}", weights.len() as f64 / possible)?;
+    }
+    if node_count > 1 {
+        // Close to 0 when the graph nearly falls apart into separate groups
+        let laplacian = graph.normalized_laplacian();
+        if let Some(second) = eigen::bottom_k(&laplacian, 2, Default::default()).get(1) {
+            writeln!(writer, "Spectral gap: {:.4}", second.value)?;
+        }
+    }
+    if !weights.is_empty() {
+        let min = weights.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = weights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let mean = stats::mean(&weights).unwrap_or(0.0);
+        writeln!(
+            writer,
+            "Edge weight: min {:.4}, mean {:.4}, max {:.4}",
+            min, mean, max
+        )?;
+    }
+
+    if let Some(clusters) = clusters {
+        let assigned: usize = clusters.iter().map(|cluster| cluster.len()).sum();
+        writeln!(writer, "Clusters: {}", clusters.len())?;
+        for (cluster_index, cluster) in clusters.iter().enumerate() {
+            writeln!(
+                writer,
+                "  Cluster {}: {} nodes",
+                cluster_index,
+                cluster.len()
+            )?;
+        }
+        writeln!(
+            writer,
+            "Unassigned nodes: {}",
+            node_count.saturating_sub(assigned)
+        )?;
+        writeln!(
+            writer,
+            "Modularity: {:.4}",
+            louvain::modularity(graph, clusters)
+        )?;
+    }
+
+    Ok(())
+}
+
+// The figures of `print_summary` as one object, for scripts: `density`,
+// `spectral_gap` and `edge_weight` are left out where the text leaves them
+// out, and `clusters` holds each cluster's size.
+fn summary_json(graph: &Graph, clusters: Option<&[Vec<usize>]>) -> Json {
+    let node_count = graph.nodes.len();
+    let weights = matrix::storage(&graph.adjacency_matrix).edge_weights();
+    let mut summary = Json::object()
+        .with("nodes", node_count)
+        .with("edges", weights.len());
+    if node_count > 1 {
+        let possible = (node_count * (node_count - 1)) as f64;
+        summary = summary.with("density", weights.len() as f64 / possible);
+        let laplacian = graph.normalized_laplacian();
+        if let Some(second) = eigen::bottom_k(&laplacian, 2, Default::default()).get(1) {
+            summary = summary.with("spectral_gap", second.value);
+        }
+    }
+    if !weights.is_empty() {
+        summary = summary.with(
+            "edge_weight",
+            Json::object()
+                .with("min", weights.iter().cloned().fold(f64::INFINITY, f64::min))
+                .with("mean", stats::mean(&weights).unwrap_or(0.0))
+                .with(
+                    "max",
+                    weights.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+                ),
+        );
+    }
+    if let Some(clusters) = clusters {
+        let assigned: usize = clusters.iter().map(|cluster| cluster.len()).sum();
+        let sizes = clusters.iter().map(|cluster| Json::from(cluster.len()));
+        summary = summary
+            .with("clusters", Json::Array(sizes.collect()))
+            .with("unassigned", node_count.saturating_sub(assigned))
+            .with("modularity", louvain::modularity(graph, clusters));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use crate::kmeans::Seeding;
+    use crate::symmetry::Symmetry;
+
+    #[test]
+    fn test_option_defaults_are_the_types_defaults() {
+        for &(name, value) in OPTION_DEFAULTS {
+            let parsed = match name {
+                "algo" => value.parse::<Algorithm>().unwrap() == Algorithm::default(),
+                "linkage" => value.parse::<Linkage>().unwrap() == Linkage::default(),
+                "seed" => value == "0",
+                "seeding" => value.parse::<Seeding>().unwrap() == Seeding::default(),
+                "select" => value.parse::<Selection>().unwrap() == Selection::default(),
+                "similarity" => {
+                    value.parse::<SimilarityMetric>().unwrap() == SimilarityMetric::default()
+                }
+                "symmetric" => value.parse::<Symmetry>().unwrap() == Symmetry::default(),
+                "threshold" => value.parse::<f64>().unwrap() == 0.0,
+                other => panic!("no type to check the default of --{} against", other),
+            };
+            assert!(parsed, "--{} does not default to {}", name, value);
+        }
+    }
+
+    #[test]
+    fn test_manifest_records_effective_options() {
+        let args: Vec<String> = [
+            "run",
+            "--demo",
+            "--algo",
+            "louvain",
+            "--similarity",
+            "spearman",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+        let Ok(cli::Parsed::Run(matches)) = cli::parse(&args) else {
+            panic!("`run` did not parse");
+        };
+        let manifest = start_manifest(&matches).to_json(&[]);
+        let parameters = manifest.get("parameters").unwrap();
+        // Given values are kept, and the unset options get their defaults
+        assert_eq!(parameters.get("algo"), Some(&Json::from("louvain")));
+        assert_eq!(parameters.get("ties"), Some(&Json::from("average")));
+        assert_eq!(parameters.get("symmetric"), Some(&Json::from("none")));
+        assert_eq!(
+            parameters.get("threads"),
+            Some(&Json::from(Parallelism::default().threads))
+        );
+        assert_eq!(parameters.get("min-weight"), None);
+    }
+
+    #[test]
+    fn test_print_summary() {
+        let graph = Graph {
+            nodes: vec!["A".to_string(), "B".to_string(), "C".to_string()],
+            adjacency_matrix: vec![
+                vec![5.0, 1.0, 0.0],
+                vec![1.0, 5.0, 3.0],
+                vec![0.0, 3.0, 5.0],
+            ],
+        };
+        let clusters = vec![vec![0, 1]];
+
+        let mut buffer = Vec::new();
+        print_summary(&mut buffer, &graph, Some(&clusters)).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+
+        // Self-loops and zero weights are not counted as edges
+        assert!(output.contains("Nodes: 3\nEdges: 4\n"));
+        assert!(output.contains("Edge weight: min 1.0000, mean 2.0000, max 3.0000"));
+        assert!(output.contains("Clusters: 1\n  Cluster 0: 2 nodes\nUnassigned nodes: 1"));
+
+        let summary = summary_json(&graph, Some(&clusters));
+        assert_eq!(summary.get("edges"), Some(&Json::from(4usize)));
+        assert_eq!(
+            summary.get("clusters"),
+            Some(&Json::Array(vec![Json::from(2usize)]))
+        );
+        assert_eq!(summary.get("unassigned"), Some(&Json::from(1usize)));
+    }
+}
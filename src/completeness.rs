@@ -0,0 +1,117 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::table::Table;
+use crate::EducationData;
+
+// How thin the dataset is, country by country: for each series, the share
+// of its years a country reports a value in. A series' years are those in
+// which any country reports it, so a series collected only every five years
+// is not held against anyone; a series no one reports scores 0 throughout.
+pub struct Completeness {
+    pub countries: Vec<String>,
+    pub series: Vec<String>,
+    // `fractions[country][series]`, from 0 to 1
+    pub fractions: Vec<Vec<f64>>,
+}
+
+pub fn completeness(data: &[EducationData]) -> Completeness {
+    let mut years: BTreeMap<&str, BTreeSet<u32>> = BTreeMap::new();
+    let mut reported: BTreeMap<(&str, &str), BTreeSet<u32>> = BTreeMap::new();
+    let mut countries = BTreeSet::new();
+    for record in data {
+        countries.insert(record.country_or_area.as_str());
+        let series_years = years.entry(record.series.as_str()).or_default();
+        if record.value.is_some() {
+            series_years.insert(record.year);
+            reported
+                .entry((record.country_or_area.as_str(), record.series.as_str()))
+                .or_default()
+                .insert(record.year);
+        }
+    }
+    let fractions = countries
+        .iter()
+        .map(|&country| {
+            years
+                .iter()
+                .map(|(&series, all)| {
+                    let count = reported.get(&(country, series)).map_or(0, BTreeSet::len);
+                    if all.is_empty() {
+                        0.0
+                    } else {
+                        count as f64 / all.len() as f64
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    Completeness {
+        countries: countries.into_iter().map(str::to_string).collect(),
+        series: years.into_keys().map(str::to_string).collect(),
+        fractions,
+    }
+}
+
+impl Completeness {
+    // Each country's mean over the series.
+    pub fn overall(&self) -> Vec<f64> {
+        self.fractions
+            .iter()
+            .map(|row| {
+                if row.is_empty() {
+                    0.0
+                } else {
+                    row.iter().sum::<f64>() / row.len() as f64
+                }
+            })
+            .collect()
+    }
+
+    // A country per row, a column per series, then the overall share.
+    pub fn table(&self) -> Table {
+        let mut headers = vec!["country"];
+        headers.extend(self.series.iter().map(String::as_str));
+        headers.push("overall");
+        let mut table = Table::new(&headers);
+        for ((country, row), overall) in self
+            .countries
+            .iter()
+            .zip(&self.fractions)
+            .zip(self.overall())
+        {
+            let mut cells = vec![country.clone()];
+            cells.extend(row.iter().map(|fraction| format!("{:.3}", fraction)));
+            cells.push(format!("{:.3}", overall));
+            table.push_row(cells);
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_share_of_years_reported() {
+        let data = vec![
+            record("Chad", "primary", 2010, Some(1.0)),
+            record("Chad", "primary", 2015, None),
+            record("Mali", "primary", 2015, Some(2.0)),
+            record("Mali", "primary", 2020, Some(2.0)),
+            record("Mali", "tertiary", 2015, Some(3.0)),
+            record("Chad", "unreported", 2015, None),
+        ];
+        let result = completeness(&data);
+        assert_eq!(result.countries, ["Chad", "Mali"]);
+        assert_eq!(result.series, ["primary", "tertiary", "unreported"]);
+        // primary was reported in 2010, 2015 and 2020
+        assert_eq!(result.fractions[0], [1.0 / 3.0, 0.0, 0.0]);
+        assert_eq!(result.fractions[1], [2.0 / 3.0, 1.0, 0.0]);
+        assert_eq!(result.overall()[1], (2.0 / 3.0 + 1.0) / 3.0);
+        let table = result.table();
+        assert_eq!(table.headers.last().unwrap(), "overall");
+        assert_eq!(table.rows[0][1], "0.333");
+    }
+}
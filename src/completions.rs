@@ -0,0 +1,345 @@
+use std::io::{self, Write};
+
+use crate::cli::{ArgKind, Command, BIN_NAME, COMMANDS, GLOBAL_FLAGS};
+
+// Completion scripts are generated from the same `cli::COMMANDS` table the
+// parser uses, so new subcommands and options show up automatically. The
+// global flags are offered before the command and among every command's
+// options.
+pub fn write_completions(writer: &mut dyn Write, shell: &str) -> io::Result<()> {
+    match shell {
+        "bash" => write_bash(writer),
+        "zsh" => write_zsh(writer),
+        "fish" => write_fish(writer),
+        other => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("no completions for shell {:?}", other),
+        )),
+    }
+}
+
+fn command_names() -> String {
+    let mut names: Vec<&str> = COMMANDS.iter().map(|command| command.name).collect();
+    names.push("help");
+    names.join(" ")
+}
+
+fn global_flags() -> impl Iterator<Item = String> {
+    GLOBAL_FLAGS.iter().map(|flag| format!("--{}", flag.name))
+}
+
+// Options and flags, i.e. everything spelled `--name`
+fn options(command: &Command) -> impl Iterator<Item = &crate::cli::Arg> {
+    command
+        .args
+        .iter()
+        .filter(|arg| arg.kind != ArgKind::Positional)
+}
+
+fn write_bash(writer: &mut dyn Write) -> io::Result<()> {
+    let function = format!("_{}", BIN_NAME);
+    writeln!(writer, "{}() {{", function)?;
+    writeln!(writer, "    local cur prev word subcommand")?;
+    writeln!(writer, "    cur=\"${{COMP_WORDS[COMP_CWORD]}}\"")?;
+    writeln!(writer, "    prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"")?;
+    writeln!(writer)?;
+    // Only global flags can come before the command, and they take no value
+    writeln!(
+        writer,
+        "    for word in \"${{COMP_WORDS[@]:1:COMP_CWORD-1}}\"; do"
+    )?;
+    writeln!(
+        writer,
+        "        [[ $word != -* ]] && subcommand=$word && break"
+    )?;
+    writeln!(writer, "    done")?;
+    writeln!(writer, "    if [[ -z $subcommand ]]; then")?;
+    let first_words: Vec<String> = global_flags().collect();
+    writeln!(
+        writer,
+        "        COMPREPLY=( $(compgen -W \"{} {}\" -- \"$cur\") )",
+        command_names(),
+        first_words.join(" ")
+    )?;
+    writeln!(writer, "        return")?;
+    writeln!(writer, "    fi")?;
+    writeln!(writer)?;
+    writeln!(writer, "    case \"$subcommand\" in")?;
+    for command in COMMANDS {
+        writeln!(writer, "        {})", command.name)?;
+
+        // Complete the value of the option that was just typed
+        writeln!(writer, "            case \"$prev\" in")?;
+        for arg in options(command) {
+            let action = if !arg.possible_values.is_empty() {
+                format!("-W \"{}\"", arg.possible_values.join(" "))
+            } else if arg.takes_path() {
+                "-f".to_string()
+            } else {
+                continue;
+            };
+            writeln!(
+                writer,
+                "                --{}) COMPREPLY=( $(compgen {} -- \"$cur\") ); return ;;",
+                arg.name, action
+            )?;
+        }
+        writeln!(writer, "            esac")?;
+
+        let mut words: Vec<String> = options(command)
+            .map(|arg| format!("--{}", arg.name))
+            .collect();
+        words.extend(global_flags());
+        words.push("--help".to_string());
+        for arg in command
+            .args
+            .iter()
+            .filter(|arg| arg.kind == ArgKind::Positional)
+        {
+            words.extend(arg.possible_values.iter().map(|value| value.to_string()));
+        }
+        writeln!(
+            writer,
+            "            COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )",
+            words.join(" ")
+        )?;
+        writeln!(writer, "            ;;")?;
+    }
+    writeln!(writer, "    esac")?;
+    writeln!(writer, "}}")?;
+    writeln!(writer, "complete -F {} {}", function, BIN_NAME)
+}
+
+// Escape text placed inside a single-quoted zsh `_arguments` spec.
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn write_zsh(writer: &mut dyn Write) -> io::Result<()> {
+    writeln!(writer, "#compdef {}", BIN_NAME)?;
+    writeln!(writer)?;
+    writeln!(writer, "_{}() {{", BIN_NAME)?;
+    writeln!(writer, "    local -a commands")?;
+    writeln!(writer, "    commands=(")?;
+    for command in COMMANDS {
+        writeln!(
+            writer,
+            "        '{}:{}'",
+            command.name,
+            zsh_escape(command.about)
+        )?;
+    }
+    writeln!(writer, "        'help:Show help for a command'")?;
+    writeln!(writer, "    )")?;
+    writeln!(writer)?;
+    // Global flags before the command are passed over
+    let flags: Vec<String> = global_flags().collect();
+    writeln!(
+        writer,
+        "    while (( CURRENT > 2 )) && [[ \" {} \" == *\" $words[2] \"* ]]; do",
+        flags.join(" ")
+    )?;
+    writeln!(writer, "        words[2]=()")?;
+    writeln!(writer, "        (( CURRENT-- ))")?;
+    writeln!(writer, "    done")?;
+    writeln!(writer, "    if (( CURRENT == 2 )); then")?;
+    writeln!(writer, "        _describe 'command' commands")?;
+    writeln!(writer, "        compadd -- {}", flags.join(" "))?;
+    writeln!(writer, "        return")?;
+    writeln!(writer, "    fi")?;
+    writeln!(writer)?;
+    writeln!(writer, "    local subcommand=$words[2]")?;
+    writeln!(writer, "    shift words")?;
+    writeln!(writer, "    (( CURRENT-- ))")?;
+    writeln!(writer, "    case $subcommand in")?;
+    for command in COMMANDS {
+        writeln!(writer, "        {})", command.name)?;
+        writeln!(writer, "            _arguments \\")?;
+        let mut position = 0;
+        for arg in command.args {
+            let action = if !arg.possible_values.is_empty() {
+                format!("({})", arg.possible_values.join(" "))
+            } else if arg.takes_path() {
+                "_files".to_string()
+            } else {
+                String::new()
+            };
+            match arg.kind {
+                ArgKind::Flag => writeln!(
+                    writer,
+                    "                '--{}[{}]' \\",
+                    arg.name,
+                    zsh_escape(arg.help)
+                )?,
+                ArgKind::Option => writeln!(
+                    writer,
+                    "                '--{}[{}]:{}:{}' \\",
+                    arg.name,
+                    zsh_escape(arg.help),
+                    arg.value_name,
+                    action
+                )?,
+                ArgKind::Positional => {
+                    position += 1;
+                    writeln!(
+                        writer,
+                        "                '{}:{}:{}' \\",
+                        position,
+                        zsh_escape(arg.help),
+                        action
+                    )?
+                }
+            }
+        }
+        for flag in GLOBAL_FLAGS {
+            writeln!(
+                writer,
+                "                '--{}[{}]' \\",
+                flag.name,
+                zsh_escape(flag.help)
+            )?;
+        }
+        writeln!(writer, "                '--help[Show help]'")?;
+        writeln!(writer, "            ;;")?;
+    }
+    writeln!(writer, "    esac")?;
+    writeln!(writer, "}}")?;
+    writeln!(writer)?;
+    writeln!(writer, "_{} \"$@\"", BIN_NAME)
+}
+
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn write_fish(writer: &mut dyn Write) -> io::Result<()> {
+    writeln!(writer, "complete -c {} -f", BIN_NAME)?;
+    for command in COMMANDS {
+        writeln!(
+            writer,
+            "complete -c {} -n '__fish_use_subcommand' -a {} -d '{}'",
+            BIN_NAME,
+            command.name,
+            fish_escape(command.about)
+        )?;
+    }
+    writeln!(
+        writer,
+        "complete -c {} -n '__fish_use_subcommand' -a help -d 'Show help for a command'",
+        BIN_NAME
+    )?;
+    // With no condition, offered before the command and after it alike
+    for flag in GLOBAL_FLAGS {
+        writeln!(
+            writer,
+            "complete -c {} -l {} -d '{}'",
+            BIN_NAME,
+            flag.name,
+            fish_escape(flag.help)
+        )?;
+    }
+
+    for command in COMMANDS {
+        let condition = format!("__fish_seen_subcommand_from {}", command.name);
+        for arg in command.args {
+            let values = if arg.possible_values.is_empty() {
+                String::new()
+            } else {
+                format!(" -a '{}'", arg.possible_values.join(" "))
+            };
+            match arg.kind {
+                ArgKind::Flag => writeln!(
+                    writer,
+                    "complete -c {} -n '{}' -l {} -d '{}'",
+                    BIN_NAME,
+                    condition,
+                    arg.name,
+                    fish_escape(arg.help)
+                )?,
+                ArgKind::Option => {
+                    let path = if arg.takes_path() { " -F" } else { "" };
+                    writeln!(
+                        writer,
+                        "complete -c {} -n '{}' -l {} -r{}{} -d '{}'",
+                        BIN_NAME,
+                        condition,
+                        arg.name,
+                        path,
+                        values,
+                        fish_escape(arg.help)
+                    )?
+                }
+                ArgKind::Positional => writeln!(
+                    writer,
+                    "complete -c {} -n '{}'{} -d '{}'",
+                    BIN_NAME,
+                    condition,
+                    values,
+                    fish_escape(arg.help)
+                )?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate(shell: &str) -> String {
+        let mut buffer = Vec::new();
+        write_completions(&mut buffer, shell).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_scripts_cover_every_subcommand() {
+        for shell in ["bash", "zsh", "fish"] {
+            let script = generate(shell);
+            for command in COMMANDS {
+                assert!(
+                    script.contains(command.name),
+                    "{} completions are missing `{}`",
+                    shell,
+                    command.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_scripts_offer_the_global_flags() {
+        for shell in ["bash", "zsh", "fish"] {
+            let script = generate(shell);
+            assert!(script.contains("--quiet") || script.contains("-l quiet"));
+        }
+        // Before the command, and among each command's options
+        let bash = generate("bash");
+        assert!(bash.contains("help --quiet\" -- \"$cur\""));
+        assert_eq!(bash.matches("--quiet --help").count(), COMMANDS.len());
+        let zsh = generate("zsh");
+        assert_eq!(zsh.matches("'--quiet[").count(), COMMANDS.len());
+        assert!(generate("fish").contains("complete -c ds210 -l quiet -d "));
+    }
+
+    #[test]
+    fn test_bash_completes_option_values() {
+        let script = generate("bash");
+
+        // Path options complete files, positional choices are offered as words
+        assert!(script.contains("--input) COMPREPLY=( $(compgen -f -- \"$cur\") ); return ;;"));
+        assert!(script.contains("--help bash zsh fish"));
+        assert!(script.ends_with("complete -F _ds210 ds210\n"));
+    }
+
+    #[test]
+    fn test_unknown_shell_is_rejected() {
+        let mut buffer = Vec::new();
+        assert!(write_completions(&mut buffer, "powershell").is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+use crate::catalog::{Direction, SeriesCatalog};
+use crate::features::{FeatureMatrix, Scaling};
+use crate::stats::mean;
+use crate::table::Table;
+
+// A composite index over every series: each country's mean z-score across
+// the series it reports, with the series where lower is better (a
+// pupil-teacher ratio, an out-of-school rate) inverted first, so a higher
+// score always reads as better. Directions come from the series catalog;
+// `invert` and `keep` override it for the series they name. Series are
+// scaled by mean and standard deviation, or robustly (`Scaling::Robust`).
+
+pub struct CountryScore {
+    pub country: String,
+    pub score: f64,
+    // Series the score averages over
+    pub series: usize,
+}
+
+// +1 for each series scored as is, -1 for each inverted one. Naming a
+// series the data does not have is an error, as it is most likely a typo.
+pub fn orientations(
+    series: &[String],
+    catalog: &SeriesCatalog,
+    invert: &[String],
+    keep: &[String],
+) -> io::Result<Vec<f64>> {
+    if let Some(unknown) = invert
+        .iter()
+        .chain(keep)
+        .find(|name| !series.contains(name))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("no series named {:?} in the data", unknown),
+        ));
+    }
+    Ok(series
+        .iter()
+        .map(|name| {
+            let inverted = if keep.contains(name) {
+                false
+            } else {
+                invert.contains(name) || catalog.direction(name) == Direction::LowerIsBetter
+            };
+            if inverted {
+                -1.0
+            } else {
+                1.0
+            }
+        })
+        .collect())
+}
+
+// Scores of the countries reporting at least one series, best first, ties
+// in name order. A series every country reports alike adds nothing.
+pub fn composite_scores(
+    features: &FeatureMatrix,
+    orientations: &[f64],
+    scaling: Scaling,
+) -> Vec<CountryScore> {
+    let scaled = features.standardized(scaling);
+    let mut scores: Vec<CountryScore> = scaled
+        .countries
+        .iter()
+        .zip(&scaled.values)
+        .filter_map(|(country, row)| {
+            let z: Vec<f64> = row
+                .iter()
+                .zip(orientations)
+                .filter(|(z, _)| !z.is_nan())
+                .map(|(z, sign)| sign * z)
+                .collect();
+            Some(CountryScore {
+                country: country.clone(),
+                score: mean(&z)?,
+                series: z.len(),
+            })
+        })
+        .collect();
+    scores.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.country.cmp(&b.country))
+    });
+    scores
+}
+
+pub fn score_table(scores: &[CountryScore], clusters: Option<&HashMap<String, String>>) -> Table {
+    let mut headers = vec!["rank", "country", "score", "series"];
+    if clusters.is_some() {
+        headers.push("cluster");
+    }
+    let mut table = Table::new(&headers);
+    for (index, entry) in scores.iter().enumerate() {
+        let mut row = vec![
+            (index + 1).to_string(),
+            entry.country.clone(),
+            format!("{:+.4}", entry.score),
+            entry.series.to_string(),
+        ];
+        if let Some(clusters) = clusters {
+            row.push(
+                clusters
+                    .get(&entry.country)
+                    .cloned()
+                    .unwrap_or_else(|| "-".to_string()),
+            );
+        }
+        table.push_row(row);
+    }
+    table
+}
+
+// Each cluster's mean score and size, best first: a one-line reading of
+// what sets the clusters apart.
+pub fn cluster_table(scores: &[CountryScore], clusters: &HashMap<String, String>) -> Table {
+    let mut members: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+    for entry in scores {
+        if let Some(cluster) = clusters.get(&entry.country) {
+            members.entry(cluster).or_default().push(entry.score);
+        }
+    }
+    let mut means: Vec<(&str, f64, usize)> = members
+        .iter()
+        .filter_map(|(cluster, scores)| Some((*cluster, mean(scores)?, scores.len())))
+        .collect();
+    means.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    let mut table = Table::new(&["cluster", "mean score", "countries"]);
+    for (cluster, score, count) in means {
+        table.push_row(vec![
+            cluster.to_string(),
+            format!("{:+.4}", score),
+            count.to_string(),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lower_is_better_series_are_inverted() {
+        let ratio = "Pupil-teacher ratio - Primary".to_string();
+        let enrolment = "Gross enrollment ratio - Primary (female)".to_string();
+        let features = FeatureMatrix {
+            countries: vec!["Chad".to_string(), "Mali".to_string(), "Sweden".to_string()],
+            series: vec![enrolment.clone(), ratio.clone()],
+            values: vec![vec![80.0, 60.0], vec![90.0, f64::NAN], vec![100.0, 10.0]],
+        };
+        let catalog = SeriesCatalog::bundled();
+        let signs = orientations(&features.series, &catalog, &[], &[]).unwrap();
+        assert_eq!(signs, [1.0, -1.0]);
+        let scores = composite_scores(&features, &signs, Scaling::Standard);
+        let order: Vec<&str> = scores.iter().map(|entry| entry.country.as_str()).collect();
+        assert_eq!(order, ["Sweden", "Mali", "Chad"]);
+        // Sweden: well above on enrolment, one deviation better on the ratio
+        let spread = (200.0f64 / 3.0).sqrt();
+        assert!((scores[0].score - (10.0 / spread + 1.0) / 2.0).abs() < 1e-12);
+        assert_eq!(scores[1].series, 1);
+
+        // Kept as is, Chad's crowded classrooms count in its favour
+        let signs = orientations(
+            &features.series,
+            &catalog,
+            &[],
+            std::slice::from_ref(&ratio),
+        )
+        .unwrap();
+        let scores = composite_scores(&features, &signs, Scaling::Standard);
+        let chad = scores.iter().find(|entry| entry.country == "Chad").unwrap();
+        assert!((chad.score - (1.0 - 10.0 / spread) / 2.0).abs() < 1e-12);
+        let signs = orientations(&features.series, &catalog, &[enrolment], &[ratio]).unwrap();
+        assert_eq!(signs, [-1.0, 1.0]);
+        assert!(orientations(&features.series, &catalog, &["Bogus".to_string()], &[]).is_err());
+    }
+}
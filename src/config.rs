@@ -0,0 +1,409 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+// A small TOML subset, enough for the configuration files this tool reads:
+// `[table]` headers (dotted names allowed), `key = value` pairs, strings,
+// integers, floats, booleans, and (multi-line) arrays. Comments start with `#`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(BTreeMap<String, Value>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // Integers are accepted wherever a float is expected
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Float(value) => Some(*value),
+            Value::Integer(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Value::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+
+    // Scalars are treated as one-element arrays, so `k = 3` and `k = [3]`
+    // mean the same thing.
+    pub fn to_list(&self) -> Vec<Value> {
+        match self {
+            Value::Array(values) => values.clone(),
+            other => vec![other.clone()],
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::String(value) => write!(f, "{}", value),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Array(values) => {
+                let items: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Table(_) => write!(f, "{{...}}"),
+        }
+    }
+}
+
+pub struct Config {
+    root: BTreeMap<String, Value>,
+}
+
+impl Config {
+    pub fn load(path: &str) -> io::Result<Config> {
+        let text = fs::read_to_string(path)?;
+        Config::parse(&text).map_err(|e| invalid_data(format!("{}: {}", path, e)))
+    }
+
+    pub fn parse(text: &str) -> io::Result<Config> {
+        let mut root = BTreeMap::new();
+        let mut current: Vec<String> = Vec::new();
+
+        let mut lines = text.lines().enumerate();
+        while let Some((line_index, raw_line)) = lines.next() {
+            let line_number = line_index + 1;
+            let line = strip_comment(raw_line).trim().to_string();
+            if line.is_empty() {
+                continue;
+            }
+
+            // Table header
+            if let Some(header) = line.strip_prefix('[') {
+                let name = header.strip_suffix(']').ok_or_else(|| {
+                    invalid_data(format!("line {}: unterminated table header", line_number))
+                })?;
+                current = name
+                    .split('.')
+                    .map(|part| unquote_key(part.trim()))
+                    .collect();
+                table_at(&mut root, &current, line_number)?;
+                continue;
+            }
+
+            let (key, raw_value) = line.split_once('=').ok_or_else(|| {
+                invalid_data(format!("line {}: expected `key = value`", line_number))
+            })?;
+            let key = unquote_key(key.trim());
+            let mut raw_value = raw_value.trim().to_string();
+
+            // Arrays may span several lines; keep reading until brackets balance
+            while bracket_depth(&raw_value) > 0 {
+                let (_, next) = lines.next().ok_or_else(|| {
+                    invalid_data(format!("line {}: unterminated array", line_number))
+                })?;
+                raw_value.push(' ');
+                raw_value.push_str(strip_comment(next).trim());
+            }
+
+            let mut parser = ValueParser {
+                chars: raw_value.chars().collect(),
+                position: 0,
+            };
+            let value = parser
+                .parse_value()
+                .and_then(|value| parser.finish().map(|_| value))
+                .map_err(|e| invalid_data(format!("line {}: {}", line_number, e)))?;
+
+            let table = table_at(&mut root, &current, line_number)?;
+            if table.insert(key.clone(), value).is_some() {
+                return Err(invalid_data(format!(
+                    "line {}: duplicate key `{}`",
+                    line_number, key
+                )));
+            }
+        }
+
+        Ok(Config { root })
+    }
+
+    // Look up a value by dotted path, e.g. `grid.k`
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        let mut parts = path.split('.');
+        let mut value = self.root.get(parts.next()?)?;
+        for part in parts {
+            value = value.as_table()?.get(part)?;
+        }
+        Some(value)
+    }
+
+    // The top-level keys and tables, by name
+    pub fn entries(&self) -> &BTreeMap<String, Value> {
+        &self.root
+    }
+
+    pub fn get_str(&self, path: &str) -> io::Result<Option<&str>> {
+        match self.get(path) {
+            Some(value) => value
+                .as_str()
+                .map(Some)
+                .ok_or_else(|| invalid_data(format!("`{}` must be a string", path))),
+            None => Ok(None),
+        }
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn table_at<'a>(
+    root: &'a mut BTreeMap<String, Value>,
+    path: &[String],
+    line_number: usize,
+) -> io::Result<&'a mut BTreeMap<String, Value>> {
+    let mut table = root;
+    for part in path {
+        let entry = table
+            .entry(part.clone())
+            .or_insert_with(|| Value::Table(BTreeMap::new()));
+        table = match entry {
+            Value::Table(inner) => inner,
+            _ => {
+                return Err(invalid_data(format!(
+                    "line {}: `{}` is not a table",
+                    line_number, part
+                )))
+            }
+        };
+    }
+    Ok(table)
+}
+
+fn unquote_key(key: &str) -> String {
+    key.trim_matches('"').to_string()
+}
+
+// Drop a trailing `# comment`, ignoring `#` characters inside strings.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = None;
+    for (index, c) in line.char_indices() {
+        match (c, in_string) {
+            ('"', None) | ('\'', None) => in_string = Some(c),
+            (c, Some(quote)) if c == quote => in_string = None,
+            ('#', None) => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn bracket_depth(text: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_string = None;
+    for c in text.chars() {
+        match (c, in_string) {
+            ('"', None) | ('\'', None) => in_string = Some(c),
+            (c, Some(quote)) if c == quote => in_string = None,
+            ('[', None) => depth += 1,
+            (']', None) => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+struct ValueParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl ValueParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(()),
+            Some(c) => Err(invalid_data(format!("unexpected {:?} after value", c))),
+        }
+    }
+
+    fn parse_value(&mut self) -> io::Result<Value> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') => self.parse_basic_string(),
+            Some('\'') => self.parse_literal_string(),
+            Some('[') => self.parse_array(),
+            Some(_) => self.parse_scalar(),
+            None => Err(invalid_data("missing value".to_string())),
+        }
+    }
+
+    fn parse_basic_string(&mut self) -> io::Result<Value> {
+        self.position += 1;
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.position += 1;
+                    return Ok(Value::String(value));
+                }
+                Some('\\') => {
+                    self.position += 1;
+                    let escaped = match self.peek() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        other => {
+                            return Err(invalid_data(format!("unsupported escape {:?}", other)))
+                        }
+                    };
+                    value.push(escaped);
+                    self.position += 1;
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.position += 1;
+                }
+                None => return Err(invalid_data("unterminated string".to_string())),
+            }
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> io::Result<Value> {
+        self.position += 1;
+        let start = self.position;
+        while self.peek().is_some_and(|c| c != '\'') {
+            self.position += 1;
+        }
+        if self.peek().is_none() {
+            return Err(invalid_data("unterminated string".to_string()));
+        }
+        let value: String = self.chars[start..self.position].iter().collect();
+        self.position += 1;
+        Ok(Value::String(value))
+    }
+
+    fn parse_array(&mut self) -> io::Result<Value> {
+        self.position += 1;
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(']') {
+                self.position += 1;
+                return Ok(Value::Array(values));
+            }
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(']') => {}
+                _ => return Err(invalid_data("expected `,` or `]` in array".to_string())),
+            }
+        }
+    }
+
+    fn parse_scalar(&mut self) -> io::Result<Value> {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| !c.is_whitespace() && c != ',' && c != ']')
+        {
+            self.position += 1;
+        }
+        let token: String = self.chars[start..self.position].iter().collect();
+        let digits = token.replace('_', "");
+
+        if token == "true" || token == "false" {
+            Ok(Value::Boolean(token == "true"))
+        } else if let Ok(integer) = digits.parse::<i64>() {
+            Ok(Value::Integer(integer))
+        } else if let Ok(float) = digits.parse::<f64>() {
+            Ok(Value::Float(float))
+        } else {
+            Err(invalid_data(format!("invalid value `{}`", token)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tables_and_values() {
+        let config = Config::parse(
+            r#"
+            # Sweep definition
+            input = "data/education.csv"
+            rank_by = 'intra_weight'
+
+            [grid]
+            threshold = [0.0, 0.5, 1_000]   # mixed floats and ints
+            label = "a # not a comment"
+            enabled = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.get_str("input").unwrap(), Some("data/education.csv"));
+        assert_eq!(config.get_str("rank_by").unwrap(), Some("intra_weight"));
+        assert_eq!(
+            config.get("grid.threshold"),
+            Some(&Value::Array(vec![
+                Value::Float(0.0),
+                Value::Float(0.5),
+                Value::Integer(1000)
+            ]))
+        );
+        assert_eq!(
+            config.get_str("grid.label").unwrap(),
+            Some("a # not a comment")
+        );
+        assert_eq!(config.get("grid.enabled"), Some(&Value::Boolean(true)));
+        assert!(config.get("grid.missing").is_none());
+    }
+
+    #[test]
+    fn test_multiline_array() {
+        let config = Config::parse("values = [\n  1,\n  2, # two\n  3,\n]\n").unwrap();
+        let values: Vec<f64> = config
+            .get("values")
+            .unwrap()
+            .to_list()
+            .iter()
+            .filter_map(Value::as_f64)
+            .collect();
+        assert_eq!(values, vec![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_errors_report_line_numbers() {
+        let error = Config::parse("a = 1\nb = [1, 2\n").err().unwrap();
+        assert!(error.to_string().contains("line 2"));
+
+        let error = Config::parse("a = 1\na = 2\n").err().unwrap();
+        assert!(error.to_string().contains("duplicate key `a`"));
+
+        assert!(Config::parse("a = nope").is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::cancel::RunStatus;
+use crate::json::Json;
+
+// `--quiet`, for Makefiles and orchestration scripts: progress and
+// warnings on stderr are dropped, and the process ends with a single JSON
+// object on stdout saying how the command finished and which files it
+// wrote, besides the exit code. Tables and reports a command would have
+// printed are held back and embedded in that object as `stdout`.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+// Outputs written so far, for the final result
+static OUTPUTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// What went to `stdout()` under `--quiet`, for the final result
+static CAPTURED: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+// `eprintln!` for progress and warnings, silenced by `--quiet`.
+macro_rules! note {
+    ($($arg:tt)*) => {
+        if !$crate::console::is_quiet() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+pub(crate) use note;
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::SeqCst);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::SeqCst)
+}
+
+// Where results meant to be read go: stdout, or the final result under
+// `--quiet`.
+pub fn stdout() -> Box<dyn Write> {
+    match is_quiet() {
+        true => Box::new(Captured),
+        false => Box::new(io::stdout().lock()),
+    }
+}
+
+struct Captured;
+
+impl Write for Captured {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        CAPTURED
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Where tables printed alongside progress go.
+pub fn stderr() -> Box<dyn Write> {
+    match is_quiet() {
+        true => Box::new(io::sink()),
+        false => Box::new(io::stderr().lock()),
+    }
+}
+
+pub fn record_output(path: &str) {
+    let mut outputs = OUTPUTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if !outputs.iter().any(|output| output == path) {
+        outputs.push(path.to_string());
+    }
+}
+
+// The final result of a quiet run, as one line of JSON, and the exit code.
+pub fn result(command: Option<&str>, result: &io::Result<RunStatus>) -> (String, i32) {
+    let outputs = OUTPUTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+    let captured = std::mem::take(
+        &mut *CAPTURED
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()),
+    );
+    let stdout = (!captured.is_empty()).then(|| String::from_utf8_lossy(&captured).into_owned());
+    let (status, code, error) = match result {
+        Ok(status) => (status.as_str(), status.exit_code(), None),
+        Err(error) => ("error", 1, Some(error.to_string())),
+    };
+    let json = Json::object()
+        .with("command", command)
+        .with("status", status)
+        .with("exit_code", f64::from(code))
+        .with("outputs", outputs)
+        .with("stdout", stdout)
+        .with("error", error);
+    (json.to_string(), code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_lists_outputs_and_errors() {
+        record_output("report.txt");
+        record_output("report.txt");
+        Captured.write_all(b"Cluster 1\n").unwrap();
+        let (text, code) = result(Some("run"), &Ok(RunStatus::TimedOut));
+        assert_eq!(code, 124);
+        let json = Json::parse(&text).unwrap();
+        assert_eq!(json.get("status").unwrap().as_str(), Some("timed_out"));
+        assert_eq!(json.get("outputs").unwrap().as_array().unwrap().len(), 1);
+        assert_eq!(json.get("stdout").unwrap().as_str(), Some("Cluster 1\n"));
+        assert_eq!(json.get("error"), Some(&Json::Null));
+
+        let failed = Err(io::Error::other("no such file"));
+        let (text, code) = result(None, &failed);
+        assert_eq!(code, 1);
+        let json = Json::parse(&text).unwrap();
+        assert_eq!(json.get("error").unwrap().as_str(), Some("no such file"));
+        assert_eq!(json.get("command"), Some(&Json::Null));
+        assert_eq!(json.get("stdout"), Some(&Json::Null));
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::stats::{self, LinearFit};
+use crate::table::Table;
+use crate::EducationData;
+
+// Beta convergence: regressing countries' average annual log growth between
+// two years on their initial log level. A negative slope means countries
+// that started lower grew faster, i.e. they are catching up.
+pub struct BetaConvergence {
+    pub start: u32,
+    pub end: u32,
+    pub fit: LinearFit,
+}
+
+impl BetaConvergence {
+    // Implied annual speed of convergence, -ln(1 + beta * T) / T.
+    pub fn speed(&self) -> Option<f64> {
+        let span = f64::from(self.end - self.start);
+        let base = 1.0 + self.fit.slope * span;
+        (base > 0.0).then(|| -base.ln() / span)
+    }
+
+    // Years to close half of the gap at that speed.
+    pub fn half_life(&self) -> Option<f64> {
+        self.speed()
+            .filter(|&speed| speed > 0.0)
+            .map(|speed| std::f64::consts::LN_2 / speed)
+    }
+}
+
+// Sigma convergence: the cross-country dispersion of log values in a year.
+pub struct SigmaPoint {
+    pub year: u32,
+    pub countries: usize,
+    pub sigma: f64,
+}
+
+// Positive values of one series by country and year; logs need positive
+// values and a country with several records in a year keeps its last one.
+fn by_country(data: &[EducationData]) -> BTreeMap<&str, BTreeMap<u32, f64>> {
+    let mut countries: BTreeMap<&str, BTreeMap<u32, f64>> = BTreeMap::new();
+    for record in data {
+        if let Some(value) = record.value.filter(|&value| value > 0.0) {
+            countries
+                .entry(record.country_or_area.as_str())
+                .or_default()
+                .insert(record.year, value);
+        }
+    }
+    countries
+}
+
+// `data` holds a single series. Years default to the first and last years
+// with data; only countries reporting both take part.
+pub fn beta_convergence(
+    data: &[EducationData],
+    start: Option<u32>,
+    end: Option<u32>,
+) -> io::Result<BetaConvergence> {
+    let countries = by_country(data);
+    let years = countries.values().flat_map(|values| values.keys().copied());
+    let start = start.or_else(|| years.clone().min());
+    let end = end.or_else(|| years.max());
+    let (start, end) = match (start, end) {
+        (Some(start), Some(end)) if start < end => (start, end),
+        _ => {
+            return Err(invalid_input(
+                "beta convergence needs a start year before the end year".to_string(),
+            ))
+        }
+    };
+
+    let span = f64::from(end - start);
+    let points: Vec<(f64, f64)> = countries
+        .values()
+        .filter_map(|values| {
+            let (initial, last) = (values.get(&start)?.ln(), values.get(&end)?.ln());
+            Some((initial, (last - initial) / span))
+        })
+        .collect();
+    match stats::linear_fit(&points) {
+        Some(fit) if points.len() >= 3 => Ok(BetaConvergence { start, end, fit }),
+        _ => Err(invalid_input(format!(
+            "only {} countries report both {} and {}; choose other years with --start/--end",
+            points.len(),
+            start,
+            end
+        ))),
+    }
+}
+
+pub fn sigma_convergence(data: &[EducationData]) -> Vec<SigmaPoint> {
+    let mut years: BTreeMap<u32, Vec<f64>> = BTreeMap::new();
+    for values in by_country(data).values() {
+        for (&year, value) in values {
+            years.entry(year).or_default().push(value.ln());
+        }
+    }
+    years
+        .into_iter()
+        .filter(|(_, logs)| logs.len() >= 2)
+        .map(|(year, logs)| SigmaPoint {
+            year,
+            countries: logs.len(),
+            sigma: stats::std_dev(&logs).expect("at least two values"),
+        })
+        .collect()
+}
+
+pub fn write_report(
+    writer: &mut dyn Write,
+    series: &str,
+    beta: &BetaConvergence,
+    sigma: &[SigmaPoint],
+) -> io::Result<()> {
+    let format_optional =
+        |value: Option<f64>| value.map_or_else(|| "-".to_string(), |value| format!("{:.4}", value));
+    writeln!(writer, "Series: {}", series)?;
+    writeln!(
+        writer,
+        "Beta convergence {}-{} ({} countries):",
+        beta.start, beta.end, beta.fit.points
+    )?;
+    writeln!(
+        writer,
+        "  beta {:+.6}, intercept {:.6}, r^2 {:.4}: {}",
+        beta.fit.slope,
+        beta.fit.intercept,
+        beta.fit.r_squared,
+        if beta.fit.slope < 0.0 {
+            "converging"
+        } else {
+            "diverging"
+        }
+    )?;
+    writeln!(
+        writer,
+        "  speed {} per year, half-life {} years",
+        format_optional(beta.speed()),
+        format_optional(beta.half_life())
+    )?;
+
+    let trend = stats::linear_fit(
+        &sigma
+            .iter()
+            .map(|point| (f64::from(point.year), point.sigma))
+            .collect::<Vec<_>>(),
+    );
+    match trend {
+        Some(trend) => writeln!(
+            writer,
+            "Sigma convergence: dispersion {} by {:.6} per year",
+            if trend.slope < 0.0 {
+                "falling"
+            } else {
+                "rising"
+            },
+            trend.slope.abs()
+        )?,
+        None => writeln!(writer, "Sigma convergence: needs two or more years")?,
+    }
+    let mut table = Table::new(&["year", "countries", "sigma"]);
+    for point in sigma {
+        table.push_row(vec![
+            point.year.to_string(),
+            point.countries.to_string(),
+            format!("{:.4}", point.sigma),
+        ]);
+    }
+    table.write_text(writer)
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    const FEMALE: &str = "Gross enrollment ratio - Primary (female)";
+
+    #[test]
+    fn test_catching_up_converges() {
+        // Lower starting levels grow faster, and the spread shrinks
+        let data = vec![
+            record("Chad", FEMALE, 2000, 20.0),
+            record("Chad", FEMALE, 2010, 40.0),
+            record("Mali", FEMALE, 2000, 40.0),
+            record("Mali", FEMALE, 2010, 56.0),
+            record("Togo", FEMALE, 2000, 80.0),
+            record("Togo", FEMALE, 2010, 88.0),
+            record("Niger", FEMALE, 2010, 30.0),
+        ];
+        let beta = beta_convergence(&data, None, None).unwrap();
+        assert_eq!((beta.start, beta.end, beta.fit.points), (2000, 2010, 3));
+        assert!(beta.fit.slope < 0.0);
+        assert!(beta.half_life().unwrap() > 0.0);
+
+        let sigma = sigma_convergence(&data);
+        assert_eq!(sigma.len(), 2);
+        assert_eq!(sigma[1].countries, 4);
+        assert!(sigma[1].sigma < sigma[0].sigma);
+
+        assert!(beta_convergence(&data, Some(2010), Some(2000)).is_err());
+    }
+}
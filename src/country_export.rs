@@ -0,0 +1,249 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::centrality::{self, Measure, CENTRALITIES};
+use crate::features;
+use crate::json::Json;
+use crate::labels;
+use crate::{EducationData, Graph};
+
+// One JSON document per country of the graph, for static profile pages:
+// its observations, its feature vector (each series' latest value, as the
+// similarity graph uses), its nearest neighbours by edge weight, every
+// centrality measure, and its cluster. An `index.json` lists the files.
+
+// The file holding `country`: its name lowercased, apostrophes dropped and
+// every other run of characters but letters and digits made one `-`.
+pub fn file_stem(country: &str) -> String {
+    let mut stem = String::new();
+    for c in country.chars() {
+        if c.is_alphanumeric() {
+            stem.extend(c.to_lowercase());
+        } else if c == '\'' || c == '’' {
+            continue;
+        } else if !stem.is_empty() && !stem.ends_with('-') {
+            stem.push('-');
+        }
+    }
+    let stem = stem.trim_end_matches('-');
+    if stem.is_empty() {
+        "country".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+pub fn country_documents(
+    data: &[EducationData],
+    graph: &Graph,
+    clusters: Option<&[Vec<usize>]>,
+    neighbours: usize,
+) -> Vec<Json> {
+    let features = features::feature_matrix(data, None);
+    let feature_row: BTreeMap<&str, &[f64]> = features
+        .countries
+        .iter()
+        .map(String::as_str)
+        .zip(features.values.iter().map(Vec::as_slice))
+        .collect();
+    let mut observations: BTreeMap<&str, Vec<&EducationData>> = BTreeMap::new();
+    for record in data {
+        observations
+            .entry(record.country_or_area.as_str())
+            .or_default()
+            .push(record);
+    }
+    let scores = centrality::centrality(graph);
+    let labels = clusters.map(|clusters| labels::cluster_labels(graph, clusters));
+    let mut cluster_of = vec![None; graph.nodes.len()];
+    for (index, members) in clusters.unwrap_or_default().iter().enumerate() {
+        for &node in members {
+            cluster_of[node] = Some(index);
+        }
+    }
+
+    let matrix = &graph.adjacency_matrix;
+    graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(node, country)| {
+            let mut records = observations
+                .get(country.as_str())
+                .cloned()
+                .unwrap_or_default();
+            records.sort_by(|a, b| a.series.cmp(&b.series).then(a.year.cmp(&b.year)));
+            let records: Vec<Json> = records
+                .iter()
+                .map(|record| {
+                    Json::object()
+                        .with("series", record.series.as_str())
+                        .with("indicator", record.indicator.as_str())
+                        .with("year", record.year as usize)
+                        .with("value", record.value)
+                })
+                .collect();
+            let feature_vector = match feature_row.get(country.as_str()) {
+                Some(row) => Json::Object(
+                    features
+                        .series
+                        .iter()
+                        .zip(row.iter())
+                        .filter(|(_, value)| !value.is_nan())
+                        .map(|(series, &value)| (series.clone(), Json::Number(value)))
+                        .collect(),
+                ),
+                None => Json::object(),
+            };
+
+            let pair = |other: usize| (matrix[node][other] + matrix[other][node]) / 2.0;
+            let mut nearest: Vec<usize> = (0..graph.nodes.len())
+                .filter(|&other| other != node && pair(other) > 0.0)
+                .collect();
+            nearest.sort_by(|&a, &b| pair(b).total_cmp(&pair(a)).then(a.cmp(&b)));
+            let nearest: Vec<Json> = nearest
+                .into_iter()
+                .take(neighbours)
+                .map(|other| {
+                    Json::object()
+                        .with("country", graph.nodes[other].as_str())
+                        .with("weight", pair(other))
+                })
+                .collect();
+
+            let centralities = Json::Object(
+                CENTRALITIES
+                    .iter()
+                    .map(|name| {
+                        let measure: Measure = name.parse().expect("a centrality measure");
+                        (name.to_string(), Json::Number(scores.values(measure)[node]))
+                    })
+                    .collect(),
+            );
+            let cluster = match (cluster_of[node], &labels) {
+                (Some(index), Some(labels)) => Json::object()
+                    .with("id", index)
+                    .with("label", labels[index].as_str()),
+                _ => Json::Null,
+            };
+            Json::object()
+                .with("country", country.as_str())
+                .with("cluster", cluster)
+                .with("features", feature_vector)
+                .with("neighbors", Json::Array(nearest))
+                .with("centrality", centralities)
+                .with("observations", Json::Array(records))
+        })
+        .collect()
+}
+
+// Write every document into `dir` (created if missing) with `index.json`;
+// returns how many country files were written. Names that share a stem
+// are told apart by a numeric suffix.
+pub fn write_country_files(dir: &Path, documents: &[Json]) -> io::Result<usize> {
+    fs::create_dir_all(dir)?;
+    let mut taken = HashSet::new();
+    let mut index = Vec::new();
+    for document in documents {
+        let country = document
+            .get("country")
+            .and_then(Json::as_str)
+            .unwrap_or_default();
+        let stem = file_stem(country);
+        let mut name = format!("{}.json", stem);
+        let mut suffix = 2;
+        while name == "index.json" || !taken.insert(name.clone()) {
+            name = format!("{}-{}.json", stem, suffix);
+            suffix += 1;
+        }
+        fs::write(dir.join(&name), document.to_pretty_string() + "\n")?;
+        index.push(
+            Json::object()
+                .with("country", country)
+                .with("file", name.as_str()),
+        );
+    }
+    fs::write(
+        dir.join("index.json"),
+        Json::Array(index).to_pretty_string() + "\n",
+    )?;
+    Ok(documents.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_one_document_per_country() {
+        assert_eq!(file_stem("Côte d'Ivoire"), "côte-divoire");
+        assert_eq!(
+            file_stem("Bolivia (Plurinational State of)"),
+            "bolivia-plurinational-state-of"
+        );
+        assert_eq!(file_stem("???"), "country");
+
+        let data = vec![
+            record("Chad", "primary", 2015, 80.0),
+            record("Chad", "primary", 2010, 70.0),
+            record("Mali", "primary", 2015, 90.0),
+            record("Niger", "tertiary", 2015, 5.0),
+        ];
+        let graph = Graph {
+            nodes: vec!["Chad".to_string(), "Mali".to_string(), "Niger".to_string()],
+            adjacency_matrix: vec![
+                vec![0.0, 3.0, 1.0],
+                vec![3.0, 0.0, 0.0],
+                vec![1.0, 0.0, 0.0],
+            ],
+        };
+        let clusters = vec![vec![0, 1]];
+        let documents = country_documents(&data, &graph, Some(&clusters), 1);
+        let chad = &documents[0];
+        assert_eq!(chad.get("country").and_then(Json::as_str), Some("Chad"));
+        let years: Vec<f64> = chad
+            .get("observations")
+            .and_then(Json::as_array)
+            .unwrap()
+            .iter()
+            .filter_map(|record| record.get("year").and_then(Json::as_f64))
+            .collect();
+        assert_eq!(years, [2010.0, 2015.0]);
+        assert_eq!(
+            chad.get("features")
+                .and_then(|features| features.get("primary")),
+            Some(&Json::Number(80.0))
+        );
+        let neighbours = chad.get("neighbors").and_then(Json::as_array).unwrap();
+        assert_eq!(neighbours.len(), 1);
+        assert_eq!(
+            neighbours[0].get("country").and_then(Json::as_str),
+            Some("Mali")
+        );
+        assert_eq!(
+            chad.get("cluster").and_then(|c| c.get("id")),
+            Some(&Json::Number(0.0))
+        );
+        assert_eq!(documents[2].get("cluster"), Some(&Json::Null));
+        assert!(chad
+            .get("centrality")
+            .and_then(|c| c.get("pagerank"))
+            .is_some());
+
+        let dir = std::env::temp_dir().join(format!("ds210-countries-{}", std::process::id()));
+        assert_eq!(write_country_files(&dir, &documents).unwrap(), 3);
+        let index = Json::parse(&fs::read_to_string(dir.join("index.json")).unwrap()).unwrap();
+        assert_eq!(
+            index.as_array().unwrap()[1]
+                .get("file")
+                .and_then(Json::as_str),
+            Some("mali.json")
+        );
+        let mali = Json::parse(&fs::read_to_string(dir.join("mali.json")).unwrap()).unwrap();
+        assert_eq!(mali, documents[1]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
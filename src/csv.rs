@@ -0,0 +1,221 @@
+use std::io;
+
+// Where the data starts: some leading rows to ignore (titles, notes), then
+// one or more stacked header rows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub skip_rows: usize,
+    pub header_rows: usize,
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout {
+            skip_rows: 0,
+            header_rows: 1,
+        }
+    }
+}
+
+impl Layout {
+    // Number of lines before the first data row
+    pub fn leading_rows(&self) -> usize {
+        self.skip_rows + self.header_rows
+    }
+
+    // Consume the leading rows and return the merged column headers.
+    pub fn read_headers(
+        &self,
+        lines: &mut impl Iterator<Item = io::Result<String>>,
+    ) -> io::Result<Vec<String>> {
+        let mut rows = Vec::with_capacity(self.header_rows);
+        for index in 0..self.leading_rows() {
+            match lines.next() {
+                Some(line) if index >= self.skip_rows => rows.push(split_record(&line?)),
+                Some(line) => drop(line?),
+                None => break,
+            }
+        }
+        Ok(merge_headers(&rows))
+    }
+}
+
+// Merge stacked header rows into one name per column, joining the parts with
+// a space. A blank cell in an upper row continues the group on its left, the
+// way spreadsheets export merged cells: ["Enrollment", ""] over
+// ["Male", "Female"] gives "Enrollment Male", "Enrollment Female".
+pub fn merge_headers(rows: &[Vec<String>]) -> Vec<String> {
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let mut headers = vec![String::new(); width];
+    for (row_index, row) in rows.iter().enumerate() {
+        let is_last = row_index + 1 == rows.len();
+        let mut group = String::new();
+        for (column, header) in headers.iter_mut().enumerate() {
+            let cell = row.get(column).map_or("", |cell| cell.trim());
+            let part = if cell.is_empty() && !is_last {
+                group.clone()
+            } else {
+                group = cell.to_string();
+                group.clone()
+            };
+            if !part.is_empty() {
+                if !header.is_empty() {
+                    header.push(' ');
+                }
+                header.push_str(&part);
+            }
+        }
+    }
+    headers
+}
+
+// Physical lines joined into CSV records: a line break inside a quoted
+// field belongs to the field, so the record goes on to the next line. A
+// quote still open at the end of the input ends the record there.
+pub struct Records<I> {
+    lines: I,
+    // Lines read so far, and the 1-based line the last record started on
+    read: usize,
+    start: usize,
+}
+
+impl<I: Iterator<Item = io::Result<String>>> Records<I> {
+    pub fn new(lines: I) -> Records<I> {
+        Records {
+            lines,
+            read: 0,
+            start: 0,
+        }
+    }
+
+    // Where the record just returned started, for messages.
+    pub fn line(&self) -> usize {
+        self.start
+    }
+}
+
+impl<I: Iterator<Item = io::Result<String>>> Iterator for Records<I> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<io::Result<String>> {
+        let mut record = match self.lines.next()? {
+            Ok(line) => line,
+            Err(error) => return Some(Err(error)),
+        };
+        self.read += 1;
+        self.start = self.read;
+        // `""` escapes count twice, so an odd count leaves a quote open
+        let mut quotes = record.matches('"').count();
+        while quotes % 2 == 1 {
+            match self.lines.next() {
+                Some(Ok(line)) => {
+                    self.read += 1;
+                    quotes += line.matches('"').count();
+                    record.push('\n');
+                    record.push_str(&line);
+                }
+                Some(Err(error)) => return Some(Err(error)),
+                None => break,
+            }
+        }
+        Some(Ok(record))
+    }
+}
+
+// Split one CSV record into fields, honouring double-quoted fields that
+// contain commas, `""` escapes and (from `Records`) line breaks, as in
+// RFC 4180.
+pub fn split_record(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+// Parse a numeric cell, accepting thousands separators ("678,907") and
+// treating empty or placeholder cells ("..", "-") as missing.
+pub fn parse_number(cell: &str) -> Option<f64> {
+    cell.trim().replace(',', "").parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_record_handles_quotes() {
+        assert_eq!(
+            split_record("1,\"Total, all\",2005,\"say \"\"hi\"\"\",,"),
+            vec!["1", "Total, all", "2005", "say \"hi\"", "", ""]
+        );
+    }
+
+    #[test]
+    fn test_records_span_quoted_line_breaks() {
+        let text = "a,\"two\nlines\",b\nc,\"\"\"\",d\n\"open\nend";
+        let mut records = Records::new(text.lines().map(|line| Ok(line.to_string())));
+        assert_eq!(records.next().unwrap().unwrap(), "a,\"two\nlines\",b");
+        assert_eq!(records.line(), 1);
+        let record = records.next().unwrap().unwrap();
+        assert_eq!(split_record(&record), ["c", "\"", "d"]);
+        assert_eq!(records.line(), 3);
+        assert_eq!(records.next().unwrap().unwrap(), "\"open\nend");
+        assert!(records.next().is_none());
+        assert_eq!(
+            split_record("\"two\nlines\",\"1,234.5\""),
+            ["two\nlines", "1,234.5"]
+        );
+    }
+
+    #[test]
+    fn test_stacked_headers_merge() {
+        let rows = vec![
+            split_record("Country,Enrollment,,Teachers"),
+            split_record(",Male,Female,"),
+        ];
+        assert_eq!(
+            merge_headers(&rows),
+            vec![
+                "Country",
+                "Enrollment Male",
+                "Enrollment Female",
+                "Teachers"
+            ]
+        );
+
+        // A title row is skipped, not merged
+        let layout = Layout {
+            skip_rows: 1,
+            header_rows: 1,
+        };
+        let mut lines = ["T07,Title", "Country,2010", "Chad,1"]
+            .into_iter()
+            .map(|line| Ok(line.to_string()));
+        assert_eq!(
+            layout.read_headers(&mut lines).unwrap(),
+            vec!["Country", "2010"]
+        );
+        assert_eq!(lines.next().unwrap().unwrap(), "Chad,1");
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("678,907"), Some(678907.0));
+        assert_eq!(parse_number(" 104.5 "), Some(104.5));
+        assert_eq!(parse_number(""), None);
+        assert_eq!(parse_number(".."), None);
+    }
+}
@@ -0,0 +1,604 @@
+use std::fmt;
+use std::io::{self, BufRead};
+
+use crate::console::note;
+use crate::csv;
+use crate::source;
+
+// One row of the UNESCO education table: a series value for a country or
+// area in a year. `value` is None where the cell is empty or, when loading
+// leniently, not a finite number.
+#[derive(Debug)]
+pub struct EducationData {
+    pub country_or_area: String,
+    pub year: u32,
+    pub indicator: String,
+    pub series: String,
+    pub value: Option<f64>,
+}
+
+// What loading does with a row it cannot read as it stands.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ParseMode {
+    // Fail on the first problem
+    Strict,
+    // Skip rows without a country and year, load unreadable values as
+    // missing, and report every problem once loading is done
+    #[default]
+    Lenient,
+}
+
+// A problem with one row of the input, by line number.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DataError {
+    // Fewer fields than the columns read; the row is skipped
+    MissingFields {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    // A field that does not parse as its column's type: a bad year skips
+    // the row, a bad value loads as missing
+    InvalidField {
+        line: usize,
+        field: &'static str,
+        text: String,
+    },
+    // A value that parses but is NaN or infinite, and loads as missing
+    NonFinite {
+        line: usize,
+        text: String,
+    },
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataError::MissingFields {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} fields, found {}",
+                line, expected, found
+            ),
+            DataError::InvalidField { line, field, text } => {
+                write!(f, "line {}: {} {:?} is not valid", line, field, text)
+            }
+            DataError::NonFinite { line, text } => {
+                write!(f, "line {}: value {} is not finite", line, text)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
+impl From<DataError> for io::Error {
+    fn from(error: DataError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+// Problems listed one by one before the rest are only counted
+const REPORTED_PROBLEMS: usize = 10;
+
+// The observations of a source, with the problems lenient loading passed
+// over (always empty in strict mode).
+pub struct Loaded {
+    pub data: Vec<EducationData>,
+    pub problems: Vec<DataError>,
+}
+
+// Lenient loading, reporting any problems on stderr.
+pub fn load_and_preprocess_data(
+    csv_source: &dyn source::DataSource,
+    layout: &csv::Layout,
+) -> io::Result<Vec<EducationData>> {
+    load_with_mode(csv_source, layout, ParseMode::Lenient)
+}
+
+pub fn load_with_mode(
+    csv_source: &dyn source::DataSource,
+    layout: &csv::Layout,
+    mode: ParseMode,
+) -> io::Result<Vec<EducationData>> {
+    let loaded = load_checked(csv_source, layout, mode)?;
+    report_problems(csv_source.location(), &loaded.problems);
+    Ok(loaded.data)
+}
+
+pub fn load_checked(
+    csv_source: &dyn source::DataSource,
+    layout: &csv::Layout,
+    mode: ParseMode,
+) -> io::Result<Loaded> {
+    let mut reader = EducationDataReader::open(csv_source, layout, mode)?;
+    let data = reader.by_ref().collect::<io::Result<Vec<_>>>()?;
+    Ok(Loaded {
+        data,
+        problems: reader.into_problems(),
+    })
+}
+
+// The observations of a source one at a time, for files too large to hold
+// in memory: the rows are read as they are asked for. In strict mode the
+// first problem is the last item; leniently, problems are kept for
+// `problems` and reading goes on.
+pub struct EducationDataReader {
+    location: String,
+    records: csv::Records<io::Lines<Box<dyn BufRead>>>,
+    layout: csv::Layout,
+    // Found from the header on the first read
+    columns: Option<Columns>,
+    mode: ParseMode,
+    problems: Vec<DataError>,
+    finished: bool,
+}
+
+impl EducationDataReader {
+    pub fn open(
+        csv_source: &dyn source::DataSource,
+        layout: &csv::Layout,
+        mode: ParseMode,
+    ) -> io::Result<EducationDataReader> {
+        // Open the CSV file (or URL); quoted fields may hold commas
+        // ("Tanzania, Mainland", "1,234.5") and line breaks
+        Ok(EducationDataReader {
+            location: csv_source.location().to_string(),
+            records: csv::Records::new(csv_source.open()?.lines()),
+            layout: *layout,
+            columns: None,
+            mode,
+            problems: Vec::new(),
+            finished: false,
+        })
+    }
+
+    // The problems passed over so far.
+    pub fn problems(&self) -> &[DataError] {
+        &self.problems
+    }
+
+    pub fn into_problems(self) -> Vec<DataError> {
+        self.problems
+    }
+
+    // Pass over the title rows, keeping a table code such as the SYB
+    // files' `T07` for the indicator, and find the columns from the merged
+    // header rows.
+    fn read_columns(&mut self) -> io::Result<Columns> {
+        let mut table = String::new();
+        for _ in 0..self.layout.skip_rows {
+            let Some(record) = self.records.next() else {
+                break;
+            };
+            let title = csv::split_record(&record?).remove(0);
+            if table.is_empty() && is_table_code(title.trim()) {
+                table = title.trim().to_string();
+            }
+        }
+        let headers = csv::Layout {
+            skip_rows: 0,
+            ..self.layout
+        }
+        .read_headers(&mut self.records)?;
+        Columns::find(&headers, table).map_err(|missing| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}: no `{}` column in the header (use --skip-rows to pass over title rows)",
+                    self.location, missing
+                ),
+            )
+        })
+    }
+}
+
+impl Iterator for EducationDataReader {
+    type Item = io::Result<EducationData>;
+
+    fn next(&mut self) -> Option<io::Result<EducationData>> {
+        if self.columns.is_none() && !self.finished {
+            match self.read_columns() {
+                Ok(columns) => self.columns = Some(columns),
+                Err(error) => {
+                    self.finished = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+        while !self.finished {
+            let record = match self.records.next() {
+                Some(Ok(record)) => record,
+                Some(Err(error)) => {
+                    self.finished = true;
+                    return Some(Err(error));
+                }
+                None => {
+                    self.finished = true;
+                    break;
+                }
+            };
+            if record.trim().is_empty() {
+                continue;
+            }
+            let columns = self.columns.as_ref().expect("columns are read first");
+            let (parsed, problem) = columns.parse_record(&record, self.records.line());
+            if let Some(problem) = problem {
+                match self.mode {
+                    ParseMode::Strict => {
+                        self.finished = true;
+                        return Some(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("{}: {}", self.location, problem),
+                        )));
+                    }
+                    ParseMode::Lenient => self.problems.push(problem),
+                }
+            }
+            if let Some(parsed) = parsed {
+                return Some(Ok(parsed));
+            }
+        }
+        None
+    }
+}
+
+fn is_table_code(title: &str) -> bool {
+    !title.is_empty() && title.len() <= 8 && title.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+// Where each field of a record is, by header name: `year`, `series` and
+// `value`, `indicator` if there is one, and the country under `country` or
+// `area`. In the SYB files' "Region/Country/Area,,Year,Series,Value,
+// Footnotes,Source" that column holds the area code and the name is in the
+// unnamed column beside it, so the last column under the country's header
+// (blank, or the same header when stacked rows merge a spanning cell over
+// both) is taken instead. Stacked header rows are merged first
+// (`csv::merge_headers`), so a name may follow its group's, as in
+// "Observation Year". Without a header
+// row the columns are country, year, indicator, series and value, in that
+// order.
+struct Columns {
+    country: usize,
+    year: usize,
+    indicator: Option<usize>,
+    series: usize,
+    value: usize,
+    // The title row's table code, for files without an indicator column
+    table: String,
+}
+
+impl Columns {
+    // The columns, or the name of one that is missing.
+    fn find(headers: &[String], table: String) -> Result<Columns, &'static str> {
+        if headers.is_empty() {
+            return Ok(Columns {
+                country: 0,
+                year: 1,
+                indicator: Some(2),
+                series: 3,
+                value: 4,
+                table,
+            });
+        }
+        let headers: Vec<String> = headers.iter().map(|header| header.to_lowercase()).collect();
+        let named = |name: &'static str| {
+            headers
+                .iter()
+                .position(|header| {
+                    header == name
+                        || header
+                            .strip_suffix(name)
+                            .is_some_and(|group| group.ends_with(' '))
+                })
+                .ok_or(name)
+        };
+        let country = headers
+            .iter()
+            .position(|header| header.contains("country") || header.contains("area"))
+            .ok_or("country")?;
+        // The last of the columns under the country's header
+        let country = (country + 1..headers.len())
+            .take_while(|&next| headers[next].is_empty() || headers[next] == headers[country])
+            .last()
+            .unwrap_or(country);
+        Ok(Columns {
+            country,
+            year: named("year")?,
+            indicator: named("indicator").ok(),
+            series: named("series")?,
+            value: named("value")?,
+            table,
+        })
+    }
+
+    // Fields a record needs to hold every column.
+    fn width(&self) -> usize {
+        [self.country, self.year, self.series, self.value]
+            .into_iter()
+            .chain(self.indicator)
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    // One record as an observation, if it has a country and year, and the
+    // problem with it, if any.
+    fn parse_record(
+        &self,
+        record: &str,
+        line: usize,
+    ) -> (Option<EducationData>, Option<DataError>) {
+        // Split the record into fields
+        let fields = csv::split_record(record);
+        if fields.len() < self.width() {
+            let problem = DataError::MissingFields {
+                line,
+                expected: self.width(),
+                found: fields.len(),
+            };
+            return (None, Some(problem));
+        }
+
+        // Extract data fields
+        let Ok(year) = fields[self.year].trim().parse::<u32>() else {
+            let problem = DataError::InvalidField {
+                line,
+                field: "year",
+                text: fields[self.year].clone(),
+            };
+            return (None, Some(problem));
+        };
+        // An empty cell is simply missing. "NaN" and "inf" parse as floats
+        // but would poison every sum they reach, so they count as missing
+        let text = &fields[self.value];
+        let (value, problem) = match csv::parse_number(text) {
+            Some(value) if !value.is_finite() => (
+                None,
+                Some(DataError::NonFinite {
+                    line,
+                    text: text.clone(),
+                }),
+            ),
+            None if !text.trim().is_empty() => (
+                None,
+                Some(DataError::InvalidField {
+                    line,
+                    field: "value",
+                    text: text.clone(),
+                }),
+            ),
+            value => (value, None),
+        };
+        let record = EducationData {
+            country_or_area: fields[self.country].clone(),
+            year,
+            indicator: self
+                .indicator
+                .map_or_else(|| self.table.clone(), |column| fields[column].clone()),
+            series: fields[self.series].clone(),
+            value,
+        };
+        (Some(record), problem)
+    }
+}
+
+// An observation of the SYB table, for the tests of the modules that take
+// them; `value` is a number or None.
+#[cfg(test)]
+pub(crate) fn record(
+    country: &str,
+    series: &str,
+    year: u32,
+    value: impl Into<Option<f64>>,
+) -> EducationData {
+    EducationData {
+        country_or_area: country.to_string(),
+        year,
+        indicator: "T07".to_string(),
+        series: series.to_string(),
+        value: value.into(),
+    }
+}
+
+// Print the first problems and a count of the rest.
+pub fn report_problems(location: &str, problems: &[DataError]) {
+    if problems.is_empty() {
+        return;
+    }
+    note!(
+        "Warning: {} problems reading {} (use --strict to fail on the first):",
+        problems.len(),
+        location
+    );
+    for problem in problems.iter().take(REPORTED_PROBLEMS) {
+        note!("  {}", problem);
+    }
+    if problems.len() > REPORTED_PROBLEMS {
+        note!("  ... and {} more", problems.len() - REPORTED_PROBLEMS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // CSV text to load, as if read from inline.csv
+    struct Text(&'static str);
+
+    impl source::DataSource for Text {
+        fn location(&self) -> &str {
+            "inline.csv"
+        }
+        fn open(&self) -> io::Result<Box<dyn BufRead>> {
+            Ok(Box::new(Cursor::new(self.0.as_bytes())))
+        }
+    }
+
+    #[test]
+    fn test_non_finite_values_load_as_missing() {
+        let text = Text(
+            "country,year,indicator,series,value\nChad,2015,T07,p,NaN\nMali,2015,T07,p,inf\nNiger,2015,T07,p,1e400\nTogo,2015,T07,p,2.5\n\"United Republic of Tanzania, Mainland\",2015,T07,\"p\",\"1,234.5\"\n",
+        );
+        let data = load_and_preprocess_data(&text, &csv::Layout::default()).unwrap();
+        let values: Vec<Option<f64>> = data.iter().map(|record| record.value).collect();
+        assert_eq!(values, [None, None, None, Some(2.5), Some(1234.5)]);
+        assert_eq!(
+            data[4].country_or_area,
+            "United Republic of Tanzania, Mainland"
+        );
+        assert_eq!(data[4].series, "p");
+    }
+
+    #[test]
+    fn test_problems_fail_strict_loading_and_are_collected_otherwise() {
+        let text = Text(
+            "country,year,indicator,series,value\nChad,2015,T07,p,1\nMali,20x5,T07,p,2\n\nNiger,2015,T07\nTogo,2015,T07,p,n/a\nPeru,2015,T07,p,\n",
+        );
+        let layout = csv::Layout::default();
+        let loaded = load_checked(&text, &layout, ParseMode::Lenient).unwrap();
+        let countries: Vec<&str> = loaded
+            .data
+            .iter()
+            .map(|record| record.country_or_area.as_str())
+            .collect();
+        // A bad year or a short row is skipped, a bad value is missing
+        assert_eq!(countries, ["Chad", "Togo", "Peru"]);
+        assert_eq!(loaded.data[1].value, None);
+        assert_eq!(
+            loaded.problems,
+            [
+                DataError::InvalidField {
+                    line: 3,
+                    field: "year",
+                    text: "20x5".to_string()
+                },
+                DataError::MissingFields {
+                    line: 5,
+                    expected: 5,
+                    found: 3
+                },
+                DataError::InvalidField {
+                    line: 6,
+                    field: "value",
+                    text: "n/a".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            loaded.problems[1].to_string(),
+            "line 5: expected 5 fields, found 3"
+        );
+
+        let error = load_checked(&text, &layout, ParseMode::Strict)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            "inline.csv: line 3: year \"20x5\" is not valid"
+        );
+
+        // Read a row at a time, the same records and problems come out, and
+        // a strict reader stops at the first problem
+        let mut reader = EducationDataReader::open(&text, &layout, ParseMode::Lenient).unwrap();
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(first.country_or_area, "Chad");
+        assert!(reader.problems().is_empty());
+        assert_eq!(reader.by_ref().count(), 2);
+        assert_eq!(reader.into_problems(), loaded.problems);
+        let mut strict = EducationDataReader::open(&text, &layout, ParseMode::Strict).unwrap();
+        assert!(strict.next().unwrap().is_ok());
+        assert!(strict.next().unwrap().is_err());
+        assert!(strict.next().is_none());
+    }
+
+    #[test]
+    fn test_syb_columns_are_found_by_header() {
+        // The first lines of the SYB file, and two of its country rows
+        let text = Text(concat!(
+            "T07,\"Enrollment in primary, lower secondary and upper secondary education levels\",,,,,\n",
+            "Region/Country/Area,,Year,Series,Value,Footnotes,Source\n",
+            "1,\"Total, all countries or areas\",2005,Students enrolled in primary education (thousands),\"678,907\",Estimate.,\"United Nations Educational, Scientific and Cultural Organization (UNESCO), Montreal, the UNESCO Institute for Statistics (UIS) statistics database, last accessed April 2023.\"\n",
+            "4,Afghanistan,2005,Students enrolled in primary education (thousands),\"4,319\",,\"United Nations Educational, Scientific and Cultural Organization (UNESCO), Montreal, the UNESCO Institute for Statistics (UIS) statistics database, last accessed April 2023.\"\n",
+            "834,United Rep. of Tanzania,2005,Gross enrollment ratio - Primary (male),107.0,,\"United Nations Educational, Scientific and Cultural Organization (UNESCO), Montreal, the UNESCO Institute for Statistics (UIS) statistics database, last accessed April 2023.\"\n",
+        ));
+        let layout = csv::Layout {
+            skip_rows: 1,
+            header_rows: 1,
+        };
+        let loaded = load_checked(&text, &layout, ParseMode::Strict).unwrap();
+        assert!(loaded.problems.is_empty());
+        let rows: Vec<(&str, u32, &str, &str, Option<f64>)> = loaded
+            .data
+            .iter()
+            .map(|record| {
+                (
+                    record.country_or_area.as_str(),
+                    record.year,
+                    record.indicator.as_str(),
+                    record.series.as_str(),
+                    record.value,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (
+                    "Total, all countries or areas",
+                    2005,
+                    "T07",
+                    "Students enrolled in primary education (thousands)",
+                    Some(678907.0)
+                ),
+                (
+                    "Afghanistan",
+                    2005,
+                    "T07",
+                    "Students enrolled in primary education (thousands)",
+                    Some(4319.0)
+                ),
+                (
+                    "United Rep. of Tanzania",
+                    2005,
+                    "T07",
+                    "Gross enrollment ratio - Primary (male)",
+                    Some(107.0)
+                ),
+            ]
+        );
+
+        // The same rows under two stacked header rows
+        let stacked = Text(concat!(
+            "T07,Enrollment\n",
+            "Region/Country/Area,,Observation,,\n",
+            ",,Year,Series,Value\n",
+            "4,Afghanistan,2005,Gross enrollment ratio - Primary (male),123.1\n",
+        ));
+        let layout = csv::Layout {
+            skip_rows: 1,
+            header_rows: 2,
+        };
+        let loaded = load_checked(&stacked, &layout, ParseMode::Strict).unwrap();
+        assert_eq!(loaded.data[0].country_or_area, "Afghanistan");
+        assert_eq!(
+            loaded.data[0].series,
+            "Gross enrollment ratio - Primary (male)"
+        );
+        assert_eq!(loaded.data[0].value, Some(123.1));
+
+        // Read with the title as the header, no column names the country
+        let error = load_checked(&text, &csv::Layout::default(), ParseMode::Lenient)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "inline.csv: no `country` column in the header (use --skip-rows to pass over title rows)"
+        );
+    }
+}
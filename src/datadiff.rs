@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+use crate::store::{self, Key};
+use crate::table::Table;
+use crate::EducationData;
+
+// Differences between two releases of the table, matched by observation key
+// (country or area, year, series) as the observation store does.
+#[derive(Debug, Default)]
+pub struct DataDiff<'a> {
+    pub added: Vec<&'a EducationData>,
+    pub removed: Vec<&'a EducationData>,
+    // (old, new) pairs whose value, or presence of a value, differs
+    pub changed: Vec<(&'a EducationData, &'a EducationData)>,
+    pub unchanged: usize,
+}
+
+impl DataDiff<'_> {
+    // Whether a re-run could come out differently: anything added or
+    // removed, or a value moving by more than `tolerance`. A value appearing
+    // or disappearing always counts.
+    pub fn material_changes(&self, tolerance: f64) -> usize {
+        let material = self
+            .changed
+            .iter()
+            .filter(|(old, new)| match (old.value, new.value) {
+                (Some(old), Some(new)) => (new - old).abs() > tolerance,
+                _ => true,
+            })
+            .count();
+        self.added.len() + self.removed.len() + material
+    }
+
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new(&["change", "country", "year", "series", "old", "new"]);
+        let mut rows: Vec<(Key, &str, Option<f64>, Option<f64>)> = Vec::new();
+        for record in &self.added {
+            rows.push((store::key(record), "added", None, record.value));
+        }
+        for record in &self.removed {
+            rows.push((store::key(record), "removed", record.value, None));
+        }
+        for (old, new) in &self.changed {
+            rows.push((store::key(old), "changed", old.value, new.value));
+        }
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+        let cell = |value: Option<f64>| value.map_or(String::new(), |value| value.to_string());
+        for ((country, year, series), change, old, new) in rows {
+            table.push_row(vec![
+                change.to_string(),
+                country,
+                year.to_string(),
+                series,
+                cell(old),
+                cell(new),
+            ]);
+        }
+        table
+    }
+}
+
+pub fn diff_data<'a>(old: &'a [EducationData], new: &'a [EducationData]) -> DataDiff<'a> {
+    // Later duplicates of a key win, as on ingest
+    let index = |data: &'a [EducationData]| -> BTreeMap<Key, &'a EducationData> {
+        data.iter()
+            .map(|record| (store::key(record), record))
+            .collect()
+    };
+    let (old, new) = (index(old), index(new));
+    let mut diff = DataDiff::default();
+    for (key, &before) in &old {
+        match new.get(key) {
+            None => diff.removed.push(before),
+            Some(&after) if before.value.map(f64::to_bits) == after.value.map(f64::to_bits) => {
+                diff.unchanged += 1
+            }
+            Some(&after) => diff.changed.push((before, after)),
+        }
+    }
+    diff.added = new
+        .iter()
+        .filter(|(key, _)| !old.contains_key(*key))
+        .map(|(_, &record)| record)
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_diff_data_and_materiality() {
+        let old = vec![
+            record("Chad", "primary", 2015, Some(10.0)),
+            record("Mali", "primary", 2015, Some(20.0)),
+            record("Niger", "primary", 2015, Some(30.0)),
+            record("Togo", "primary", 2015, None),
+        ];
+        let new = vec![
+            record("Chad", "primary", 2015, Some(10.0)),
+            record("Mali", "primary", 2015, Some(20.04)),
+            record("Togo", "primary", 2015, Some(5.0)),
+            record("Benin", "primary", 2015, Some(1.0)),
+        ];
+        let diff = diff_data(&old, &new);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.removed[0].country_or_area, "Niger");
+        assert_eq!(diff.changed.len(), 2);
+        // Mali's revision is immaterial at 0.1; Togo gaining a value is not
+        assert_eq!(diff.material_changes(0.1), 3);
+        assert_eq!(diff.material_changes(0.0), 4);
+
+        let table = diff.to_table();
+        let changes: Vec<(&str, &str)> = table
+            .rows
+            .iter()
+            .map(|row| (row[0].as_str(), row[1].as_str()))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("added", "Benin"),
+                ("changed", "Mali"),
+                ("removed", "Niger"),
+                ("changed", "Togo")
+            ]
+        );
+        assert_eq!(table.rows[3][4..], ["".to_string(), "5".to_string()]);
+        assert_eq!(diff_data(&new, &new).material_changes(0.0), 0);
+    }
+}
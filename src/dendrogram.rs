@@ -0,0 +1,200 @@
+use std::io;
+use std::str::FromStr;
+
+use crate::cluster::{Linkage, MergeTree};
+use crate::json::Json;
+use crate::Graph;
+
+pub const DENDROGRAM_FORMATS: &[&str] = &["newick", "json"];
+
+// The merge tree of agglomerative clustering drawn as a dendrogram. Merges
+// happen at a similarity, which falls from one merge to the next under
+// every linkage; a dendrogram wants heights that rise from the leaves, so
+// a merge sits as far below the graph's highest self-similarity (its
+// largest diagonal weight, or the first merge's similarity if that is
+// higher) as its own similarity is. For cosine or correlation graphs that
+// is one minus the similarity. Cutting the tree at a height h keeps the
+// merges at similarities of at least reference - h, as `--cutoff` does.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    // Nested parentheses with branch lengths, e.g. `((Chad:0.1,Mali:0.1):0.4,Peru:0.5);`
+    #[default]
+    Newick,
+    // A nested object per merge, for scripts and d3-style hierarchy views
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Format> {
+        match value {
+            "newick" => Ok(Format::Newick),
+            "json" => Ok(Format::Json),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown dendrogram format `{}`; expected one of {}",
+                    other,
+                    DENDROGRAM_FORMATS.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+// The tree's heights: the leaves' (all 0) and then each merge's, never
+// below the merges it joins.
+fn heights(tree: &MergeTree, graph: &Graph) -> (f64, Vec<f64>) {
+    let diagonal = (0..graph.nodes.len())
+        .map(|node| graph.adjacency_matrix[node][node])
+        .fold(f64::NEG_INFINITY, f64::max);
+    let reference = tree
+        .merges
+        .first()
+        .map_or(diagonal, |merge| diagonal.max(merge.similarity));
+    let mut heights = vec![0.0; tree.leaves];
+    for merge in &tree.merges {
+        let [a, b] = merge.clusters.map(|id| heights[id]);
+        heights.push((reference - merge.similarity).max(a).max(b));
+    }
+    (reference, heights)
+}
+
+// The id of the root: the last merge, or the only leaf.
+fn root(tree: &MergeTree) -> Option<usize> {
+    (tree.leaves > 0).then(|| tree.leaves + tree.merges.len() - 1)
+}
+
+pub fn to_newick(tree: &MergeTree, graph: &Graph) -> String {
+    let (_, heights) = heights(tree, graph);
+    let mut text = String::new();
+    if let Some(root) = root(tree) {
+        write_newick(tree, graph, &heights, root, &mut text);
+    }
+    text.push_str(";\n");
+    text
+}
+
+fn write_newick(tree: &MergeTree, graph: &Graph, heights: &[f64], id: usize, text: &mut String) {
+    if id < tree.leaves {
+        text.push_str(&newick_label(&graph.nodes[id]));
+        return;
+    }
+    text.push('(');
+    for (index, child) in tree.merges[id - tree.leaves]
+        .clusters
+        .into_iter()
+        .enumerate()
+    {
+        if index > 0 {
+            text.push(',');
+        }
+        write_newick(tree, graph, heights, child, text);
+        text.push_str(&format!(":{:.6}", heights[id] - heights[child]));
+    }
+    text.push(')');
+}
+
+// Names with spaces or Newick punctuation are quoted, doubling any quote.
+fn newick_label(name: &str) -> String {
+    if name
+        .chars()
+        .any(|c| c.is_whitespace() || "()[]':;,".contains(c))
+    {
+        format!("'{}'", name.replace('\'', "''"))
+    } else {
+        name.to_string()
+    }
+}
+
+pub fn to_json(tree: &MergeTree, graph: &Graph) -> Json {
+    let (reference, heights) = heights(tree, graph);
+    let linkage = match tree.linkage {
+        Linkage::Average => "average",
+        Linkage::Single => "single",
+        Linkage::Complete => "complete",
+    };
+    Json::object()
+        .with("linkage", linkage)
+        .with("reference", reference)
+        .with(
+            "tree",
+            root(tree).map_or(Json::Null, |root| json_node(tree, graph, &heights, root)),
+        )
+}
+
+fn json_node(tree: &MergeTree, graph: &Graph, heights: &[f64], id: usize) -> Json {
+    if id < tree.leaves {
+        return Json::object()
+            .with("name", graph.nodes[id].as_str())
+            .with("height", 0.0);
+    }
+    let merge = &tree.merges[id - tree.leaves];
+    let children: Vec<Json> = merge
+        .clusters
+        .iter()
+        .map(|&child| json_node(tree, graph, heights, child))
+        .collect();
+    Json::object()
+        .with("height", heights[id])
+        .with("similarity", merge.similarity)
+        .with("size", merge.size)
+        .with("children", Json::Array(children))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::{agglomerative, merge_tree, Stop};
+    use crate::observer::Unobserved;
+    use crate::parallel::Parallelism;
+
+    #[test]
+    fn test_merge_tree_exports_and_cuts() {
+        let graph = Graph {
+            nodes: ["Chad", "Mali", "Peru", "Côte d'Ivoire"]
+                .map(str::to_string)
+                .to_vec(),
+            adjacency_matrix: vec![
+                vec![1.0, 0.9, 0.1, 0.6],
+                vec![0.9, 1.0, 0.2, 0.5],
+                vec![0.1, 0.2, 1.0, 0.3],
+                vec![0.6, 0.5, 0.3, 1.0],
+            ],
+        };
+        let sequential = Parallelism::sequential();
+        let single = merge_tree(&graph, Linkage::Single, sequential);
+        // Chad and Mali, then Côte d'Ivoire, then Peru; heights are 1 - similarity
+        assert_eq!(
+            to_newick(&single, &graph),
+            "(((Chad:0.100000,Mali:0.100000):0.300000,'Côte d''Ivoire':0.400000):0.300000,\
+             Peru:0.700000);\n"
+        );
+        // Complete linkage joins Peru last at its least similar member
+        let complete = merge_tree(&graph, Linkage::Complete, sequential);
+        assert_eq!(complete.merges.last().unwrap().similarity, 0.1);
+        let json = to_json(&complete, &graph);
+        assert_eq!(
+            json.get("tree").unwrap().get("size").unwrap().as_f64(),
+            Some(4.0)
+        );
+
+        // Cutting the average tree is stopping average merging there
+        let average = merge_tree(&graph, Linkage::Average, sequential);
+        for stop in [
+            Stop::Auto,
+            Stop::Clusters(3),
+            Stop::Clusters(1),
+            Stop::Cutoff(0.5),
+        ] {
+            assert_eq!(
+                average.cut(stop),
+                agglomerative(&graph, None, stop, sequential, &mut Unobserved),
+                "{:?}",
+                stop
+            );
+        }
+        assert_eq!("upgma".parse::<Format>().ok(), None);
+    }
+}
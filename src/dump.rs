@@ -0,0 +1,116 @@
+use std::io::{self, Write};
+
+use crate::features::FeatureMatrix;
+use crate::table;
+use crate::{EducationData, Graph};
+
+// Intermediates written out by `--dump-cleaned`, `--dump-features` and
+// `--dump-distances`, for finding out why a clustering looks the way it
+// does. Each is taken at a fixed point of the pipeline:
+//
+//   cleaned    the observations after filtering, sampling, imputation,
+//              transforms and trends: what the graph is built from
+//   features   the countries x series matrix a --similarity graph compares,
+//              after --normalize; empty cells are unreported values
+//   distances  the graph's weights after --symmetric, --no-self-loops,
+//              --min-weight and --top-k: what the clustering sees
+//
+// All three are CSV with a header row; the cleaned observations are in the
+// long layout the loader reads back.
+
+pub fn write_observations(writer: &mut dyn Write, data: &[EducationData]) -> io::Result<()> {
+    let header = ["country", "year", "indicator", "series", "value"];
+    table::write_csv_record(writer, &header.map(String::from))?;
+    for record in data {
+        table::write_csv_record(
+            writer,
+            &[
+                record.country_or_area.clone(),
+                record.year.to_string(),
+                record.indicator.clone(),
+                record.series.clone(),
+                record
+                    .value
+                    .map_or(String::new(), |value| value.to_string()),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn write_features(writer: &mut dyn Write, features: &FeatureMatrix) -> io::Result<()> {
+    let header: Vec<String> = std::iter::once("country".to_string())
+        .chain(features.series.iter().cloned())
+        .collect();
+    table::write_csv_record(writer, &header)?;
+    for (country, values) in features.countries.iter().zip(&features.values) {
+        let row: Vec<String> = std::iter::once(country.clone())
+            .chain(values.iter().map(|&value| cell(value)))
+            .collect();
+        table::write_csv_record(writer, &row)?;
+    }
+    Ok(())
+}
+
+// One row per country, its weight to each country in the header; rows are
+// the edges' sources.
+pub fn write_distances(writer: &mut dyn Write, graph: &Graph) -> io::Result<()> {
+    let header: Vec<String> = std::iter::once("country".to_string())
+        .chain(graph.nodes.iter().cloned())
+        .collect();
+    table::write_csv_record(writer, &header)?;
+    for (node, weights) in graph.nodes.iter().zip(&graph.adjacency_matrix) {
+        let row: Vec<String> = std::iter::once(node.clone())
+            .chain(weights.iter().map(|&weight| cell(weight)))
+            .collect();
+        table::write_csv_record(writer, &row)?;
+    }
+    Ok(())
+}
+
+fn cell(value: f64) -> String {
+    match value.is_nan() {
+        true => String::new(),
+        false => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_dumps_are_csv_with_headers() {
+        let features = FeatureMatrix {
+            countries: vec!["Chad".to_string(), "Mali, Rep.".to_string()],
+            series: vec!["primary".to_string(), "tertiary".to_string()],
+            values: vec![vec![1.5, f64::NAN], vec![2.0, 3.0]],
+        };
+        let mut written = Vec::new();
+        write_features(&mut written, &features).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "country,primary,tertiary\nChad,1.5,\n\"Mali, Rep.\",2,3\n"
+        );
+
+        let graph = Graph {
+            nodes: vec!["Chad".to_string(), "Peru".to_string()],
+            adjacency_matrix: vec![vec![0.0, 0.25], vec![0.5, 0.0]],
+        };
+        let mut written = Vec::new();
+        write_distances(&mut written, &graph).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "country,Chad,Peru\nChad,0,0.25\nPeru,0.5,0\n"
+        );
+
+        let data = [record("Peru", "primary", 2015, None)];
+        let mut written = Vec::new();
+        write_observations(&mut written, &data).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "country,year,indicator,series,value\nPeru,2015,T07,primary,\n"
+        );
+    }
+}
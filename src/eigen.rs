@@ -0,0 +1,376 @@
+use crate::random::Rng;
+use crate::stats::sum;
+
+// Eigenpairs of real symmetric matrices, for the spectral stages (PCA of the
+// feature matrix, spectral clustering, eigenvector centrality) to share:
+//
+//   power_iteration  the eigenpair of largest magnitude
+//   top_k, bottom_k  the k largest or smallest eigenvalues, by power
+//                    iteration on a shifted matrix with deflation; exact
+//                    for repeated eigenvalues, but each pair costs a full
+//                    power iteration
+//   lanczos          the k largest or smallest from a short Lanczos run,
+//                    much cheaper on large matrices; a Krylov space holds
+//                    one copy of a repeated eigenvalue, so it can return
+//                    fewer than k pairs
+//   jacobi           every eigenpair of a small matrix
+//
+// Matrices are row-major and assumed symmetric; only callers know whether
+// theirs is, so nothing here checks. Start vectors come from a fixed seed,
+// so results are reproducible.
+
+const SEED: u64 = 0x5eed;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Eigenpair {
+    pub value: f64,
+    // Unit length; its sign is arbitrary
+    pub vector: Vec<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Options {
+    pub max_iterations: usize,
+    // Stop once |Av - λv| <= tolerance * max(1, |λ|)
+    pub tolerance: f64,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            max_iterations: 1000,
+            tolerance: 1e-10,
+        }
+    }
+}
+
+// Which end of the spectrum to take.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum End {
+    Largest,
+    Smallest,
+}
+
+// The eigenpair of largest |λ|. If the iterations run out first, the last
+// estimate is returned.
+pub fn power_iteration(matrix: &[Vec<f64>], options: Options) -> Option<Eigenpair> {
+    let vector = start_vector(matrix.len(), &mut Rng::new(SEED))?;
+    Some(iterate(matrix, vector, options))
+}
+
+fn iterate(matrix: &[Vec<f64>], mut vector: Vec<f64>, options: Options) -> Eigenpair {
+    let mut value = 0.0;
+    for _ in 0..options.max_iterations {
+        let mut next = multiply(matrix, &vector);
+        value = dot(&vector, &next);
+        let residual = norm(
+            &next
+                .iter()
+                .zip(&vector)
+                .map(|(x, v)| x - value * v)
+                .collect::<Vec<f64>>(),
+        );
+        if residual <= options.tolerance * value.abs().max(1.0) {
+            break;
+        }
+        if normalize(&mut next).is_none() {
+            // The vector is in the null space: an eigenvector for 0
+            break;
+        }
+        vector = next;
+    }
+    Eigenpair { value, vector }
+}
+
+// The k largest eigenpairs, largest first.
+pub fn top_k(matrix: &[Vec<f64>], k: usize, options: Options) -> Vec<Eigenpair> {
+    // Shifting by a bound on |λ| makes every eigenvalue non-negative, so the
+    // dominant one is the largest rather than the most negative
+    let shift = spectral_bound(matrix);
+    let shifted = shifted(matrix, shift, 1.0);
+    let mut pairs = deflated(shifted, k, options);
+    for pair in &mut pairs {
+        pair.value -= shift;
+    }
+    pairs
+}
+
+// The k smallest eigenpairs, smallest first.
+pub fn bottom_k(matrix: &[Vec<f64>], k: usize, options: Options) -> Vec<Eigenpair> {
+    let shift = spectral_bound(matrix);
+    let flipped = shifted(matrix, shift, -1.0);
+    let mut pairs = deflated(flipped, k, options);
+    for pair in &mut pairs {
+        pair.value = shift - pair.value;
+    }
+    pairs
+}
+
+// Ritz pairs from a Lanczos run with full reorthogonalization, taking up to
+// `k` from the requested end (ordered from that end inwards).
+pub fn lanczos(matrix: &[Vec<f64>], k: usize, end: End) -> Vec<Eigenpair> {
+    let size = matrix.len();
+    let Some(start) = start_vector(size, &mut Rng::new(SEED)) else {
+        return Vec::new();
+    };
+    let steps = size.min(2 * k + 20);
+    let mut basis = vec![start];
+    let (mut alphas, mut betas) = (Vec::new(), Vec::new());
+    while alphas.len() < steps {
+        let current = basis.last().unwrap();
+        let mut next = multiply(matrix, current);
+        alphas.push(dot(current, &next));
+        for vector in &basis {
+            let overlap = dot(vector, &next);
+            for (x, v) in next.iter_mut().zip(vector) {
+                *x -= overlap * v;
+            }
+        }
+        if alphas.len() == steps {
+            break;
+        }
+        match normalize(&mut next) {
+            // The Krylov space is exhausted; T already holds its spectrum
+            Some(beta) if beta > 1e-12 => {
+                betas.push(beta);
+                basis.push(next);
+            }
+            _ => break,
+        }
+    }
+
+    let m = alphas.len();
+    let mut tridiagonal = vec![vec![0.0; m]; m];
+    for i in 0..m {
+        tridiagonal[i][i] = alphas[i];
+        if i + 1 < m {
+            tridiagonal[i][i + 1] = betas[i];
+            tridiagonal[i + 1][i] = betas[i];
+        }
+    }
+    let mut ritz = jacobi(&tridiagonal);
+    if end == End::Largest {
+        ritz.reverse();
+    }
+    ritz.truncate(k);
+    ritz.into_iter()
+        .map(|pair| {
+            let mut vector = vec![0.0; size];
+            for (weight, basis_vector) in pair.vector.iter().zip(&basis) {
+                for (x, b) in vector.iter_mut().zip(basis_vector) {
+                    *x += weight * b;
+                }
+            }
+            normalize(&mut vector);
+            Eigenpair {
+                value: pair.value,
+                vector,
+            }
+        })
+        .collect()
+}
+
+// Every eigenpair, smallest value first, by cyclic Jacobi rotations.
+// O(n^3) per sweep: meant for small matrices such as Lanczos' tridiagonal.
+pub fn jacobi(matrix: &[Vec<f64>]) -> Vec<Eigenpair> {
+    let size = matrix.len();
+    let mut a = matrix.to_vec();
+    let mut vectors: Vec<Vec<f64>> = (0..size)
+        .map(|i| (0..size).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect();
+    let scale = sum(a.iter().flatten().map(|x| x * x)).max(f64::MIN_POSITIVE);
+    for _ in 0..100 {
+        let off_diagonal = sum((0..size)
+            .flat_map(|p| (p + 1..size).map(move |q| (p, q)))
+            .map(|(p, q)| a[p][q] * a[p][q]));
+        if off_diagonal <= 1e-30 * scale {
+            break;
+        }
+        for p in 0..size {
+            for q in p + 1..size {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+                // The rotation that zeroes a[p][q] (Numerical Recipes 11.1)
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in a.iter_mut().chain(vectors.iter_mut()) {
+                    let (x, y) = (row[p], row[q]);
+                    row[p] = c * x - s * y;
+                    row[q] = s * x + c * y;
+                }
+                let (row_p, row_q) = (a[p].clone(), a[q].clone());
+                for (k, (x, y)) in row_p.iter().zip(&row_q).enumerate() {
+                    a[p][k] = c * x - s * y;
+                    a[q][k] = s * x + c * y;
+                }
+            }
+        }
+    }
+
+    let mut pairs: Vec<Eigenpair> = (0..size)
+        .map(|i| Eigenpair {
+            value: a[i][i],
+            vector: vectors.iter().map(|row| row[i]).collect(),
+        })
+        .collect();
+    pairs.sort_by(|a, b| a.value.total_cmp(&b.value));
+    pairs
+}
+
+// Repeatedly take the dominant pair of a positive semi-definite matrix and
+// remove it (Hotelling deflation), which leaves the rest of the spectrum.
+// Each pair starts from a fresh vector: the last one has no component along
+// the rest of a repeated eigenvalue's space, so reusing it would skip them.
+fn deflated(mut matrix: Vec<Vec<f64>>, k: usize, options: Options) -> Vec<Eigenpair> {
+    let mut rng = Rng::new(SEED);
+    let mut pairs = Vec::new();
+    while pairs.len() < k.min(matrix.len()) {
+        let Some(start) = start_vector(matrix.len(), &mut rng) else {
+            break;
+        };
+        let pair = iterate(&matrix, start, options);
+        for (row, &vi) in matrix.iter_mut().zip(&pair.vector) {
+            for (x, &vj) in row.iter_mut().zip(&pair.vector) {
+                *x -= pair.value * vi * vj;
+            }
+        }
+        pairs.push(pair);
+    }
+    pairs
+}
+
+// `sign * matrix + shift * I`.
+fn shifted(matrix: &[Vec<f64>], shift: f64, sign: f64) -> Vec<Vec<f64>> {
+    matrix
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(j, &x)| sign * x + if i == j { shift } else { 0.0 })
+                .collect()
+        })
+        .collect()
+}
+
+// Gershgorin: no eigenvalue is larger in magnitude than the largest
+// absolute row sum.
+fn spectral_bound(matrix: &[Vec<f64>]) -> f64 {
+    matrix
+        .iter()
+        .map(|row| sum(row.iter().map(|x| x.abs())))
+        .fold(0.0, f64::max)
+}
+
+fn start_vector(size: usize, rng: &mut Rng) -> Option<Vec<f64>> {
+    if size == 0 {
+        return None;
+    }
+    let mut vector: Vec<f64> = (0..size).map(|_| rng.normal()).collect();
+    normalize(&mut vector);
+    Some(vector)
+}
+
+fn multiply(matrix: &[Vec<f64>], vector: &[f64]) -> Vec<f64> {
+    matrix.iter().map(|row| dot(row, vector)).collect()
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    sum(a.iter().zip(b).map(|(x, y)| x * y))
+}
+
+fn norm(vector: &[f64]) -> f64 {
+    dot(vector, vector).sqrt()
+}
+
+// Scale to unit length, returning the old length; None for a zero vector.
+fn normalize(vector: &mut [f64]) -> Option<f64> {
+    let length = norm(vector);
+    if length == 0.0 || !length.is_finite() {
+        return None;
+    }
+    for x in vector.iter_mut() {
+        *x /= length;
+    }
+    Some(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 2x2 block with eigenvalues 2 and 4 beside a diagonal block adding a
+    // second 2 and a 1
+    fn matrix() -> Vec<Vec<f64>> {
+        vec![
+            vec![3.0, 1.0, 0.0, 0.0],
+            vec![1.0, 3.0, 0.0, 0.0],
+            vec![0.0, 0.0, 2.0, 0.0],
+            vec![0.0, 0.0, 0.0, 1.0],
+        ]
+    }
+
+    fn assert_eigenpair(matrix: &[Vec<f64>], pair: &Eigenpair, value: f64) {
+        assert!(
+            (pair.value - value).abs() < 1e-6,
+            "{} != {}",
+            pair.value,
+            value
+        );
+        assert!((norm(&pair.vector) - 1.0).abs() < 1e-9);
+        let image = multiply(matrix, &pair.vector);
+        for (x, v) in image.iter().zip(&pair.vector) {
+            assert!((x - value * v).abs() < 1e-6, "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn test_extreme_eigenpairs() {
+        let matrix = matrix();
+        let dominant = power_iteration(&matrix, Options::default()).unwrap();
+        assert_eigenpair(&matrix, &dominant, 4.0);
+
+        let top = top_k(&matrix, 3, Options::default());
+        for (pair, value) in top.iter().zip([4.0, 2.0, 2.0]) {
+            assert_eigenpair(&matrix, pair, value);
+        }
+        let bottom = bottom_k(&matrix, 2, Options::default());
+        for (pair, value) in bottom.iter().zip([1.0, 2.0]) {
+            assert_eigenpair(&matrix, pair, value);
+        }
+        assert_eq!(top_k(&matrix, 9, Options::default()).len(), 4);
+        assert!(power_iteration(&[], Options::default()).is_none());
+    }
+
+    #[test]
+    fn test_jacobi_and_lanczos() {
+        let matrix = matrix();
+        let all = jacobi(&matrix);
+        let values: Vec<f64> = all.iter().map(|pair| pair.value).collect();
+        for (pair, value) in all.iter().zip([1.0, 2.0, 2.0, 4.0]) {
+            assert_eigenpair(&matrix, pair, value);
+        }
+        assert_eq!(values.len(), 4);
+
+        // The Krylov space sees the repeated 2 once
+        let largest = lanczos(&matrix, 2, End::Largest);
+        assert_eigenpair(&matrix, &largest[0], 4.0);
+        assert_eigenpair(&matrix, &largest[1], 2.0);
+        let smallest = lanczos(&matrix, 4, End::Smallest);
+        assert_eq!(smallest.len(), 3);
+        assert_eigenpair(&matrix, &smallest[0], 1.0);
+
+        // A negative definite case, where largest is not largest magnitude
+        let negative = vec![vec![-5.0, 0.0], vec![0.0, -1.0]];
+        assert_eigenpair(&negative, &top_k(&negative, 1, Options::default())[0], -1.0);
+        assert_eigenpair(&negative, &lanczos(&negative, 1, End::Largest)[0], -1.0);
+        assert_eigenpair(
+            &negative,
+            &power_iteration(&negative, Options::default()).unwrap(),
+            -5.0,
+        );
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::commands::load_data;
+use crate::filter::Filter;
+use crate::json::Json;
+use crate::metrics::Metrics;
+use crate::msgpack::Value;
+use crate::registry::{self, Registry};
+use crate::similar::{self, Neighbor};
+use crate::{cluster_graph, construct_graph, labels, EducationData, Graph};
+
+// The `/v1` analysis calls of `server`, without the HTTP server in front of
+// them: loaded datasets, built graphs and clustering
+// results are kept in memory under numeric handles, so each call can build
+// on an earlier one.
+#[derive(Default)]
+pub struct Engine {
+    datasets: BTreeMap<u64, Vec<EducationData>>,
+    graphs: BTreeMap<u64, Graph>,
+    results: BTreeMap<u64, AnalysisResult>,
+    next_id: u64,
+    // Results are also written here as `result-<id>.msgpack`, so they can
+    // be fetched again after a restart
+    cache_dir: Option<PathBuf>,
+    // Named datasets for the `/datasets` routes, apart from the handles
+    pub registry: Registry,
+    pub metrics: Metrics,
+}
+
+// A clustering of one of the engine's graphs, with members by name so the
+// result stands on its own once the graph is gone.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnalysisResult {
+    pub graph_id: u64,
+    pub clusters: Vec<ResultCluster>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResultCluster {
+    pub label: String,
+    pub members: Vec<String>,
+}
+
+impl Engine {
+    // An engine caching results in `dir`. Ids continue after the largest
+    // cached one, so a restarted server never reuses and overwrites them.
+    pub fn with_cache(dir: &Path) -> io::Result<Engine> {
+        fs::create_dir_all(dir)?;
+        let mut next_id = 0;
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            let id = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("result-")?.strip_suffix(".msgpack"))
+                .and_then(|id| id.parse::<u64>().ok());
+            next_id = next_id.max(id.unwrap_or(0));
+        }
+        Ok(Engine {
+            next_id,
+            cache_dir: Some(dir.to_path_buf()),
+            ..Engine::default()
+        })
+    }
+
+    // LoadDataset: returns (dataset_id, observations, countries).
+    pub fn load_dataset(
+        &mut self,
+        path: &str,
+        filter: Option<&str>,
+    ) -> io::Result<(u64, usize, usize)> {
+        let data = self.load(path, filter)?;
+        let (observations, countries) = (data.len(), country_count(&data));
+
+        let id = self.allocate();
+        self.datasets.insert(id, data);
+        Ok((id, observations, countries))
+    }
+
+    // BuildGraph: returns (graph_id, nodes).
+    pub fn build_graph(&mut self, dataset_id: u64) -> io::Result<(u64, usize)> {
+        let data = self
+            .datasets
+            .get(&dataset_id)
+            .ok_or_else(|| not_found("dataset", dataset_id))?;
+        let started = Instant::now();
+        let graph = construct_graph(data);
+        self.metrics.record_stage("build", started.elapsed());
+        let nodes = graph.nodes.len();
+
+        let id = self.allocate();
+        self.graphs.insert(id, graph);
+        Ok((id, nodes))
+    }
+
+    // Cluster: returns (result_id, clusters).
+    pub fn cluster(&mut self, graph_id: u64) -> io::Result<(u64, usize)> {
+        let graph = self
+            .graphs
+            .get(&graph_id)
+            .ok_or_else(|| not_found("graph", graph_id))?;
+        let started = Instant::now();
+        let result = AnalysisResult::of(graph_id, graph);
+        self.metrics.record_stage("cluster", started.elapsed());
+        let count = result.clusters.len();
+
+        let id = self.allocate();
+        if let Some(path) = self.cached_result(id) {
+            fs::write(path, result.to_msgpack(id).encode())?;
+        }
+        self.results.insert(id, result);
+        Ok((id, count))
+    }
+
+    // FindSimilar: the `k` countries most heavily linked to `country`.
+    pub fn similar(&self, graph_id: u64, country: &str, k: usize) -> io::Result<Vec<Neighbor>> {
+        let graph = self
+            .graphs
+            .get(&graph_id)
+            .ok_or_else(|| not_found("graph", graph_id))?;
+        let node = graph
+            .nodes
+            .iter()
+            .position(|node| node == country)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} is not in graph {}", country, graph_id),
+                )
+            })?;
+        Ok(similar::by_weight(graph, node, k))
+    }
+
+    // POST /datasets: load, build and cluster under `name` in one go,
+    // returning the names evicted to make room.
+    pub fn register(
+        &mut self,
+        name: &str,
+        path: &str,
+        filter: Option<&str>,
+    ) -> io::Result<Vec<String>> {
+        // Before the load, which may be slow
+        registry::validate_name(name)?;
+        let run = Run::execute(path, filter)?;
+        self.record_run(&run);
+        self.registry.insert(name, run.data, run.graph, run.result)
+    }
+
+    pub fn record_run(&mut self, run: &Run) {
+        for &(stage, elapsed) in &run.stages {
+            self.metrics.record_stage(stage, elapsed);
+        }
+    }
+
+    // GetResult, from memory or else the cache.
+    pub fn result(&mut self, result_id: u64) -> io::Result<&AnalysisResult> {
+        if self.results.contains_key(&result_id) {
+            self.metrics.record_result_lookup("memory");
+        } else {
+            let cached = self.cached_result(result_id).filter(|path| path.is_file());
+            let Some(path) = cached else {
+                self.metrics.record_result_lookup("miss");
+                return Err(not_found("result", result_id));
+            };
+            let (_, result) = AnalysisResult::from_msgpack(&Value::decode(&fs::read(path)?)?)?;
+            self.metrics.record_result_lookup("disk");
+            self.results.insert(result_id, result);
+        }
+        Ok(&self.results[&result_id])
+    }
+
+    // The `/metrics` page.
+    pub fn metrics_text(&self) -> String {
+        let graph_nodes: Vec<(u64, usize)> = self
+            .graphs
+            .iter()
+            .map(|(&id, graph)| (id, graph.nodes.len()))
+            .collect();
+        let dataset_bytes: Vec<(&str, usize)> = self
+            .registry
+            .entries()
+            .map(|(name, entry)| (name, entry.bytes))
+            .collect();
+        self.metrics.render(&graph_nodes, &dataset_bytes)
+    }
+
+    fn load(&mut self, path: &str, filter: Option<&str>) -> io::Result<Vec<EducationData>> {
+        let started = Instant::now();
+        let data = load_filtered(path, filter)?;
+        self.metrics.record_stage("load", started.elapsed());
+        Ok(data)
+    }
+
+    fn cached_result(&self, result_id: u64) -> Option<PathBuf> {
+        let dir = self.cache_dir.as_ref()?;
+        Some(dir.join(format!("result-{}.msgpack", result_id)))
+    }
+
+    // Handles are shared across kinds, so a stale id of one kind never
+    // silently names an object of another.
+    fn allocate(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+// A whole load-build-cluster pass over one file. It needs nothing from an
+// engine, so job workers run it without holding the engine's lock and
+// record its stage timings afterwards.
+pub struct Run {
+    pub data: Vec<EducationData>,
+    pub graph: Graph,
+    pub result: AnalysisResult,
+    stages: Vec<(&'static str, Duration)>,
+}
+
+impl Run {
+    pub fn execute(path: &str, filter: Option<&str>) -> io::Result<Run> {
+        let started = Instant::now();
+        let data = load_filtered(path, filter)?;
+        let loaded = Instant::now();
+        let graph = construct_graph(&data);
+        let built = Instant::now();
+        // Results outside the handle space belong to no graph id, hence 0
+        let result = AnalysisResult::of(0, &graph);
+        Ok(Run {
+            data,
+            graph,
+            result,
+            stages: vec![
+                ("load", loaded - started),
+                ("build", built - loaded),
+                ("cluster", built.elapsed()),
+            ],
+        })
+    }
+}
+
+fn load_filtered(path: &str, filter: Option<&str>) -> io::Result<Vec<EducationData>> {
+    let filter = filter.map(Filter::parse).transpose()?;
+    let mut data = load_data(path)?;
+    if let Some(filter) = &filter {
+        data.retain(|record| filter.matches(record));
+    }
+    Ok(data)
+}
+
+impl AnalysisResult {
+    // Cluster `graph` and name the members.
+    fn of(graph_id: u64, graph: &Graph) -> AnalysisResult {
+        let clusters = cluster_graph(graph, None);
+        AnalysisResult {
+            graph_id,
+            clusters: labels::cluster_labels(graph, &clusters)
+                .into_iter()
+                .zip(&clusters)
+                .map(|(label, cluster)| ResultCluster {
+                    label,
+                    members: cluster
+                        .iter()
+                        .map(|&node| graph.nodes[node].clone())
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    // The GetResult reply as JSON.
+    pub fn to_json(&self, result_id: u64) -> Json {
+        Json::object()
+            .with("result_id", result_id)
+            .with("graph_id", self.graph_id)
+            .with("clusters", self.clusters_json())
+    }
+
+    pub fn clusters_json(&self) -> Json {
+        Json::Array(
+            self.clusters
+                .iter()
+                .map(|cluster| {
+                    Json::object().with("label", cluster.label.as_str()).with(
+                        "members",
+                        Json::Array(cluster.members.iter().cloned().map(Json::from).collect()),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+impl AnalysisResult {
+    // The GetResult reply as MessagePack, with the same fields as the JSON.
+    pub fn to_msgpack(&self, result_id: u64) -> Value {
+        let clusters = self
+            .clusters
+            .iter()
+            .map(|cluster| {
+                Value::map(vec![
+                    ("label", Value::String(cluster.label.clone())),
+                    (
+                        "members",
+                        Value::Array(cluster.members.iter().cloned().map(Value::String).collect()),
+                    ),
+                ])
+            })
+            .collect();
+        Value::map(vec![
+            ("result_id", Value::Int(result_id as i64)),
+            ("graph_id", Value::Int(self.graph_id as i64)),
+            ("clusters", Value::Array(clusters)),
+        ])
+    }
+
+    // The result and its id back from `to_msgpack`.
+    pub fn from_msgpack(value: &Value) -> io::Result<(u64, AnalysisResult)> {
+        let malformed = |field: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("analysis result has no valid {}", field),
+            )
+        };
+        let id = |field: &str| {
+            value
+                .get(field)
+                .and_then(Value::as_u64)
+                .ok_or_else(|| malformed(field))
+        };
+        let clusters = value
+            .get("clusters")
+            .and_then(Value::as_array)
+            .ok_or_else(|| malformed("clusters"))?
+            .iter()
+            .map(|cluster| {
+                let label = cluster
+                    .get("label")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| malformed("cluster label"))?;
+                let members = cluster
+                    .get("members")
+                    .and_then(Value::as_array)
+                    .ok_or_else(|| malformed("cluster members"))?
+                    .iter()
+                    .map(|member| {
+                        member
+                            .as_str()
+                            .map(str::to_string)
+                            .ok_or_else(|| malformed("cluster member"))
+                    })
+                    .collect::<io::Result<_>>()?;
+                Ok(ResultCluster {
+                    label: label.to_string(),
+                    members,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        Ok((
+            id("result_id")?,
+            AnalysisResult {
+                graph_id: id("graph_id")?,
+                clusters,
+            },
+        ))
+    }
+}
+
+pub fn country_count(data: &[EducationData]) -> usize {
+    let mut countries: Vec<&str> = data
+        .iter()
+        .map(|record| record.country_or_area.as_str())
+        .collect();
+    countries.sort_unstable();
+    countries.dedup();
+    countries.len()
+}
+
+fn not_found(kind: &str, id: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no {} with id {}", kind, id),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_chains_handles() {
+        let path = std::env::temp_dir().join(format!("ds210-engine-{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "country,year,indicator,series,value\nChad,2015,T07,primary,10\nMali,2015,T07,primary,20\nMali,2010,T07,primary,5\n",
+        )
+        .unwrap();
+        let mut engine = Engine::default();
+        let (dataset, observations, countries) = engine
+            .load_dataset(path.to_str().unwrap(), Some("year = 2015"))
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((observations, countries), (2, 2));
+
+        let (graph, nodes) = engine.build_graph(dataset).unwrap();
+        assert_eq!(nodes, 2);
+        let (result, _) = engine.cluster(graph).unwrap();
+        assert_eq!(engine.result(result).unwrap().graph_id, graph);
+
+        // Ids are not interchangeable across kinds
+        assert_eq!(
+            engine.build_graph(graph).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert!(engine.result(dataset).is_err());
+    }
+
+    #[test]
+    fn test_result_msgpack_round_trip() {
+        let result = AnalysisResult {
+            graph_id: 4,
+            clusters: vec![
+                ResultCluster {
+                    label: "Chad".to_string(),
+                    members: vec!["Chad".to_string(), "Niger".to_string()],
+                },
+                ResultCluster {
+                    label: "empty".to_string(),
+                    members: Vec::new(),
+                },
+            ],
+        };
+        let bytes = result.to_msgpack(9).encode();
+        let decoded = Value::decode(&bytes).unwrap();
+        assert_eq!(
+            AnalysisResult::from_msgpack(&decoded).unwrap(),
+            (9, result.clone())
+        );
+        // Far smaller than the pretty JSON of the same response
+        assert!(bytes.len() * 2 < result.to_json(9).to_pretty_string().len());
+        assert!(AnalysisResult::from_msgpack(&Value::Nil).is_err());
+
+        // A cached result survives a restart and later ids skip past it
+        let dir = std::env::temp_dir().join(format!("ds210-cache-{}", std::process::id()));
+        let mut engine = Engine::with_cache(&dir).unwrap();
+        engine.graphs.insert(
+            1,
+            Graph {
+                nodes: vec!["Chad".to_string()],
+                adjacency_matrix: vec![vec![0.0]],
+            },
+        );
+        engine.next_id = 1;
+        let (id, _) = engine.cluster(1).unwrap();
+        let mut restarted = Engine::with_cache(&dir).unwrap();
+        assert_eq!(restarted.result(id).unwrap().graph_id, 1);
+        assert_eq!(restarted.allocate(), id + 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
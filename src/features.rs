@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::str::FromStr;
+
+use crate::parallel::Parallelism;
+use crate::stats::{mean, quantile, ranks, std_dev, Ties};
+use crate::EducationData;
+
+pub const SCALINGS: &[&str] = &["standard", "robust", "min-max", "rank"];
+
+// Robust z-scores are clipped to this many (scaled) deviations.
+const WINSOR_LIMIT: f64 = 3.0;
+
+// How `FeatureMatrix::standardized` puts each series on a common scale.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Scaling {
+    // (x - mean) / standard deviation
+    #[default]
+    Standard,
+    // (x - median) / (1.4826 MAD), clipped to +-3: the UN indicators are
+    // heavy-tailed, and one outlier would otherwise set a series' whole
+    // scale. The 1.4826 makes the MAD match the standard deviation on normal
+    // data; when more than half the values are equal the MAD is 0, and the
+    // mean absolute deviation (times 1.2533, for the same reason) stands in.
+    Robust,
+    // (x - min) / (max - min), from 0 to 1
+    MinMax,
+    // The value's rank among the series' values, from 0 for the lowest to 1
+    // for the highest; tied values share their average rank
+    Rank,
+}
+
+impl FromStr for Scaling {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Scaling> {
+        match value {
+            "standard" => Ok(Scaling::Standard),
+            "robust" => Ok(Scaling::Robust),
+            "min-max" => Ok(Scaling::MinMax),
+            "rank" => Ok(Scaling::Rank),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown scaling `{}`; expected one of {}",
+                    other,
+                    SCALINGS.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+impl Scaling {
+    // Each value on the common scale; all 0 when the values do not vary.
+    pub fn scale(&self, values: &[f64]) -> Vec<f64> {
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let (center, spread) = match self {
+            Scaling::MinMax => (Some(min), Some(max - min)),
+            Scaling::Rank if max > min => {
+                let last = (values.len() - 1) as f64;
+                return ranks(values, Ties::Average)
+                    .into_iter()
+                    .map(|rank| (rank - 1.0) / last)
+                    .collect();
+            }
+            Scaling::Rank => (None, None),
+            Scaling::Standard => (mean(values), std_dev(values)),
+            Scaling::Robust => {
+                let median = median(values);
+                let spread = median.and_then(|median| {
+                    let deviations: Vec<f64> = values.iter().map(|x| (x - median).abs()).collect();
+                    match median_of(deviations.clone()) {
+                        Some(mad) if mad > 0.0 => Some(1.4826 * mad),
+                        _ => mean(&deviations).map(|deviation| 1.2533 * deviation),
+                    }
+                });
+                (median, spread)
+            }
+        };
+        match (center, spread) {
+            (Some(center), Some(spread)) if spread > 0.0 => values
+                .iter()
+                .map(|value| {
+                    let z = (value - center) / spread;
+                    match self {
+                        Scaling::Robust => z.clamp(-WINSOR_LIMIT, WINSOR_LIMIT),
+                        _ => z,
+                    }
+                })
+                .collect(),
+            _ => vec![0.0; values.len()],
+        }
+    }
+}
+
+fn median(values: &[f64]) -> Option<f64> {
+    median_of(values.to_vec())
+}
+
+fn median_of(mut values: Vec<f64>) -> Option<f64> {
+    values.sort_by(f64::total_cmp);
+    quantile(&values, 0.5)
+}
+
+// Countries x series matrix of values for numeric tooling, NaN where a
+// country does not report a series.
+pub struct FeatureMatrix {
+    pub countries: Vec<String>,
+    pub series: Vec<String>,
+    // `values[country][series]`
+    pub values: Vec<Vec<f64>>,
+}
+
+// Each country's value of each series, taken from `year` or, without one,
+// from the latest year the country reports the series.
+pub fn feature_matrix(data: &[EducationData], year: Option<u32>) -> FeatureMatrix {
+    let mut chosen: BTreeMap<(&str, &str), (u32, f64)> = BTreeMap::new();
+    for record in data {
+        let Some(value) = record.value else { continue };
+        if year.is_some_and(|year| record.year != year) {
+            continue;
+        }
+        let slot = chosen
+            .entry((record.country_or_area.as_str(), record.series.as_str()))
+            .or_insert((record.year, value));
+        if record.year >= slot.0 {
+            *slot = (record.year, value);
+        }
+    }
+
+    let index = |names: Vec<&str>| -> BTreeMap<String, usize> {
+        names
+            .into_iter()
+            .map(str::to_string)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .enumerate()
+            .map(|(position, name)| (name, position))
+            .collect()
+    };
+    let country_index = index(chosen.keys().map(|&(country, _)| country).collect());
+    let series_index = index(chosen.keys().map(|&(_, series)| series).collect());
+
+    let mut values = vec![vec![f64::NAN; series_index.len()]; country_index.len()];
+    for (&(country, series), &(_, value)) in &chosen {
+        values[country_index[country]][series_index[series]] = value;
+    }
+    FeatureMatrix {
+        countries: country_index.into_keys().collect(),
+        series: series_index.into_keys().collect(),
+        values,
+    }
+}
+
+impl FeatureMatrix {
+    // Pairwise Euclidean distances over the series both countries report,
+    // scaled up by sqrt(series / shared) so that pairs sharing fewer series
+    // remain comparable. NaN when two countries share no series. Rows are
+    // computed in parallel.
+    pub fn distances(&self, parallelism: Parallelism) -> Vec<Vec<f64>> {
+        let n = self.countries.len();
+        parallelism.map_rows(n, |i| {
+            (0..n)
+                .map(|j| distance(&self.values[i], &self.values[j]))
+                .collect()
+        })
+    }
+}
+
+impl FeatureMatrix {
+    // Every series scaled to z-scores over the countries reporting it;
+    // unreported cells stay NaN.
+    pub fn standardized(&self, scaling: Scaling) -> FeatureMatrix {
+        let mut values = self.values.clone();
+        for column in 0..self.series.len() {
+            let reporting: Vec<usize> = (0..values.len())
+                .filter(|&row| !values[row][column].is_nan())
+                .collect();
+            let column_values: Vec<f64> =
+                reporting.iter().map(|&row| values[row][column]).collect();
+            for (&row, z) in reporting.iter().zip(scaling.scale(&column_values)) {
+                values[row][column] = z;
+            }
+        }
+        FeatureMatrix {
+            countries: self.countries.clone(),
+            series: self.series.clone(),
+            values,
+        }
+    }
+}
+
+// The distance `distances` puts between two rows of values.
+pub fn distance(a: &[f64], b: &[f64]) -> f64 {
+    let (mut squared, mut shared) = (0.0, 0);
+    for (x, y) in a.iter().zip(b) {
+        if !x.is_nan() && !y.is_nan() {
+            squared += (x - y).powi(2);
+            shared += 1;
+        }
+    }
+    if shared == 0 {
+        f64::NAN
+    } else {
+        (squared * a.len() as f64 / shared as f64).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_feature_matrix_and_distances() {
+        let data = vec![
+            record("Togo", "primary", 2010, 1.0),
+            record("Togo", "primary", 2015, 4.0),
+            record("Togo", "tertiary", 2015, 2.0),
+            record("Chad", "primary", 2015, 1.0),
+            record("Chad", "tertiary", 2015, 6.0),
+            record("Mali", "tertiary", 2015, 3.0),
+        ];
+        let features = feature_matrix(&data, None);
+        assert_eq!(features.countries, ["Chad", "Mali", "Togo"]);
+        assert_eq!(features.series, ["primary", "tertiary"]);
+        assert_eq!(features.values[2], [4.0, 2.0]);
+        assert!(features.values[1][0].is_nan());
+
+        let distances = features.distances(Parallelism::sequential());
+        let parallel = Parallelism {
+            threads: 2,
+            chunk_size: 1,
+        };
+        assert_eq!(features.distances(parallel), distances);
+        assert_eq!(distances[0][2], 5.0);
+        assert_eq!(distances[0][0], 0.0);
+        // Mali only shares tertiary: |6 - 3| scaled by sqrt(2 / 1)
+        assert!((distances[0][1] - 3.0 * 2f64.sqrt()).abs() < 1e-12);
+
+        let features = feature_matrix(&data, Some(2010));
+        assert_eq!(features.countries, ["Togo"]);
+    }
+
+    #[test]
+    fn test_robust_scaling_resists_outliers() {
+        let values = [1.0, 2.0, 3.0, 4.0, 1000.0];
+        let standard = Scaling::Standard.scale(&values);
+        // The outlier squeezes the others together under the standard scaling
+        assert!(
+            standard[..4].iter().all(|z| z.abs() < 0.6),
+            "{:?}",
+            standard
+        );
+        let robust = Scaling::Robust.scale(&values);
+        // Median 3, MAD 1
+        assert!((robust[0] + 2.0 / 1.4826).abs() < 1e-12);
+        assert_eq!(robust[2], 0.0);
+        assert_eq!(robust[4], 3.0);
+
+        // Mostly equal values have no MAD; the mean deviation stands in
+        let tied = Scaling::Robust.scale(&[100.0, 100.0, 100.0, 90.0, 110.0]);
+        assert!((tied[3] + 10.0 / (1.2533 * 4.0)).abs() < 1e-12);
+        assert_eq!(Scaling::Robust.scale(&[5.0, 5.0]), [0.0, 0.0]);
+
+        // Min-max and rank scalings run from 0 to 1, whatever the outlier
+        assert_eq!(Scaling::MinMax.scale(&[2.0, 4.0, 6.0]), [0.0, 0.5, 1.0]);
+        assert_eq!(Scaling::Rank.scale(&values), [0.0, 0.25, 0.5, 0.75, 1.0]);
+        assert_eq!(Scaling::Rank.scale(&[1.0, 1.0, 3.0]), [0.25, 0.25, 1.0]);
+        assert_eq!(Scaling::MinMax.scale(&[5.0, 5.0]), [0.0, 0.0]);
+
+        let features = FeatureMatrix {
+            countries: vec!["Chad".to_string(), "Mali".to_string(), "Togo".to_string()],
+            series: vec!["primary".to_string()],
+            values: vec![vec![1.0], vec![f64::NAN], vec![3.0]],
+        };
+        let scaled = features.standardized(Scaling::Standard);
+        assert_eq!(scaled.values[0], [-1.0]);
+        assert!(scaled.values[1][0].is_nan());
+        assert!("minmax".parse::<Scaling>().is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::hash;
+use crate::http::Client;
+use crate::parallel::Parallelism;
+use crate::source;
+
+// Downloads of several tables at once, e.g. the education file alongside
+// the population and GDP tables it is joined with. Fetching is the only part
+// of the pipeline that waits on the network, so it is the only part run
+// concurrently: each location is read to the end on a worker thread and the
+// bytes handed back, leaving parsing, building and clustering synchronous
+// on the caller's thread. Blocking sockets on a few scoped threads do for a
+// handful of tables what an async runtime would, without a dependency.
+// http:// locations go through the caller's `Client`, which retries, resumes
+// and spaces out requests across all the threads.
+
+// Every location's bytes (or its error), in the order given, fetching at
+// most `concurrency` at a time.
+pub fn fetch_all(
+    locations: &[String],
+    concurrency: usize,
+    client: &Client,
+) -> Vec<io::Result<Vec<u8>>> {
+    let parallelism = Parallelism {
+        threads: concurrency.max(1),
+        chunk_size: 1,
+    };
+    parallelism.map_rows(locations.len(), |i| fetch(&locations[i], client))
+}
+
+fn fetch(location: &str, client: &Client) -> io::Result<Vec<u8>> {
+    if location.starts_with("http://") {
+        return client.download(location);
+    }
+    let mut bytes = Vec::new();
+    source::open_location(location)?
+        .open()?
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+// The file name each location is saved under: the last segment of its
+// path, or `table-N.csv` when it has none. Two locations that would land on
+// the same name are refused before anything is fetched.
+pub fn file_names(locations: &[String]) -> io::Result<Vec<String>> {
+    let mut seen = BTreeSet::new();
+    locations
+        .iter()
+        .enumerate()
+        .map(|(index, location)| {
+            let name = match location.split_once("://") {
+                // The path after the host, without a query
+                Some((_, rest)) => rest
+                    .split(['?', '#'])
+                    .next()
+                    .and_then(|rest| rest.split_once('/'))
+                    .and_then(|(_, path)| path.rsplit('/').next()),
+                None => Path::new(location)
+                    .file_name()
+                    .and_then(|name| name.to_str()),
+            }
+            .filter(|name| !name.is_empty())
+            .map_or_else(|| format!("table-{}.csv", index + 1), str::to_string);
+            if !seen.insert(name.clone()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("two inputs would both be saved as {}", name),
+                ));
+            }
+            Ok(name)
+        })
+        .collect()
+}
+
+// The SHA-256 of each dataset version analyzed, one `<hex digest>  <location>`
+// line per location (the layout of `sha256sum` output; blank lines and `#`
+// comments are skipped). `fetch --lock` checks downloads against it and
+// records the digests of ones it has not seen, so a later fetch of a changed,
+// corrupted or truncated file is refused rather than analyzed.
+#[derive(Debug, Default, PartialEq)]
+pub struct Lockfile {
+    digests: BTreeMap<String, String>,
+}
+
+impl Lockfile {
+    // A missing file is an empty lockfile, to be written by `save`.
+    pub fn load(path: &str) -> io::Result<Lockfile> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Lockfile::default()),
+            Err(error) => return Err(error),
+        };
+        let mut digests = BTreeMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once(char::is_whitespace) {
+                Some((digest, location)) if is_digest(digest) => {
+                    digests.insert(location.trim().to_string(), digest.to_ascii_lowercase());
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{} line {}: expected a 64-digit SHA-256 and a location",
+                            path,
+                            number + 1
+                        ),
+                    ))
+                }
+            }
+        }
+        Ok(Lockfile { digests })
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let text: String = self
+            .digests
+            .iter()
+            .map(|(location, digest)| format!("{}  {}\n", digest, location))
+            .collect();
+        fs::write(path, text)
+    }
+
+    pub fn digest(&self, location: &str) -> Option<&str> {
+        self.digests.get(location).map(String::as_str)
+    }
+
+    pub fn record(&mut self, location: &str, digest: &str) {
+        self.digests
+            .insert(location.to_string(), digest.to_ascii_lowercase());
+    }
+}
+
+pub fn is_digest(text: &str) -> bool {
+    text.len() == 64 && text.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// The SHA-256 of a download, or InvalidData when it is not the `expected` one.
+pub fn verify(location: &str, bytes: &[u8], expected: Option<&str>) -> io::Result<String> {
+    let digest = hash::sha256_hex(bytes);
+    match expected {
+        Some(expected) if !expected.eq_ignore_ascii_case(&digest) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} has SHA-256 {} ({} bytes), expected {}; the download may be corrupted or truncated, or the dataset has changed",
+                location,
+                digest,
+                bytes.len(),
+                expected
+            ),
+        )),
+        _ => Ok(digest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fetch_in_order() {
+        let path = std::env::temp_dir().join(format!("ds210-fetch-{}.csv", std::process::id()));
+        std::fs::write(&path, "country,year\n").unwrap();
+        let locations = vec![
+            source::DEMO_LOCATION.to_string(),
+            "/nonexistent/missing.csv".to_string(),
+            path.to_str().unwrap().to_string(),
+        ];
+        let fetched = fetch_all(&locations, 2, &Client::default());
+        std::fs::remove_file(&path).unwrap();
+        assert!(fetched[0].as_ref().unwrap().starts_with(b"country,year,"));
+        assert!(fetched[1].is_err());
+        assert_eq!(fetched[2].as_ref().unwrap(), b"country,year\n");
+
+        let names = file_names(&[
+            "http://example.org/data/gdp.csv?year=2015".to_string(),
+            "demo://education".to_string(),
+            "population.csv".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(names, ["gdp.csv", "table-2.csv", "population.csv"]);
+        let same = ["a/gdp.csv".to_string(), "b/gdp.csv".to_string()];
+        assert!(file_names(&same).is_err());
+    }
+
+    #[test]
+    fn test_lockfile_verifies_digests() {
+        let path = std::env::temp_dir().join(format!("ds210-lock-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut lock = Lockfile::load(path).unwrap();
+        assert_eq!(lock, Lockfile::default());
+
+        let empty = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert_eq!(verify("a.csv", b"", None).unwrap(), empty);
+        lock.record("http://example.org/a.csv", &empty.to_ascii_uppercase());
+        lock.save(path).unwrap();
+        let lock = Lockfile::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let expected = lock.digest("http://example.org/a.csv");
+        assert_eq!(expected, Some(empty));
+        assert!(verify("a.csv", b"", expected).is_ok());
+        let error = verify("a.csv", b"a,b", expected).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(lock.digest("b.csv").is_none());
+    }
+}
@@ -0,0 +1,560 @@
+use std::io;
+use std::ops::RangeInclusive;
+
+use crate::EducationData;
+
+// A small expression language for selecting observations, e.g.
+//
+//   country in ("France", "Spain") and year >= 2015 and series ~ "enrol"
+//
+// Fields are `country`, `year`, `indicator`, `series` and `value`. Text
+// fields compare with `=`, `!=`, `~` (case-insensitive substring) and `in`;
+// `year` and `value` with `=`, `!=`, `<`, `<=`, `>`, `>=` and `in`.
+// Conditions combine with `and`, `or`, `not` and parentheses, `and` binding
+// tighter than `or`. A missing value fails every comparison on `value`.
+#[derive(Debug)]
+pub struct Filter {
+    expr: Expr,
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Field, Op, Literal),
+    In(Field, Vec<Literal>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    Country,
+    Year,
+    Indicator,
+    Series,
+    Value,
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Field::Country => "country",
+            Field::Year => "year",
+            Field::Indicator => "indicator",
+            Field::Series => "series",
+            Field::Value => "value",
+        }
+    }
+
+    fn is_text(self) -> bool {
+        matches!(self, Field::Country | Field::Indicator | Field::Series)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug)]
+enum Literal {
+    Text(String),
+    Number(f64),
+}
+
+impl Filter {
+    pub fn parse(source: &str) -> io::Result<Filter> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+        };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(Filter { expr }),
+            Some(token) => Err(parser.error(&format!("unexpected {}", token.text))),
+        }
+    }
+
+    pub fn matches(&self, record: &EducationData) -> bool {
+        self.expr.eval(record)
+    }
+}
+
+impl Expr {
+    fn eval(&self, record: &EducationData) -> bool {
+        match self {
+            Expr::And(left, right) => left.eval(record) && right.eval(record),
+            Expr::Or(left, right) => left.eval(record) || right.eval(record),
+            Expr::Not(inner) => !inner.eval(record),
+            Expr::Compare(field, op, literal) => compare(record, *field, *op, literal),
+            Expr::In(field, literals) => literals
+                .iter()
+                .any(|literal| compare(record, *field, Op::Eq, literal)),
+        }
+    }
+}
+
+fn compare(record: &EducationData, field: Field, op: Op, literal: &Literal) -> bool {
+    match literal {
+        Literal::Text(expected) => {
+            let actual = match field {
+                Field::Country => &record.country_or_area,
+                Field::Indicator => &record.indicator,
+                Field::Series => &record.series,
+                Field::Year | Field::Value => return false,
+            };
+            match op {
+                Op::Eq => actual == expected,
+                Op::Ne => actual != expected,
+                Op::Contains => actual.to_lowercase().contains(&expected.to_lowercase()),
+                _ => false,
+            }
+        }
+        Literal::Number(expected) => {
+            let actual = match field {
+                Field::Year => f64::from(record.year),
+                Field::Value => match record.value {
+                    Some(value) => value,
+                    None => return false,
+                },
+                _ => return false,
+            };
+            match op {
+                Op::Eq => actual == *expected,
+                Op::Ne => actual != *expected,
+                Op::Lt => actual < *expected,
+                Op::Le => actual <= *expected,
+                Op::Gt => actual > *expected,
+                Op::Ge => actual >= *expected,
+                Op::Contains => false,
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Kind {
+    Word,
+    Text,
+    Number,
+    Symbol,
+}
+
+struct Token {
+    kind: Kind,
+    text: String,
+    // 1-based character column, for error messages
+    column: usize,
+}
+
+fn tokenize(source: &str) -> io::Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(syntax_error(column, "unterminated string")),
+                    Some('"') => break,
+                    Some('\\') if i + 1 < chars.len() => {
+                        text.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some(&c) => {
+                        text.push(c);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push(Token {
+                kind: Kind::Text,
+                text,
+                column,
+            });
+        } else if c.is_ascii_digit()
+            || (c == '-' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token {
+                kind: Kind::Number,
+                text: chars[start..i].iter().collect(),
+                column,
+            });
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token {
+                kind: Kind::Word,
+                text: chars[start..i].iter().collect(),
+                column,
+            });
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let symbol = match two.as_str() {
+                "<=" | ">=" | "!=" | "==" => two,
+                _ if "()<>=~,".contains(c) => c.to_string(),
+                _ => {
+                    return Err(syntax_error(
+                        column,
+                        &format!("unexpected character {:?}", c),
+                    ))
+                }
+            };
+            i += symbol.len();
+            tokens.push(Token {
+                kind: Kind::Symbol,
+                text: symbol,
+                column,
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+// Recursive descent over `or := and ("or" and)*`, `and := unary ("and"
+// unary)*`, `unary := "not" unary | "(" or ")" | condition`.
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    // Consume the next token if it is the given keyword or symbol.
+    fn accept(&mut self, text: &str) -> bool {
+        let found = self
+            .peek()
+            .is_some_and(|token| token.kind != Kind::Text && token.text.eq_ignore_ascii_case(text));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, text: &str) -> io::Result<()> {
+        if self.accept(text) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", text)))
+        }
+    }
+
+    fn error(&self, message: &str) -> io::Error {
+        match self.peek() {
+            Some(token) => syntax_error(token.column, message),
+            None => invalid_input(format!("filter: {} at end of expression", message)),
+        }
+    }
+
+    fn or(&mut self) -> io::Result<Expr> {
+        let mut expr = self.and()?;
+        while self.accept("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> io::Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.accept("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> io::Result<Expr> {
+        if self.accept("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.accept("(") {
+            let expr = self.or()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        self.condition()
+    }
+
+    fn condition(&mut self) -> io::Result<Expr> {
+        let field = match self.peek() {
+            Some(token) if token.kind == Kind::Word => match token.text.to_lowercase().as_str() {
+                "country" => Field::Country,
+                "year" => Field::Year,
+                "indicator" => Field::Indicator,
+                "series" => Field::Series,
+                "value" => Field::Value,
+                _ => return Err(self.error(&format!("unknown field `{}`", token.text))),
+            },
+            _ => return Err(self.error("expected a field name")),
+        };
+        self.position += 1;
+
+        if self.accept("in") {
+            self.expect("(")?;
+            let mut literals = vec![self.literal(field)?];
+            while self.accept(",") {
+                literals.push(self.literal(field)?);
+            }
+            self.expect(")")?;
+            return Ok(Expr::In(field, literals));
+        }
+
+        let column = self.peek().map(|token| token.column);
+        let op = match self.peek().map(|token| token.text.as_str()) {
+            Some("=") | Some("==") => Op::Eq,
+            Some("!=") => Op::Ne,
+            Some("<") => Op::Lt,
+            Some("<=") => Op::Le,
+            Some(">") => Op::Gt,
+            Some(">=") => Op::Ge,
+            Some("~") => Op::Contains,
+            _ => return Err(self.error("expected a comparison or `in`")),
+        };
+        self.position += 1;
+        let ordering = matches!(op, Op::Lt | Op::Le | Op::Gt | Op::Ge);
+        if (field.is_text() && ordering) || (!field.is_text() && op == Op::Contains) {
+            return Err(syntax_error(
+                column.unwrap_or(0),
+                &format!("this comparison does not apply to `{}`", field.name()),
+            ));
+        }
+        Ok(Expr::Compare(field, op, self.literal(field)?))
+    }
+
+    // A literal of the field's type: quoted text or a number.
+    fn literal(&mut self, field: Field) -> io::Result<Literal> {
+        let wanted = if field.is_text() {
+            Kind::Text
+        } else {
+            Kind::Number
+        };
+        match self.peek() {
+            Some(token) if token.kind == wanted => {}
+            _ if field.is_text() => return Err(self.error("expected a quoted string")),
+            _ => return Err(self.error("expected a number")),
+        }
+        let token = self.next().expect("peeked");
+        Ok(match wanted {
+            Kind::Text => Literal::Text(token.text.clone()),
+            _ => {
+                let column = token.column;
+                let number = token
+                    .text
+                    .parse()
+                    .map_err(|_| syntax_error(column, &format!("invalid number {}", token.text)))?;
+                Literal::Number(number)
+            }
+        })
+    }
+}
+
+fn syntax_error(column: usize, message: &str) -> io::Error {
+    invalid_input(format!("filter: {} at column {}", message, column))
+}
+
+// The common selections without the expression language: a year range and
+// lists of series, indicators and countries, each matched exactly. A field
+// left unset keeps everything; a record must pass every field that is set.
+//
+//     let filter = DataFilter::new()
+//         .years(2010..=2020)
+//         .series("Gross enrollment ratio - Primary (female)");
+//     filter.apply(&mut data);
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DataFilter {
+    years: Option<RangeInclusive<u32>>,
+    series: Vec<String>,
+    indicators: Vec<String>,
+    countries: Vec<String>,
+}
+
+impl DataFilter {
+    pub fn new() -> DataFilter {
+        DataFilter::default()
+    }
+
+    pub fn years(mut self, years: RangeInclusive<u32>) -> DataFilter {
+        self.years = Some(years);
+        self
+    }
+
+    // Each call adds a series to the ones kept.
+    pub fn series(mut self, series: &str) -> DataFilter {
+        self.series.push(series.to_string());
+        self
+    }
+
+    pub fn indicator(mut self, indicator: &str) -> DataFilter {
+        self.indicators.push(indicator.to_string());
+        self
+    }
+
+    pub fn country(mut self, country: &str) -> DataFilter {
+        self.countries.push(country.to_string());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == DataFilter::default()
+    }
+
+    pub fn matches(&self, record: &EducationData) -> bool {
+        let listed =
+            |names: &[String], name: &str| names.is_empty() || names.iter().any(|n| n == name);
+        self.years
+            .as_ref()
+            .is_none_or(|years| years.contains(&record.year))
+            && listed(&self.series, &record.series)
+            && listed(&self.indicators, &record.indicator)
+            && listed(&self.countries, &record.country_or_area)
+    }
+
+    pub fn apply(&self, data: &mut Vec<EducationData>) {
+        if !self.is_empty() {
+            data.retain(|record| self.matches(record));
+        }
+    }
+}
+
+// "2010..2020" (both ends included), "2010.." or "..2020" for an open end,
+// or a single year.
+pub fn parse_years(text: &str) -> io::Result<RangeInclusive<u32>> {
+    let year = |part: &str, default: u32| -> io::Result<u32> {
+        let part = part.trim();
+        if part.is_empty() {
+            return Ok(default);
+        }
+        part.parse().map_err(|_| {
+            invalid_input(format!(
+                "invalid year range `{}`; expected e.g. 2010..2020, 2010.. or ..2020",
+                text
+            ))
+        })
+    };
+    let (start, end) = match text.split_once("..") {
+        Some((start, end)) => (
+            year(start, 0)?,
+            year(end.strip_prefix('=').unwrap_or(end), u32::MAX)?,
+        ),
+        None => {
+            let single = year(text, 0)?;
+            (single, single)
+        }
+    };
+    if start > end {
+        return Err(invalid_input(format!(
+            "year range `{}` ends before it starts",
+            text
+        )));
+    }
+    Ok(start..=end)
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_filter_selects_observations() {
+        let filter = Filter::parse(
+            r#"country in ("France", "Spain") and year >= 2015 and series ~ "ENROL""#,
+        )
+        .unwrap();
+        assert!(filter.matches(&record("Spain", "Gross enrollment ratio", 2015, None)));
+        assert!(!filter.matches(&record("Spain", "Gross enrollment ratio", 2010, None)));
+        assert!(!filter.matches(&record("Italy", "Gross enrollment ratio", 2020, None)));
+        assert!(!filter.matches(&record("France", "Teachers", 2020, None)));
+
+        // `and` binds tighter than `or`; a missing value fails comparisons
+        let filter =
+            Filter::parse("value > 50 or not (year != 2000) and country = \"Chad\"").unwrap();
+        assert!(filter.matches(&record("Mali", "", 2010, Some(60.0))));
+        assert!(filter.matches(&record("Chad", "", 2000, None)));
+        assert!(!filter.matches(&record("Mali", "", 2000, None)));
+    }
+
+    #[test]
+    fn test_data_filter() {
+        let primary = "Gross enrollment ratio - Primary (female)";
+        let mut data = vec![
+            record("Chad", primary, 2009, Some(1.0)),
+            record("Chad", primary, 2010, Some(2.0)),
+            record("Chad", "Teachers", 2020, Some(3.0)),
+            record("Mali", primary, 2020, None),
+        ];
+        let filter = DataFilter::new()
+            .years(parse_years("2010..2020").unwrap())
+            .series(primary);
+        filter.apply(&mut data);
+        let kept: Vec<(&str, u32)> = data
+            .iter()
+            .map(|record| (record.country_or_area.as_str(), record.year))
+            .collect();
+        assert_eq!(kept, [("Chad", 2010), ("Mali", 2020)]);
+        DataFilter::new().country("Mali").apply(&mut data);
+        assert_eq!(data.len(), 1);
+        assert!(!DataFilter::new().indicator("T08").matches(&data[0]));
+
+        assert_eq!(parse_years("2015").unwrap(), 2015..=2015);
+        assert_eq!(parse_years("..=2015").unwrap(), 0..=2015);
+        assert_eq!(parse_years("2015..").unwrap(), 2015..=u32::MAX);
+        assert!(parse_years("2020..2010").is_err());
+        assert!(parse_years("twenty").is_err());
+    }
+
+    #[test]
+    fn test_filter_errors_point_at_the_problem() {
+        let message = |source: &str| Filter::parse(source).unwrap_err().to_string();
+        assert_eq!(
+            message("year >= \"2015\""),
+            "filter: expected a number at column 9"
+        );
+        assert_eq!(
+            message("colour = \"red\""),
+            "filter: unknown field `colour` at column 1"
+        );
+        assert_eq!(
+            message("series < \"b\""),
+            "filter: this comparison does not apply to `series` at column 8"
+        );
+        assert_eq!(
+            message("(year = 2015"),
+            "filter: expected `)` at end of expression"
+        );
+        assert!(Filter::parse("country = \"Chad").is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use std::io;
+use std::str::FromStr;
+
+use crate::leadlag::History;
+use crate::stats;
+use crate::table::Table;
+use crate::Graph;
+
+pub const CORRECTIONS: &[&str] = &["bh", "bonferroni", "none"];
+
+// How p-values are adjusted for testing every ordered pair of countries.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Correction {
+    // Benjamini-Hochberg false discovery rate
+    #[default]
+    BenjaminiHochberg,
+    Bonferroni,
+    None,
+}
+
+impl Correction {
+    fn adjust(self, p_values: &[f64]) -> Vec<f64> {
+        match self {
+            Correction::BenjaminiHochberg => stats::benjamini_hochberg(p_values),
+            Correction::Bonferroni => p_values
+                .iter()
+                .map(|p| (p * p_values.len() as f64).min(1.0))
+                .collect(),
+            Correction::None => p_values.to_vec(),
+        }
+    }
+}
+
+impl FromStr for Correction {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Correction> {
+        match value {
+            "bh" => Ok(Correction::BenjaminiHochberg),
+            "bonferroni" => Ok(Correction::Bonferroni),
+            "none" => Ok(Correction::None),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown correction `{}`; expected one of {}",
+                    other,
+                    CORRECTIONS.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+// Does A's value `lag` years earlier improve a linear prediction of B's
+// value beyond B's own past? Compares
+//   restricted:   B[t] = c + b * B[t - lag]
+//   unrestricted: B[t] = c + b * B[t - lag] + a * A[t - lag]
+// with an F test on the one extra parameter.
+pub struct GrangerTest {
+    pub lag: u32,
+    // Fewest usable years; the unrestricted model has three parameters
+    pub min_points: usize,
+    pub alpha: f64,
+    pub correction: Correction,
+}
+
+impl Default for GrangerTest {
+    fn default() -> Self {
+        GrangerTest {
+            lag: 1,
+            min_points: 6,
+            alpha: 0.05,
+            correction: Correction::default(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GrangerEdge {
+    pub cause: usize,
+    pub effect: usize,
+    pub f_statistic: f64,
+    pub points: usize,
+    pub p_value: f64,
+    pub adjusted_p: f64,
+}
+
+impl GrangerTest {
+    // F statistic, its p-value and the number of years used, or None when
+    // there is too little data or a model is degenerate.
+    pub fn test(&self, cause: &History, effect: &History) -> Option<(f64, f64, usize)> {
+        let mut restricted = Vec::new();
+        let mut unrestricted = Vec::new();
+        let mut targets = Vec::new();
+        for (&year, &value) in effect {
+            let Some(earlier) = year.checked_sub(self.lag) else {
+                continue;
+            };
+            if let (Some(&own), Some(&other)) = (effect.get(&earlier), cause.get(&earlier)) {
+                restricted.push(vec![1.0, own]);
+                unrestricted.push(vec![1.0, own, other]);
+                targets.push(value);
+            }
+        }
+        let points = targets.len();
+        if points < self.min_points.max(4) {
+            return None;
+        }
+        let (_, rss_restricted) = stats::least_squares(&restricted, &targets)?;
+        let (_, rss_unrestricted) = stats::least_squares(&unrestricted, &targets)?;
+        let df = (points - 3) as f64;
+        if rss_unrestricted <= 1e-12 * rss_restricted.max(1e-300) {
+            // A perfect fit: infinitely strong evidence unless nothing improved
+            return (rss_restricted > rss_unrestricted).then_some((f64::INFINITY, 0.0, points));
+        }
+        let f = ((rss_restricted - rss_unrestricted).max(0.0)) / (rss_unrestricted / df);
+        Some((f, stats::f_survival(f, 1.0, df), points))
+    }
+
+    // Test every ordered pair and keep those significant after correction,
+    // strongest first.
+    pub fn edges(&self, histories: &[History]) -> Vec<GrangerEdge> {
+        let mut tested = Vec::new();
+        for cause in 0..histories.len() {
+            for effect in 0..histories.len() {
+                if cause == effect {
+                    continue;
+                }
+                if let Some((f_statistic, p_value, points)) =
+                    self.test(&histories[cause], &histories[effect])
+                {
+                    tested.push(GrangerEdge {
+                        cause,
+                        effect,
+                        f_statistic,
+                        points,
+                        p_value,
+                        adjusted_p: p_value,
+                    });
+                }
+            }
+        }
+
+        let p_values: Vec<f64> = tested.iter().map(|edge| edge.p_value).collect();
+        for (edge, adjusted) in tested.iter_mut().zip(self.correction.adjust(&p_values)) {
+            edge.adjusted_p = adjusted;
+        }
+        tested.retain(|edge| edge.adjusted_p <= self.alpha);
+        tested.sort_by(|x, y| {
+            y.f_statistic
+                .total_cmp(&x.f_statistic)
+                .then((x.cause, x.effect).cmp(&(y.cause, y.effect)))
+        });
+        tested
+    }
+}
+
+// `adjacency_matrix[cause][effect]` holds the F statistic.
+pub fn directed_graph(nodes: Vec<String>, edges: &[GrangerEdge]) -> Graph {
+    let mut adjacency_matrix = vec![vec![0.0; nodes.len()]; nodes.len()];
+    for edge in edges {
+        adjacency_matrix[edge.cause][edge.effect] = edge.f_statistic;
+    }
+    Graph {
+        nodes,
+        adjacency_matrix,
+    }
+}
+
+pub fn edge_table(nodes: &[String], edges: &[GrangerEdge]) -> Table {
+    let mut table = Table::new(&["cause", "effect", "f", "points", "p", "adjusted_p"]);
+    for edge in edges {
+        table.push_row(vec![
+            nodes[edge.cause].clone(),
+            nodes[edge.effect].clone(),
+            format!("{:.4}", edge.f_statistic),
+            edge.points.to_string(),
+            format!("{:.6}", edge.p_value),
+            format!("{:.6}", edge.adjusted_p),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_past_of_cause_predicts_effect() {
+        // B follows A's previous year plus a little noise of its own
+        let a = [3.0, 7.0, 1.0, 8.0, 2.0, 9.0, 4.0, 6.0, 2.0, 7.0, 5.0];
+        let noise = [0.1, -0.2, 0.15, 0.0, -0.1, 0.2, -0.15, 0.05, 0.1, -0.05];
+        let cause: History = (2000..).zip(a).collect();
+        let effect: History = (2001..)
+            .zip(
+                a.iter()
+                    .zip(noise)
+                    .map(|(value, noise)| 2.0 * value + noise),
+            )
+            .collect();
+
+        let test = GrangerTest::default();
+        let (f, p, points) = test.test(&cause, &effect).unwrap();
+        assert_eq!(points, 9);
+        assert!(f > 100.0 && p < 1e-4);
+
+        let edges = test.edges(&[cause, effect]);
+        assert_eq!(edges.len(), 1);
+        assert_eq!((edges[0].cause, edges[0].effect), (0, 1));
+        assert!(edges[0].adjusted_p >= edges[0].p_value);
+    }
+}
@@ -0,0 +1,627 @@
+use std::collections::HashMap;
+use std::io;
+use std::str::FromStr;
+
+use crate::console::note;
+use crate::data::EducationData;
+use crate::features::{self, FeatureMatrix};
+use crate::matrix::{Csr, MatrixBackend};
+use crate::parallel::Parallelism;
+use crate::stats::{self, sum, KahanSum, Ties};
+
+pub const SIMILARITIES: &[&str] = &["cosine", "pearson", "spearman", "euclidean"];
+
+// How alike two countries' series values are, for `construct_similarity_graph`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SimilarityMetric {
+    // Cosine of the angle between the value vectors
+    #[default]
+    Cosine,
+    // Correlation of the values across series
+    Pearson,
+    // Correlation of the values' ranks across series, ties ranked as given
+    Spearman(Ties),
+    // 1 / (1 + d) for the scaled Euclidean distance d of `FeatureMatrix`
+    Euclidean,
+}
+
+impl FromStr for SimilarityMetric {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<SimilarityMetric> {
+        match value {
+            "cosine" => Ok(SimilarityMetric::Cosine),
+            "pearson" => Ok(SimilarityMetric::Pearson),
+            "spearman" => Ok(SimilarityMetric::Spearman(Ties::default())),
+            "euclidean" => Ok(SimilarityMetric::Euclidean),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown similarity `{}`; expected one of {}",
+                    other,
+                    SIMILARITIES.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+impl SimilarityMetric {
+    // Over the series both countries report; 0 when they share none (or,
+    // for the correlations, fewer than two, or one of them does not vary). Negative
+    // cosines and correlations are held at 0, as weights are never negative.
+    pub fn similarity(&self, a: &[f64], b: &[f64]) -> f64 {
+        let shared: Vec<(f64, f64)> = a
+            .iter()
+            .zip(b)
+            .filter(|(x, y)| !x.is_nan() && !y.is_nan())
+            .map(|(&x, &y)| (x, y))
+            .collect();
+        let cosine = |pairs: &[(f64, f64)]| {
+            let dot = sum(pairs.iter().map(|(x, y)| x * y));
+            let norms = sum(pairs.iter().map(|(x, _)| x * x)).sqrt()
+                * sum(pairs.iter().map(|(_, y)| y * y)).sqrt();
+            if norms > 0.0 {
+                dot / norms
+            } else {
+                0.0
+            }
+        };
+        let similarity = match self {
+            _ if shared.is_empty() => 0.0,
+            SimilarityMetric::Cosine => cosine(&shared),
+            SimilarityMetric::Pearson if shared.len() < 2 => 0.0,
+            SimilarityMetric::Pearson => {
+                let count = shared.len() as f64;
+                let mean_x = sum(shared.iter().map(|(x, _)| *x)) / count;
+                let mean_y = sum(shared.iter().map(|(_, y)| *y)) / count;
+                let centered: Vec<(f64, f64)> = shared
+                    .iter()
+                    .map(|(x, y)| (x - mean_x, y - mean_y))
+                    .collect();
+                cosine(&centered)
+            }
+            SimilarityMetric::Spearman(ties) => stats::spearman(&shared, *ties).unwrap_or(0.0),
+            SimilarityMetric::Euclidean => 1.0 / (1.0 + features::distance(a, b)),
+        };
+        if similarity.is_finite() {
+            similarity.max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+// Countries or areas as nodes, with a square matrix of edge weights indexed
+// by node: row i, column j is the weight from node i to node j.
+pub struct Graph {
+    pub nodes: Vec<String>,
+    pub adjacency_matrix: Vec<Vec<f64>>,
+}
+
+impl Graph {
+    // Each row divided by its total weight, so row i holds the probabilities
+    // of a random walk stepping from node i to each node. Rows with no
+    // weight stay all zero.
+    pub fn row_stochastic(&self) -> Vec<Vec<f64>> {
+        self.adjacency_matrix
+            .iter()
+            .map(|row| {
+                let degree = sum(row.iter().copied());
+                row.iter()
+                    .map(|&weight| if degree == 0.0 { 0.0 } else { weight / degree })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Groups of nodes linked to each other by a path of non-zero weights
+    // in either direction; an edge that thresholding zeroed links nothing.
+    // Components are in order of their first node, members ascending.
+    pub fn connected_components(&self) -> Vec<Vec<usize>> {
+        let matrix = &self.adjacency_matrix;
+        let size = matrix.len();
+        let mut component = vec![usize::MAX; size];
+        let mut components = Vec::new();
+        for start in 0..size {
+            if component[start] != usize::MAX {
+                continue;
+            }
+            let index = components.len();
+            let mut members = vec![start];
+            component[start] = index;
+            let mut stack = vec![start];
+            while let Some(node) = stack.pop() {
+                for other in 0..size {
+                    let linked = matrix[node][other] != 0.0 || matrix[other][node] != 0.0;
+                    if linked && component[other] == usize::MAX {
+                        component[other] = index;
+                        members.push(other);
+                        stack.push(other);
+                    }
+                }
+            }
+            members.sort_unstable();
+            components.push(members);
+        }
+        components
+    }
+
+    // The graph on `nodes` alone, in the order given, keeping the weights
+    // among them. Panics on an index out of range.
+    pub fn subgraph(&self, nodes: &[usize]) -> Graph {
+        Graph {
+            nodes: nodes.iter().map(|&node| self.nodes[node].clone()).collect(),
+            adjacency_matrix: nodes
+                .iter()
+                .map(|&i| nodes.iter().map(|&j| self.adjacency_matrix[i][j]).collect())
+                .collect(),
+        }
+    }
+
+    // The symmetric normalized Laplacian I - D^-1/2 W D^-1/2, where W is the
+    // mean of the matrix and its transpose (built graphs are directed) and D
+    // holds W's row sums. An isolated node gets an all-zero row and column.
+    pub fn normalized_laplacian(&self) -> Vec<Vec<f64>> {
+        let matrix = &self.adjacency_matrix;
+        let size = matrix.len();
+        let symmetric = |i: usize, j: usize| (matrix[i][j] + matrix[j][i]) / 2.0;
+        let scale: Vec<f64> = (0..size)
+            .map(|i| {
+                let degree = sum((0..size).map(|j| symmetric(i, j)));
+                if degree > 0.0 {
+                    1.0 / degree.sqrt()
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        (0..size)
+            .map(|i| {
+                (0..size)
+                    .map(|j| {
+                        let identity = if i == j && scale[i] > 0.0 { 1.0 } else { 0.0 };
+                        identity - scale[i] * symmetric(i, j) * scale[j]
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+// What graph algorithms need of a graph: its nodes, and its weights read
+// through `MatrixBackend`. Algorithms written against it (`louvain`,
+// `modularity`) take the dense `Graph` as built or a `SparseGraph`.
+pub trait WeightedGraph {
+    fn nodes(&self) -> &[String];
+
+    fn weights(&self) -> &dyn MatrixBackend;
+}
+
+impl WeightedGraph for Graph {
+    fn nodes(&self) -> &[String] {
+        &self.nodes
+    }
+
+    fn weights(&self) -> &dyn MatrixBackend {
+        &self.adjacency_matrix
+    }
+}
+
+// A graph that stores only its non-zero weights, in compressed sparse rows:
+// memory grows with the edges rather than with the square of the nodes,
+// which is what lets graphs of thousands of countries (after thresholding,
+// most pairs unconnected) be built and clustered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseGraph {
+    pub nodes: Vec<String>,
+    pub weights: Csr,
+}
+
+impl WeightedGraph for SparseGraph {
+    fn nodes(&self) -> &[String] {
+        &self.nodes
+    }
+
+    fn weights(&self) -> &dyn MatrixBackend {
+        &self.weights
+    }
+}
+
+impl Graph {
+    pub fn to_sparse(&self) -> SparseGraph {
+        SparseGraph {
+            nodes: self.nodes.clone(),
+            weights: Csr::from_backend(self.adjacency_matrix.as_slice()),
+        }
+    }
+}
+
+impl SparseGraph {
+    pub fn to_dense(&self) -> Graph {
+        let size = self.nodes.len();
+        let mut adjacency_matrix = vec![vec![0.0; size]; size];
+        for (i, row) in adjacency_matrix.iter_mut().enumerate() {
+            for (j, weight) in self.weights.row_entries(i) {
+                row[j] = weight;
+            }
+        }
+        Graph {
+            nodes: self.nodes.clone(),
+            adjacency_matrix,
+        }
+    }
+
+    // Number of non-zero weights, self-loops included
+    pub fn edge_count(&self) -> usize {
+        self.weights.nonzeros()
+    }
+}
+
+// A graph whose edge weights are the similarity of each pair of countries'
+// series values, taken as in `features::feature_matrix` (each series'
+// latest year). Nodes are in name order; countries with no values are left
+// out. Every node's self-similarity is on the diagonal.
+pub fn construct_similarity_graph(data: &[EducationData], metric: SimilarityMetric) -> Graph {
+    construct_similarity_graph_with(data, metric, Parallelism::default())
+}
+
+// `construct_similarity_graph` with the rows of pairs spread over at most
+// `parallelism.threads` threads; the graph is the same for any setting.
+pub fn construct_similarity_graph_with(
+    data: &[EducationData],
+    metric: SimilarityMetric,
+    parallelism: Parallelism,
+) -> Graph {
+    similarity_graph_of(features::feature_matrix(data, None), metric, parallelism)
+}
+
+// The similarity graph of a feature matrix already built.
+pub fn similarity_graph_of(
+    features: FeatureMatrix,
+    metric: SimilarityMetric,
+    parallelism: Parallelism,
+) -> Graph {
+    let values = &features.values;
+    let adjacency_matrix = parallelism.map_rows(values.len(), |i| {
+        values
+            .iter()
+            .map(|b| metric.similarity(&values[i], b))
+            .collect()
+    });
+    Graph {
+        nodes: features.countries,
+        adjacency_matrix,
+    }
+}
+
+// `construct_similarity_graph` without the dense matrix: only pairs at
+// least `min_weight` alike are kept (each node's self-similarity always
+// is), row by row as they are computed over `parallelism`.
+pub fn construct_sparse_similarity_graph(
+    data: &[EducationData],
+    metric: SimilarityMetric,
+    min_weight: f64,
+    parallelism: Parallelism,
+) -> SparseGraph {
+    sparse_similarity_graph_of(
+        features::feature_matrix(data, None),
+        metric,
+        min_weight,
+        parallelism,
+    )
+}
+
+// The sparse similarity graph of a feature matrix already built.
+pub fn sparse_similarity_graph_of(
+    features: FeatureMatrix,
+    metric: SimilarityMetric,
+    min_weight: f64,
+    parallelism: Parallelism,
+) -> SparseGraph {
+    let values = &features.values;
+    let rows = parallelism.map_rows(values.len(), |i| {
+        values
+            .iter()
+            .enumerate()
+            .map(|(j, b)| (j, metric.similarity(&values[i], b)))
+            .filter(|&(j, weight)| i == j || weight >= min_weight)
+            .collect()
+    });
+    SparseGraph {
+        nodes: features.countries,
+        weights: Csr::from_rows(rows),
+    }
+}
+
+// The country graph: the similarity graph under the default metric.
+pub fn construct_graph(data: &[EducationData]) -> Graph {
+    construct_similarity_graph(data, SimilarityMetric::default())
+}
+
+// The graph of the original analysis, kept for `--value-graph`: each
+// record's value is added to every weight in its country's row, so a
+// country's links all weigh the sum of its values.
+pub fn construct_value_graph(data: &[EducationData]) -> Graph {
+    construct_value_graph_with(data, &mut |_, _| {})
+}
+
+// Build the value graph, reporting for each record the weight it added to
+// edges between distinct nodes (used by `profile` to attribute weight to
+// series).
+pub fn construct_value_graph_with(
+    data: &[EducationData],
+    on_record: &mut dyn FnMut(&EducationData, f64),
+) -> Graph {
+    let mut builder = ValueGraph::default();
+    for record in data {
+        builder.add(record, on_record);
+    }
+    builder.finish()
+}
+
+// `construct_value_graph` over records as they are read, say from an
+// `EducationDataReader`, so that only the graph is held in memory and not
+// the rows behind it. The first error ends it.
+pub fn construct_value_graph_from_records<I>(records: I) -> io::Result<Graph>
+where
+    I: IntoIterator<Item = io::Result<EducationData>>,
+{
+    let mut builder = ValueGraph::default();
+    for record in records {
+        builder.add(&record?, &mut |_, _| {});
+    }
+    Ok(builder.finish())
+}
+
+// The value graph as it is built, one record at a time.
+#[derive(Default)]
+struct ValueGraph {
+    nodes: Vec<String>,
+    // Weights are summed with compensation, since a country's row collects
+    // one addition per record and the values span several magnitudes
+    sums: Vec<Vec<KahanSum>>,
+    node_indices: HashMap<String, usize>,
+}
+
+impl ValueGraph {
+    fn add(&mut self, record: &EducationData, on_record: &mut dyn FnMut(&EducationData, f64)) {
+        let nodes = &mut self.nodes;
+        let sums = &mut self.sums;
+        let country_or_area = &record.country_or_area;
+
+        // If the country is not yet in the graph, add it
+        let node_index = *self
+            .node_indices
+            .entry(country_or_area.clone())
+            .or_insert_with(|| {
+                nodes.push(country_or_area.clone());
+                // Keep the matrix square: existing rows gain a column for the new node
+                for row in sums.iter_mut() {
+                    row.push(KahanSum::default());
+                }
+                sums.push(vec![KahanSum::default(); nodes.len()]);
+                nodes.len() - 1
+            });
+
+        let value_to_add = record.value.unwrap_or(0.0);
+        if !value_to_add.is_finite() {
+            note!(
+                "Warning: skipping {} {} {}: its weight {} is not finite",
+                record.country_or_area,
+                record.year,
+                record.series,
+                value_to_add
+            );
+            return;
+        }
+        for weight in sums[node_index].iter_mut() {
+            weight.add(value_to_add);
+        }
+        // Every cell of the row except the diagonal is an edge
+        on_record(record, value_to_add * (nodes.len() - 1) as f64);
+    }
+
+    fn finish(self) -> Graph {
+        // Sums too large for an f64 are held at the largest finite weight, so
+        // later statistics stay finite
+        let adjacency_matrix = self
+            .sums
+            .iter()
+            .zip(&self.nodes)
+            .map(|(row, node)| {
+                let row: Vec<f64> = row.iter().map(KahanSum::value).collect();
+                if row.iter().any(|weight| !weight.is_finite()) {
+                    note!(
+                        "Warning: edge weights of {} overflowed and were capped at {:e}",
+                        node,
+                        f64::MAX
+                    );
+                }
+                row.into_iter()
+                    .map(|weight| weight.clamp(-f64::MAX, f64::MAX))
+                    .collect()
+            })
+            .collect();
+
+        Graph {
+            nodes: self.nodes,
+            adjacency_matrix,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_construct_value_graph_with_extreme_values() {
+        // Small values are not lost next to a huge one that cancels out
+        let mut data = vec![record("Chad", "Students", 100, 1e16)];
+        data.extend((0..1000).map(|_| record("Chad", "Students", 100, 1.0)));
+        data.push(record("Chad", "Students", 100, -1e16));
+        // Overflow is capped, infinite or NaN values are skipped, and the
+        // year does not scale a value
+        data.push(record("Mali", "Students", 100, f64::MAX));
+        data.push(record("Mali", "Students", 100, f64::MAX));
+        data.push(record("Niger", "Students", 100, f64::INFINITY));
+        data.push(record("Niger", "Students", 100, f64::NAN));
+        data.push(record("Niger", "Students", 2015, 2.0));
+
+        let graph = construct_value_graph(&data);
+        assert_eq!(graph.adjacency_matrix[0], vec![1000.0, 0.0, 0.0]);
+        assert_eq!(graph.adjacency_matrix[1], vec![f64::MAX, f64::MAX, 0.0]);
+        assert_eq!(graph.adjacency_matrix[2], vec![2.0, 2.0, 2.0]);
+
+        // Streamed in, the records make the same graph; an error ends it
+        let streamed = construct_value_graph_from_records(data.into_iter().map(Ok)).unwrap();
+        assert_eq!(streamed.nodes, graph.nodes);
+        assert_eq!(streamed.adjacency_matrix, graph.adjacency_matrix);
+        let failing = vec![
+            Ok(record("Chad", "Students", 100, 1.0)),
+            Err(io::Error::new(io::ErrorKind::InvalidData, "line 3")),
+        ];
+        assert!(construct_value_graph_from_records(failing).is_err());
+    }
+
+    #[test]
+    fn test_similarity_graph() {
+        let data = vec![
+            record("Chad", "primary", 2015, 1.0),
+            record("Chad", "tertiary", 2015, 2.0),
+            record("Chad", "upper", 2015, 3.0),
+            record("Mali", "primary", 2015, 2.0),
+            record("Mali", "tertiary", 2015, 4.0),
+            record("Mali", "upper", 2015, 6.0),
+            record("Niger", "primary", 2015, 3.0),
+            record("Niger", "tertiary", 2015, 2.0),
+            record("Niger", "upper", 2015, 1.0),
+        ];
+        let metric = |name: &str| name.parse::<SimilarityMetric>().unwrap();
+        let graph = construct_similarity_graph(&data, metric("pearson"));
+        assert_eq!(graph.nodes, ["Chad", "Mali", "Niger"]);
+        let close = |actual: f64, expected: f64| (actual - expected).abs() < 1e-12;
+        // Mali is Chad doubled; Niger runs the other way and is held at 0
+        assert!(close(graph.adjacency_matrix[0][1], 1.0));
+        assert_eq!(graph.adjacency_matrix[0][2], 0.0);
+        assert!(close(graph.adjacency_matrix[1][1], 1.0));
+
+        let graph = construct_similarity_graph(&data, metric("cosine"));
+        assert!(close(graph.adjacency_matrix[0][1], 1.0));
+        // A row per chunk, so all three threads take part
+        let threaded = Parallelism {
+            threads: 3,
+            chunk_size: 1,
+        };
+        let parallel = construct_similarity_graph_with(&data, metric("cosine"), threaded);
+        assert_eq!(parallel.adjacency_matrix, graph.adjacency_matrix);
+        // Same order, so a perfect rank correlation; tied ranks count too
+        let spearman = metric("spearman");
+        assert_eq!(spearman, SimilarityMetric::Spearman(Ties::Average));
+        let ranked = construct_similarity_graph(&data, spearman);
+        assert!(close(ranked.adjacency_matrix[0][1], 1.0));
+        assert_eq!(ranked.adjacency_matrix[0][2], 0.0);
+        assert!(close(
+            spearman.similarity(&[1.0, 1.0, 2.0], &[1.0, 2.0, 3.0]),
+            0.75f64.sqrt()
+        ));
+
+        // The sparse graph keeps the same weights above the threshold
+        let sparse = construct_sparse_similarity_graph(
+            &data,
+            metric("cosine"),
+            0.9,
+            Parallelism::sequential(),
+        );
+        assert_eq!(sparse.nodes, graph.nodes);
+        for (i, row) in graph.adjacency_matrix.iter().enumerate() {
+            for (j, &weight) in row.iter().enumerate() {
+                let kept = if i == j || weight >= 0.9 { weight } else { 0.0 };
+                assert_eq!(sparse.weights.weight(i, j), kept);
+            }
+        }
+        assert!(sparse.edge_count() < 9);
+        assert_eq!(sparse.to_dense().to_sparse(), sparse);
+        assert!(close(graph.adjacency_matrix[0][2], 10.0 / 14.0));
+        let graph = construct_similarity_graph(&data, metric("euclidean"));
+        // Chad to Niger: sqrt(4 + 0 + 4)
+        assert!(close(
+            graph.adjacency_matrix[0][2],
+            1.0 / (1.0 + 8f64.sqrt())
+        ));
+        assert_eq!(graph.adjacency_matrix[2][2], 1.0);
+
+        assert_eq!(
+            metric("cosine").similarity(&[1.0, f64::NAN], &[f64::NAN, 1.0]),
+            0.0
+        );
+        assert!("jaccard".parse::<SimilarityMetric>().is_err());
+    }
+
+    #[test]
+    fn test_degree_normalizations() {
+        let graph = Graph {
+            nodes: vec!["Chad".to_string(), "Mali".to_string(), "Niger".to_string()],
+            adjacency_matrix: vec![
+                vec![0.0, 1.0, 3.0],
+                vec![1.0, 0.0, 0.0],
+                vec![0.0, 0.0, 0.0],
+            ],
+        };
+        assert_eq!(
+            graph.row_stochastic(),
+            vec![
+                vec![0.0, 0.25, 0.75],
+                vec![1.0, 0.0, 0.0],
+                vec![0.0, 0.0, 0.0],
+            ]
+        );
+
+        // W = [[0, 1, 1.5], [1, 0, 0], [1.5, 0, 0]]: degrees 2.5, 1, 1.5
+        let laplacian = graph.normalized_laplacian();
+        let expected = [
+            [1.0, -1.0 / 2.5f64.sqrt(), -1.5 / (2.5f64 * 1.5).sqrt()],
+            [-1.0 / 2.5f64.sqrt(), 1.0, 0.0],
+            [-1.5 / (2.5f64 * 1.5).sqrt(), 0.0, 1.0],
+        ];
+        for (row, expected) in laplacian.iter().zip(&expected) {
+            for (actual, expected) in row.iter().zip(expected) {
+                assert!((actual - expected).abs() < 1e-12, "{:?}", laplacian);
+            }
+        }
+
+        // An isolated node contributes nothing
+        let isolated = Graph {
+            nodes: vec!["Chad".to_string()],
+            adjacency_matrix: vec![vec![0.0]],
+        };
+        assert_eq!(isolated.normalized_laplacian(), vec![vec![0.0]]);
+    }
+
+    #[test]
+    fn test_components_and_subgraphs() {
+        // 0 -> 2 one way only, 1 alone but for a self-loop, 3 - 4
+        let mut matrix = vec![vec![0.0; 5]; 5];
+        matrix[0][2] = 1.0;
+        matrix[1][1] = 5.0;
+        matrix[3][4] = 2.0;
+        matrix[4][3] = 2.0;
+        let graph = Graph {
+            nodes: ["Chad", "Mali", "Niger", "Peru", "Chile"]
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            adjacency_matrix: matrix,
+        };
+        assert_eq!(
+            graph.connected_components(),
+            [vec![0, 2], vec![1], vec![3, 4]]
+        );
+        let sub = graph.subgraph(&[2, 0]);
+        assert_eq!(sub.nodes, ["Niger", "Chad"]);
+        assert_eq!(sub.adjacency_matrix, [[0.0, 0.0], [1.0, 0.0]]);
+        assert_eq!(sub.connected_components(), [vec![0, 1]]);
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use crate::stats;
+use crate::table::Table;
+use crate::Graph;
+
+// How far apart two graphs over the same countries are, for instance the
+// graphs of two years or of two construction settings. Only countries in
+// both graphs are compared, each pair of them once, weighing the mean of
+// its two directions as agglomerative clustering does; self-loops are left
+// out.
+pub struct Comparison {
+    pub nodes: [usize; 2],
+    pub shared: usize,
+    // Pearson correlation of the pair weights; None when either graph is
+    // constant over the shared countries
+    pub weight_correlation: Option<f64>,
+    // Pairs weighing more than the threshold in both, over pairs weighing
+    // more in either; None when neither has such a pair
+    pub edge_jaccard: Option<f64>,
+    // 1 for identical structure, towards 0 as it diverges
+    pub deltacon: f64,
+}
+
+pub fn compare_graphs(first: &Graph, second: &Graph, threshold: f64) -> Comparison {
+    let positions: HashMap<&str, usize> = second
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (node.as_str(), index))
+        .collect();
+    let shared: Vec<(usize, usize)> = first
+        .nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, node)| Some((index, *positions.get(node.as_str())?)))
+        .collect();
+    let restrict = |matrix: &[Vec<f64>], pick: fn(&(usize, usize)) -> usize| -> Vec<Vec<f64>> {
+        shared
+            .iter()
+            .map(|a| {
+                let i = pick(a);
+                shared
+                    .iter()
+                    .map(|b| match pick(b) {
+                        j if j == i => 0.0,
+                        j => (matrix[i][j] + matrix[j][i]) / 2.0,
+                    })
+                    .collect()
+            })
+            .collect()
+    };
+    let a = restrict(&first.adjacency_matrix, |&(i, _)| i);
+    let b = restrict(&second.adjacency_matrix, |&(_, j)| j);
+
+    let mut pairs = Vec::new();
+    let (mut both, mut either) = (0usize, 0usize);
+    for i in 0..shared.len() {
+        for j in i + 1..shared.len() {
+            pairs.push((a[i][j], b[i][j]));
+            let (in_a, in_b) = (a[i][j] > threshold, b[i][j] > threshold);
+            both += usize::from(in_a && in_b);
+            either += usize::from(in_a || in_b);
+        }
+    }
+    Comparison {
+        nodes: [first.nodes.len(), second.nodes.len()],
+        shared: shared.len(),
+        weight_correlation: stats::pearson(&pairs),
+        edge_jaccard: (either > 0).then(|| both as f64 / either as f64),
+        deltacon: deltacon(&a, &b),
+    }
+}
+
+// DeltaCon without its node grouping: every node's affinity to every other
+// by fast belief propagation, S = [I + e²D - eA]^-1, approximated to second
+// order as I + eA + e²(A² - D), and the root Euclidean distance d between
+// the two affinity matrices turned into a similarity 1 / (1 + d). Each
+// graph's weights are first divided by its largest, so graphs built at
+// different scales compare on their shape; e keeps the series convergent
+// for both.
+fn deltacon(a: &[Vec<f64>], b: &[Vec<f64>]) -> f64 {
+    let scaled = |matrix: &[Vec<f64>]| -> Vec<Vec<f64>> {
+        let largest = matrix
+            .iter()
+            .flatten()
+            .fold(0.0f64, |max, &weight| max.max(weight.abs()));
+        matrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&weight| if largest > 0.0 { weight / largest } else { 0.0 })
+                    .collect()
+            })
+            .collect()
+    };
+    let (a, b) = (scaled(a), scaled(b));
+    let degree = |matrix: &[Vec<f64>]| {
+        matrix
+            .iter()
+            .map(|row| row.iter().map(|weight| weight.abs()).sum::<f64>())
+            .fold(0.0f64, f64::max)
+    };
+    let epsilon = 1.0 / (1.0 + degree(&a).max(degree(&b)));
+    let affinities = |matrix: &[Vec<f64>]| -> Vec<Vec<f64>> {
+        let n = matrix.len();
+        let degrees: Vec<f64> = matrix.iter().map(|row| row.iter().sum()).collect();
+        (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| {
+                        let squared: f64 = (0..n).map(|k| matrix[i][k] * matrix[k][j]).sum();
+                        let diagonal = if i == j {
+                            1.0 - epsilon.powi(2) * degrees[i]
+                        } else {
+                            0.0
+                        };
+                        diagonal + epsilon * matrix[i][j] + epsilon.powi(2) * squared
+                    })
+                    .collect()
+            })
+            .collect()
+    };
+    let distance = affinities(&a)
+        .iter()
+        .flatten()
+        .zip(affinities(&b).iter().flatten())
+        .map(|(x, y)| (x.max(0.0).sqrt() - y.max(0.0).sqrt()).powi(2))
+        .sum::<f64>()
+        .sqrt();
+    1.0 / (1.0 + distance)
+}
+
+pub fn comparison_table(comparison: &Comparison) -> Table {
+    let score =
+        |value: Option<f64>| value.map_or_else(|| "NA".to_string(), |v| format!("{:.4}", v));
+    let mut table = Table::new(&["measure", "value"]);
+    for (measure, value) in [
+        ("first countries", comparison.nodes[0].to_string()),
+        ("second countries", comparison.nodes[1].to_string()),
+        ("shared countries", comparison.shared.to_string()),
+        ("weight correlation", score(comparison.weight_correlation)),
+        ("edge jaccard", score(comparison.edge_jaccard)),
+        ("deltacon", score(Some(comparison.deltacon))),
+    ] {
+        table.push_row(vec![measure.to_string(), value]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(nodes: &[&str], adjacency_matrix: Vec<Vec<f64>>) -> Graph {
+        Graph {
+            nodes: nodes.iter().map(|node| node.to_string()).collect(),
+            adjacency_matrix,
+        }
+    }
+
+    #[test]
+    fn test_compare_graphs_on_shared_countries() {
+        let first = graph(
+            &["Chad", "Mali", "Peru"],
+            vec![
+                vec![0.0, 4.0, 1.0],
+                vec![4.0, 0.0, 2.0],
+                vec![1.0, 2.0, 0.0],
+            ],
+        );
+        // The same graph at ten times the scale, in another order
+        let scaled = graph(
+            &["Peru", "Chad", "Mali"],
+            vec![
+                vec![0.0, 10.0, 20.0],
+                vec![10.0, 0.0, 40.0],
+                vec![20.0, 40.0, 0.0],
+            ],
+        );
+        let same = compare_graphs(&first, &scaled, 1.5);
+        assert_eq!(same.shared, 3);
+        assert!((same.weight_correlation.unwrap() - 1.0).abs() < 1e-12);
+        assert!((same.deltacon - 1.0).abs() < 1e-12);
+        // Chad-Mali and Mali-Peru in the first; all three pairs in the second
+        assert!((same.edge_jaccard.unwrap() - 2.0 / 3.0).abs() < 1e-12);
+
+        let other = graph(
+            &["Chad", "Mali", "Peru", "Oman"],
+            vec![
+                vec![0.0, 1.0, 4.0, 1.0],
+                vec![1.0, 0.0, 2.0, 1.0],
+                vec![4.0, 2.0, 0.0, 1.0],
+                vec![1.0, 1.0, 1.0, 0.0],
+            ],
+        );
+        let apart = compare_graphs(&first, &other, 1.5);
+        assert_eq!((apart.nodes, apart.shared), ([3, 4], 3));
+        assert!(apart.weight_correlation.unwrap() < 0.0);
+        assert!(apart.deltacon < same.deltacon);
+        assert_eq!(compare_graphs(&first, &other, 10.0).edge_jaccard, None);
+
+        let table = comparison_table(&apart);
+        assert_eq!(table.rows[2], ["shared countries", "3"]);
+    }
+}
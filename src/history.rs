@@ -0,0 +1,285 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+use crate::json::Json;
+use crate::labels;
+use crate::manifest::Manifest;
+use crate::table::Table;
+use crate::Graph;
+
+// A log of clusterings kept across invocations, to follow how results move
+// as the dataset or the method changes. The log is JSON Lines and only ever
+// appended to: one object per run holding what its manifest records (when,
+// which build, the command, input hashes and parameters) and the members of
+// each cluster by name.
+//
+// Cluster numbers mean nothing from one run to the next, so a country is
+// taken to have moved when the countries it shares a cluster with differ,
+// counting only countries both runs clustered.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub created_at: String,
+    pub command: String,
+    pub parameters: Vec<(String, String)>,
+    pub labels: Vec<String>,
+    pub clusters: Vec<Vec<String>>,
+}
+
+// Append this run to the log at `path`, creating it if needed.
+pub fn append_run(
+    path: &str,
+    manifest: &Manifest,
+    graph: &Graph,
+    clusters: &[Vec<usize>],
+) -> io::Result<()> {
+    let run = manifest.to_json(&[]);
+    let mut entry = Json::object();
+    for key in ["created_at", "version", "command", "inputs", "parameters"] {
+        if let Some(value) = run.get(key) {
+            entry = entry.with(key, value.clone());
+        }
+    }
+    let labels = labels::cluster_labels(graph, clusters);
+    let clusters: Vec<Json> = clusters
+        .iter()
+        .zip(&labels)
+        .map(|(members, label)| {
+            let names: Vec<&str> = members
+                .iter()
+                .map(|&node| graph.nodes[node].as_str())
+                .collect();
+            Json::object()
+                .with("label", label.as_str())
+                .with("members", names)
+        })
+        .collect();
+    entry = entry.with("clusters", Json::Array(clusters));
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", entry)
+}
+
+pub fn read_history(path: &str) -> io::Result<Vec<HistoryEntry>> {
+    let text = fs::read_to_string(path)?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            parse_entry(line).map_err(|message| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} line {}: {}", path, index + 1, message),
+                )
+            })
+        })
+        .collect()
+}
+
+fn parse_entry(line: &str) -> Result<HistoryEntry, String> {
+    let json = Json::parse(line).map_err(|e| e.to_string())?;
+    let string = |key: &str| {
+        json.get(key)
+            .and_then(Json::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| format!("missing `{}`", key))
+    };
+    let parameters = match json.get("parameters") {
+        Some(Json::Object(fields)) => fields
+            .iter()
+            .map(|(name, value)| match value {
+                Json::String(value) => (name.clone(), value.clone()),
+                other => (name.clone(), other.to_string()),
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    let mut labels = Vec::new();
+    let mut clusters = Vec::new();
+    for cluster in json
+        .get("clusters")
+        .and_then(Json::as_array)
+        .ok_or("missing `clusters`")?
+    {
+        labels.push(
+            cluster
+                .get("label")
+                .and_then(Json::as_str)
+                .unwrap_or_default()
+                .to_string(),
+        );
+        clusters.push(
+            cluster
+                .get("members")
+                .and_then(Json::as_array)
+                .and_then(|members| {
+                    members
+                        .iter()
+                        .map(|member| member.as_str().map(str::to_string))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or("cluster members must be a list of names")?,
+        );
+    }
+    Ok(HistoryEntry {
+        created_at: string("created_at")?,
+        command: string("command")?,
+        parameters,
+        labels,
+        clusters,
+    })
+}
+
+impl HistoryEntry {
+    pub fn countries(&self) -> usize {
+        self.clusters.iter().map(Vec::len).sum()
+    }
+
+    pub fn cluster_of(&self, country: &str) -> Option<usize> {
+        self.clusters
+            .iter()
+            .position(|members| members.iter().any(|member| member == country))
+    }
+
+    // "algo=louvain min-weight=0.5", leaving out where the output went.
+    pub fn parameter_summary(&self) -> String {
+        self.parameters
+            .iter()
+            .filter(|(name, _)| !matches!(name.as_str(), "output" | "save" | "history"))
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// Countries clustered in both runs whose cluster-mates changed, by name.
+pub fn moved(previous: &HistoryEntry, next: &HistoryEntry) -> Vec<String> {
+    fn mates<'a>(
+        entry: &'a HistoryEntry,
+        other: &HistoryEntry,
+    ) -> BTreeMap<&'a str, BTreeSet<&'a str>> {
+        let mut mates = BTreeMap::new();
+        for members in &entry.clusters {
+            let shared: BTreeSet<&str> = members
+                .iter()
+                .filter(|member| other.cluster_of(member).is_some())
+                .map(String::as_str)
+                .collect();
+            for member in &shared {
+                mates.insert(*member, shared.clone());
+            }
+        }
+        mates
+    }
+    let before = mates(previous, next);
+    let after = mates(next, previous);
+    before
+        .iter()
+        .filter(|(country, mates)| after.get(*country) != Some(mates))
+        .map(|(country, _)| country.to_string())
+        .collect()
+}
+
+// One row per run: when, what, and how many countries moved since the run
+// before.
+pub fn runs_table(entries: &[HistoryEntry]) -> Table {
+    let mut table = Table::new(&[
+        "run",
+        "created_at",
+        "command",
+        "clusters",
+        "countries",
+        "moved",
+        "parameters",
+    ]);
+    for (index, entry) in entries.iter().enumerate() {
+        let moved = match index {
+            0 => "-".to_string(),
+            _ => moved(&entries[index - 1], entry).len().to_string(),
+        };
+        table.push_row(vec![
+            (index + 1).to_string(),
+            entry.created_at.clone(),
+            entry.command.clone(),
+            entry.clusters.len().to_string(),
+            entry.countries().to_string(),
+            moved,
+            entry.parameter_summary(),
+        ]);
+    }
+    table
+}
+
+// One country's cluster in every run, with the countries it shared it with.
+pub fn country_table(entries: &[HistoryEntry], country: &str) -> Table {
+    let mut table = Table::new(&["run", "created_at", "cluster", "with"]);
+    for (index, entry) in entries.iter().enumerate() {
+        let (cluster, with) = match entry.cluster_of(country) {
+            Some(cluster) => (
+                format!("{} ({})", cluster, entry.labels[cluster]),
+                entry.clusters[cluster]
+                    .iter()
+                    .filter(|member| *member != country)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            ),
+            None => ("-".to_string(), String::new()),
+        };
+        table.push_row(vec![
+            (index + 1).to_string(),
+            entry.created_at.clone(),
+            cluster,
+            with,
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(nodes: &[&str]) -> Graph {
+        Graph {
+            nodes: nodes.iter().map(|node| node.to_string()).collect(),
+            adjacency_matrix: vec![vec![1.0; nodes.len()]; nodes.len()],
+        }
+    }
+
+    #[test]
+    fn test_log_appends_and_tracks_moves() {
+        let path = std::env::temp_dir().join(format!("ds210-history-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+
+        let mut manifest = Manifest::new("run");
+        manifest.parameter("algo", "louvain");
+        manifest.parameter("output", "report.txt");
+        let first = graph(&["Chad", "Mali", "Niger", "Peru"]);
+        append_run(path, &manifest, &first, &[vec![0, 1, 2], vec![3]]).unwrap();
+        // Peru joins Chad; Niger is gone and Bolivia new, neither counted
+        let second = graph(&["Bolivia", "Chad", "Mali", "Peru"]);
+        append_run(path, &manifest, &second, &[vec![1, 3], vec![0, 2]]).unwrap();
+
+        let entries = read_history(path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].clusters[0], ["Chad", "Mali", "Niger"]);
+        assert_eq!(entries[1].cluster_of("Peru"), Some(0));
+        assert_eq!(entries[0].parameter_summary(), "algo=louvain");
+        assert_eq!(moved(&entries[0], &entries[1]), ["Chad", "Mali", "Peru"]);
+
+        let runs = runs_table(&entries);
+        assert_eq!(runs.rows[0][5], "-");
+        assert_eq!(runs.rows[1][5], "3");
+        let chad = country_table(&entries, "Chad");
+        assert_eq!(chad.rows[0][3], "Mali; Niger");
+        assert_eq!(chad.rows[1][3], "Peru");
+        assert_eq!(country_table(&entries, "Bolivia").rows[0][2], "-");
+
+        fs::write(path, "{\"command\": \"run\"}\n").unwrap();
+        let error = read_history(path).unwrap_err().to_string();
+        assert!(error.ends_with("line 1: missing `clusters`"), "{}", error);
+        let _ = fs::remove_file(path);
+    }
+}
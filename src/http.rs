@@ -0,0 +1,502 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::console::note;
+
+const MAX_REDIRECTS: usize = 5;
+const TIMEOUT: Duration = Duration::from_secs(30);
+// Backoff doubles per retry up to this, and a server's Retry-After is
+// honoured up to this too
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// A parsed `http://host[:port]/path` URL. HTTPS needs a TLS stack this tool
+// does not link, so it is rejected with a hint to download the file first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Url {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl Url {
+    pub fn parse(url: &str) -> io::Result<Url> {
+        let rest = match url.split_once("://") {
+            Some(("http", rest)) => rest,
+            Some(("https", _)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!(
+                        "{}: HTTPS is not supported; download the file and pass its path",
+                        url
+                    ),
+                ))
+            }
+            _ => return Err(invalid_input(format!("{} is not an http:// URL", url))),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| invalid_input(format!("invalid port in {}", url)))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid_input(format!("{} has no host", url)));
+        }
+        Ok(Url {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// POST a body to a URL; redirects are not followed and non-2xx responses
+// are errors.
+pub fn post(url: &str, content_type: &str, body: &[u8]) -> io::Result<Response> {
+    let response = request("POST", &Url::parse(url)?, Some((content_type, body)))?;
+    match response.status {
+        200..=299 => Ok(response),
+        status => Err(io::Error::other(format!(
+            "POST {} failed with HTTP {}",
+            url, status
+        ))),
+    }
+}
+
+// How a `Client` copes with flaky servers: up to `attempts` tries per
+// download, waiting `backoff` before the first retry and twice as long before
+// each one after.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Retry {
+    pub attempts: u32,
+    pub backoff: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Retry {
+        Retry {
+            attempts: 4,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+// Downloads for `fetch` and http:// inputs. Connection failures, truncated
+// bodies, 429 and 5xx responses are retried with exponential backoff (or
+// after the server's Retry-After); a retry after a truncated body asks for
+// the rest with a Range request and appends it, unless the server sends the
+// whole file again. With a rate, requests from every thread sharing the
+// client are spaced at least 1/rate seconds apart.
+#[derive(Debug, Default)]
+pub struct Client {
+    retry: Retry,
+    interval: Option<Duration>,
+    // When the next request may be sent
+    next_slot: Mutex<Option<Instant>>,
+}
+
+// What went wrong with one attempt, and whether another may do better.
+enum Failure {
+    Retry(io::Error, Option<Duration>),
+    Fatal(io::Error),
+}
+
+impl Client {
+    // `rate` is in requests per second; None or zero leaves them unlimited.
+    pub fn new(retry: Retry, rate: Option<f64>) -> Client {
+        Client {
+            retry,
+            interval: rate
+                .filter(|rate| *rate > 0.0 && rate.is_finite())
+                .map(|rate| Duration::from_secs_f64(1.0 / rate)),
+            next_slot: Mutex::new(None),
+        }
+    }
+
+    // The body of a GET, following redirects.
+    pub fn download(&self, url: &str) -> io::Result<Vec<u8>> {
+        let mut body = Vec::new();
+        let mut backoff = self.retry.backoff;
+        let mut attempt = 1;
+        loop {
+            self.wait_for_slot();
+            let (error, retry_after) = match self.attempt(url, &mut body) {
+                Ok(()) => return Ok(body),
+                Err(Failure::Retry(error, retry_after)) if attempt < self.retry.attempts => {
+                    (error, retry_after)
+                }
+                Err(Failure::Retry(error, _) | Failure::Fatal(error)) => return Err(error),
+            };
+            let wait = retry_after.unwrap_or(backoff).min(MAX_BACKOFF);
+            note!(
+                "Retrying {} in {:.1}s (attempt {} of {}{}): {}",
+                url,
+                wait.as_secs_f64(),
+                attempt + 1,
+                self.retry.attempts,
+                if body.is_empty() { "" } else { ", resuming" },
+                error
+            );
+            thread::sleep(wait);
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+            attempt += 1;
+        }
+    }
+
+    fn attempt(&self, url: &str, body: &mut Vec<u8>) -> Result<(), Failure> {
+        let mut url = url.to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            let parsed = Url::parse(&url).map_err(Failure::Fatal)?;
+            let range: Vec<(&str, String)> = if body.is_empty() {
+                Vec::new()
+            } else {
+                vec![("Range", format!("bytes={}-", body.len()))]
+            };
+            let (response, mut reader) =
+                send("GET", &parsed, &range, None).map_err(|error| Failure::Retry(error, None))?;
+            match response.status {
+                200..=299 => {
+                    // Anything but Partial Content is the whole file again
+                    if response.status != 206 {
+                        body.clear();
+                    }
+                    return read_body(&response, &mut reader, body)
+                        .map_err(|error| Failure::Retry(error, None));
+                }
+                // The range starts at the end: nothing was missing after all
+                416 if !body.is_empty() => return Ok(()),
+                301 | 302 | 303 | 307 | 308 => {
+                    let location = response.header("location").ok_or_else(|| {
+                        Failure::Fatal(invalid_data("redirect without Location".to_string()))
+                    })?;
+                    url = if location.starts_with('/') {
+                        format!("http://{}:{}{}", parsed.host, parsed.port, location)
+                    } else {
+                        location.to_string()
+                    };
+                }
+                status => {
+                    let error =
+                        io::Error::other(format!("GET {} failed with HTTP {}", url, status));
+                    return Err(if status == 429 || status >= 500 {
+                        let retry_after = response
+                            .header("retry-after")
+                            .and_then(|seconds| seconds.parse().ok())
+                            .map(Duration::from_secs);
+                        Failure::Retry(error, retry_after)
+                    } else {
+                        Failure::Fatal(error)
+                    });
+                }
+            }
+        }
+        Err(Failure::Fatal(io::Error::other(format!(
+            "too many redirects fetching {}",
+            url
+        ))))
+    }
+
+    // Take the next free request slot, sleeping until it comes round.
+    fn wait_for_slot(&self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        let slot = {
+            let mut next_slot = self
+                .next_slot
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let now = Instant::now();
+            let slot = next_slot.map_or(now, |next| next.max(now));
+            *next_slot = Some(slot + interval);
+            slot
+        };
+        thread::sleep(slot.saturating_duration_since(Instant::now()));
+    }
+}
+
+fn request(method: &str, url: &Url, body: Option<(&str, &[u8])>) -> io::Result<Response> {
+    let (mut response, mut reader) = send(method, url, &[], body)?;
+    let mut body = Vec::new();
+    read_body(&response, &mut reader, &mut body)?;
+    response.body = body;
+    Ok(response)
+}
+
+// Send a request and read the status line and headers, leaving the body
+// (if any) to be read from the returned reader.
+fn send(
+    method: &str,
+    url: &Url,
+    headers: &[(&str, String)],
+    body: Option<(&str, &[u8])>,
+) -> io::Result<(Response, BufReader<TcpStream>)> {
+    let mut stream = TcpStream::connect((url.host.as_str(), url.port))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}:{}\r\nUser-Agent: {}/{}\r\nAccept-Encoding: identity\r\nConnection: close\r\n",
+        method,
+        url.path,
+        url.host,
+        url.port,
+        crate::cli::BIN_NAME,
+        env!("CARGO_PKG_VERSION")
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if let Some((content_type, body)) = body {
+        request.push_str(&format!(
+            "Content-Type: {}\r\nContent-Length: {}\r\n",
+            content_type,
+            body.len()
+        ));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+    if let Some((_, body)) = body {
+        stream.write_all(body)?;
+    }
+    stream.flush()?;
+    let mut reader = BufReader::new(stream);
+    let response = read_head(&mut reader)?;
+    Ok((response, reader))
+}
+
+fn read_head(reader: &mut impl BufRead) -> io::Result<Response> {
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| invalid_data(format!("malformed status line {:?}", status_line.trim())))?;
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    Ok(Response {
+        status,
+        headers,
+        body: Vec::new(),
+    })
+}
+
+// Append the body of `response` to `body`. On an error, whatever arrived
+// before it is left in `body`, so a download can resume from there.
+fn read_body(response: &Response, reader: &mut impl BufRead, body: &mut Vec<u8>) -> io::Result<()> {
+    let chunked = response
+        .header("transfer-encoding")
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"));
+    let length = response
+        .header("content-length")
+        .and_then(|length| length.parse::<u64>().ok());
+    if chunked {
+        read_chunked(reader, body)
+    } else if let Some(length) = length {
+        let read = reader.take(length).read_to_end(body)?;
+        if (read as u64) < length {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("body ended after {} of {} bytes", read, length),
+            ));
+        }
+        Ok(())
+    } else {
+        reader.read_to_end(body).map(drop)
+    }
+}
+
+fn read_chunked(reader: &mut impl BufRead, body: &mut Vec<u8>) -> io::Result<()> {
+    loop {
+        let mut size_line = String::new();
+        reader.read_line(&mut size_line)?;
+        // Chunk extensions (`;name=value`) are allowed and ignored
+        let size = size_line.trim().split(';').next().unwrap_or("");
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| invalid_data(format!("bad chunk size {:?}", size_line.trim())))?;
+        if size == 0 {
+            return Ok(());
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        if let Err(error) = reader.read_exact(&mut body[start..]) {
+            body.truncate(start);
+            return Err(error);
+        }
+        let mut crlf = [0u8; 2];
+        reader.read_exact(&mut crlf)?;
+    }
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            Url::parse("http://example.org:8080/data/a.csv").unwrap(),
+            Url {
+                host: "example.org".to_string(),
+                port: 8080,
+                path: "/data/a.csv".to_string(),
+            }
+        );
+        assert_eq!(Url::parse("http://example.org").unwrap().path, "/");
+        assert_eq!(
+            Url::parse("https://example.org/a.csv").unwrap_err().kind(),
+            io::ErrorKind::Unsupported
+        );
+        assert!(Url::parse("ftp://example.org/a.csv").is_err());
+    }
+
+    #[test]
+    fn test_download_follows_redirect_and_decodes_chunks() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let responses = [
+                "HTTP/1.1 302 Found\r\nLocation: /data.csv\r\nContent-Length: 0\r\n\r\n".to_string(),
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\na,b\n\r\n4\r\n1,2\n\r\n0\r\n\r\n"
+                    .to_string(),
+            ];
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                // Read the whole request head before answering
+                let mut reader = BufReader::new(&stream);
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+                requests.push(request.trim_end().to_string());
+            }
+            requests
+        });
+
+        let body = Client::default()
+            .download(&format!("http://127.0.0.1:{}/old.csv", port))
+            .unwrap();
+        let requests = server.join().unwrap();
+        assert_eq!(
+            requests,
+            ["GET /old.csv HTTP/1.1", "GET /data.csv HTTP/1.1"]
+        );
+        assert_eq!(body, b"a,b\n1,2\n");
+    }
+
+    // Serve each canned response to one connection, returning the requests'
+    // head lines (request line and headers)
+    fn serve(responses: Vec<Vec<u8>>) -> (u16, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut head = String::new();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    head.push_str(&line);
+                    line.clear();
+                }
+                stream.write_all(&response).unwrap();
+                requests.push(head);
+            }
+            requests
+        });
+        (port, server)
+    }
+
+    #[test]
+    fn test_client_retries_and_resumes() {
+        let (port, server) = serve(vec![
+            b"HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\n\r\n"
+                .to_vec(),
+            // Promises eight bytes but hangs up after four
+            b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\na,b\n".to_vec(),
+            b"HTTP/1.1 206 Partial Content\r\nContent-Length: 4\r\n\r\n1,2\n".to_vec(),
+        ]);
+        let retry = Retry {
+            attempts: 3,
+            backoff: Duration::from_millis(1),
+        };
+        let client = Client::new(retry, Some(1000.0));
+        let body = client
+            .download(&format!("http://127.0.0.1:{}/data.csv", port))
+            .unwrap();
+        assert_eq!(body, b"a,b\n1,2\n");
+        let requests = server.join().unwrap();
+        assert!(!requests[1].contains("Range"));
+        assert!(
+            requests[2].contains("Range: bytes=4-\r\n"),
+            "{}",
+            requests[2]
+        );
+
+        // Client errors are not retried, and attempts run out
+        let (port, server) = serve(vec![
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec(),
+            b"HTTP/1.1 500 Oops\r\nContent-Length: 0\r\n\r\n".to_vec(),
+            b"HTTP/1.1 500 Oops\r\nContent-Length: 0\r\n\r\n".to_vec(),
+            b"HTTP/1.1 500 Oops\r\nContent-Length: 0\r\n\r\n".to_vec(),
+        ]);
+        let url = format!("http://127.0.0.1:{}/data.csv", port);
+        assert!(client.download(&url).is_err());
+        let error = client.download(&url).unwrap_err();
+        assert!(error.to_string().contains("HTTP 500"), "{}", error);
+        assert_eq!(server.join().unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::str::FromStr;
+
+use crate::stats;
+use crate::EducationData;
+
+pub const IMPUTATIONS: &[&str] = &["drop", "mean", "median", "forward-fill", "zero"];
+
+// What preprocessing does with observations that have no value. Left
+// alone, the value graph counts them as zero and the similarity graphs
+// compare countries on the series both report.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Imputation {
+    // Leave the records out
+    Drop,
+    // The mean, or the median, of the series over every country and year
+    Mean,
+    Median,
+    // The latest earlier value of the same country and series; with none
+    // before it, the value stays missing
+    ForwardFill,
+    Zero,
+}
+
+impl FromStr for Imputation {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Imputation> {
+        match value {
+            "drop" => Ok(Imputation::Drop),
+            "mean" => Ok(Imputation::Mean),
+            "median" => Ok(Imputation::Median),
+            "forward-fill" => Ok(Imputation::ForwardFill),
+            "zero" => Ok(Imputation::Zero),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown imputation `{}`; expected one of {}",
+                    other,
+                    IMPUTATIONS.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+impl Imputation {
+    // Fill (or drop) the missing values; returns how many records changed.
+    pub fn apply(&self, data: &mut Vec<EducationData>) -> usize {
+        let missing = data.iter().filter(|record| record.value.is_none()).count();
+        match self {
+            Imputation::Drop => {
+                data.retain(|record| record.value.is_some());
+                missing
+            }
+            Imputation::Zero => fill(data, |_| Some(0.0)),
+            Imputation::Mean | Imputation::Median => {
+                let mut values: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+                for record in data.iter() {
+                    if let Some(value) = record.value {
+                        values.entry(record.series.clone()).or_default().push(value);
+                    }
+                }
+                let centres: BTreeMap<String, f64> = values
+                    .into_iter()
+                    .filter_map(|(series, mut values)| {
+                        let centre = if *self == Imputation::Mean {
+                            stats::mean(&values)
+                        } else {
+                            values.sort_by(f64::total_cmp);
+                            stats::quantile(&values, 0.5)
+                        };
+                        Some((series, centre?))
+                    })
+                    .collect();
+                fill(data, |record| centres.get(&record.series).copied())
+            }
+            Imputation::ForwardFill => {
+                let mut histories: BTreeMap<(&str, &str), Vec<usize>> = BTreeMap::new();
+                for (index, record) in data.iter().enumerate() {
+                    histories
+                        .entry((&record.country_or_area, &record.series))
+                        .or_default()
+                        .push(index);
+                }
+                let mut filled = Vec::new();
+                for mut indices in histories.into_values() {
+                    indices.sort_by_key(|&index| data[index].year);
+                    let mut last = None;
+                    for index in indices {
+                        match data[index].value {
+                            Some(value) => last = Some((data[index].year, value)),
+                            None => {
+                                // Only from a strictly earlier year
+                                if let Some((year, value)) = last {
+                                    if year < data[index].year {
+                                        filled.push((index, value));
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                for &(index, value) in &filled {
+                    data[index].value = Some(value);
+                }
+                filled.len()
+            }
+        }
+    }
+}
+
+// Give each missing value `value(record)`, where there is one.
+fn fill(data: &mut [EducationData], value: impl Fn(&EducationData) -> Option<f64>) -> usize {
+    let mut filled = 0;
+    for record in data.iter_mut() {
+        if record.value.is_none() {
+            record.value = value(record);
+            filled += usize::from(record.value.is_some());
+        }
+    }
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    fn observations() -> Vec<EducationData> {
+        vec![
+            record("Chad", "primary", 2012, None),
+            record("Chad", "primary", 2010, Some(10.0)),
+            record("Chad", "primary", 2015, None),
+            record("Mali", "primary", 2010, Some(20.0)),
+            record("Mali", "primary", 2015, Some(60.0)),
+            record("Peru", "primary", 2010, None),
+        ]
+    }
+
+    #[test]
+    fn test_strategies_fill_missing_values() {
+        let values = |imputation: &str| {
+            let mut data = observations();
+            let imputation: Imputation = imputation.parse().unwrap();
+            let changed = imputation.apply(&mut data);
+            let values: Vec<Option<f64>> = data.iter().map(|record| record.value).collect();
+            (changed, values)
+        };
+        let (dropped, kept) = values("drop");
+        assert_eq!(dropped, 3);
+        assert_eq!(kept, [Some(10.0), Some(20.0), Some(60.0)]);
+        assert_eq!(values("zero").1[0], Some(0.0));
+        assert_eq!(values("mean").1[5], Some(30.0));
+        assert_eq!(values("median").1[2], Some(20.0));
+        // Chad carries 2010 forward; Peru has nothing earlier to carry
+        let (filled, carried) = values("forward-fill");
+        assert_eq!(filled, 2);
+        assert_eq!(carried[0], Some(10.0));
+        assert_eq!(carried[2], Some(10.0));
+        assert_eq!(carried[5], None);
+        assert!("interpolate".parse::<Imputation>().is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+
+use crate::stats;
+use crate::table::Table;
+use crate::EducationData;
+
+// Gini slopes smaller than this (per year) count as flat
+const FLAT_SLOPE: f64 = 1e-4;
+
+// Dispersion of one series across countries in one year.
+pub struct YearInequality {
+    pub series: String,
+    pub year: u32,
+    pub countries: usize,
+    pub mean: f64,
+    // Coefficient of variation: population standard deviation over the mean
+    pub cv: Option<f64>,
+    pub gini: Option<f64>,
+}
+
+// Least-squares trend of a series' inequality over the years.
+pub struct Trend {
+    pub series: String,
+    pub first_year: u32,
+    pub last_year: u32,
+    pub gini_first: Option<f64>,
+    pub gini_last: Option<f64>,
+    pub gini_slope: Option<f64>,
+    pub cv_slope: Option<f64>,
+}
+
+impl Trend {
+    pub fn direction(&self) -> &'static str {
+        match self.gini_slope {
+            Some(slope) if slope > FLAT_SLOPE => "rising",
+            Some(slope) if slope < -FLAT_SLOPE => "falling",
+            Some(_) => "flat",
+            None => "-",
+        }
+    }
+}
+
+// Gini coefficient of non-negative values: 0 when all are equal, approaching
+// 1 when one country holds everything. Undefined for fewer than two values,
+// negative values or an all-zero total.
+pub fn gini(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 || values.iter().any(|&value| value < 0.0) {
+        return None;
+    }
+    let total: f64 = values.iter().sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let n = sorted.len() as f64;
+    let weighted: f64 = sorted
+        .iter()
+        .enumerate()
+        .map(|(index, value)| (index + 1) as f64 * value)
+        .sum();
+    Some(2.0 * weighted / (n * total) - (n + 1.0) / n)
+}
+
+pub fn coefficient_of_variation(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+    let mean = stats::mean(values)?;
+    if mean == 0.0 {
+        return None;
+    }
+    Some(stats::std_dev(values)? / mean.abs())
+}
+
+// Inequality for every (series, year), sorted by series then year. Missing
+// values are skipped; a country with several records in a year counts once,
+// with its last value.
+pub fn by_year(data: &[EducationData]) -> Vec<YearInequality> {
+    let mut groups: BTreeMap<(&str, u32), BTreeMap<&str, f64>> = BTreeMap::new();
+    for record in data {
+        if let Some(value) = record.value {
+            groups
+                .entry((record.series.as_str(), record.year))
+                .or_default()
+                .insert(record.country_or_area.as_str(), value);
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|((series, year), countries)| {
+            let values: Vec<f64> = countries.into_values().collect();
+            YearInequality {
+                series: series.to_string(),
+                year,
+                countries: values.len(),
+                mean: values.iter().sum::<f64>() / values.len() as f64,
+                cv: coefficient_of_variation(&values),
+                gini: gini(&values),
+            }
+        })
+        .collect()
+}
+
+// One trend per series from the output of `by_year`.
+pub fn trends(years: &[YearInequality]) -> Vec<Trend> {
+    let mut by_series: BTreeMap<&str, Vec<&YearInequality>> = BTreeMap::new();
+    for year in years {
+        by_series
+            .entry(year.series.as_str())
+            .or_default()
+            .push(year);
+    }
+
+    by_series
+        .into_iter()
+        .map(|(series, years)| {
+            let points = |metric: fn(&YearInequality) -> Option<f64>| -> Vec<(f64, f64)> {
+                years
+                    .iter()
+                    .filter_map(|year| Some((f64::from(year.year), metric(year)?)))
+                    .collect()
+            };
+            let gini_points = points(|year| year.gini);
+            Trend {
+                series: series.to_string(),
+                first_year: years[0].year,
+                last_year: years[years.len() - 1].year,
+                gini_first: gini_points.first().map(|&(_, gini)| gini),
+                gini_last: gini_points.last().map(|&(_, gini)| gini),
+                gini_slope: slope(&gini_points),
+                cv_slope: slope(&points(|year| year.cv)),
+            }
+        })
+        .collect()
+}
+
+fn slope(points: &[(f64, f64)]) -> Option<f64> {
+    stats::linear_fit(points).map(|fit| fit.slope)
+}
+
+fn format_metric(metric: Option<f64>) -> String {
+    metric.map_or_else(|| "-".to_string(), |metric| format!("{:.4}", metric))
+}
+
+pub fn by_year_table(years: &[YearInequality]) -> Table {
+    let mut table = Table::new(&["series", "year", "countries", "mean", "cv", "gini"]);
+    for year in years {
+        table.push_row(vec![
+            year.series.clone(),
+            year.year.to_string(),
+            year.countries.to_string(),
+            format!("{:.4}", year.mean),
+            format_metric(year.cv),
+            format_metric(year.gini),
+        ]);
+    }
+    table
+}
+
+pub fn trend_table(trends: &[Trend]) -> Table {
+    let mut table = Table::new(&[
+        "series",
+        "years",
+        "gini_first",
+        "gini_last",
+        "gini_slope",
+        "cv_slope",
+        "inequality",
+    ]);
+    for trend in trends {
+        table.push_row(vec![
+            trend.series.clone(),
+            format!("{}-{}", trend.first_year, trend.last_year),
+            format_metric(trend.gini_first),
+            format_metric(trend.gini_last),
+            trend
+                .gini_slope
+                .map_or_else(|| "-".to_string(), |slope| format!("{:+.6}", slope)),
+            trend
+                .cv_slope
+                .map_or_else(|| "-".to_string(), |slope| format!("{:+.6}", slope)),
+            trend.direction().to_string(),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    const FEMALE: &str = "Gross enrollment ratio - Primary (female)";
+
+    #[test]
+    fn test_gini_and_cv() {
+        assert_eq!(gini(&[5.0, 5.0, 5.0]), Some(0.0));
+        // One of four holding everything: (n - 1) / n
+        assert!((gini(&[0.0, 0.0, 0.0, 8.0]).unwrap() - 0.75).abs() < 1e-12);
+        assert_eq!(gini(&[1.0, -1.0]), None);
+        assert_eq!(gini(&[3.0]), None);
+        assert!((coefficient_of_variation(&[2.0, 4.0]).unwrap() - 1.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_trend_direction() {
+        let data = vec![
+            record("Chad", FEMALE, 2005, 20.0),
+            record("Mali", FEMALE, 2005, 80.0),
+            record("Chad", FEMALE, 2010, 40.0),
+            record("Mali", FEMALE, 2010, 60.0),
+            record("Chad", FEMALE, 2015, 50.0),
+            record("Mali", FEMALE, 2015, 50.0),
+        ];
+        let years = by_year(&data);
+        assert_eq!(years.len(), 3);
+        assert_eq!(years[2].gini, Some(0.0));
+
+        let trends = trends(&years);
+        assert_eq!(trends[0].direction(), "falling");
+        assert_eq!((trends[0].first_year, trends[0].last_year), (2005, 2015));
+        assert_eq!(trends[0].gini_last, Some(0.0));
+    }
+}
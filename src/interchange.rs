@@ -0,0 +1,207 @@
+use std::fmt::Write as _;
+
+use crate::chart::xml_escape;
+use crate::notebook::PALETTE;
+use crate::Graph;
+
+// The graph in the interchange formats of visualization tools: GraphViz DOT
+// for `dot`/`neato`, and GEXF for Gephi. Every non-zero weight becomes an
+// edge, self-loops included; a symmetric matrix is written as an undirected
+// graph with one edge per pair. Given a clustering, nodes are coloured by
+// cluster as in the HTML report and, in GEXF, carry their cluster index.
+
+impl Graph {
+    pub fn to_dot(&self, clusters: Option<&[Vec<usize>]>) -> String {
+        let colours = node_colours(self.nodes.len(), clusters);
+        let directed = !self.is_symmetric();
+        let heaviest = self
+            .edges(directed)
+            .map(|(_, _, weight)| weight)
+            .fold(0.0, f64::max);
+
+        let mut dot = String::new();
+        let (keyword, arrow) = if directed {
+            ("digraph", "->")
+        } else {
+            ("graph", "--")
+        };
+        let _ = writeln!(dot, "{} countries {{", keyword);
+        let _ = writeln!(dot, "  node [style=filled, fillcolor=\"#cccccc\"];");
+        for (node, name) in self.nodes.iter().enumerate() {
+            let _ = match colours[node] {
+                Some((cluster, colour)) => writeln!(
+                    dot,
+                    "  {} [fillcolor=\"{}\", cluster={}];",
+                    dot_id(name),
+                    colour,
+                    cluster
+                ),
+                None => writeln!(dot, "  {};", dot_id(name)),
+            };
+        }
+        // `weight` must be an integer for `dot`, so the weight is the label
+        // and sets the pen width instead
+        for (a, b, weight) in self.edges(directed) {
+            let _ = writeln!(
+                dot,
+                "  {} {} {} [label=\"{}\", penwidth={:.2}];",
+                dot_id(&self.nodes[a]),
+                arrow,
+                dot_id(&self.nodes[b]),
+                weight,
+                0.5 + 2.5 * weight / heaviest
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_gexf(&self, clusters: Option<&[Vec<usize>]>) -> String {
+        let colours = node_colours(self.nodes.len(), clusters);
+        let directed = !self.is_symmetric();
+
+        let mut gexf = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <gexf xmlns=\"http://www.gexf.net/1.2draft\" \
+             xmlns:viz=\"http://www.gexf.net/1.2draft/viz\" version=\"1.2\">\n",
+        );
+        let _ = writeln!(
+            gexf,
+            "  <graph mode=\"static\" defaultedgetype=\"{}\">",
+            if directed { "directed" } else { "undirected" }
+        );
+        if clusters.is_some() {
+            gexf.push_str(
+                "    <attributes class=\"node\">\n      \
+                 <attribute id=\"cluster\" title=\"cluster\" type=\"integer\"/>\n    \
+                 </attributes>\n",
+            );
+        }
+        gexf.push_str("    <nodes>\n");
+        for (node, name) in self.nodes.iter().enumerate() {
+            match colours[node] {
+                Some((cluster, colour)) => {
+                    let (r, g, b) = rgb(colour);
+                    let _ = writeln!(
+                        gexf,
+                        "      <node id=\"{}\" label=\"{}\">\n        \
+                         <attvalues><attvalue for=\"cluster\" value=\"{}\"/></attvalues>\n        \
+                         <viz:color r=\"{}\" g=\"{}\" b=\"{}\"/>\n      </node>",
+                        node,
+                        xml_escape(name),
+                        cluster,
+                        r,
+                        g,
+                        b
+                    );
+                }
+                None => {
+                    let _ = writeln!(
+                        gexf,
+                        "      <node id=\"{}\" label=\"{}\"/>",
+                        node,
+                        xml_escape(name)
+                    );
+                }
+            }
+        }
+        gexf.push_str("    </nodes>\n    <edges>\n");
+        for (id, (a, b, weight)) in self.edges(directed).enumerate() {
+            let _ = writeln!(
+                gexf,
+                "      <edge id=\"{}\" source=\"{}\" target=\"{}\" weight=\"{}\"/>",
+                id, a, b, weight
+            );
+        }
+        gexf.push_str("    </edges>\n  </graph>\n</gexf>\n");
+        gexf
+    }
+
+    fn is_symmetric(&self) -> bool {
+        let matrix = &self.adjacency_matrix;
+        (0..matrix.len()).all(|i| (0..i).all(|j| matrix[i][j] == matrix[j][i]))
+    }
+
+    // Non-zero weights as (from, to, weight); only the upper triangle when
+    // the graph is undirected.
+    fn edges(&self, directed: bool) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        self.adjacency_matrix
+            .iter()
+            .enumerate()
+            .flat_map(move |(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .skip(if directed { 0 } else { i })
+                    .filter(|(_, &weight)| weight != 0.0)
+                    .map(move |(j, &weight)| (i, j, weight))
+            })
+    }
+}
+
+// Each node's cluster and colour, or None outside the clustering.
+fn node_colours(
+    node_count: usize,
+    clusters: Option<&[Vec<usize>]>,
+) -> Vec<Option<(usize, &'static str)>> {
+    let mut colours = vec![None; node_count];
+    for (index, cluster) in clusters.unwrap_or_default().iter().enumerate() {
+        for &node in cluster {
+            colours[node] = Some((index, PALETTE[index % PALETTE.len()]));
+        }
+    }
+    colours
+}
+
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// "#1f77b4" as (31, 119, 180)
+fn rgb(colour: &str) -> (u8, u8, u8) {
+    let channel = |at: usize| u8::from_str_radix(&colour[at..at + 2], 16).unwrap_or(0);
+    (channel(1), channel(3), channel(5))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dot_and_gexf() {
+        let graph = Graph {
+            nodes: vec![
+                "Chad".to_string(),
+                "Côte \"d'Ivoire\"".to_string(),
+                "Mali".to_string(),
+            ],
+            adjacency_matrix: vec![
+                vec![0.0, 2.0, 0.0],
+                vec![2.0, 0.0, 1.5],
+                vec![0.0, 1.5, 0.0],
+            ],
+        };
+        let clusters = vec![vec![0, 1]];
+        let dot = graph.to_dot(Some(&clusters));
+        assert!(dot.starts_with("graph countries {\n"), "{}", dot);
+        assert!(dot.contains("  \"Chad\" [fillcolor=\"#1f77b4\", cluster=0];\n"));
+        assert!(dot.contains("  \"Mali\";\n"));
+        assert!(dot.contains("\"Chad\" -- \"Côte \\\"d'Ivoire\\\"\" [label=\"2\", penwidth=3.00];"));
+        assert_eq!(dot.matches(" -- ").count(), 2);
+
+        let gexf = graph.to_gexf(Some(&clusters));
+        assert!(gexf.contains("defaultedgetype=\"undirected\""));
+        assert!(gexf.contains("label=\"Côte &quot;d'Ivoire&quot;\""));
+        assert!(gexf.contains("<viz:color r=\"31\" g=\"119\" b=\"180\"/>"));
+        assert!(gexf.contains("<edge id=\"1\" source=\"1\" target=\"2\" weight=\"1.5\"/>"));
+
+        // One direction only: a directed graph, and no cluster attribute
+        let directed = Graph {
+            nodes: vec!["Chad".to_string(), "Mali".to_string()],
+            adjacency_matrix: vec![vec![1.0, 3.0], vec![0.0, 0.0]],
+        };
+        let dot = directed.to_dot(None);
+        assert!(dot.starts_with("digraph countries {\n"));
+        assert!(dot.contains("\"Chad\" -> \"Chad\""));
+        assert!(!directed.to_gexf(None).contains("<attributes"));
+    }
+}
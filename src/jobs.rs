@@ -0,0 +1,264 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+use crate::engine::{country_count, AnalysisResult, Engine, Run};
+use crate::filter::Filter;
+use crate::http::Url;
+use crate::json::Json;
+use crate::notify::Notifier;
+use crate::registry;
+
+// Analyses submitted with `POST /jobs` and run by a pool of worker threads,
+// so a slow clustering never holds up other requests. Clients poll
+// `GET /jobs/{id}` until the job is done or failed, or have the same JSON
+// POSTed to a webhook when it finishes.
+
+// Finished jobs kept for polling; the oldest are forgotten past this.
+const MAX_FINISHED: usize = 1000;
+
+#[derive(Clone, Debug)]
+pub struct JobSpec {
+    pub path: String,
+    pub filter: Option<String>,
+    // Also keep the outcome in the registry under this name
+    pub name: Option<String>,
+    // Webhook for this job, in place of the queue's default
+    pub notify: Option<String>,
+}
+
+impl JobSpec {
+    // Catch bad input at submission rather than in a worker.
+    pub fn validate(&self) -> io::Result<()> {
+        if let Some(filter) = &self.filter {
+            Filter::parse(filter)?;
+        }
+        if let Some(name) = &self.name {
+            registry::validate_name(name)?;
+        }
+        if let Some(url) = &self.notify {
+            Url::parse(url)?;
+        }
+        Ok(())
+    }
+}
+
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done(JobOutput),
+    Failed(String),
+}
+
+pub struct JobOutput {
+    pub observations: usize,
+    pub countries: usize,
+    pub nodes: usize,
+    pub result: AnalysisResult,
+}
+
+struct Job {
+    spec: JobSpec,
+    status: JobStatus,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: BTreeMap<u64, Job>,
+    pending: VecDeque<u64>,
+    next_id: u64,
+}
+
+#[derive(Default)]
+pub struct JobQueue {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    // Webhook for jobs that do not name their own
+    default_webhook: Option<String>,
+}
+
+impl JobQueue {
+    pub fn with_webhook(url: Option<String>) -> JobQueue {
+        JobQueue {
+            default_webhook: url,
+            ..JobQueue::default()
+        }
+    }
+
+    pub fn submit(&self, spec: JobSpec) -> io::Result<u64> {
+        spec.validate()?;
+        let mut queue = self.lock();
+        queue.next_id += 1;
+        let id = queue.next_id;
+        queue.jobs.insert(
+            id,
+            Job {
+                spec,
+                status: JobStatus::Queued,
+            },
+        );
+        queue.pending.push_back(id);
+        self.ready.notify_one();
+        Ok(id)
+    }
+
+    // GET /jobs/{id}: the job's state, with its outcome once it has one.
+    pub fn to_json(&self, id: u64) -> io::Result<Json> {
+        let queue = self.lock();
+        let job = queue.jobs.get(&id).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no job with id {}", id))
+        })?;
+        let json = Json::object()
+            .with("job_id", id)
+            .with("path", job.spec.path.as_str())
+            .with("name", job.spec.name.as_deref());
+        Ok(match &job.status {
+            JobStatus::Queued => json.with("state", "queued"),
+            JobStatus::Running => json.with("state", "running"),
+            JobStatus::Failed(error) => json.with("state", "failed").with("error", error.as_str()),
+            JobStatus::Done(output) => json
+                .with("state", "done")
+                .with("observations", output.observations)
+                .with("countries", output.countries)
+                .with("nodes", output.nodes)
+                .with("clusters", output.result.clusters_json()),
+        })
+    }
+
+    // Start `count` workers taking jobs off the queue for the life of the
+    // process.
+    pub fn start_workers(self: &Arc<Self>, count: usize, engine: &Arc<Mutex<Engine>>) {
+        for _ in 0..count {
+            let (jobs, engine) = (Arc::clone(self), Arc::clone(engine));
+            thread::spawn(move || loop {
+                let (id, spec) = jobs.next();
+                let outcome = execute(&spec, &engine);
+                jobs.finish(id, outcome);
+                let webhook = spec.notify.as_ref().or(jobs.default_webhook.as_ref());
+                if let (Some(url), Ok(body)) = (webhook, jobs.to_json(id)) {
+                    Notifier::default().post(url, &(body.to_pretty_string() + "\n"));
+                }
+            });
+        }
+    }
+
+    // Block until a job is pending, and mark it running.
+    fn next(&self) -> (u64, JobSpec) {
+        let mut queue = self.lock();
+        loop {
+            if let Some(id) = queue.pending.pop_front() {
+                let job = queue.jobs.get_mut(&id).expect("pending jobs are tracked");
+                job.status = JobStatus::Running;
+                return (id, job.spec.clone());
+            }
+            queue = self
+                .ready
+                .wait(queue)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    fn finish(&self, id: u64, outcome: io::Result<JobOutput>) {
+        let mut queue = self.lock();
+        if let Some(job) = queue.jobs.get_mut(&id) {
+            job.status = match outcome {
+                Ok(output) => JobStatus::Done(output),
+                Err(error) => JobStatus::Failed(error.to_string()),
+            };
+        }
+        let finished: Vec<u64> = queue
+            .jobs
+            .iter()
+            .filter(|(_, job)| matches!(job.status, JobStatus::Done(_) | JobStatus::Failed(_)))
+            .map(|(&id, _)| id)
+            .collect();
+        for id in finished
+            .iter()
+            .take(finished.len().saturating_sub(MAX_FINISHED))
+        {
+            queue.jobs.remove(id);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// The pipeline runs without the engine's lock, which is taken only to
+// record timings and keep a named outcome.
+fn execute(spec: &JobSpec, engine: &Mutex<Engine>) -> io::Result<JobOutput> {
+    let run = Run::execute(&spec.path, spec.filter.as_deref())?;
+    let output = JobOutput {
+        observations: run.data.len(),
+        countries: country_count(&run.data),
+        nodes: run.graph.nodes.len(),
+        result: run.result.clone(),
+    };
+    let mut engine = engine
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    engine.record_run(&run);
+    if let Some(name) = &spec.name {
+        engine
+            .registry
+            .insert(name, run.data, run.graph, run.result)?;
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn state(jobs: &JobQueue, id: u64) -> String {
+        let json = jobs.to_json(id).unwrap().to_pretty_string();
+        let start = json.find("\"state\": \"").unwrap() + 10;
+        json[start..start + json[start..].find('"').unwrap()].to_string()
+    }
+
+    #[test]
+    fn test_jobs_run_in_the_background() {
+        let path = std::env::temp_dir().join(format!("ds210-jobs-{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "country,year,indicator,series,value\nChad,2015,T07,primary,10\nMali,2015,T07,primary,20\n",
+        )
+        .unwrap();
+        let engine = Arc::new(Mutex::new(Engine::default()));
+        let jobs = Arc::new(JobQueue::default());
+        let spec = |path: &str, name: Option<&str>| JobSpec {
+            path: path.to_string(),
+            filter: None,
+            name: name.map(str::to_string),
+            notify: None,
+        };
+        let good = jobs
+            .submit(spec(path.to_str().unwrap(), Some("unesco")))
+            .unwrap();
+        let bad = jobs.submit(spec("/nonexistent.csv", None)).unwrap();
+        assert_eq!(state(&jobs, good), "queued");
+        assert!(jobs.submit(spec("x.csv", Some("a/b"))).is_err());
+
+        jobs.start_workers(2, &engine);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while ["queued", "running"].contains(&state(&jobs, good).as_str())
+            || ["queued", "running"].contains(&state(&jobs, bad).as_str())
+        {
+            assert!(Instant::now() < deadline, "jobs did not finish");
+            thread::sleep(Duration::from_millis(5));
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(state(&jobs, good), "done");
+        assert_eq!(state(&jobs, bad), "failed");
+        assert!(engine.lock().unwrap().registry.get("unesco").is_ok());
+        assert_eq!(
+            jobs.to_json(99).err().map(|error| error.kind()),
+            Some(io::ErrorKind::NotFound)
+        );
+    }
+}
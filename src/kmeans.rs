@@ -0,0 +1,431 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::str::FromStr;
+
+use crate::cancel::RunStatus;
+use crate::features::FeatureMatrix;
+use crate::observer::{Control, Iteration, IterationObserver};
+use crate::parallel::Parallelism;
+use crate::random::Rng;
+use crate::EducationData;
+
+// K-means over countries' feature vectors, apart from the graph clustering
+// of `cluster`: every (series, year) pair a country reports is a feature, so
+// two countries are close when their values agree year by year.
+
+pub const SEEDINGS: &[&str] = &["kmeans++", "random"];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Seeding {
+    // Each further centroid is drawn in proportion to the squared distance
+    // to the nearest one already chosen (Arthur and Vassilvitskii)
+    #[default]
+    PlusPlus,
+    // k distinct countries, uniformly
+    Random,
+}
+
+impl FromStr for Seeding {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Seeding> {
+        match value {
+            "kmeans++" => Ok(Seeding::PlusPlus),
+            "random" => Ok(Seeding::Random),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown seeding `{}`; expected one of {}",
+                    other,
+                    SEEDINGS.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Options {
+    pub k: usize,
+    pub max_iterations: usize,
+    pub seeding: Seeding,
+    pub seed: u64,
+    // Each pass finds the rows' nearest centroids, and k-means++ their
+    // distances to those chosen, a chunk of rows per thread
+    pub parallelism: Parallelism,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            k: 3,
+            max_iterations: 100,
+            seeding: Seeding::default(),
+            seed: 0,
+            parallelism: Parallelism::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KMeans {
+    // The cluster of each row of the feature matrix
+    pub assignments: Vec<usize>,
+    // One row per cluster, in the feature matrix's columns
+    pub centroids: Vec<Vec<f64>>,
+    // Sum of squared distances of the rows to their centroids
+    pub inertia: f64,
+    pub iterations: usize,
+    // Whether the passes ran out or the observer stopped them early
+    pub status: RunStatus,
+}
+
+// Countries x (series, year) values, with columns named "<series> <year>".
+// Cells a country does not report are filled with the column's mean over
+// the countries that do, so they pull no country either way.
+pub fn country_features(data: &[EducationData]) -> FeatureMatrix {
+    let mut cells: BTreeMap<(&str, (&str, u32)), f64> = BTreeMap::new();
+    for record in data {
+        if let Some(value) = record.value {
+            cells.insert(
+                (
+                    record.country_or_area.as_str(),
+                    (record.series.as_str(), record.year),
+                ),
+                value,
+            );
+        }
+    }
+    let countries: Vec<&str> = cells
+        .keys()
+        .map(|&(country, _)| country)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let columns: Vec<(&str, u32)> = cells
+        .keys()
+        .map(|&(_, column)| column)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let column_index: BTreeMap<(&str, u32), usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(index, &column)| (column, index))
+        .collect();
+    let country_index: BTreeMap<&str, usize> = countries
+        .iter()
+        .enumerate()
+        .map(|(index, &country)| (country, index))
+        .collect();
+
+    let mut values = vec![vec![f64::NAN; columns.len()]; countries.len()];
+    let mut totals = vec![(0.0, 0usize); columns.len()];
+    for (&(country, column), &value) in &cells {
+        let column = column_index[&column];
+        values[country_index[country]][column] = value;
+        totals[column].0 += value;
+        totals[column].1 += 1;
+    }
+    for row in &mut values {
+        for (cell, &(total, count)) in row.iter_mut().zip(&totals) {
+            if cell.is_nan() {
+                *cell = total / count as f64;
+            }
+        }
+    }
+    FeatureMatrix {
+        countries: countries
+            .iter()
+            .map(|country| country.to_string())
+            .collect(),
+        series: columns
+            .iter()
+            .map(|(series, year)| format!("{} {}", series, year))
+            .collect(),
+        values,
+    }
+}
+
+// Lloyd's algorithm from the chosen seeding, until no row changes cluster
+// or `max_iterations` passes. `k` is capped at the number of rows; a cluster
+// left empty takes the row farthest from its centroid. A warm start's
+// clusters give the first centroids instead, as their members' means, and
+// then `k` is their number; rows they leave out join the nearest centroid
+// on the first pass. The observer hears the inertia after every pass and
+// may stop there, leaving the assignments of that pass.
+pub fn kmeans(
+    rows: &[Vec<f64>],
+    options: Options,
+    initial: Option<&[Vec<usize>]>,
+    observer: &mut dyn IterationObserver,
+) -> KMeans {
+    let initial: Vec<&Vec<usize>> = initial
+        .unwrap_or_default()
+        .iter()
+        .filter(|cluster| !cluster.is_empty())
+        .collect();
+    let mut assignments = vec![usize::MAX; rows.len()];
+    let (k, mut centroids) = if initial.is_empty() {
+        let k = options.k.min(rows.len());
+        let mut rng = Rng::new(options.seed);
+        let centroids = match options.seeding {
+            Seeding::Random => {
+                let mut order: Vec<usize> = (0..rows.len()).collect();
+                rng.shuffle(&mut order);
+                order[..k].iter().map(|&row| rows[row].clone()).collect()
+            }
+            Seeding::PlusPlus => plus_plus(rows, k, &mut rng, options.parallelism),
+        };
+        (k, centroids)
+    } else {
+        for (cluster, members) in initial.iter().enumerate() {
+            for &row in members.iter() {
+                assignments[row] = cluster;
+            }
+        }
+        let width = rows.first().map_or(0, Vec::len);
+        let centroids = initial
+            .iter()
+            .map(|members| {
+                let mut centroid = vec![0.0; width];
+                for &row in members.iter() {
+                    for (total, value) in centroid.iter_mut().zip(&rows[row]) {
+                        *total += value;
+                    }
+                }
+                centroid
+                    .into_iter()
+                    .map(|total| total / members.len() as f64)
+                    .collect()
+            })
+            .collect();
+        (initial.len(), centroids)
+    };
+    let mut iterations = 0;
+    let mut status = RunStatus::Completed;
+    while iterations < options.max_iterations && k > 0 {
+        iterations += 1;
+        let mut changed = false;
+        let nearest = options
+            .parallelism
+            .map_rows(rows.len(), |row| nearest(&rows[row], &centroids).0);
+        for (assignment, nearest) in assignments.iter_mut().zip(nearest) {
+            if *assignment != nearest {
+                *assignment = nearest;
+                changed = true;
+            }
+        }
+        if changed {
+            centroids = means(rows, &assignments, &centroids);
+            for cluster in 0..k {
+                if assignments.contains(&cluster) {
+                    continue;
+                }
+                let farthest = (0..rows.len())
+                    .max_by(|&a, &b| {
+                        let distance =
+                            |row: usize| squared_distance(&rows[row], &centroids[assignments[row]]);
+                        distance(a).total_cmp(&distance(b))
+                    })
+                    .expect("k is at most the number of rows");
+                centroids[cluster] = rows[farthest].clone();
+                assignments[farthest] = cluster;
+            }
+        }
+        let control = observer.on_iteration(&Iteration {
+            stage: "kmeans",
+            iteration: iterations,
+            total: Some(options.max_iterations),
+            objective: inertia(rows, &assignments, &centroids),
+        });
+        if !changed {
+            break;
+        }
+        if let Control::Stop(reason) = control {
+            if iterations < options.max_iterations {
+                status = reason;
+            }
+            break;
+        }
+    }
+    KMeans {
+        inertia: inertia(rows, &assignments, &centroids),
+        assignments,
+        centroids,
+        iterations,
+        status,
+    }
+}
+
+fn inertia(rows: &[Vec<f64>], assignments: &[usize], centroids: &[Vec<f64>]) -> f64 {
+    rows.iter()
+        .zip(assignments)
+        .map(|(row, &cluster)| squared_distance(row, &centroids[cluster]))
+        .sum()
+}
+
+fn plus_plus(
+    rows: &[Vec<f64>],
+    k: usize,
+    rng: &mut Rng,
+    parallelism: Parallelism,
+) -> Vec<Vec<f64>> {
+    let mut centroids = Vec::with_capacity(k);
+    if k == 0 {
+        return centroids;
+    }
+    centroids.push(rows[rng.below(rows.len())].clone());
+    while centroids.len() < k {
+        let weights = parallelism.map_rows(rows.len(), |row| nearest(&rows[row], &centroids).1);
+        let total: f64 = weights.iter().sum();
+        let mut target = rng.next_f64() * total;
+        let chosen = if total > 0.0 {
+            weights
+                .iter()
+                .position(|&weight| {
+                    target -= weight;
+                    target < 0.0
+                })
+                .unwrap_or(rows.len() - 1)
+        } else {
+            // Every row sits on a centroid already: any other will do
+            centroids.len()
+        };
+        centroids.push(rows[chosen].clone());
+    }
+    centroids
+}
+
+// The nearest centroid to `row` and its squared distance.
+fn nearest(row: &[f64], centroids: &[Vec<f64>]) -> (usize, f64) {
+    centroids
+        .iter()
+        .map(|centroid| squared_distance(row, centroid))
+        .enumerate()
+        .fold((0, f64::INFINITY), |best, (index, distance)| {
+            if distance < best.1 {
+                (index, distance)
+            } else {
+                best
+            }
+        })
+}
+
+// Each cluster's mean; an empty cluster keeps its previous centroid.
+fn means(rows: &[Vec<f64>], assignments: &[usize], previous: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let mut sums = vec![vec![0.0; previous.first().map_or(0, Vec::len)]; previous.len()];
+    let mut counts = vec![0usize; previous.len()];
+    for (row, &cluster) in rows.iter().zip(assignments) {
+        for (total, value) in sums[cluster].iter_mut().zip(row) {
+            *total += value;
+        }
+        counts[cluster] += 1;
+    }
+    sums.into_iter()
+        .zip(&counts)
+        .zip(previous)
+        .map(|((total, &count), previous)| {
+            if count == 0 {
+                previous.clone()
+            } else {
+                total.iter().map(|value| value / count as f64).collect()
+            }
+        })
+        .collect()
+}
+
+pub fn squared_distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+    use crate::observer::{ObjectiveTrace, Unobserved};
+
+    #[test]
+    fn test_country_features() {
+        let data = vec![
+            record("Chad", "primary", 2010, 1.0),
+            record("Chad", "primary", 2015, 3.0),
+            record("Mali", "primary", 2015, 5.0),
+        ];
+        let features = country_features(&data);
+        assert_eq!(features.countries, ["Chad", "Mali"]);
+        assert_eq!(features.series, ["primary 2010", "primary 2015"]);
+        // Mali's missing 2010 takes the column mean
+        assert_eq!(features.values, [[1.0, 3.0], [1.0, 5.0]]);
+    }
+
+    #[test]
+    fn test_kmeans_separates_groups() {
+        let rows = vec![
+            vec![0.0, 0.0],
+            vec![0.0, 1.0],
+            vec![10.0, 10.0],
+            vec![10.0, 11.0],
+            vec![1.0, 0.0],
+        ];
+        for seeding in [Seeding::PlusPlus, Seeding::Random] {
+            let options = Options {
+                k: 2,
+                seeding,
+                seed: 7,
+                ..Options::default()
+            };
+            let result = kmeans(&rows, options, None, &mut Unobserved);
+            // Rows two to a chunk over three threads: the same clusters
+            let threaded = Options {
+                parallelism: Parallelism {
+                    threads: 3,
+                    chunk_size: 2,
+                },
+                ..options
+            };
+            assert_eq!(kmeans(&rows, threaded, None, &mut Unobserved), result);
+            let a = result.assignments[0];
+            assert_eq!(result.assignments[1], a);
+            assert_eq!(result.assignments[4], a);
+            assert_ne!(result.assignments[2], a);
+            assert_eq!(result.assignments[3], result.assignments[2]);
+            let centroid = &result.centroids[a];
+            assert!((centroid[0] - 1.0 / 3.0).abs() < 1e-12);
+            assert!((result.inertia - (2.0 / 3.0 + 2.0 / 3.0 + 0.5)).abs() < 1e-9);
+        }
+
+        // More clusters than rows: one each
+        let options = Options {
+            k: 9,
+            ..Options::default()
+        };
+        let result = kmeans(&rows[..2], options, None, &mut Unobserved);
+        assert_eq!(result.centroids.len(), 2);
+        assert_ne!(result.assignments[0], result.assignments[1]);
+        assert!("forgy".parse::<Seeding>().is_err());
+    }
+
+    #[test]
+    fn test_warm_start_and_inertia_trace() {
+        let rows = vec![vec![0.0], vec![1.0], vec![9.0], vec![10.0], vec![2.0]];
+        // The last row is left out of the warm start and joins the nearest
+        let initial = [vec![0, 1, 2], vec![3]];
+        let mut trace = ObjectiveTrace::new();
+        let result = kmeans(&rows, Options::default(), Some(&initial), &mut trace);
+        assert_eq!(result.assignments, [0, 0, 1, 1, 0]);
+        assert_eq!(result.centroids, [vec![1.0], vec![9.5]]);
+        assert_eq!(result.status, RunStatus::Completed);
+        // 9 moves on the first pass; the second moves nothing
+        let objective = trace.to_json();
+        let passes = objective
+            .get("kmeans")
+            .and_then(|stage| stage.get("objective"));
+        assert_eq!(passes.unwrap().to_string(), "[2.5, 2.5]");
+
+        let mut cancel = crate::cancel::CancelToken::new().with_timeout(std::time::Duration::ZERO);
+        let stopped = kmeans(&rows, Options::default(), Some(&initial), &mut cancel);
+        assert_eq!(stopped.iterations, 1);
+        assert_eq!(stopped.status, RunStatus::TimedOut);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::matrix::{self, MatrixBackend};
+use crate::Graph;
+
+// Name each cluster after its medoid: the member with the largest total edge
+// weight to the rest of its cluster, ties going to the alphabetically first
+// name. Unlike an index this survives reruns and reads well in diffs.
+pub fn cluster_labels(graph: &Graph, clusters: &[Vec<usize>]) -> Vec<String> {
+    let matrix = matrix::storage(&graph.adjacency_matrix);
+    clusters
+        .iter()
+        .map(|cluster| match medoid(&matrix, &graph.nodes, cluster) {
+            Some(node_index) => graph.nodes[node_index].clone(),
+            None => "empty".to_string(),
+        })
+        .collect()
+}
+
+pub fn medoid(matrix: &impl MatrixBackend, nodes: &[String], cluster: &[usize]) -> Option<usize> {
+    let affinity = |node_index: usize| -> f64 {
+        cluster
+            .iter()
+            .filter(|&&other| other != node_index)
+            .map(|&other| matrix.weight(node_index, other))
+            .sum()
+    };
+
+    cluster.iter().copied().reduce(|best, candidate| {
+        let (best_affinity, candidate_affinity) = (affinity(best), affinity(candidate));
+        if candidate_affinity > best_affinity
+            || (candidate_affinity == best_affinity && nodes[candidate] < nodes[best])
+        {
+            candidate
+        } else {
+            best
+        }
+    })
+}
+
+// Reorder `clusters` so each one takes the index of the previous cluster it
+// shares the most members with. Pairs are matched greedily by overlap; a
+// previous cluster with no successor stays as an empty slot, so indices keep
+// meaning the same thing, and genuinely new clusters are appended.
+pub fn align_to_previous(previous: &[Vec<usize>], clusters: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+    let mut pairs = Vec::new();
+    for (previous_index, previous_cluster) in previous.iter().enumerate() {
+        for (index, cluster) in clusters.iter().enumerate() {
+            let overlap = cluster
+                .iter()
+                .filter(|node_index| previous_cluster.contains(node_index))
+                .count();
+            if overlap > 0 {
+                pairs.push((overlap, previous_index, index));
+            }
+        }
+    }
+    // Largest overlap first; ties in index order keep the result deterministic
+    pairs.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+    let mut slots: Vec<Option<usize>> = vec![None; previous.len()];
+    let mut placed = vec![false; clusters.len()];
+    for (_, previous_index, index) in pairs {
+        if slots[previous_index].is_none() && !placed[index] {
+            slots[previous_index] = Some(index);
+            placed[index] = true;
+        }
+    }
+
+    let mut clusters: Vec<Option<Vec<usize>>> = clusters.into_iter().map(Some).collect();
+    let mut aligned: Vec<Vec<usize>> = slots
+        .into_iter()
+        .map(|slot| {
+            slot.and_then(|index| clusters[index].take())
+                .unwrap_or_default()
+        })
+        .collect();
+    aligned.extend(clusters.into_iter().flatten());
+    aligned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> Graph {
+        Graph {
+            nodes: vec![
+                "Chad".to_string(),
+                "Mali".to_string(),
+                "Niger".to_string(),
+                "Peru".to_string(),
+            ],
+            adjacency_matrix: vec![
+                vec![0.0, 1.0, 1.0, 0.0],
+                vec![1.0, 0.0, 2.0, 0.0],
+                vec![1.0, 2.0, 0.0, 0.0],
+                vec![0.0, 0.0, 0.0, 0.0],
+            ],
+        }
+    }
+
+    #[test]
+    fn test_labels_use_medoid() {
+        let graph = graph();
+        // Mali and Niger tie on affinity (3.0); Mali sorts first
+        let labels = cluster_labels(&graph, &[vec![0, 2, 1], vec![3], vec![]]);
+        assert_eq!(labels, vec!["Mali", "Peru", "empty"]);
+    }
+
+    #[test]
+    fn test_align_to_previous() {
+        let previous = vec![vec![3], vec![0, 1, 2]];
+        let clusters = vec![vec![0, 1], vec![2], vec![3, 4]];
+
+        // [0, 1] continues cluster 1 and [3, 4] cluster 0; [2] is new
+        assert_eq!(
+            align_to_previous(&previous, clusters),
+            vec![vec![3, 4], vec![0, 1], vec![2]]
+        );
+
+        // A previous cluster that disappeared leaves an empty slot
+        assert_eq!(
+            align_to_previous(&previous, vec![vec![0]]),
+            vec![vec![], vec![0]]
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+
+use crate::stats;
+use crate::table::Table;
+use crate::{EducationData, Graph};
+
+// A country's values of one series by year.
+pub type History = BTreeMap<u32, f64>;
+
+// Lagged correlation between countries' histories of one series: A leads B
+// by k years when A's value in year t correlates with B's in year t + k.
+pub struct LeadLag {
+    pub max_lag: u32,
+    // Fewest (t, t + k) pairs a correlation is computed from
+    pub min_points: usize,
+    pub min_correlation: f64,
+}
+
+impl Default for LeadLag {
+    fn default() -> Self {
+        LeadLag {
+            max_lag: 5,
+            min_points: 3,
+            min_correlation: 0.8,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LeadEdge {
+    pub leader: usize,
+    pub follower: usize,
+    pub lag: u32,
+    pub correlation: f64,
+    pub points: usize,
+}
+
+// Country names (sorted) and each one's values by year; a repeated year
+// keeps its last value and missing values are skipped.
+pub fn histories(data: &[EducationData]) -> (Vec<String>, Vec<History>) {
+    let mut by_country: BTreeMap<&str, History> = BTreeMap::new();
+    for record in data {
+        if let Some(value) = record.value {
+            by_country
+                .entry(record.country_or_area.as_str())
+                .or_default()
+                .insert(record.year, value);
+        }
+    }
+    by_country
+        .into_iter()
+        .map(|(country, history)| (country.to_string(), history))
+        .unzip()
+}
+
+// Correlation of `leader` at t with `follower` at t + lag, over the years
+// both report, with the number of pairs used.
+pub fn lagged_correlation(leader: &History, follower: &History, lag: u32) -> Option<(f64, usize)> {
+    let pairs: Vec<(f64, f64)> = leader
+        .iter()
+        .filter_map(|(&year, &value)| Some((value, *follower.get(&(year + lag))?)))
+        .collect();
+    Some((stats::pearson(&pairs)?, pairs.len()))
+}
+
+impl LeadLag {
+    // The best lag for A -> B, if any reaches the thresholds.
+    fn best(&self, leader: &History, follower: &History) -> Option<(u32, f64, usize)> {
+        (1..=self.max_lag)
+            .filter_map(|lag| {
+                let (correlation, points) = lagged_correlation(leader, follower, lag)?;
+                (points >= self.min_points).then_some((lag, correlation, points))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+            .filter(|&(_, correlation, _)| correlation >= self.min_correlation)
+    }
+
+    // One directed edge per pair at most, pointing from the country whose
+    // lagged correlation is stronger; strongest edges first.
+    pub fn edges(&self, histories: &[History]) -> Vec<LeadEdge> {
+        let mut edges = Vec::new();
+        for a in 0..histories.len() {
+            for b in a + 1..histories.len() {
+                let forward = self.best(&histories[a], &histories[b]);
+                let backward = self.best(&histories[b], &histories[a]);
+                let edge = match (forward, backward) {
+                    (Some(f), Some(r)) if r.1 > f.1 => Some((b, a, r)),
+                    (Some(f), _) => Some((a, b, f)),
+                    (None, Some(r)) => Some((b, a, r)),
+                    (None, None) => None,
+                };
+                if let Some((leader, follower, (lag, correlation, points))) = edge {
+                    edges.push(LeadEdge {
+                        leader,
+                        follower,
+                        lag,
+                        correlation,
+                        points,
+                    });
+                }
+            }
+        }
+        edges.sort_by(|x, y| {
+            y.correlation
+                .total_cmp(&x.correlation)
+                .then((x.leader, x.follower).cmp(&(y.leader, y.follower)))
+        });
+        edges
+    }
+}
+
+// A directed graph: `adjacency_matrix[leader][follower]` holds the lagged
+// correlation, so the matrix is generally not symmetric.
+pub fn directed_graph(nodes: Vec<String>, edges: &[LeadEdge]) -> Graph {
+    let mut adjacency_matrix = vec![vec![0.0; nodes.len()]; nodes.len()];
+    for edge in edges {
+        adjacency_matrix[edge.leader][edge.follower] = edge.correlation;
+    }
+    Graph {
+        nodes,
+        adjacency_matrix,
+    }
+}
+
+pub fn edge_table(nodes: &[String], edges: &[LeadEdge]) -> Table {
+    let mut table = Table::new(&["leader", "follower", "lag", "correlation", "points"]);
+    for edge in edges {
+        table.push_row(vec![
+            nodes[edge.leader].clone(),
+            nodes[edge.follower].clone(),
+            edge.lag.to_string(),
+            format!("{:.4}", edge.correlation),
+            edge.points.to_string(),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(points: &[(u32, f64)]) -> History {
+        points.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_leader_precedes_follower() {
+        // Togo repeats Chad's path two years later; Mali is unrelated noise
+        let chad = history(&[
+            (2000, 10.0),
+            (2001, 30.0),
+            (2002, 20.0),
+            (2003, 50.0),
+            (2004, 40.0),
+        ]);
+        let togo = history(&[
+            (2002, 11.0),
+            (2003, 31.0),
+            (2004, 21.0),
+            (2005, 51.0),
+            (2006, 41.0),
+        ]);
+        let mali = history(&[
+            (2000, 5.0),
+            (2001, 5.0),
+            (2002, 9.0),
+            (2003, 1.0),
+            (2004, 5.0),
+        ]);
+        assert_eq!(
+            lagged_correlation(&chad, &togo, 2).map(|(_, points)| points),
+            Some(5)
+        );
+
+        let edges = LeadLag::default().edges(&[chad, mali, togo]);
+        assert_eq!(edges.len(), 1);
+        assert_eq!(
+            (edges[0].leader, edges[0].follower, edges[0].lag),
+            (0, 2, 2)
+        );
+        assert!((edges[0].correlation - 1.0).abs() < 1e-9);
+
+        let graph = directed_graph(vec!["Chad".into(), "Mali".into(), "Togo".into()], &edges);
+        assert!(graph.adjacency_matrix[0][2] > 0.99);
+        assert_eq!(graph.adjacency_matrix[2][0], 0.0);
+    }
+}
@@ -0,0 +1,111 @@
+// The education-graph pipeline as a library: load the UNESCO table
+// (`data`), build the country graph (`graph`) and cluster it (`cluster`).
+// The `ds210` binary is a thin wrapper over `cli` and `commands`; other
+// programs can depend on this crate and call the same stages directly.
+//
+//     let input = ds210::source::open_location("education.csv")?;
+//     let data = ds210::load_and_preprocess_data(input.as_ref(), &Default::default())?;
+//     let graph = ds210::construct_graph(&data);
+//     let clusters = ds210::cluster_graph(&graph, None);
+
+mod anonymize;
+mod artifact;
+mod auth;
+mod binning;
+pub mod cancel;
+pub mod catalog;
+pub mod centrality;
+mod changepoint;
+mod chart;
+pub mod cli;
+pub mod cluster;
+pub mod commands;
+mod completeness;
+mod completions;
+mod composite;
+mod config;
+pub mod console;
+mod convergence;
+mod country_export;
+pub mod csv;
+pub mod data;
+mod datadiff;
+mod dendrogram;
+mod dump;
+pub mod eigen;
+mod engine;
+mod features;
+mod fetch;
+mod filter;
+mod granger;
+pub mod graph;
+mod graphcompare;
+mod hash;
+mod history;
+mod http;
+mod impute;
+mod inequality;
+mod interchange;
+mod jobs;
+pub mod json;
+pub mod kmeans;
+mod labels;
+mod leadlag;
+pub mod louvain;
+mod manifest;
+mod mat;
+pub mod matrix;
+pub mod metrics;
+mod movers;
+mod msgpack;
+mod names;
+pub mod notebook;
+mod notify;
+mod npy;
+pub mod observer;
+mod ordering;
+pub mod parallel;
+mod paths;
+mod pipeline;
+mod pivot;
+mod preset;
+mod profile;
+pub mod quality;
+mod random;
+mod rank;
+mod reference;
+mod registry;
+mod rundir;
+mod sample;
+mod server;
+pub mod similar;
+mod similarity_cache;
+pub mod source;
+mod stability;
+mod stats;
+mod store;
+mod sweep;
+mod symmetry;
+mod table;
+mod temporal;
+mod trajectory;
+pub mod transform;
+mod trend;
+mod unpivot;
+mod weights;
+
+pub use cluster::{cluster_graph, print_clusters, Clustering};
+pub use data::{
+    load_and_preprocess_data, load_checked, DataError, EducationData, EducationDataReader, Loaded,
+    ParseMode,
+};
+pub use filter::{parse_years, DataFilter};
+pub use graph::{
+    construct_graph, construct_similarity_graph, construct_sparse_similarity_graph,
+    construct_value_graph, construct_value_graph_from_records, construct_value_graph_with, Graph,
+    SimilarityMetric, SparseGraph, WeightedGraph,
+};
+pub use observer::{
+    Control, Iteration, IterationObserver, ObjectiveTrace, ProgressPrinter, Unobserved,
+};
+pub use parallel::Parallelism;
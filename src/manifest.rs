@@ -0,0 +1,363 @@
+use std::fs;
+use std::io;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::cancel::RunStatus;
+use crate::hash;
+use crate::json::Json;
+use crate::observer::ObjectiveTrace;
+use crate::source::DataSource;
+
+// Reproducibility record written next to every file a command produces:
+// which inputs (by content hash) and parameters went in, which build of the
+// tool ran, the seed, and how long each stage took.
+//
+// Written manifests also carry the output's own hash, a version that goes
+// up whenever a run changes what is at the output path, and the lineage:
+// every step that produced the inputs, oldest first, ending with this one.
+// An input contributes its steps when its own manifest records the same
+// hash, so the chain from any output leads back to exact raw inputs; inputs
+// without a matching manifest are marked untraced.
+pub struct Manifest {
+    command: String,
+    started: Instant,
+    created_at: String,
+    inputs: Vec<Json>,
+    // Steps behind the traced inputs, deduplicated
+    upstream: Vec<Json>,
+    parameters: Vec<(String, Json)>,
+    // Null unless a randomized stage ran
+    seed: Option<u64>,
+    timings: Vec<(String, f64)>,
+    // Per-iteration objective values of iterative stages
+    convergence: Json,
+    status: RunStatus,
+}
+
+impl Manifest {
+    pub fn new(command: &str) -> Manifest {
+        Manifest {
+            command: command.to_string(),
+            started: Instant::now(),
+            created_at: utc_timestamp(SystemTime::now()),
+            inputs: Vec::new(),
+            upstream: Vec::new(),
+            parameters: Vec::new(),
+            seed: None,
+            timings: Vec::new(),
+            convergence: Json::object(),
+            status: RunStatus::Completed,
+        }
+    }
+
+    // Record an input file together with its SHA-256 and size.
+    pub fn input(&mut self, path: &str) -> io::Result<()> {
+        let (sha256, bytes) = hash::sha256_file(path)?;
+        let steps = upstream_steps(path, &sha256);
+        self.inputs.push(
+            Json::object()
+                .with("path", path)
+                .with("sha256", sha256.as_str())
+                .with("bytes", bytes)
+                .with("traced", steps.is_some()),
+        );
+        for step in steps.unwrap_or_default() {
+            if !self.upstream.contains(&step) {
+                self.upstream.push(step);
+            }
+        }
+        Ok(())
+    }
+
+    // Record a data source: local files are hashed, remote ones are recorded
+    // by location only.
+    pub fn input_source(&mut self, source: &dyn DataSource) -> io::Result<()> {
+        match source.local_path() {
+            Some(path) => self.input(path),
+            None => {
+                self.inputs.push(
+                    Json::object()
+                        .with("path", source.location())
+                        .with("sha256", Json::Null)
+                        .with("bytes", Json::Null)
+                        .with("traced", false),
+                );
+                Ok(())
+            }
+        }
+    }
+
+    pub fn parameter(&mut self, name: &str, value: impl Into<Json>) {
+        self.parameters.push((name.to_string(), value.into()));
+    }
+
+    // Run one pipeline stage and record its wall-clock duration.
+    pub fn time<T>(&mut self, stage: &str, run: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = run();
+        self.timings
+            .push((stage.to_string(), start.elapsed().as_secs_f64() * 1000.0));
+        result
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    // Record that the run stopped early, so partial outputs are recognizable.
+    pub fn set_status(&mut self, status: RunStatus) {
+        self.status = status;
+    }
+
+    pub fn set_convergence(&mut self, trace: &ObjectiveTrace) {
+        self.convergence = trace.to_json();
+    }
+
+    pub fn to_json(&self, outputs: &[&str]) -> Json {
+        let mut timings: Vec<(String, Json)> = self
+            .timings
+            .iter()
+            .map(|(stage, millis)| (stage.clone(), Json::Number(round_millis(*millis))))
+            .collect();
+        timings.push((
+            "total".to_string(),
+            Json::Number(round_millis(self.started.elapsed().as_secs_f64() * 1000.0)),
+        ));
+
+        Json::object()
+            .with("tool", env!("CARGO_PKG_NAME"))
+            .with("version", env!("CARGO_PKG_VERSION"))
+            .with("command", self.command.as_str())
+            .with("created_at", self.created_at.as_str())
+            .with("status", self.status.as_str())
+            .with("inputs", Json::Array(self.inputs.clone()))
+            .with("parameters", Json::Object(self.parameters.clone()))
+            .with("seed", self.seed)
+            .with("timings_ms", Json::Object(timings))
+            .with("convergence", self.convergence.clone())
+            .with("outputs", outputs.to_vec())
+    }
+
+    // Write `<output>.manifest.json` next to the given output file,
+    // returning the manifest as written.
+    pub fn write_for(&self, output_path: &str) -> io::Result<Json> {
+        let sha256 = hash::sha256_file(output_path)
+            .ok()
+            .map(|(sha256, _)| sha256);
+        let previous = read_manifest(output_path);
+        let previous_version = previous
+            .as_ref()
+            .and_then(|manifest| manifest.get("output")?.get("version")?.as_f64())
+            .map_or(0, |version| version as u64);
+        let unchanged =
+            sha256.is_some() && sha256.as_deref() == previous.as_ref().and_then(output_sha256);
+        let version = if unchanged {
+            previous_version
+        } else {
+            previous_version + 1
+        };
+
+        let output = Json::object()
+            .with("path", output_path)
+            .with("sha256", sha256)
+            .with("version", version);
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| {
+                Json::object()
+                    .with("path", input.get("path").cloned().unwrap_or(Json::Null))
+                    .with("sha256", input.get("sha256").cloned().unwrap_or(Json::Null))
+            })
+            .collect();
+        let step = Json::object()
+            .with("command", self.command.as_str())
+            .with("created_at", self.created_at.as_str())
+            .with("inputs", Json::Array(inputs))
+            .with("parameters", Json::Object(self.parameters.clone()))
+            .with("output", output.clone());
+        let mut lineage = self.upstream.clone();
+        lineage.push(step);
+
+        let manifest = self
+            .to_json(&[output_path])
+            .with("output", output)
+            .with("lineage", Json::Array(lineage));
+        let mut text = manifest.to_pretty_string();
+        text.push('\n');
+        fs::write(manifest_path(output_path), text)?;
+        Ok(manifest)
+    }
+}
+
+pub fn manifest_path(output_path: &str) -> String {
+    format!("{}.manifest.json", output_path)
+}
+
+// The manifest last written for `path`, if there is a readable one.
+fn read_manifest(path: &str) -> Option<Json> {
+    Json::parse(&fs::read_to_string(manifest_path(path)).ok()?).ok()
+}
+
+fn output_sha256(manifest: &Json) -> Option<&str> {
+    manifest.get("output")?.get("sha256")?.as_str()
+}
+
+// The lineage of an input, when its manifest describes this exact content.
+fn upstream_steps(path: &str, sha256: &str) -> Option<Vec<Json>> {
+    let manifest = read_manifest(path)?;
+    if output_sha256(&manifest) != Some(sha256) {
+        return None;
+    }
+    Some(manifest.get("lineage")?.as_array()?.to_vec())
+}
+
+// The convergence traces recorded with `path`, when its manifest describes
+// this exact content.
+pub fn recorded_convergence(path: &str) -> Option<Json> {
+    let (sha256, _) = hash::sha256_file(path).ok()?;
+    let manifest = read_manifest(path)?;
+    if output_sha256(&manifest) != Some(sha256.as_str()) {
+        return None;
+    }
+    manifest.get("convergence").cloned()
+}
+
+fn round_millis(millis: f64) -> f64 {
+    (millis * 1000.0).round() / 1000.0
+}
+
+// Format a time as an RFC 3339 UTC timestamp (second precision).
+pub fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let remainder = seconds % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        remainder / 3600,
+        remainder % 3600 / 60,
+        remainder % 60
+    )
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian (year, month, day), after
+// Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_utc_timestamp() {
+        assert_eq!(utc_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+
+        // 2024-02-29 (a leap day) 13:45:30 UTC
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_214_330);
+        assert_eq!(utc_timestamp(time), "2024-02-29T13:45:30Z");
+    }
+
+    #[test]
+    fn test_manifest_records_inputs_and_parameters() {
+        let input = std::env::temp_dir().join(format!("ds210-{}-manifest.csv", std::process::id()));
+        fs::write(&input, "abc").unwrap();
+        let input = input.to_string_lossy().into_owned();
+
+        let mut manifest = Manifest::new("build");
+        manifest.input(&input).unwrap();
+        manifest.parameter("threshold", 0.5);
+        let built = manifest.time("build", || 42);
+        let json = manifest.to_json(&["graph.bin"]).to_string();
+        fs::remove_file(&input).unwrap();
+
+        assert_eq!(built, 42);
+        assert!(json.contains(
+            "\"sha256\": \"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\""
+        ));
+        assert!(json.contains("\"bytes\": 3"));
+        assert!(json.contains("\"parameters\": {\"threshold\": 0.5}"));
+        assert!(json.contains("\"status\": \"completed\""));
+        assert!(json.contains("\"seed\": null"));
+        assert!(json.contains("\"timings_ms\": {\"build\": "));
+        assert!(json.contains("\"convergence\": {}"));
+        assert!(json.contains("\"outputs\": [\"graph.bin\"]"));
+        assert!(json.contains("\"traced\": false"));
+    }
+
+    #[test]
+    fn test_lineage_and_versions_chain_through_manifests() {
+        let dir = std::env::temp_dir().join(format!("ds210-lineage-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        let step_commands = |manifest: &Json| -> Vec<String> {
+            let lineage = manifest.get("lineage").and_then(Json::as_array).unwrap();
+            lineage
+                .iter()
+                .map(|step| {
+                    step.get("command")
+                        .and_then(Json::as_str)
+                        .unwrap()
+                        .to_string()
+                })
+                .collect()
+        };
+        let version = |manifest: &Json| manifest.get("output")?.get("version")?.as_f64();
+        fs::write(path("raw.csv"), "a").unwrap();
+
+        fs::write(path("data.bin"), "data").unwrap();
+        let mut load = Manifest::new("load");
+        load.input(&path("raw.csv")).unwrap();
+        let loaded = load.write_for(&path("data.bin")).unwrap();
+        assert_eq!(version(&loaded), Some(1.0));
+
+        fs::write(path("graph.bin"), "graph").unwrap();
+        let mut build = Manifest::new("build");
+        build.input(&path("data.bin")).unwrap();
+        let built = build.write_for(&path("graph.bin")).unwrap();
+        assert_eq!(step_commands(&built), ["load", "build"]);
+        assert!(built.to_string().contains("\"traced\": true"));
+
+        // Rewriting identical content keeps the version; new content bumps it
+        assert_eq!(
+            version(&load.write_for(&path("data.bin")).unwrap()),
+            Some(1.0)
+        );
+        fs::write(path("data.bin"), "data v2").unwrap();
+        assert_eq!(
+            version(&load.write_for(&path("data.bin")).unwrap()),
+            Some(2.0)
+        );
+
+        // A stale manifest no longer vouches for the file it sits beside
+        fs::write(path("data.bin"), "edited by hand").unwrap();
+        let mut rebuild = Manifest::new("build");
+        rebuild.input(&path("data.bin")).unwrap();
+        let rebuilt = rebuild.write_for(&path("graph.bin")).unwrap();
+        assert_eq!(step_commands(&rebuilt), ["build"]);
+        assert_eq!(version(&rebuilt), Some(1.0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
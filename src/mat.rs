@@ -0,0 +1,162 @@
+use std::io::{self, Write};
+
+// Level 5 MAT-files (MATLAB 5.0 and later, also read by Octave and
+// scipy.io.loadmat): a 128-byte header followed by one uncompressed
+// miMATRIX element per variable.
+
+const MI_INT8: u32 = 1;
+const MI_UINT16: u32 = 4;
+const MI_INT32: u32 = 5;
+const MI_UINT32: u32 = 6;
+const MI_DOUBLE: u32 = 9;
+const MI_MATRIX: u32 = 14;
+
+const MX_CELL_CLASS: u32 = 1;
+const MX_CHAR_CLASS: u32 = 4;
+const MX_DOUBLE_CLASS: u32 = 6;
+
+pub enum Variable<'a> {
+    // Row-major rows of a real double matrix
+    Matrix(&'a [Vec<f64>]),
+    // A column cell array of strings
+    Strings(&'a [String]),
+}
+
+pub fn write_mat(writer: &mut dyn Write, variables: &[(&str, Variable)]) -> io::Result<()> {
+    let mut header = format!(
+        "MATLAB 5.0 MAT-file, Platform: {}, Created by: ds210",
+        std::env::consts::OS
+    )
+    .into_bytes();
+    header.resize(116, b' ');
+    // No subsystem data, version 0x0100, and the "IM" little-endian marker
+    header.extend_from_slice(&[0; 8]);
+    header.extend_from_slice(&0x0100u16.to_le_bytes());
+    header.extend_from_slice(b"IM");
+    writer.write_all(&header)?;
+
+    for (name, variable) in variables {
+        let element = match variable {
+            Variable::Matrix(rows) => double_matrix(name, rows),
+            Variable::Strings(values) => string_cells(name, values),
+        };
+        writer.write_all(&element)?;
+    }
+    Ok(())
+}
+
+fn double_matrix(name: &str, rows: &[Vec<f64>]) -> Vec<u8> {
+    let columns = rows.first().map_or(0, Vec::len);
+    // MATLAB stores matrices column by column
+    let mut data = Vec::with_capacity(8 * rows.len() * columns);
+    for column in 0..columns {
+        for row in rows {
+            data.extend_from_slice(&row[column].to_le_bytes());
+        }
+    }
+    matrix(
+        name,
+        MX_DOUBLE_CLASS,
+        [rows.len(), columns],
+        &[(MI_DOUBLE, data)],
+    )
+}
+
+fn string_cells(name: &str, values: &[String]) -> Vec<u8> {
+    let cells: Vec<u8> = values
+        .iter()
+        .flat_map(|value| {
+            let units: Vec<u16> = value.encode_utf16().collect();
+            let data: Vec<u8> = units.iter().flat_map(|unit| unit.to_le_bytes()).collect();
+            matrix("", MX_CHAR_CLASS, [1, units.len()], &[(MI_UINT16, data)])
+        })
+        .collect();
+    // Cells are nested miMATRIX elements, already tagged
+    let mut element = matrix(name, MX_CELL_CLASS, [values.len(), 1], &[]);
+    element.extend_from_slice(&cells);
+    let size = (element.len() - 8) as u32;
+    element[4..8].copy_from_slice(&size.to_le_bytes());
+    element
+}
+
+// An miMATRIX element: flags, dimensions, name, then the given data parts.
+fn matrix(name: &str, class: u32, dimensions: [usize; 2], parts: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut body = Vec::new();
+    let mut flags = Vec::new();
+    flags.extend_from_slice(&class.to_le_bytes());
+    flags.extend_from_slice(&0u32.to_le_bytes());
+    push_element(&mut body, MI_UINT32, &flags);
+    let dimensions: Vec<u8> = dimensions
+        .iter()
+        .flat_map(|&dimension| (dimension as i32).to_le_bytes())
+        .collect();
+    push_element(&mut body, MI_INT32, &dimensions);
+    push_element(&mut body, MI_INT8, name.as_bytes());
+    for (data_type, data) in parts {
+        push_element(&mut body, *data_type, data);
+    }
+
+    let mut element = Vec::with_capacity(8 + body.len());
+    element.extend_from_slice(&MI_MATRIX.to_le_bytes());
+    element.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    element.extend_from_slice(&body);
+    element
+}
+
+// Tag (type, byte count), data, then zero padding to an 8-byte boundary.
+fn push_element(out: &mut Vec<u8>, data_type: u32, data: &[u8]) {
+    out.extend_from_slice(&data_type.to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    out.resize(out.len() + (8 - data.len() % 8) % 8, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_mat_layout() {
+        let rows = vec![vec![1.0, 2.0], vec![3.0, 4.0], vec![5.0, 6.0]];
+        let names = vec!["Chad".to_string(), "Côte d'Ivoire".to_string()];
+        let mut bytes = Vec::new();
+        write_mat(
+            &mut bytes,
+            &[
+                ("adjacency", Variable::Matrix(&rows)),
+                ("nodes", Variable::Strings(&names)),
+            ],
+        )
+        .unwrap();
+        assert!(bytes.starts_with(b"MATLAB 5.0 MAT-file"));
+        assert_eq!(&bytes[126..128], b"IM");
+
+        // First variable: tag, flags, 3 x 2 dimensions, name, doubles
+        let first = 128;
+        assert_eq!(u32_at(&bytes, first), MI_MATRIX);
+        let size = u32_at(&bytes, first + 4) as usize;
+        assert_eq!(size % 8, 0);
+        assert_eq!(u32_at(&bytes, first + 16), MX_DOUBLE_CLASS);
+        assert_eq!(
+            (u32_at(&bytes, first + 32), u32_at(&bytes, first + 36)),
+            (3, 2)
+        );
+        assert_eq!(&bytes[first + 48..first + 57], b"adjacency");
+        let data = first + 64;
+        assert_eq!(u32_at(&bytes, data), MI_DOUBLE);
+        assert_eq!(u32_at(&bytes, data + 4), 48);
+        // Column-major: 1, 3, 5, 2, ...
+        let second_value = f64::from_le_bytes(bytes[data + 16..data + 24].try_into().unwrap());
+        assert_eq!(second_value, 3.0);
+
+        // The cell array fills the rest of the file exactly
+        let cells = first + 8 + size;
+        assert_eq!(u32_at(&bytes, cells), MI_MATRIX);
+        assert_eq!(cells + 8 + u32_at(&bytes, cells + 4) as usize, bytes.len());
+        assert_eq!(u32_at(&bytes, cells + 16), MX_CELL_CLASS);
+    }
+}
@@ -0,0 +1,218 @@
+// Storage for square weight matrices. Graphs keep a dense row-major matrix,
+// which is fastest to fill and to index; algorithms that only walk the
+// non-zero weights are written against `MatrixBackend` instead, so they run
+// just as well over a compressed sparse row copy when most pairs are
+// unconnected. `graph::SparseGraph` holds only that copy.
+//
+// Building with the `sparse` feature makes `Storage` (what those algorithms
+// convert a graph into) the CSR form. There is no ndarray backend, as this
+// crate takes no dependencies; one would be another impl of the trait.
+pub trait MatrixBackend {
+    // Number of rows (and columns)
+    fn dim(&self) -> usize;
+
+    fn weight(&self, row: usize, col: usize) -> f64;
+
+    // The non-zero weights of `row` as (column, weight), by column.
+    fn row_entries(&self, row: usize) -> Box<dyn Iterator<Item = (usize, f64)> + '_>;
+
+    // Every non-zero weight off the diagonal, row by row.
+    fn edge_weights(&self) -> Vec<f64> {
+        (0..self.dim())
+            .flat_map(|row| {
+                self.row_entries(row)
+                    .filter(move |&(col, _)| col != row)
+                    .map(|(_, weight)| weight)
+            })
+            .collect()
+    }
+}
+
+impl MatrixBackend for [Vec<f64>] {
+    fn dim(&self) -> usize {
+        self.len()
+    }
+
+    fn weight(&self, row: usize, col: usize) -> f64 {
+        self[row][col]
+    }
+
+    fn row_entries(&self, row: usize) -> Box<dyn Iterator<Item = (usize, f64)> + '_> {
+        Box::new(
+            self[row]
+                .iter()
+                .copied()
+                .enumerate()
+                .filter(|&(_, weight)| weight != 0.0),
+        )
+    }
+}
+
+impl MatrixBackend for Vec<Vec<f64>> {
+    fn dim(&self) -> usize {
+        self.as_slice().dim()
+    }
+
+    fn weight(&self, row: usize, col: usize) -> f64 {
+        self.as_slice().weight(row, col)
+    }
+
+    fn row_entries(&self, row: usize) -> Box<dyn Iterator<Item = (usize, f64)> + '_> {
+        self.as_slice().row_entries(row)
+    }
+}
+
+impl<M: MatrixBackend + ?Sized> MatrixBackend for &M {
+    fn dim(&self) -> usize {
+        (**self).dim()
+    }
+
+    fn weight(&self, row: usize, col: usize) -> f64 {
+        (**self).weight(row, col)
+    }
+
+    fn row_entries(&self, row: usize) -> Box<dyn Iterator<Item = (usize, f64)> + '_> {
+        (**self).row_entries(row)
+    }
+}
+
+// Compressed sparse row: the non-zero weights of row `i` are
+// `values[row_starts[i]..row_starts[i + 1]]`, in the columns given by the
+// same slice of `columns`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Csr {
+    row_starts: Vec<usize>,
+    columns: Vec<usize>,
+    values: Vec<f64>,
+}
+
+impl Csr {
+    pub fn from_backend<M: MatrixBackend + ?Sized>(matrix: &M) -> Csr {
+        let mut csr = Csr {
+            row_starts: vec![0],
+            columns: Vec::new(),
+            values: Vec::new(),
+        };
+        for row in 0..matrix.dim() {
+            for (col, weight) in matrix.row_entries(row) {
+                csr.columns.push(col);
+                csr.values.push(weight);
+            }
+            csr.row_starts.push(csr.values.len());
+        }
+        csr
+    }
+
+    // From each row's (column, weight) entries in any order; zero weights
+    // are left out and repeated columns summed.
+    pub fn from_rows(rows: Vec<Vec<(usize, f64)>>) -> Csr {
+        let mut csr = Csr {
+            row_starts: vec![0],
+            columns: Vec::new(),
+            values: Vec::new(),
+        };
+        for mut row in rows {
+            row.sort_by_key(|&(col, _)| col);
+            let mut merged: Vec<(usize, f64)> = Vec::with_capacity(row.len());
+            for (col, weight) in row {
+                match merged.last_mut() {
+                    Some((last, total)) if *last == col => *total += weight,
+                    _ => merged.push((col, weight)),
+                }
+            }
+            for (col, weight) in merged.into_iter().filter(|&(_, weight)| weight != 0.0) {
+                csr.columns.push(col);
+                csr.values.push(weight);
+            }
+            csr.row_starts.push(csr.values.len());
+        }
+        csr
+    }
+
+    // Number of stored (non-zero) weights
+    pub fn nonzeros(&self) -> usize {
+        self.values.len()
+    }
+
+    fn row_range(&self, row: usize) -> std::ops::Range<usize> {
+        self.row_starts[row]..self.row_starts[row + 1]
+    }
+}
+
+impl MatrixBackend for Csr {
+    fn dim(&self) -> usize {
+        self.row_starts.len() - 1
+    }
+
+    fn weight(&self, row: usize, col: usize) -> f64 {
+        let range = self.row_range(row);
+        match self.columns[range.clone()].binary_search(&col) {
+            Ok(offset) => self.values[range.start + offset],
+            Err(_) => 0.0,
+        }
+    }
+
+    fn row_entries(&self, row: usize) -> Box<dyn Iterator<Item = (usize, f64)> + '_> {
+        let range = self.row_range(row);
+        Box::new(
+            self.columns[range.clone()]
+                .iter()
+                .copied()
+                .zip(self.values[range].iter().copied()),
+        )
+    }
+}
+
+// A graph's matrix in the backend chosen at compile time: the dense rows
+// themselves, or a CSR copy of their non-zero weights.
+#[cfg(not(feature = "sparse"))]
+pub type Storage<'a> = &'a [Vec<f64>];
+#[cfg(feature = "sparse")]
+pub type Storage<'a> = Csr;
+
+#[cfg(not(feature = "sparse"))]
+pub fn storage(matrix: &[Vec<f64>]) -> Storage<'_> {
+    matrix
+}
+
+#[cfg(feature = "sparse")]
+pub fn storage(matrix: &[Vec<f64>]) -> Storage<'_> {
+    Csr::from_backend(matrix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparse_matches_dense() {
+        let dense = vec![
+            vec![1.0, 0.0, 2.0],
+            vec![0.0, 0.0, 0.0],
+            vec![3.0, 4.0, 0.0],
+        ];
+        let sparse = Csr::from_backend(dense.as_slice());
+        assert_eq!(sparse.dim(), 3);
+        assert_eq!(sparse.values.len(), 4);
+        for row in 0..3 {
+            for col in 0..3 {
+                assert_eq!(sparse.weight(row, col), dense.as_slice().weight(row, col));
+            }
+            assert!(sparse
+                .row_entries(row)
+                .eq(dense.as_slice().row_entries(row)));
+        }
+        assert_eq!(sparse.edge_weights(), [2.0, 3.0, 4.0]);
+        assert_eq!(dense.as_slice().edge_weights(), sparse.edge_weights());
+        assert_eq!(storage(&dense).edge_weights(), [2.0, 3.0, 4.0]);
+
+        // Rows in any order, with repeats summed and zeros left out
+        let rows = vec![
+            vec![(2, 2.0), (0, 1.0)],
+            vec![(1, 5.0), (1, -5.0)],
+            vec![(1, 4.0), (0, 3.0), (2, 0.0)],
+        ];
+        assert_eq!(Csr::from_rows(rows), sparse);
+        assert_eq!(sparse.nonzeros(), 4);
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+// Counters behind the server's `/metrics` endpoint, rendered in the
+// Prometheus text exposition format.
+#[derive(Default)]
+pub struct Metrics {
+    // (route, status) -> requests
+    requests: BTreeMap<(String, u16), u64>,
+    // stage -> (runs, total seconds)
+    stages: BTreeMap<&'static str, (u64, f64)>,
+    // outcome ("memory", "disk", "miss") -> result lookups
+    result_lookups: BTreeMap<&'static str, u64>,
+}
+
+impl Metrics {
+    pub fn record_request(&mut self, route: &str, status: u16) {
+        *self
+            .requests
+            .entry((route.to_string(), status))
+            .or_default() += 1;
+    }
+
+    pub fn record_stage(&mut self, stage: &'static str, elapsed: Duration) {
+        let (runs, seconds) = self.stages.entry(stage).or_default();
+        *runs += 1;
+        *seconds += elapsed.as_secs_f64();
+    }
+
+    pub fn record_result_lookup(&mut self, outcome: &'static str) {
+        *self.result_lookups.entry(outcome).or_default() += 1;
+    }
+
+    // All metrics, with the sizes of the graphs and named datasets
+    // currently held as gauges.
+    pub fn render(&self, graph_nodes: &[(u64, usize)], dataset_bytes: &[(&str, usize)]) -> String {
+        let mut out = String::new();
+        family(
+            &mut out,
+            "ds210_requests_total",
+            "counter",
+            "HTTP requests served, by route and status",
+        );
+        for ((route, status), count) in &self.requests {
+            let _ = writeln!(
+                out,
+                "ds210_requests_total{{route=\"{}\",status=\"{}\"}} {}",
+                escape(route),
+                status,
+                count
+            );
+        }
+
+        family(
+            &mut out,
+            "ds210_stage_duration_seconds",
+            "summary",
+            "Time spent in each pipeline stage",
+        );
+        for (stage, (runs, seconds)) in &self.stages {
+            let _ = writeln!(
+                out,
+                "ds210_stage_duration_seconds_sum{{stage=\"{}\"}} {}",
+                stage, seconds
+            );
+            let _ = writeln!(
+                out,
+                "ds210_stage_duration_seconds_count{{stage=\"{}\"}} {}",
+                stage, runs
+            );
+        }
+
+        family(
+            &mut out,
+            "ds210_result_lookups_total",
+            "counter",
+            "GetResult lookups answered from memory, from the disk cache, or missed",
+        );
+        for outcome in ["memory", "disk", "miss"] {
+            let count = self.result_lookups.get(outcome).copied().unwrap_or(0);
+            let _ = writeln!(
+                out,
+                "ds210_result_lookups_total{{outcome=\"{}\"}} {}",
+                outcome, count
+            );
+        }
+
+        family(
+            &mut out,
+            "ds210_graphs",
+            "gauge",
+            "Graphs currently held in memory",
+        );
+        let _ = writeln!(out, "ds210_graphs {}", graph_nodes.len());
+        family(
+            &mut out,
+            "ds210_graph_nodes",
+            "gauge",
+            "Nodes of each graph held in memory",
+        );
+        for (graph_id, nodes) in graph_nodes {
+            let _ = writeln!(
+                out,
+                "ds210_graph_nodes{{graph_id=\"{}\"}} {}",
+                graph_id, nodes
+            );
+        }
+        family(
+            &mut out,
+            "ds210_dataset_bytes",
+            "gauge",
+            "Estimated memory held by each named dataset",
+        );
+        for (name, bytes) in dataset_bytes {
+            let _ = writeln!(
+                out,
+                "ds210_dataset_bytes{{name=\"{}\"}} {}",
+                escape(name),
+                bytes
+            );
+        }
+        out
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+// Label values escape backslashes, quotes and newlines.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_exposition_format() {
+        let mut metrics = Metrics::default();
+        metrics.record_request("/v1/Cluster", 200);
+        metrics.record_request("/v1/Cluster", 200);
+        metrics.record_request("/v1/GetResult", 404);
+        metrics.record_stage("build", Duration::from_millis(250));
+        metrics.record_stage("build", Duration::from_millis(750));
+        metrics.record_result_lookup("disk");
+
+        let text = metrics.render(&[(2, 190)], &[("unesco", 4096)]);
+        assert!(text.contains("# TYPE ds210_requests_total counter\n"));
+        assert!(text.contains("ds210_requests_total{route=\"/v1/Cluster\",status=\"200\"} 2\n"));
+        assert!(text.contains("ds210_stage_duration_seconds_sum{stage=\"build\"} 1\n"));
+        assert!(text.contains("ds210_stage_duration_seconds_count{stage=\"build\"} 2\n"));
+        assert!(text.contains("ds210_result_lookups_total{outcome=\"miss\"} 0\n"));
+        assert!(text.contains("ds210_graph_nodes{graph_id=\"2\"} 190\n"));
+        assert!(text.contains("ds210_dataset_bytes{name=\"unesco\"} 4096\n"));
+        assert_eq!(escape("a\"b"), "a\\\"b");
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::RangeInclusive;
+
+use crate::cluster::{Algorithm, Stop};
+use crate::features::Scaling;
+use crate::parallel::Parallelism;
+use crate::table::Table;
+use crate::{EducationData, Graph, SimilarityMetric};
+
+// Who changed the most between two windows of years, say the 2000s and the
+// 2010s. Each window gives a country each series' mean over the years it
+// reports in the window. Every series is scaled to z-scores over the values
+// of both windows together, so that a series in large units does not drown
+// the others and a shift means the same in either window; the similarity
+// graph of each window is built on those scores and clustered.
+//
+// Only countries reporting in both windows are compared, on two counts:
+//
+//   neighbours  share of its nearest neighbours replaced (1 - Jaccard of the
+//               two neighbour sets)
+//   cluster     share of the other countries it went from sharing a cluster
+//               with to not, or back
+//
+// and ranked by their mean. The series responsible are those whose score
+// moved the most.
+
+// How many series are named as responsible for a country's move.
+const RESPONSIBLE: usize = 3;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    pub metric: SimilarityMetric,
+    pub algorithm: Algorithm,
+    // Nearest neighbours compared per country
+    pub neighbours: usize,
+    // The windows' graphs are built and clustered over these threads
+    pub parallelism: Parallelism,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            metric: SimilarityMetric::Cosine,
+            algorithm: Algorithm::default(),
+            neighbours: 5,
+            parallelism: Parallelism::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mover {
+    pub country: String,
+    pub score: f64,
+    pub neighbours_changed: f64,
+    pub cluster_changed: f64,
+    // Neighbours in the later window only, and in the earlier window only
+    pub joined: Vec<String>,
+    pub left: Vec<String>,
+    // (series, change in z-score), largest change first
+    pub indicators: Vec<(String, f64)>,
+}
+
+// Countries in both windows, most changed first (ties in name order).
+pub fn top_movers(
+    data: &[EducationData],
+    before: &RangeInclusive<u32>,
+    after: &RangeInclusive<u32>,
+    options: Options,
+) -> Vec<Mover> {
+    let earlier = window_means(data, before);
+    let later = window_means(data, after);
+    let countries: Vec<&str> = earlier
+        .keys()
+        .filter(|country| later.contains_key(*country))
+        .copied()
+        .collect();
+    let series: Vec<&str> = countries
+        .iter()
+        .flat_map(|country| earlier[country].keys().chain(later[country].keys()))
+        .copied()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    // z-scores of both windows, `scores[window][country][series]`
+    let mut scores = [
+        vec![vec![f64::NAN; series.len()]; countries.len()],
+        vec![vec![f64::NAN; series.len()]; countries.len()],
+    ];
+    for (column, name) in series.iter().enumerate() {
+        let mut cells = Vec::new();
+        let mut values = Vec::new();
+        for (window, means) in [&earlier, &later].into_iter().enumerate() {
+            for (row, country) in countries.iter().enumerate() {
+                if let Some(&value) = means[country].get(name) {
+                    cells.push((window, row));
+                    values.push(value);
+                }
+            }
+        }
+        for ((window, row), z) in cells.into_iter().zip(Scaling::Standard.scale(&values)) {
+            scores[window][row][column] = z;
+        }
+    }
+
+    let nodes: Vec<String> = countries
+        .iter()
+        .map(|country| country.to_string())
+        .collect();
+    let graphs: Vec<Graph> = scores
+        .iter()
+        .map(|values| Graph {
+            nodes: nodes.clone(),
+            adjacency_matrix: options.parallelism.map_rows(values.len(), |i| {
+                values
+                    .iter()
+                    .map(|b| options.metric.similarity(&values[i], b))
+                    .collect()
+            }),
+        })
+        .collect();
+    let cluster_of: Vec<Vec<usize>> = graphs
+        .iter()
+        .map(|graph| {
+            let mut cluster_of = vec![usize::MAX; nodes.len()];
+            for (index, members) in options
+                .algorithm
+                .cluster(graph, None, Stop::Auto, options.parallelism)
+                .iter()
+                .enumerate()
+            {
+                for &node in members {
+                    cluster_of[node] = index;
+                }
+            }
+            cluster_of
+        })
+        .collect();
+
+    let others = nodes.len().saturating_sub(1).max(1) as f64;
+    let mut movers: Vec<Mover> = (0..nodes.len())
+        .map(|node| {
+            let [earlier, later] =
+                [&graphs[0], &graphs[1]].map(|graph| nearest(graph, node, options.neighbours));
+            let union = earlier.union(&later).count();
+            let neighbours_changed = match union {
+                0 => 0.0,
+                _ => 1.0 - earlier.intersection(&later).count() as f64 / union as f64,
+            };
+            let together = |window: usize, other: usize| {
+                cluster_of[window][node] != usize::MAX
+                    && cluster_of[window][node] == cluster_of[window][other]
+            };
+            let flipped = (0..nodes.len())
+                .filter(|&other| other != node && together(0, other) != together(1, other))
+                .count();
+            let cluster_changed = flipped as f64 / others;
+
+            let mut indicators: Vec<(String, f64)> = series
+                .iter()
+                .enumerate()
+                .map(|(column, name)| {
+                    let change = scores[1][node][column] - scores[0][node][column];
+                    (name.to_string(), change)
+                })
+                .filter(|(_, change)| !change.is_nan())
+                .collect();
+            indicators.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()).then(a.0.cmp(&b.0)));
+            indicators.truncate(RESPONSIBLE);
+
+            let names = |set: BTreeSet<&usize>| {
+                set.into_iter().map(|&other| nodes[other].clone()).collect()
+            };
+            Mover {
+                country: nodes[node].clone(),
+                score: (neighbours_changed + cluster_changed) / 2.0,
+                neighbours_changed,
+                cluster_changed,
+                joined: names(later.difference(&earlier).collect()),
+                left: names(earlier.difference(&later).collect()),
+                indicators,
+            }
+        })
+        .collect();
+    movers.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.country.cmp(&b.country)));
+    movers
+}
+
+// Each country's mean of each series over the years in `years`.
+fn window_means<'a>(
+    data: &'a [EducationData],
+    years: &RangeInclusive<u32>,
+) -> BTreeMap<&'a str, BTreeMap<&'a str, f64>> {
+    let mut sums: BTreeMap<&str, BTreeMap<&str, (f64, usize)>> = BTreeMap::new();
+    for record in data {
+        let Some(value) = record.value else { continue };
+        if !years.contains(&record.year) {
+            continue;
+        }
+        let slot = sums
+            .entry(record.country_or_area.as_str())
+            .or_default()
+            .entry(record.series.as_str())
+            .or_insert((0.0, 0));
+        slot.0 += value;
+        slot.1 += 1;
+    }
+    sums.into_iter()
+        .map(|(country, series)| {
+            let means = series
+                .into_iter()
+                .map(|(name, (sum, count))| (name, sum / count as f64))
+                .collect();
+            (country, means)
+        })
+        .collect()
+}
+
+// The `count` countries most like `node`, leaving out any not alike at all.
+fn nearest(graph: &Graph, node: usize, count: usize) -> BTreeSet<usize> {
+    let row = &graph.adjacency_matrix[node];
+    let mut others: Vec<usize> = (0..row.len())
+        .filter(|&other| other != node && row[other] > 0.0)
+        .collect();
+    others.sort_by(|&a, &b| row[b].total_cmp(&row[a]).then(a.cmp(&b)));
+    others.into_iter().take(count).collect()
+}
+
+// The `top` movers, with the series that moved them as "series +z".
+pub fn movers_table(movers: &[Mover], top: usize) -> Table {
+    let mut table = Table::new(&[
+        "rank",
+        "country",
+        "score",
+        "neighbors_changed",
+        "cluster_changed",
+        "joined",
+        "left",
+        "indicators",
+    ]);
+    for (index, mover) in movers.iter().take(top).enumerate() {
+        let indicators: Vec<String> = mover
+            .indicators
+            .iter()
+            .map(|(series, change)| format!("{} {:+.2}", series, change))
+            .collect();
+        table.push_row(vec![
+            (index + 1).to_string(),
+            mover.country.clone(),
+            format!("{:.3}", mover.score),
+            format!("{:.3}", mover.neighbours_changed),
+            format!("{:.3}", mover.cluster_changed),
+            mover.joined.join("; "),
+            mover.left.join("; "),
+            indicators.join("; "),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_country_that_switched_groups_moves_most() {
+        // Two groups alike in both decades, apart from Chad, which leaves the
+        // first for the second through its tertiary value
+        let mut data = Vec::new();
+        for (country, first, second) in [
+            ("Chad", (10.0, 90.0), (90.0, 90.0)),
+            ("Mali", (12.0, 88.0), (12.0, 88.0)),
+            ("Niger", (11.0, 91.0), (11.0, 91.0)),
+            ("Peru", (89.0, 92.0), (89.0, 92.0)),
+            ("Togo", (92.0, 89.0), (92.0, 89.0)),
+        ] {
+            data.push(record(country, "tertiary", 2005, first.0));
+            data.push(record(country, "primary", 2005, first.1));
+            data.push(record(country, "tertiary", 2015, second.0));
+            data.push(record(country, "primary", 2015, second.1));
+        }
+        // Out of both windows, and a country reporting in one window only
+        data.push(record("Chad", "tertiary", 1995, 500.0));
+        data.push(record("Benin", "primary", 2015, 50.0));
+
+        let options = Options {
+            metric: SimilarityMetric::Euclidean,
+            neighbours: 2,
+            ..Options::default()
+        };
+        let movers = top_movers(&data, &(2000..=2009), &(2010..=2019), options);
+        assert_eq!(movers.len(), 5);
+        let chad = &movers[0];
+        assert_eq!(chad.country, "Chad");
+        assert_eq!(chad.joined, ["Peru", "Togo"]);
+        assert_eq!(chad.left, ["Mali", "Niger"]);
+        assert_eq!(chad.neighbours_changed, 1.0);
+        assert_eq!(chad.indicators[0].0, "tertiary");
+        assert!(chad.indicators[0].1 > 0.0);
+        assert!(movers[1].score < chad.score);
+
+        let table = movers_table(&movers, 2);
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(table.rows[0][5], "Peru; Togo");
+        assert!(table.rows[0][7].starts_with("tertiary +"));
+    }
+}
@@ -0,0 +1,322 @@
+use std::io;
+
+// MessagePack encoding and decoding of a small value model: the compact
+// binary alternative to JSON for results served or cached by the engine.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Binary(Vec<u8>),
+    Array(Vec<Value>),
+    // Keys in insertion order, as with json::Json objects
+    Map(Vec<(Value, Value)>),
+}
+
+impl Value {
+    pub fn map(fields: Vec<(&str, Value)>) -> Value {
+        Value::Map(
+            fields
+                .into_iter()
+                .map(|(key, value)| (Value::String(key.to_string()), value))
+                .collect(),
+        )
+    }
+
+    // The value under a string key of a map.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(fields) => fields
+                .iter()
+                .find(|(name, _)| matches!(name, Value::String(name) if name == key))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Int(value) => u64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out);
+        out
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            Value::Nil => out.push(0xc0),
+            Value::Bool(false) => out.push(0xc2),
+            Value::Bool(true) => out.push(0xc3),
+            Value::Int(value) => encode_int(*value, out),
+            Value::Float(value) => {
+                out.push(0xcb);
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+            Value::String(value) => {
+                let length = value.len();
+                match length {
+                    0..=31 => out.push(0xa0 | length as u8),
+                    32..=0xff => out.extend_from_slice(&[0xd9, length as u8]),
+                    0x100..=0xffff => {
+                        out.push(0xda);
+                        out.extend_from_slice(&(length as u16).to_be_bytes());
+                    }
+                    _ => {
+                        out.push(0xdb);
+                        out.extend_from_slice(&(length as u32).to_be_bytes());
+                    }
+                }
+                out.extend_from_slice(value.as_bytes());
+            }
+            Value::Binary(bytes) => {
+                encode_length(bytes.len(), None, [0xc4, 0xc5, 0xc6], out);
+                out.extend_from_slice(bytes);
+            }
+            Value::Array(items) => {
+                encode_length(items.len(), Some(0x90), [0, 0xdc, 0xdd], out);
+                for item in items {
+                    item.encode_into(out);
+                }
+            }
+            Value::Map(fields) => {
+                encode_length(fields.len(), Some(0x80), [0, 0xde, 0xdf], out);
+                for (key, value) in fields {
+                    key.encode_into(out);
+                    value.encode_into(out);
+                }
+            }
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Value> {
+        let mut reader = Reader { bytes, position: 0 };
+        let value = reader.value(0)?;
+        if reader.position != bytes.len() {
+            return Err(invalid_data(format!(
+                "{} trailing bytes after the value",
+                bytes.len() - reader.position
+            )));
+        }
+        Ok(value)
+    }
+}
+
+fn encode_int(value: i64, out: &mut Vec<u8>) {
+    match value {
+        0..=0x7f => out.push(value as u8),
+        -32..=-1 => out.push(value as i8 as u8),
+        0x80..=0xff => out.extend_from_slice(&[0xcc, value as u8]),
+        0x100..=0xffff => {
+            out.push(0xcd);
+            out.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xce);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        _ if value > 0 => {
+            out.push(0xcf);
+            out.extend_from_slice(&(value as u64).to_be_bytes());
+        }
+        -0x80..=-33 => out.extend_from_slice(&[0xd0, value as i8 as u8]),
+        -0x8000..=-0x81 => {
+            out.push(0xd1);
+            out.extend_from_slice(&(value as i16).to_be_bytes());
+        }
+        -0x8000_0000..=-0x8001 => {
+            out.push(0xd2);
+            out.extend_from_slice(&(value as i32).to_be_bytes());
+        }
+        _ => {
+            out.push(0xd3);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+// A length header: the fix-format marker when there is one and the length
+// fits in four bits, else the 8-, 16- or 32-bit marker (0 for "none").
+fn encode_length(length: usize, fixed: Option<u8>, markers: [u8; 3], out: &mut Vec<u8>) {
+    match (fixed, length) {
+        (Some(fixed), 0..=15) => out.push(fixed | length as u8),
+        (None, 0..=0xff) => out.extend_from_slice(&[markers[0], length as u8]),
+        (_, 0..=0xffff) => {
+            out.push(markers[1]);
+            out.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        _ => {
+            out.push(markers[2]);
+            out.extend_from_slice(&(length as u32).to_be_bytes());
+        }
+    }
+}
+
+// Nesting deeper than this is rejected rather than risking the stack.
+const MAX_DEPTH: usize = 64;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> io::Result<&[u8]> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| invalid_data("truncated MessagePack value".to_string()))?;
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn be(&mut self, count: usize) -> io::Result<u64> {
+        Ok(self
+            .take(count)?
+            .iter()
+            .fold(0u64, |value, &byte| value << 8 | u64::from(byte)))
+    }
+
+    fn value(&mut self, depth: usize) -> io::Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(invalid_data(
+                "MessagePack value nests too deeply".to_string(),
+            ));
+        }
+        let marker = self.take(1)?[0];
+        Ok(match marker {
+            0x00..=0x7f => Value::Int(i64::from(marker)),
+            0x80..=0x8f => self.map(usize::from(marker & 0x0f), depth)?,
+            0x90..=0x9f => self.array(usize::from(marker & 0x0f), depth)?,
+            0xa0..=0xbf => self.string(usize::from(marker & 0x1f))?,
+            0xc0 => Value::Nil,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xc4..=0xc6 => {
+                let length = self.be(1 << (marker - 0xc4))? as usize;
+                Value::Binary(self.take(length)?.to_vec())
+            }
+            0xca => Value::Float(f64::from(f32::from_bits(self.be(4)? as u32))),
+            0xcb => Value::Float(f64::from_bits(self.be(8)?)),
+            0xcc..=0xce => Value::Int(self.be(1 << (marker - 0xcc))? as i64),
+            0xcf => {
+                let value = self.be(8)?;
+                Value::Int(i64::try_from(value).map_err(|_| {
+                    invalid_data(format!("integer {} does not fit in 64 signed bits", value))
+                })?)
+            }
+            0xd0 => Value::Int(i64::from(self.be(1)? as u8 as i8)),
+            0xd1 => Value::Int(i64::from(self.be(2)? as u16 as i16)),
+            0xd2 => Value::Int(i64::from(self.be(4)? as u32 as i32)),
+            0xd3 => Value::Int(self.be(8)? as i64),
+            0xd9..=0xdb => {
+                let length = self.be(1 << (marker - 0xd9))? as usize;
+                self.string(length)?
+            }
+            0xdc | 0xdd => {
+                let length = self.be(2 << (marker - 0xdc))? as usize;
+                self.array(length, depth)?
+            }
+            0xde | 0xdf => {
+                let length = self.be(2 << (marker - 0xde))? as usize;
+                self.map(length, depth)?
+            }
+            0xe0..=0xff => Value::Int(i64::from(marker as i8)),
+            other => {
+                return Err(invalid_data(format!(
+                    "unsupported MessagePack marker 0x{:02x}",
+                    other
+                )))
+            }
+        })
+    }
+
+    fn string(&mut self, length: usize) -> io::Result<Value> {
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec())
+            .map(Value::String)
+            .map_err(|_| invalid_data("MessagePack string is not UTF-8".to_string()))
+    }
+
+    fn array(&mut self, length: usize, depth: usize) -> io::Result<Value> {
+        // Every element takes at least a byte, which bounds the allocation
+        let mut items = Vec::with_capacity(length.min(self.bytes.len() - self.position));
+        for _ in 0..length {
+            items.push(self.value(depth + 1)?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn map(&mut self, length: usize, depth: usize) -> io::Result<Value> {
+        let mut fields = Vec::with_capacity(length.min(self.bytes.len() - self.position));
+        for _ in 0..length {
+            let key = self.value(depth + 1)?;
+            fields.push((key, self.value(depth + 1)?));
+        }
+        Ok(Value::Map(fields))
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_encodings() {
+        let value = Value::map(vec![
+            ("nil", Value::Nil),
+            ("flag", Value::Bool(true)),
+            (
+                "ints",
+                Value::Array(
+                    [0, 127, 128, 65_536, -1, -33, -40_000, i64::MIN, i64::MAX]
+                        .into_iter()
+                        .map(Value::Int)
+                        .collect(),
+                ),
+            ),
+            ("float", Value::Float(-2.5)),
+            ("text", Value::String("Côte d'Ivoire".repeat(3))),
+            ("bytes", Value::Binary(vec![0, 1, 2])),
+            ("long", Value::Array(vec![Value::Nil; 20])),
+        ]);
+        let bytes = value.encode();
+        assert_eq!(Value::decode(&bytes).unwrap(), value);
+
+        // The compact forms from the spec
+        assert_eq!(Value::Int(-1).encode(), [0xff]);
+        assert_eq!(Value::Int(200).encode(), [0xcc, 200]);
+        assert_eq!(Value::String("a".into()).encode(), [0xa1, b'a']);
+        assert_eq!(Value::Array(vec![]).encode(), [0x90]);
+
+        assert!(Value::decode(&[0x92, 0x01]).is_err());
+        assert!(Value::decode(&[0x01, 0x02]).is_err());
+        assert!(Value::decode(&[0xc1]).is_err());
+    }
+}
@@ -0,0 +1,448 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io;
+use std::str::FromStr;
+
+use crate::reference::{Place, ReferenceData};
+use crate::table::Table;
+use crate::EducationData;
+
+// Country names in the data against the reference tables. The UN tables
+// list regions and other groupings ("Total, all countries or areas",
+// "Sub-Saharan Africa", "SIDS") among the countries, and their figures
+// aggregate the countries' own; in a country graph they are near
+// duplicates of whole clusters. `--aggregates` keeps them (the default),
+// drops them or keeps only them, and `--iso-codes` names countries by
+// their ISO 3166-1 alpha-3 code. Names the tables do not know are kept
+// as they are and reported.
+//
+// `--reconcile-names` names every country and region by its reference
+// name, so that one spelt differently over the years (Swaziland, then
+// Eswatini) is one node with its whole history. Where the data has the
+// same observation under both names, the one under the current name is
+// kept.
+//
+// States that have dissolved (Serbia and Montenegro, Sudan before South
+// Sudan split off) are a problem for anything following countries over
+// time: the data has a node that stops, and its successors start with no
+// past. `--dissolved` keeps them as nodes of their own (the default), drops
+// their records, or merges them into the successor that continues them, as
+// for a renamed country. The tables name one successor per state; the ones
+// that split off are left as they are.
+
+pub const AGGREGATE_POLICIES: &[&str] = &["include", "exclude", "only"];
+
+pub const DISSOLVED_POLICIES: &[&str] = &["keep", "successor", "drop"];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Aggregates {
+    #[default]
+    Include,
+    Exclude,
+    Only,
+}
+
+impl FromStr for Aggregates {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Aggregates> {
+        match value {
+            "include" => Ok(Aggregates::Include),
+            "exclude" => Ok(Aggregates::Exclude),
+            "only" => Ok(Aggregates::Only),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown aggregate policy `{}`; expected one of {}",
+                    other,
+                    AGGREGATE_POLICIES.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Dissolved {
+    #[default]
+    Keep,
+    Successor,
+    Drop,
+}
+
+impl FromStr for Dissolved {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Dissolved> {
+        match value {
+            "keep" => Ok(Dissolved::Keep),
+            "successor" => Ok(Dissolved::Successor),
+            "drop" => Ok(Dissolved::Drop),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown dissolved-state policy `{}`; expected one of {}",
+                    other,
+                    DISSOLVED_POLICIES.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Options {
+    pub aggregates: Aggregates,
+    pub iso_codes: bool,
+    pub reconcile: bool,
+    pub dissolved: Dissolved,
+}
+
+impl Options {
+    // Whether these options change any name at all
+    pub fn changes_names(&self) -> bool {
+        *self != Options::default()
+    }
+
+    // Whether records of two names can become one country's, so that the
+    // same observation may turn up twice
+    pub fn merges_records(&self) -> bool {
+        self.reconcile || self.dissolved == Dissolved::Successor
+    }
+}
+
+// What a name in the data becomes
+struct Renamed {
+    name: String,
+    // Whether the name is another one for a place the tables name
+    // differently, e.g. a former name
+    alias: bool,
+}
+
+pub struct Names {
+    reference: ReferenceData,
+    options: Options,
+    // Each name seen so far, None when its records are dropped
+    renamed: HashMap<String, Option<Renamed>>,
+    unknown: BTreeSet<String>,
+    // Names reconciled with the reference name
+    reconciled: BTreeMap<String, String>,
+    // Dissolved states merged into their successors
+    merged: BTreeMap<String, String>,
+}
+
+impl Names {
+    pub fn new(reference: ReferenceData, options: Options) -> Names {
+        Names {
+            reference,
+            options,
+            renamed: HashMap::new(),
+            unknown: BTreeSet::new(),
+            reconciled: BTreeMap::new(),
+            merged: BTreeMap::new(),
+        }
+    }
+
+    pub fn options(&self) -> Options {
+        self.options
+    }
+
+    // Rename the record's country as asked; false when the record is to be
+    // dropped.
+    pub fn apply(&mut self, record: &mut EducationData) -> bool {
+        self.resolve(record).is_some()
+    }
+
+    // As `apply`, returning whether a kept record's name was an alias.
+    fn resolve(&mut self, record: &mut EducationData) -> Option<bool> {
+        if !self.renamed.contains_key(&record.country_or_area) {
+            let name = &record.country_or_area;
+            let found = self.reference.place(name);
+            let former = matches!(found, Some(Place::Former(_)));
+            let keep = match (self.options.aggregates, found) {
+                (Aggregates::Include, _) => true,
+                (Aggregates::Exclude, place) => !matches!(place, Some(Place::Aggregate(_))),
+                (Aggregates::Only, place) => matches!(place, Some(Place::Aggregate(_))),
+            } && !(former && self.options.dissolved == Dissolved::Drop);
+            let successor = match (found, self.options.dissolved) {
+                (Some(Place::Former(state)), Dissolved::Successor) => {
+                    self.reference.successor(state)
+                }
+                _ => None,
+            };
+            let place = successor.map(Place::Country).or(found);
+            let reference_name = match place {
+                Some(Place::Country(country)) => Some(&country.name),
+                Some(Place::Aggregate(region)) => Some(&region.name),
+                Some(Place::Former(state)) => Some(&state.name),
+                None => None,
+            };
+            let alias = reference_name.is_some_and(|reference| reference != name);
+            let rename = self.options.reconcile || successor.is_some();
+            let renamed = keep.then(|| Renamed {
+                name: match (place, reference_name) {
+                    (Some(Place::Country(country)), _) if self.options.iso_codes => {
+                        country.iso3.clone()
+                    }
+                    (_, Some(reference)) if rename => reference.clone(),
+                    _ => name.clone(),
+                },
+                alias,
+            });
+            if place.is_none() {
+                self.unknown.insert(name.clone());
+            }
+            match (reference_name, successor) {
+                (Some(reference), Some(_)) if keep => {
+                    self.merged.insert(name.clone(), reference.clone());
+                }
+                (Some(reference), None) if keep && alias && self.options.reconcile => {
+                    self.reconciled.insert(name.clone(), reference.clone());
+                }
+                _ => {}
+            }
+            self.renamed.insert(name.clone(), renamed);
+        }
+        let renamed = self.renamed[&record.country_or_area].as_ref()?;
+        if renamed.name != record.country_or_area {
+            record.country_or_area = renamed.name.clone();
+        }
+        Some(renamed.alias)
+    }
+
+    pub fn apply_all(&mut self, data: &mut Vec<EducationData>) {
+        let mut aliased = Vec::with_capacity(data.len());
+        data.retain_mut(|record| match self.resolve(record) {
+            Some(alias) => {
+                aliased.push(alias);
+                true
+            }
+            None => false,
+        });
+        if !self.options.merges_records() {
+            return;
+        }
+
+        // An observation under two names of one place, as when a renamed
+        // country's old and new names overlap for a year or a dissolved
+        // state and its successor both report one, is kept once: under the
+        // reference name if the data has it there, otherwise the first seen.
+        let key = |record: &EducationData| {
+            (
+                record.country_or_area.clone(),
+                record.year,
+                record.indicator.clone(),
+                record.series.clone(),
+            )
+        };
+        let current: HashSet<_> = data
+            .iter()
+            .zip(&aliased)
+            .filter(|(_, &alias)| !alias)
+            .map(|(record, _)| key(record))
+            .collect();
+        let mut seen = HashSet::new();
+        let mut aliased = aliased.into_iter();
+        data.retain(|record| {
+            let alias = aliased.next().unwrap_or(false);
+            !alias || (!current.contains(&key(record)) && seen.insert(key(record)))
+        });
+    }
+
+    // Names seen that are neither a known country nor a known aggregate
+    pub fn unknown(&self) -> &BTreeSet<String> {
+        &self.unknown
+    }
+
+    // Names seen that `--reconcile-names` replaced, with what replaced them
+    pub fn reconciled(&self) -> &BTreeMap<String, String> {
+        &self.reconciled
+    }
+
+    // Dissolved states seen that `--dissolved successor` merged, with the
+    // successor each went into
+    pub fn merged(&self) -> &BTreeMap<String, String> {
+        &self.merged
+    }
+}
+
+// What each of `names` stands for: its kind, codes and reference name.
+pub fn names_table<'a>(
+    reference: &ReferenceData,
+    names: impl IntoIterator<Item = &'a str>,
+) -> Table {
+    let mut table = Table::new(&["name", "kind", "iso3", "m49", "reference_name", "successor"]);
+    for name in names {
+        let mut successor = String::new();
+        let (kind, iso3, m49, reference_name) = match reference.place(name) {
+            Some(Place::Country(country)) => (
+                "country",
+                country.iso3.clone(),
+                format!("{:03}", country.m49),
+                country.name.clone(),
+            ),
+            Some(Place::Aggregate(region)) => (
+                "aggregate",
+                String::new(),
+                format!("{:03}", region.m49),
+                region.name.clone(),
+            ),
+            Some(Place::Former(state)) => {
+                if let Some(country) = reference.successor(state) {
+                    successor = country.name.clone();
+                }
+                (
+                    "dissolved",
+                    String::new(),
+                    format!("{:03}", state.m49),
+                    state.name.clone(),
+                )
+            }
+            None => ("unknown", String::new(), String::new(), String::new()),
+        };
+        table.push_row(vec![
+            name.to_string(),
+            kind.to_string(),
+            iso3,
+            m49,
+            reference_name,
+            successor,
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_aggregates_and_iso_codes() {
+        let countries = |names: &mut Names| {
+            let mut data: Vec<EducationData> = [
+                "United States of America",
+                "Sub-Saharan Africa",
+                "Total, all countries or areas",
+                "Atlantis",
+            ]
+            .into_iter()
+            .map(|country| record(country, "primary", 2015, 1.0))
+            .collect();
+            names.apply_all(&mut data);
+            data.into_iter()
+                .map(|record| record.country_or_area)
+                .collect::<Vec<_>>()
+        };
+        let reference = ReferenceData::bundled;
+        let options = |aggregates, iso_codes| Options {
+            aggregates,
+            iso_codes,
+            ..Options::default()
+        };
+        let mut excluded = Names::new(reference(), options(Aggregates::Exclude, true));
+        assert_eq!(countries(&mut excluded), ["USA", "Atlantis"]);
+        assert_eq!(excluded.unknown().len(), 1);
+        let mut only = Names::new(reference(), options(Aggregates::Only, false));
+        assert_eq!(
+            countries(&mut only),
+            ["Sub-Saharan Africa", "Total, all countries or areas"]
+        );
+        assert!(!Options::default().changes_names());
+        assert_eq!(
+            countries(&mut Names::new(reference(), Options::default())).len(),
+            4
+        );
+
+        let table = names_table(&reference(), ["LLDCs", "usa"]);
+        assert_eq!(table.rows[0][1..4], ["aggregate", "", "432"]);
+        assert_eq!(
+            table.rows[1][1..5],
+            ["country", "USA", "840", "United States of America"]
+        );
+    }
+
+    #[test]
+    fn test_reconciled_names_merge_histories() {
+        // Swaziland reports until 2018, Eswatini from 2018 on
+        let mut data = vec![
+            record("Swaziland", "primary", 2017, 1.0),
+            record("Swaziland", "primary", 2018, 2.0),
+            record("Eswatini", "primary", 2018, 3.0),
+            record("Eswatini", "primary", 2019, 4.0),
+            record(
+                "The former Yugoslav Republic of Macedonia",
+                "primary",
+                2017,
+                5.0,
+            ),
+        ];
+        let options = Options {
+            reconcile: true,
+            ..Options::default()
+        };
+        let mut names = Names::new(ReferenceData::bundled(), options);
+        names.apply_all(&mut data);
+        let merged: Vec<(&str, u32, Option<f64>)> = data
+            .iter()
+            .map(|record| (record.country_or_area.as_str(), record.year, record.value))
+            .collect();
+        assert_eq!(
+            merged,
+            [
+                ("Eswatini", 2017, Some(1.0)),
+                ("Eswatini", 2018, Some(3.0)),
+                ("Eswatini", 2019, Some(4.0)),
+                ("North Macedonia", 2017, Some(5.0)),
+            ]
+        );
+        assert_eq!(names.reconciled()["Swaziland"], "Eswatini");
+        assert_eq!(names.reconciled().len(), 2);
+    }
+
+    #[test]
+    fn test_dissolved_states_by_policy() {
+        let data = || {
+            vec![
+                record("Serbia and Montenegro", "primary", 2005, 1.0),
+                record("Serbia and Montenegro", "primary", 2006, 1.0),
+                record("Serbia", "primary", 2006, 1.0),
+                record("Serbia", "primary", 2007, 1.0),
+                record("Montenegro", "primary", 2007, 1.0),
+            ]
+        };
+        let countries = |dissolved| {
+            let options = Options {
+                dissolved,
+                ..Options::default()
+            };
+            let mut names = Names::new(ReferenceData::bundled(), options);
+            let mut data = data();
+            names.apply_all(&mut data);
+            let countries: Vec<(String, u32)> = data
+                .into_iter()
+                .map(|record| (record.country_or_area, record.year))
+                .collect();
+            (countries, names)
+        };
+
+        let (kept, names) = countries(Dissolved::Keep);
+        assert_eq!(kept.len(), 5);
+        assert!(names.unknown().is_empty());
+        let (dropped, _) = countries(Dissolved::Drop);
+        assert_eq!(dropped.len(), 3);
+        // Serbia continues Serbia and Montenegro; 2006 is kept once
+        let (merged, names) = countries(Dissolved::Successor);
+        let serbia: Vec<u32> = merged
+            .iter()
+            .filter(|(country, _)| country == "Serbia")
+            .map(|&(_, year)| year)
+            .collect();
+        assert_eq!(serbia, [2005, 2006, 2007]);
+        assert_eq!(merged.len(), 4);
+        assert_eq!(names.merged()["Serbia and Montenegro"], "Serbia");
+
+        let table = names_table(&ReferenceData::bundled(), ["Sudan [former]"]);
+        assert_eq!(table.rows[0][1], "dissolved");
+        assert_eq!(table.rows[0][5], "Sudan");
+    }
+}
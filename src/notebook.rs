@@ -0,0 +1,239 @@
+use std::f64::consts::PI;
+use std::fmt::Write as _;
+
+use crate::chart::xml_escape;
+use crate::labels;
+use crate::Graph;
+
+// Rich displays of a clustering: an HTML table of clusters and an inline SVG
+// drawing of the graph. `export --html` writes both to a standalone page;
+// with the `evcxr` feature they also render inline in the evcxr Jupyter
+// kernel.
+
+// Colours cycled through by cluster index.
+pub const PALETTE: &[&str] = &[
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+    "#bcbd22", "#17becf",
+];
+
+// Edges drawn per node at most; dense graphs are unreadable otherwise.
+const EDGES_PER_NODE: usize = 3;
+
+pub struct ClusterTable<'a> {
+    pub graph: &'a Graph,
+    pub clusters: &'a [Vec<usize>],
+}
+
+impl ClusterTable<'_> {
+    pub fn to_html(&self) -> String {
+        let labels = labels::cluster_labels(self.graph, self.clusters);
+        let mut html = String::from(
+            "<table>\n<thead><tr><th>Cluster</th><th>Medoid</th><th>Size</th>\
+             <th>Members</th></tr></thead>\n<tbody>\n",
+        );
+        for (index, (cluster, label)) in self.clusters.iter().zip(&labels).enumerate() {
+            let members: Vec<String> = cluster
+                .iter()
+                .map(|&node| xml_escape(&self.graph.nodes[node]))
+                .collect();
+            let _ = writeln!(
+                html,
+                "<tr><td><span style=\"color:{}\">&#9679;</span> {}</td><td>{}</td>\
+                 <td>{}</td><td>{}</td></tr>",
+                PALETTE[index % PALETTE.len()],
+                index,
+                xml_escape(label),
+                cluster.len(),
+                members.join(", ")
+            );
+        }
+        html.push_str("</tbody>\n</table>\n");
+        html
+    }
+
+    #[cfg(feature = "evcxr")]
+    pub fn evcxr_display(&self) {
+        evcxr_output("text/html", &self.to_html());
+    }
+}
+
+// Nodes on a circle, grouped and coloured by cluster, with each node's
+// strongest edges drawn more opaque the heavier they are.
+pub struct GraphSvg<'a> {
+    pub graph: &'a Graph,
+    pub clusters: &'a [Vec<usize>],
+    pub size: f64,
+}
+
+impl GraphSvg<'_> {
+    pub fn to_svg(&self) -> String {
+        let n = self.graph.nodes.len();
+        let mut cluster_of = vec![None; n];
+        // Cluster members first, in cluster order, then anything unclustered
+        let mut order: Vec<usize> = Vec::with_capacity(n);
+        for (index, cluster) in self.clusters.iter().enumerate() {
+            for &node in cluster {
+                if cluster_of[node].is_none() {
+                    cluster_of[node] = Some(index);
+                    order.push(node);
+                }
+            }
+        }
+        order.extend((0..n).filter(|&node| cluster_of[node].is_none()));
+
+        let center = self.size / 2.0;
+        let radius = self.size * 0.38;
+        let mut position = vec![(center, center); n];
+        for (slot, &node) in order.iter().enumerate() {
+            let angle = 2.0 * PI * slot as f64 / n.max(1) as f64 - PI / 2.0;
+            position[node] = (center + radius * angle.cos(), center + radius * angle.sin());
+        }
+
+        let edges = self.strongest_edges();
+        let heaviest = edges
+            .iter()
+            .map(|&(_, _, weight)| weight)
+            .fold(0.0, f64::max);
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{s}\" height=\"{s}\" \
+             viewBox=\"0 0 {s} {s}\" font-family=\"sans-serif\" font-size=\"10\">",
+            s = self.size
+        );
+        for &(a, b, weight) in &edges {
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#555\" \
+                 stroke-opacity=\"{:.2}\"/>",
+                position[a].0,
+                position[a].1,
+                position[b].0,
+                position[b].1,
+                0.1 + 0.7 * weight / heaviest
+            );
+        }
+        for node in 0..n {
+            let colour = cluster_of[node].map_or("#cccccc", |index| PALETTE[index % PALETTE.len()]);
+            let (x, y) = position[node];
+            let _ = writeln!(
+                svg,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"5\" fill=\"{}\">\
+                 <title>{}</title></circle>",
+                x,
+                y,
+                colour,
+                xml_escape(&self.graph.nodes[node])
+            );
+            // Labels sit just outside the circle, on the node's side
+            let anchor = if x < center { "end" } else { "start" };
+            let (label_x, label_y) = (
+                center + (x - center) * 1.06,
+                center + (y - center) * 1.06 + 3.0,
+            );
+            let _ = writeln!(
+                svg,
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"{}\">{}</text>",
+                label_x,
+                label_y,
+                anchor,
+                xml_escape(&self.graph.nodes[node])
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    // Each node's `EDGES_PER_NODE` heaviest positive edges, as deduplicated
+    // (a, b, weight) pairs with a < b. Directed weights use the larger
+    // direction.
+    fn strongest_edges(&self) -> Vec<(usize, usize, f64)> {
+        let matrix = &self.graph.adjacency_matrix;
+        let n = self.graph.nodes.len();
+        let weight = |a: usize, b: usize| matrix[a][b].max(matrix[b][a]);
+        let mut edges = Vec::new();
+        for a in 0..n {
+            let mut neighbours: Vec<usize> =
+                (0..n).filter(|&b| b != a && weight(a, b) > 0.0).collect();
+            neighbours.sort_by(|&x, &y| weight(a, y).total_cmp(&weight(a, x)));
+            for &b in neighbours.iter().take(EDGES_PER_NODE) {
+                edges.push((a.min(b), a.max(b), weight(a, b)));
+            }
+        }
+        edges.sort_by_key(|&(a, b, _)| (a, b));
+        edges.dedup_by(|x, y| (x.0, x.1) == (y.0, y.1));
+        edges
+    }
+
+    #[cfg(feature = "evcxr")]
+    pub fn evcxr_display(&self) {
+        evcxr_output("image/svg+xml", &self.to_svg());
+    }
+}
+
+// A standalone page with the cluster table above the graph drawing.
+pub fn html_report(title: &str, graph: &Graph, clusters: &[Vec<usize>]) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>body {{ font-family: sans-serif; }} \
+         table {{ border-collapse: collapse; }} \
+         td, th {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}</style>\n\
+         </head>\n<body>\n<h1>{title}</h1>\n{}{}</body>\n</html>\n",
+        ClusterTable { graph, clusters }.to_html(),
+        GraphSvg {
+            graph,
+            clusters,
+            size: 720.0,
+        }
+        .to_svg(),
+        title = xml_escape(title)
+    )
+}
+
+// The evcxr display protocol: tagged content blocks on stdout.
+#[cfg(feature = "evcxr")]
+fn evcxr_output(mime_type: &str, content: &str) {
+    println!(
+        "EVCXR_BEGIN_CONTENT {}\n{}\nEVCXR_END_CONTENT",
+        mime_type, content
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cluster_table_and_graph_svg() {
+        let graph = Graph {
+            nodes: vec!["Chad".into(), "Mali".into(), "Côte <d>".into()],
+            adjacency_matrix: vec![
+                vec![0.0, 2.0, 0.0],
+                vec![2.0, 0.0, 1.0],
+                vec![0.0, 1.0, 0.0],
+            ],
+        };
+        let clusters = vec![vec![0, 1], vec![2]];
+
+        let html = ClusterTable {
+            graph: &graph,
+            clusters: &clusters,
+        }
+        .to_html();
+        assert!(html.contains("<td>Chad</td><td>2</td><td>Chad, Mali</td>"));
+        assert!(html.contains("Côte &lt;d&gt;"));
+
+        let svg = GraphSvg {
+            graph: &graph,
+            clusters: &clusters,
+            size: 300.0,
+        }
+        .to_svg();
+        assert_eq!(svg.matches("<circle").count(), 3);
+        // Chad-Mali and Mali-Côte; no edge where the weight is zero
+        assert_eq!(svg.matches("<line").count(), 2);
+
+        let page = html_report("Clusters", &graph, &clusters);
+        assert!(page.starts_with("<!DOCTYPE html>") && page.contains("<svg "));
+    }
+}
@@ -0,0 +1,111 @@
+use std::env;
+use std::fs;
+use std::io;
+
+use crate::console::note;
+use crate::http;
+use crate::json::Json;
+
+// Completion notices for downstream systems: a webhook receiving a JSON
+// body, a `<output>.done` marker file next to the output, or both. Markers
+// are written whole under a temporary name and renamed into place, so a
+// watcher never sees a partial one.
+//
+// Batch commands take these from the environment, so schedulers can set
+// them once for every step:
+//   DS210_NOTIFY_URL   http:// endpoint POSTed the run manifest
+//   DS210_DONE_MARKER  when set (and not "0"), write `<output>.done`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Notifier {
+    pub webhook: Option<String>,
+    pub marker: bool,
+}
+
+impl Notifier {
+    pub fn from_env() -> Notifier {
+        Notifier {
+            webhook: env::var("DS210_NOTIFY_URL")
+                .ok()
+                .filter(|url| !url.is_empty()),
+            marker: env::var("DS210_DONE_MARKER").is_ok_and(|value| value != "0"),
+        }
+    }
+
+    // Announce a finished output. A marker that cannot be written fails the
+    // run, since watchers would wait on it forever; a webhook that cannot be
+    // reached only warns, as the output itself is fine.
+    pub fn completed(&self, output_path: &str, body: &Json) -> io::Result<()> {
+        let mut text = body.to_pretty_string();
+        text.push('\n');
+        if self.marker {
+            let marker = marker_path(output_path);
+            let partial = format!("{}.partial", marker);
+            fs::write(&partial, &text)?;
+            fs::rename(&partial, &marker)?;
+        }
+        if let Some(url) = &self.webhook {
+            self.post(url, &text);
+        }
+        Ok(())
+    }
+
+    // POST a body to the webhook, warning on stderr if that fails.
+    pub fn post(&self, url: &str, text: &str) {
+        if let Err(error) = http::post(url, "application/json", text.as_bytes()) {
+            note!("Warning: could not notify {}: {}", url, error);
+        }
+    }
+}
+
+pub fn marker_path(output_path: &str) -> String {
+    format!("{}.done", output_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_marker_and_webhook() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            String::from_utf8(body).unwrap()
+        });
+
+        let output = std::env::temp_dir().join(format!("ds210-notify-{}.csv", std::process::id()));
+        let output = output.to_str().unwrap();
+        let notifier = Notifier {
+            webhook: Some(format!("http://127.0.0.1:{}/hook", port)),
+            marker: true,
+        };
+        let body = Json::object().with("status", "completed");
+        notifier.completed(output, &body).unwrap();
+
+        assert!(server.join().unwrap().contains("\"status\": \"completed\""));
+        let marker = fs::read_to_string(marker_path(output)).unwrap();
+        assert_eq!(marker, body.to_pretty_string() + "\n");
+        fs::remove_file(marker_path(output)).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use std::io::{self, Write};
+
+// NumPy's .npy format (version 1.0) for little-endian float64 arrays in C
+// order, and .npz archives of them: an uncompressed zip with one
+// `<name>.npy` member per array, readable by `numpy.load`.
+
+const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+
+// Serialize `values` (row-major, `shape.iter().product()` long) as .npy.
+pub fn npy_bytes(shape: &[usize], values: &[f64]) -> Vec<u8> {
+    debug_assert_eq!(shape.iter().product::<usize>(), values.len());
+    let shape = match shape {
+        [length] => format!("({},)", length),
+        dimensions => format!(
+            "({})",
+            dimensions
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': {}, }}",
+        shape
+    );
+    // Pad with spaces so the data starts on a 64-byte boundary
+    let unpadded = MAGIC.len() + 2 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    let mut bytes = Vec::with_capacity(MAGIC.len() + 2 + header.len() + 8 * values.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    for value in values {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+// A square or rectangular matrix as a 2-d array.
+pub fn matrix_bytes(rows: &[Vec<f64>]) -> Vec<u8> {
+    let columns = rows.first().map_or(0, Vec::len);
+    let values: Vec<f64> = rows.iter().flatten().copied().collect();
+    npy_bytes(&[rows.len(), columns], &values)
+}
+
+// Write `(name, .npy bytes)` members as a stored (uncompressed) zip.
+pub fn write_npz(writer: &mut dyn Write, arrays: &[(&str, Vec<u8>)]) -> io::Result<()> {
+    // 1980-01-01 00:00, the earliest DOS timestamp, keeps archives reproducible
+    const DOS_TIME: u16 = 0;
+    const DOS_DATE: u16 = (1 << 5) | 1;
+
+    let mut central = Vec::new();
+    let mut offset: u32 = 0;
+    for (name, data) in arrays {
+        let file_name = format!("{}.npy", name);
+        let crc = crc32(data);
+        let size = u32::try_from(data.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "array too large for npz"))?;
+
+        let mut local = Vec::new();
+        local.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        local.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        local.extend_from_slice(&0u16.to_le_bytes()); // flags
+        local.extend_from_slice(&0u16.to_le_bytes()); // stored
+        local.extend_from_slice(&DOS_TIME.to_le_bytes());
+        local.extend_from_slice(&DOS_DATE.to_le_bytes());
+        local.extend_from_slice(&crc.to_le_bytes());
+        local.extend_from_slice(&size.to_le_bytes()); // compressed
+        local.extend_from_slice(&size.to_le_bytes()); // uncompressed
+        local.extend_from_slice(&(file_name.len() as u16).to_le_bytes());
+        local.extend_from_slice(&0u16.to_le_bytes()); // extra field
+        local.extend_from_slice(file_name.as_bytes());
+        writer.write_all(&local)?;
+        writer.write_all(data)?;
+
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        central.extend_from_slice(&local[4..30]);
+        central.extend_from_slice(&0u16.to_le_bytes()); // comment
+        central.extend_from_slice(&0u16.to_le_bytes()); // disk
+        central.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+        central.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(file_name.as_bytes());
+
+        offset = offset
+            .checked_add((local.len() + data.len()) as u32)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "npz too large"))?;
+    }
+
+    writer.write_all(&central)?;
+    let count = arrays.len() as u16;
+    let mut end = Vec::new();
+    end.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes()); // this disk
+    end.extend_from_slice(&0u16.to_le_bytes()); // central directory disk
+    end.extend_from_slice(&count.to_le_bytes());
+    end.extend_from_slice(&count.to_le_bytes());
+    end.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    end.extend_from_slice(&offset.to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes()); // comment
+    writer.write_all(&end)
+}
+
+// CRC-32 (IEEE), as zip requires.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_npy_header_and_npz_layout() {
+        let bytes = matrix_bytes(&[vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]);
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+        assert!(header.starts_with("{'descr': '<f8', 'fortran_order': False, 'shape': (2, 3), }"));
+        assert!(header.ends_with('\n'));
+        assert_eq!(bytes.len(), 10 + header_len + 6 * 8);
+        assert_eq!(&bytes[bytes.len() - 8..], &6.0f64.to_le_bytes());
+        assert!(String::from_utf8_lossy(&npy_bytes(&[2], &[0.0, 1.0])).contains("'shape': (2,)"));
+
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let mut archive = Vec::new();
+        write_npz(&mut archive, &[("features", bytes.clone())]).unwrap();
+        assert_eq!(&archive[..4], b"PK\x03\x04");
+        assert_eq!(&archive[30..42], b"features.npy");
+        assert_eq!(&archive[42..42 + bytes.len()], &bytes[..]);
+        // End of central directory: one entry
+        let end = &archive[archive.len() - 22..];
+        assert_eq!(&end[..4], b"PK\x05\x06");
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 1);
+    }
+}
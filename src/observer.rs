@@ -0,0 +1,213 @@
+use std::io::Write;
+
+use crate::cancel::{CancelToken, RunStatus};
+use crate::json::Json;
+
+// Progress report from an iterative stage, sent after every iteration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Iteration<'a> {
+    pub stage: &'a str,
+    // 1-based
+    pub iteration: usize,
+    // Upper bound on iterations, when the stage knows it up front
+    pub total: Option<usize>,
+    // Current value of whatever the stage optimizes (inertia, modularity, ...)
+    pub objective: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Control {
+    Continue,
+    // Stop after this iteration and return the partial result
+    Stop(RunStatus),
+}
+
+// Called by iterative stages once per iteration. Observers can display
+// progress, record the objective for convergence plots, or end the run
+// early by returning `Control::Stop`.
+pub trait IterationObserver {
+    fn on_iteration(&mut self, iteration: &Iteration) -> Control;
+}
+
+// For callers with no use for the iterations: every one continues.
+pub struct Unobserved;
+
+impl IterationObserver for Unobserved {
+    fn on_iteration(&mut self, _iteration: &Iteration) -> Control {
+        Control::Continue
+    }
+}
+
+// The cancellation token is itself an observer: Ctrl-C or an expired
+// --timeout stops the stage at the next iteration boundary.
+impl IterationObserver for CancelToken {
+    fn on_iteration(&mut self, _iteration: &Iteration) -> Control {
+        match self.status() {
+            RunStatus::Completed => Control::Continue,
+            status => Control::Stop(status),
+        }
+    }
+}
+
+// Fan out to several observers. Every observer sees every iteration; the
+// first one asking to stop decides the reason.
+impl IterationObserver for Vec<&mut dyn IterationObserver> {
+    fn on_iteration(&mut self, iteration: &Iteration) -> Control {
+        let mut control = Control::Continue;
+        for observer in self.iter_mut() {
+            let next = observer.on_iteration(iteration);
+            if control == Control::Continue {
+                control = next;
+            }
+        }
+        control
+    }
+}
+
+// Objective value per iteration of every stage, in the order stages ran.
+// Written to the manifest so users can check convergence and choose
+// iteration counts.
+#[derive(Debug, Default)]
+pub struct ObjectiveTrace {
+    stages: Vec<(String, Vec<f64>)>,
+}
+
+impl ObjectiveTrace {
+    pub fn new() -> ObjectiveTrace {
+        ObjectiveTrace::default()
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::Object(
+            self.stages
+                .iter()
+                .map(|(stage, values)| (stage.clone(), trace_json(values)))
+                .collect(),
+        )
+    }
+}
+
+// Summary diagnostics first, then the full trace. `last_change` is the
+// objective difference between the final two iterations; a value near zero
+// suggests the stage had converged.
+fn trace_json(values: &[f64]) -> Json {
+    let last_change = match values {
+        [.., previous, last] => Some(last - previous),
+        _ => None,
+    };
+    Json::object()
+        .with("iterations", values.len())
+        .with("final", values.last().copied())
+        .with("last_change", last_change)
+        .with("objective", values.to_vec())
+}
+
+impl IterationObserver for ObjectiveTrace {
+    fn on_iteration(&mut self, iteration: &Iteration) -> Control {
+        match self.stages.last_mut() {
+            Some((stage, values)) if stage == iteration.stage => values.push(iteration.objective),
+            _ => self
+                .stages
+                .push((iteration.stage.to_string(), vec![iteration.objective])),
+        }
+        Control::Continue
+    }
+}
+
+// Write one line per iteration, e.g. `[sweep] 3/12 objective=0.4180`.
+pub struct ProgressPrinter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> ProgressPrinter<W> {
+    pub fn new(writer: W) -> ProgressPrinter<W> {
+        ProgressPrinter { writer }
+    }
+}
+
+impl<W: Write> IterationObserver for ProgressPrinter<W> {
+    fn on_iteration(&mut self, iteration: &Iteration) -> Control {
+        let position = match iteration.total {
+            Some(total) => format!("{}/{}", iteration.iteration, total),
+            None => iteration.iteration.to_string(),
+        };
+        // Progress output is best-effort; a closed stderr must not end the run
+        let _ = writeln!(
+            self.writer,
+            "[{}] {} objective={:.4}",
+            iteration.stage, position, iteration.objective
+        );
+        Control::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    struct StopAfter(usize);
+
+    impl IterationObserver for StopAfter {
+        fn on_iteration(&mut self, iteration: &Iteration) -> Control {
+            if iteration.iteration >= self.0 {
+                Control::Stop(RunStatus::Interrupted)
+            } else {
+                Control::Continue
+            }
+        }
+    }
+
+    fn iteration(number: usize) -> Iteration<'static> {
+        Iteration {
+            stage: "test",
+            iteration: number,
+            total: Some(3),
+            objective: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_progress_printer_format() {
+        let mut printer = ProgressPrinter::new(Vec::new());
+        assert_eq!(printer.on_iteration(&iteration(2)), Control::Continue);
+        assert_eq!(
+            String::from_utf8(printer.writer).unwrap(),
+            "[test] 2/3 objective=0.5000\n"
+        );
+    }
+
+    #[test]
+    fn test_objective_trace_diagnostics() {
+        let mut trace = ObjectiveTrace::new();
+        for (number, objective) in [0.25, 0.5, 0.75].into_iter().enumerate() {
+            trace.on_iteration(&Iteration {
+                objective,
+                ..iteration(number + 1)
+            });
+        }
+
+        assert_eq!(
+            trace.to_json().to_string(),
+            "{\"test\": {\"iterations\": 3, \"final\": 0.75, \"last_change\": 0.25, \"objective\": [0.25, 0.5, 0.75]}}"
+        );
+    }
+
+    #[test]
+    fn test_chain_notifies_all_and_first_stop_wins() {
+        let mut stop_after = StopAfter(2);
+        let mut cancel = CancelToken::new().with_timeout(Duration::ZERO);
+        let mut observers: Vec<&mut dyn IterationObserver> = vec![&mut stop_after, &mut cancel];
+
+        // The expired token stops the first iteration...
+        assert_eq!(
+            observers.on_iteration(&iteration(1)),
+            Control::Stop(RunStatus::TimedOut)
+        );
+        // ...and earlier observers take precedence once they ask too
+        assert_eq!(
+            observers.on_iteration(&iteration(2)),
+            Control::Stop(RunStatus::Interrupted)
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::io;
+use std::str::FromStr;
+
+use crate::Graph;
+
+pub const ORDERS: &[&str] = &["name", "id"];
+
+// How printers and exporters order nodes (matrix rows and columns) and the
+// members of each cluster. Either key is total, so output never depends on
+// the order the algorithm happened to produce. Cluster order itself is left
+// alone: cluster indices are meaningful (see `labels::align_to_previous`).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NodeOrder {
+    // Alphabetically by country or area name
+    #[default]
+    Name,
+    // By node index, i.e. first appearance in the input data
+    Id,
+}
+
+impl FromStr for NodeOrder {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<NodeOrder> {
+        match value {
+            "name" => Ok(NodeOrder::Name),
+            "id" => Ok(NodeOrder::Id),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown order `{}`; expected one of {}",
+                    other,
+                    ORDERS.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+// Renumber the graph's nodes in the requested order and sort every
+// cluster's members to match.
+pub fn ordered(
+    graph: &Graph,
+    clusters: &[Vec<usize>],
+    order: NodeOrder,
+) -> (Graph, Vec<Vec<usize>>) {
+    let mut permutation: Vec<usize> = (0..graph.nodes.len()).collect();
+    if order == NodeOrder::Name {
+        permutation.sort_by(|&a, &b| graph.nodes[a].cmp(&graph.nodes[b]).then(a.cmp(&b)));
+    }
+    let mut new_index = vec![0; permutation.len()];
+    for (position, &old_index) in permutation.iter().enumerate() {
+        new_index[old_index] = position;
+    }
+
+    let graph = Graph {
+        nodes: permutation
+            .iter()
+            .map(|&i| graph.nodes[i].clone())
+            .collect(),
+        adjacency_matrix: permutation
+            .iter()
+            .map(|&i| {
+                permutation
+                    .iter()
+                    .map(|&j| graph.adjacency_matrix[i][j])
+                    .collect()
+            })
+            .collect(),
+    };
+    let clusters = clusters
+        .iter()
+        .map(|cluster| {
+            let mut members: Vec<usize> = cluster.iter().map(|&i| new_index[i]).collect();
+            members.sort_unstable();
+            members
+        })
+        .collect();
+    (graph, clusters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordered_by_name_and_id() {
+        let graph = Graph {
+            nodes: vec!["Peru".to_string(), "Chad".to_string(), "Mali".to_string()],
+            adjacency_matrix: vec![
+                vec![0.0, 1.0, 2.0],
+                vec![1.0, 0.0, 3.0],
+                vec![2.0, 3.0, 0.0],
+            ],
+        };
+        let clusters = vec![vec![2, 0, 1]];
+
+        let (by_name, members) = ordered(&graph, &clusters, NodeOrder::Name);
+        assert_eq!(by_name.nodes, vec!["Chad", "Mali", "Peru"]);
+        assert_eq!(by_name.adjacency_matrix[0], vec![0.0, 3.0, 1.0]);
+        assert_eq!(members, vec![vec![0, 1, 2]]);
+
+        let (by_id, members) = ordered(&graph, &clusters, NodeOrder::Id);
+        assert_eq!(by_id.nodes, graph.nodes);
+        assert_eq!(by_id.adjacency_matrix, graph.adjacency_matrix);
+        assert_eq!(members, vec![vec![0, 1, 2]]);
+    }
+}
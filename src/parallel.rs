@@ -0,0 +1,118 @@
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+// How much of the machine the tiled loops (pairwise distances and the like)
+// may use: at most `threads` scoped threads, each taking `chunk_size` rows
+// at a time off a shared counter. The CLI sets it from `--threads` and
+// `--chunk-size`; programs embedding the crate, such as a server sharing its
+// host, build one themselves to bound CPU usage.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Parallelism {
+    pub threads: usize,
+    pub chunk_size: usize,
+}
+
+const DEFAULT_CHUNK_SIZE: usize = 16;
+
+// Every available core, 16 rows at a time.
+impl Default for Parallelism {
+    fn default() -> Parallelism {
+        Parallelism {
+            threads: thread::available_parallelism().map_or(1, |count| count.get()),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+impl Parallelism {
+    // Everything on the calling thread.
+    pub fn sequential() -> Parallelism {
+        Parallelism {
+            threads: 1,
+            ..Parallelism::default()
+        }
+    }
+
+    // The default with either setting overridden; zero is refused.
+    pub fn new(threads: Option<usize>, chunk_size: Option<usize>) -> io::Result<Parallelism> {
+        let defaults = Parallelism::default();
+        let parallelism = Parallelism {
+            threads: threads.unwrap_or(defaults.threads),
+            chunk_size: chunk_size.unwrap_or(defaults.chunk_size),
+        };
+        if parallelism.threads == 0 || parallelism.chunk_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--threads and --chunk-size must be at least 1",
+            ));
+        }
+        Ok(parallelism)
+    }
+
+    // `row(i)` for every i in 0..count, in order. Rows are handed out in
+    // chunks, so uneven rows still spread across the threads; a loop too
+    // small to fill two chunks runs on the calling thread.
+    pub fn map_rows<T, F>(&self, count: usize, row: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(usize) -> T + Sync,
+    {
+        let chunk_size = self.chunk_size.max(1);
+        let chunks = count.div_ceil(chunk_size);
+        let threads = self.threads.min(chunks);
+        if threads <= 1 {
+            return (0..count).map(row).collect();
+        }
+        let next = AtomicUsize::new(0);
+        let mut done: Vec<(usize, Vec<T>)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut rows = Vec::new();
+                        loop {
+                            let chunk = next.fetch_add(1, Ordering::Relaxed);
+                            if chunk >= chunks {
+                                break rows;
+                            }
+                            let start = chunk * chunk_size;
+                            let end = (start + chunk_size).min(count);
+                            rows.push((chunk, (start..end).map(&row).collect()));
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        });
+        done.sort_by_key(|&(chunk, _)| chunk);
+        done.into_iter().flat_map(|(_, rows)| rows).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_keep_their_order() {
+        let square = |i: usize| i * i;
+        let expected: Vec<usize> = (0..100).map(square).collect();
+        for threads in [1, 3, 8] {
+            let parallelism = Parallelism {
+                threads,
+                chunk_size: 7,
+            };
+            assert_eq!(parallelism.map_rows(100, square), expected);
+        }
+        assert!(Parallelism::sequential().map_rows(0, square).is_empty());
+        assert!(Parallelism::new(Some(0), None).is_err());
+        assert_eq!(Parallelism::new(Some(2), Some(4)).unwrap().threads, 2);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+use std::io;
+use std::str::FromStr;
+
+use crate::centrality;
+use crate::table::Table;
+use crate::Graph;
+
+// Shortest paths between two countries. Weights are similarities, so a
+// heavy link is a short one; how long is set by the length:
+//
+//   inverse     1 / w, as closeness and betweenness take it
+//   complement  1 - w, for similarities in [0, 1] such as cosine
+//   log         -ln w, so that a path's length is minus the log of the
+//               product of its weights
+//   hops        1 per link whatever its weight (breadth-first search)
+//
+// The graph is read as undirected, as for centrality: each pair weighs the
+// mean of its two directions, and only pairs weighing more than zero are
+// linked. Lengths that would come out negative (weights above 1 under
+// `complement` or `log`) count as 0.
+
+pub const LENGTHS: &[&str] = &["inverse", "complement", "log", "hops"];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Length {
+    #[default]
+    Inverse,
+    Complement,
+    Log,
+    Hops,
+}
+
+impl FromStr for Length {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Length> {
+        match value {
+            "inverse" => Ok(Length::Inverse),
+            "complement" => Ok(Length::Complement),
+            "log" => Ok(Length::Log),
+            "hops" => Ok(Length::Hops),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown path length `{}`; expected one of {}",
+                    other,
+                    LENGTHS.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+impl Length {
+    // The length of a link of weight `weight` (more than zero).
+    pub fn of(self, weight: f64) -> f64 {
+        match self {
+            Length::Inverse => 1.0 / weight,
+            Length::Complement => (1.0 - weight).max(0.0),
+            Length::Log => (-weight.ln()).max(0.0),
+            Length::Hops => 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    // From the first country to the second, both included
+    pub nodes: Vec<usize>,
+    pub cost: f64,
+}
+
+// The shortest path from `from` to `to`, None when they are not connected.
+// Of several equally short paths the one through lower-numbered nodes is
+// taken.
+pub fn shortest_path(graph: &Graph, from: usize, to: usize, length: Length) -> Option<Path> {
+    let weights = centrality::undirected(graph);
+    let previous = match length {
+        Length::Hops => breadth_first(&weights, from),
+        length => dijkstra(&weights, from, to, length),
+    };
+    let mut nodes = vec![to];
+    while let Some(&node) = nodes.last() {
+        if node == from {
+            break;
+        }
+        nodes.push(previous[node]?);
+    }
+    nodes.reverse();
+    let cost = nodes
+        .windows(2)
+        .map(|pair| length.of(weights[pair[0]][pair[1]]))
+        .sum();
+    Some(Path { nodes, cost })
+}
+
+// Each node's predecessor on a fewest-links path from `source`.
+fn breadth_first(weights: &[Vec<f64>], source: usize) -> Vec<Option<usize>> {
+    let mut previous = vec![None; weights.len()];
+    let mut seen = vec![false; weights.len()];
+    seen[source] = true;
+    let mut queue = VecDeque::from([source]);
+    while let Some(node) = queue.pop_front() {
+        for (next, &weight) in weights[node].iter().enumerate() {
+            if weight > 0.0 && !seen[next] {
+                seen[next] = true;
+                previous[next] = Some(node);
+                queue.push_back(next);
+            }
+        }
+    }
+    previous
+}
+
+// Each settled node's predecessor on a shortest path from `source`,
+// stopping once `target` is settled.
+fn dijkstra(
+    weights: &[Vec<f64>],
+    source: usize,
+    target: usize,
+    length: Length,
+) -> Vec<Option<usize>> {
+    let size = weights.len();
+    let mut distance = vec![f64::INFINITY; size];
+    let mut previous = vec![None; size];
+    let mut done = vec![false; size];
+    distance[source] = 0.0;
+    while let Some(node) = (0..size)
+        .filter(|&node| !done[node] && distance[node].is_finite())
+        .min_by(|&a, &b| distance[a].total_cmp(&distance[b]))
+    {
+        done[node] = true;
+        if node == target {
+            break;
+        }
+        for (next, &weight) in weights[node].iter().enumerate() {
+            if weight <= 0.0 || done[next] {
+                continue;
+            }
+            let through = distance[node] + length.of(weight);
+            if through < distance[next] {
+                distance[next] = through;
+                previous[next] = Some(node);
+            }
+        }
+    }
+    previous
+}
+
+// One row per country along the path, with the link that leads to it.
+pub fn path_table(graph: &Graph, path: &Path, length: Length) -> Table {
+    let weights = centrality::undirected(graph);
+    let mut table = Table::new(&["step", "country", "weight", "length", "total"]);
+    let mut total = 0.0;
+    for (step, &node) in path.nodes.iter().enumerate() {
+        let (weight, link) = match step {
+            0 => (String::new(), String::new()),
+            _ => {
+                let weight = weights[path.nodes[step - 1]][node];
+                total += length.of(weight);
+                (
+                    format!("{:.4}", weight),
+                    format!("{:.4}", length.of(weight)),
+                )
+            }
+        };
+        table.push_row(vec![
+            step.to_string(),
+            graph.nodes[node].clone(),
+            weight,
+            link,
+            format!("{:.4}", total),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shortest_paths_by_length() {
+        // A strong two-link detour around a weak direct link, and an
+        // isolated node
+        let graph = Graph {
+            nodes: ["France", "Mali", "Kenya", "Peru"]
+                .map(str::to_string)
+                .to_vec(),
+            adjacency_matrix: vec![
+                vec![1.0, 0.9, 0.1, 0.0],
+                vec![0.9, 1.0, 0.8, 0.0],
+                vec![0.1, 0.8, 1.0, 0.0],
+                vec![0.0, 0.0, 0.0, 1.0],
+            ],
+        };
+        let weighted = shortest_path(&graph, 0, 2, Length::Inverse).unwrap();
+        assert_eq!(weighted.nodes, [0, 1, 2]);
+        assert!((weighted.cost - (1.0 / 0.9 + 1.0 / 0.8)).abs() < 1e-12);
+        let hops = shortest_path(&graph, 0, 2, Length::Hops).unwrap();
+        assert_eq!((hops.nodes, hops.cost), (vec![0, 2], 1.0));
+        let complement = shortest_path(&graph, 0, 2, Length::Complement).unwrap();
+        assert!((complement.cost - 0.3).abs() < 1e-12);
+
+        assert_eq!(shortest_path(&graph, 0, 3, Length::Inverse), None);
+        let itself = shortest_path(&graph, 1, 1, Length::Log).unwrap();
+        assert_eq!((itself.nodes, itself.cost), (vec![1], 0.0));
+
+        let table = path_table(&graph, &weighted, Length::Inverse);
+        assert_eq!(table.rows[2][1..], ["Kenya", "0.8000", "1.2500", "2.3611"]);
+        assert!("shortest".parse::<Length>().is_err());
+    }
+}
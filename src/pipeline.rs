@@ -0,0 +1,169 @@
+use std::io;
+
+use crate::config::{Config, Value};
+
+// A `pipeline.toml` for `run --pipeline`, so one file holds a whole
+// load -> graph -> cluster -> report setup that is run again and again:
+//
+//   preset = "balanced"
+//
+//   [input]
+//   path = "SYB66_309_202310_Education.csv"
+//   skip_rows = 1
+//
+//   [filters]
+//   years = "2010..2020"
+//   series = ["Gross enrollment ratio - Primary (male)", "..."]
+//
+//   [graph]
+//   similarity = "cosine"
+//   min_weight = 0.2
+//
+//   [cluster]
+//   algo = "agglomerative"
+//   clusters = 6
+//
+//   [output]
+//   report = "clusters.html"
+//   format = "html"
+//
+// Every key stands for the `run` option of the same name (with `-` for
+// `_`) and fills it in only when the command line leaves it out; a preset
+// named here fills in what both leave out. `true` sets a flag, arrays are
+// joined into the comma-separated lists the options take, and paths are
+// as they would be on the command line, relative to the working directory.
+
+// (table, key, option); "" is the top level
+const KEYS: &[(&str, &str, &str)] = &[
+    ("", "preset", "preset"),
+    ("", "timeout", "timeout"),
+    ("", "threads", "threads"),
+    ("input", "path", "input"),
+    ("input", "demo", "demo"),
+    ("input", "skip_rows", "skip-rows"),
+    ("input", "header_rows", "header-rows"),
+    ("input", "strict", "strict"),
+    ("input", "iso_codes", "iso-codes"),
+    ("input", "reconcile_names", "reconcile-names"),
+    ("filters", "where", "where"),
+    ("filters", "years", "years"),
+    ("filters", "series", "series"),
+    ("filters", "indicator", "indicator"),
+    ("filters", "aggregates", "aggregates"),
+    ("filters", "dissolved", "dissolved"),
+    ("filters", "sample", "sample"),
+    ("filters", "seed", "seed"),
+    ("filters", "impute", "impute"),
+    ("filters", "trend", "trend"),
+    ("filters", "transforms", "transforms"),
+    ("graph", "similarity", "similarity"),
+    ("graph", "ties", "ties"),
+    ("graph", "normalize", "normalize"),
+    ("graph", "cache_dir", "cache-dir"),
+    ("graph", "symmetric", "symmetric"),
+    ("graph", "no_self_loops", "no-self-loops"),
+    ("graph", "min_weight", "min-weight"),
+    ("graph", "top_k", "top-k"),
+    ("cluster", "algo", "algo"),
+    ("cluster", "clusters", "clusters"),
+    ("cluster", "cutoff", "cutoff"),
+    ("cluster", "order", "order"),
+    ("output", "report", "output"),
+    ("output", "out_dir", "out-dir"),
+    ("output", "run_id", "run-id"),
+    ("output", "format", "format"),
+    ("output", "history", "history"),
+    ("debug", "dump_cleaned", "dump-cleaned"),
+    ("debug", "dump_features", "dump-features"),
+    ("debug", "dump_distances", "dump-distances"),
+];
+
+// The options the file sets, as (option, value) pairs; a key outside
+// `KEYS` is an error rather than silently ignored.
+pub fn options(config: &Config) -> io::Result<Vec<(&'static str, &Value)>> {
+    let mut options = Vec::new();
+    for (name, value) in config.entries() {
+        match value {
+            Value::Table(table) => {
+                if !KEYS.iter().any(|&(section, _, _)| section == name) {
+                    return Err(invalid_data(format!("unknown table [{}]", name)));
+                }
+                for (key, value) in table {
+                    options.push((option(name, key)?, value));
+                }
+            }
+            value => options.push((option("", name)?, value)),
+        }
+    }
+    Ok(options)
+}
+
+fn option(section: &str, key: &str) -> io::Result<&'static str> {
+    KEYS.iter()
+        .find(|&&(table, name, _)| table == section && name == key)
+        .map(|&(_, _, option)| option)
+        .ok_or_else(|| match section {
+            "" => invalid_data(format!("unknown key `{}`", key)),
+            section => invalid_data(format!("unknown key `{}` in [{}]", key, section)),
+        })
+}
+
+// The command-line text of an option's value: arrays become lists.
+pub fn option_value(value: &Value) -> String {
+    match value {
+        Value::Array(values) => values
+            .iter()
+            .map(Value::to_string)
+            .collect::<Vec<_>>()
+            .join(","),
+        value => value.to_string(),
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{find_command, ArgKind};
+
+    #[test]
+    fn test_keys_name_run_options() {
+        let run = find_command("run").unwrap();
+        for &(_, _, option) in KEYS {
+            assert!(
+                run.args
+                    .iter()
+                    .any(|arg| arg.name == option && arg.kind != ArgKind::Positional),
+                "{}",
+                option
+            );
+        }
+
+        let config = Config::parse(
+            "preset = \"quick\"\n[input]\npath = \"a.csv\"\n[filters]\nseries = [\"p\", \"q\"]\n[graph]\nmin_weight = 0.5\n",
+        )
+        .unwrap();
+        let given: Vec<(&str, String)> = options(&config)
+            .unwrap()
+            .into_iter()
+            .map(|(option, value)| (option, option_value(value)))
+            .collect();
+        assert_eq!(
+            given,
+            [
+                ("series", "p,q".to_string()),
+                ("min-weight", "0.5".to_string()),
+                ("input", "a.csv".to_string()),
+                ("preset", "quick".to_string()),
+            ]
+        );
+
+        let misplaced = Config::parse("[graph]\nalgo = \"louvain\"\n").unwrap();
+        let error = options(&misplaced).unwrap_err().to_string();
+        assert_eq!(error, "unknown key `algo` in [graph]");
+        assert!(options(&Config::parse("[plot]\nx = 1\n").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::str::FromStr;
+
+use crate::table::Table;
+use crate::EducationData;
+
+pub const DIMENSIONS: &[&str] = &["country", "year", "indicator", "series"];
+pub const MEASURES: &[&str] = &["value", "year"];
+pub const AGGREGATES: &[&str] = &["mean", "sum", "min", "max", "median", "count"];
+
+// A field observations are grouped by along the rows or columns.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dimension {
+    Country,
+    Year,
+    Indicator,
+    Series,
+}
+
+impl Dimension {
+    fn name(self) -> &'static str {
+        match self {
+            Dimension::Country => "country",
+            Dimension::Year => "year",
+            Dimension::Indicator => "indicator",
+            Dimension::Series => "series",
+        }
+    }
+
+    // Labels sort as text, except years, which sort numerically.
+    fn label(self, record: &EducationData) -> Label {
+        match self {
+            Dimension::Country => Label::Text(record.country_or_area.clone()),
+            Dimension::Year => Label::Year(record.year),
+            Dimension::Indicator => Label::Text(record.indicator.clone()),
+            Dimension::Series => Label::Text(record.series.clone()),
+        }
+    }
+}
+
+impl FromStr for Dimension {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Dimension> {
+        match value {
+            "country" => Ok(Dimension::Country),
+            "year" => Ok(Dimension::Year),
+            "indicator" => Ok(Dimension::Indicator),
+            "series" => Ok(Dimension::Series),
+            other => Err(unknown("dimension", other, DIMENSIONS)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Label {
+    Year(u32),
+    Text(String),
+}
+
+impl Label {
+    fn to_cell(&self) -> String {
+        match self {
+            Label::Year(year) => year.to_string(),
+            Label::Text(text) => text.clone(),
+        }
+    }
+}
+
+// The numeric field that is aggregated into each cell.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Measure {
+    #[default]
+    Value,
+    // E.g. `--values year --agg max` for the latest year reported
+    Year,
+}
+
+impl Measure {
+    fn of(self, record: &EducationData) -> Option<f64> {
+        match self {
+            Measure::Value => record.value,
+            Measure::Year => Some(f64::from(record.year)),
+        }
+    }
+}
+
+impl FromStr for Measure {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Measure> {
+        match value {
+            "value" => Ok(Measure::Value),
+            "year" => Ok(Measure::Year),
+            other => Err(unknown("measure", other, MEASURES)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Aggregate {
+    #[default]
+    Mean,
+    Sum,
+    Min,
+    Max,
+    Median,
+    Count,
+}
+
+impl Aggregate {
+    // Missing values have already been dropped; `values` is never empty.
+    fn apply(self, values: &mut [f64]) -> f64 {
+        match self {
+            Aggregate::Mean => values.iter().sum::<f64>() / values.len() as f64,
+            Aggregate::Sum => values.iter().sum(),
+            Aggregate::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            Aggregate::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Aggregate::Median => {
+                values.sort_by(f64::total_cmp);
+                let middle = values.len() / 2;
+                if values.len().is_multiple_of(2) {
+                    (values[middle - 1] + values[middle]) / 2.0
+                } else {
+                    values[middle]
+                }
+            }
+            Aggregate::Count => values.len() as f64,
+        }
+    }
+}
+
+impl FromStr for Aggregate {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Aggregate> {
+        match value {
+            "mean" => Ok(Aggregate::Mean),
+            "sum" => Ok(Aggregate::Sum),
+            "min" => Ok(Aggregate::Min),
+            "max" => Ok(Aggregate::Max),
+            "median" => Ok(Aggregate::Median),
+            "count" => Ok(Aggregate::Count),
+            other => Err(unknown("aggregate", other, AGGREGATES)),
+        }
+    }
+}
+
+pub struct PivotSpec {
+    pub rows: Dimension,
+    pub cols: Dimension,
+    pub values: Measure,
+    pub agg: Aggregate,
+}
+
+// Cross-tabulate observations: one row per `rows` label, one column per
+// `cols` label, each cell aggregating the measure over the matching records.
+// Cells without any (non-missing) values are left blank.
+pub fn pivot(data: &[EducationData], spec: &PivotSpec) -> Table {
+    let mut cells: BTreeMap<(Label, Label), Vec<f64>> = BTreeMap::new();
+    let mut row_labels = BTreeSet::new();
+    let mut col_labels = BTreeSet::new();
+    for record in data {
+        let (row, col) = (spec.rows.label(record), spec.cols.label(record));
+        row_labels.insert(row.clone());
+        col_labels.insert(col.clone());
+        if let Some(value) = spec.values.of(record) {
+            cells.entry((row, col)).or_default().push(value);
+        }
+    }
+
+    let mut headers = vec![spec.rows.name().to_string()];
+    headers.extend(col_labels.iter().map(Label::to_cell));
+    let mut table = Table {
+        headers,
+        rows: Vec::new(),
+    };
+    for row in row_labels {
+        let mut cells_in_row = vec![row.to_cell()];
+        for col in &col_labels {
+            cells_in_row.push(match cells.get_mut(&(row.clone(), col.clone())) {
+                Some(values) => format_cell(spec.agg.apply(values)),
+                None => String::new(),
+            });
+        }
+        table.push_row(cells_in_row);
+    }
+    table
+}
+
+// Trim trailing zeros so counts and whole years print as integers.
+fn format_cell(value: f64) -> String {
+    let text = format!("{:.4}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn unknown(what: &str, value: &str, expected: &[&str]) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "unknown {} `{}`; expected one of {}",
+            what,
+            value,
+            expected.join(", ")
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    const FEMALE: &str = "Gross enrollment ratio - Primary (female)";
+
+    #[test]
+    fn test_pivot_aggregates_cells() {
+        let data = vec![
+            record("Mali", FEMALE, 2015, Some(70.0)),
+            record("Mali", FEMALE, 2015, Some(75.0)),
+            record("Chad", FEMALE, 2005, Some(60.5)),
+            record("Chad", FEMALE, 2015, None),
+            record("Mali", FEMALE, 2005, None),
+        ];
+        let mut spec = PivotSpec {
+            rows: Dimension::Country,
+            cols: Dimension::Year,
+            values: Measure::Value,
+            agg: Aggregate::Mean,
+        };
+
+        let table = pivot(&data, &spec);
+        assert_eq!(table.headers, ["country", "2005", "2015"]);
+        assert_eq!(table.rows[0], ["Chad", "60.5", ""]);
+        assert_eq!(table.rows[1], ["Mali", "", "72.5"]);
+
+        spec.agg = Aggregate::Count;
+        spec.cols = Dimension::Series;
+        let table = pivot(&data, &spec);
+        assert_eq!(table.rows[0][1], "1");
+        assert_eq!(table.rows[1][1], "2");
+    }
+}
@@ -0,0 +1,90 @@
+// Named bundles of options for `run`, `build` and `cluster`, so a first
+// analysis needs one choice rather than a dozen. A preset only fills in
+// options left off the command line, and only those the command takes:
+// `build --preset quick` sets the graph options, `cluster --preset quick`
+// the clustering ones.
+//
+// Presets set no flags, since a flag cannot be switched back off.
+//
+//   quick     cosine similarity of the latest values, links under 0.5
+//             dropped, Louvain: a sparse graph and the fastest clustering
+//   balanced  cosine similarity with links under 0.2 dropped,
+//             average-linkage clustering
+//   thorough  the full cosine similarity matrix with no links dropped,
+//             average-linkage clustering
+
+pub const PRESETS: &[&str] = &["quick", "balanced", "thorough"];
+
+pub struct Preset {
+    pub name: &'static str,
+    // (option, value) pairs
+    pub options: &'static [(&'static str, &'static str)],
+}
+
+const TABLE: &[Preset] = &[
+    Preset {
+        name: "quick",
+        options: &[
+            ("similarity", "cosine"),
+            ("min-weight", "0.5"),
+            ("algo", "louvain"),
+        ],
+    },
+    Preset {
+        name: "balanced",
+        options: &[
+            ("similarity", "cosine"),
+            ("min-weight", "0.2"),
+            ("algo", "agglomerative"),
+        ],
+    },
+    Preset {
+        name: "thorough",
+        options: &[("similarity", "cosine"), ("algo", "agglomerative")],
+    },
+];
+
+// Options that stand in for a preset's option rather than combine with it:
+// asking for a Hamming, profiled or value graph leaves out the preset's
+// similarity.
+const REPLACED_BY: &[(&str, &str)] = &[
+    ("similarity", "hamming-bins"),
+    ("similarity", "profile"),
+    ("similarity", "value-graph"),
+];
+
+pub fn find(name: &str) -> Option<&'static Preset> {
+    TABLE.iter().find(|preset| preset.name == name)
+}
+
+// Whether the preset's `option` gives way, `given` telling which options
+// are on the command line.
+pub fn replaced(option: &str, given: &dyn Fn(&str) -> bool) -> bool {
+    REPLACED_BY
+        .iter()
+        .any(|&(replaced, by)| replaced == option && given(by))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{find_command, ArgKind};
+
+    #[test]
+    fn test_presets_name_valid_options() {
+        assert_eq!(
+            TABLE.iter().map(|preset| preset.name).collect::<Vec<_>>(),
+            PRESETS
+        );
+        // Every option is one `run` takes, with a value it accepts
+        let run = find_command("run").unwrap();
+        for preset in TABLE {
+            for (name, value) in preset.options {
+                let arg = run.args.iter().find(|arg| arg.name == *name).unwrap();
+                assert!(arg.kind == ArgKind::Option, "{}", name);
+                assert!(arg.possible_values.is_empty() || arg.possible_values.contains(value));
+            }
+        }
+        assert!(find("bogus").is_none());
+    }
+}
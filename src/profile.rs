@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+
+use crate::table::Table;
+use crate::{construct_value_graph_with, EducationData, Graph};
+
+// Per-series statistics gathered while building the graph, to help spot
+// series that add little but noise: few values, no variance, or no weight.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SeriesProfile {
+    pub series: String,
+    pub records: usize,
+    pub missing: usize,
+    // Sample variance of the non-missing values
+    pub variance: Option<f64>,
+    // Total weight this series' records added to edges
+    pub edge_weight: f64,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    records: usize,
+    missing: usize,
+    values: Vec<f64>,
+    edge_weight: f64,
+}
+
+// Build the value graph while attributing edge weight to series. Profiles
+// are ordered by contribution, largest first, ties by series name.
+pub fn profile_series(data: &[EducationData]) -> (Graph, Vec<SeriesProfile>) {
+    let mut by_series: BTreeMap<String, Accumulator> = BTreeMap::new();
+    let graph = construct_value_graph_with(data, &mut |record, added| {
+        let accumulator = by_series.entry(record.series.clone()).or_default();
+        accumulator.records += 1;
+        match record.value {
+            Some(value) => accumulator.values.push(value),
+            None => accumulator.missing += 1,
+        }
+        accumulator.edge_weight += added;
+    });
+
+    let mut profiles: Vec<SeriesProfile> = by_series
+        .into_iter()
+        .map(|(series, accumulator)| SeriesProfile {
+            series,
+            records: accumulator.records,
+            missing: accumulator.missing,
+            variance: sample_variance(&accumulator.values),
+            edge_weight: accumulator.edge_weight,
+        })
+        .collect();
+    // Stable sort over name-ordered input, so ties stay alphabetical
+    profiles.sort_by(|a, b| b.edge_weight.total_cmp(&a.edge_weight));
+    (graph, profiles)
+}
+
+fn sample_variance(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let squares: f64 = values.iter().map(|value| (value - mean).powi(2)).sum();
+    Some(squares / (values.len() - 1) as f64)
+}
+
+pub fn profile_table(profiles: &[SeriesProfile]) -> Table {
+    let total: f64 = profiles.iter().map(|profile| profile.edge_weight).sum();
+    let mut table = Table::new(&[
+        "series",
+        "records",
+        "missing",
+        "variance",
+        "edge_weight",
+        "share",
+    ]);
+    for profile in profiles {
+        table.push_row(vec![
+            profile.series.clone(),
+            profile.records.to_string(),
+            profile.missing.to_string(),
+            profile
+                .variance
+                .map_or_else(|| "-".to_string(), |variance| format!("{:.4}", variance)),
+            format!("{:.4}", profile.edge_weight),
+            if total == 0.0 {
+                "-".to_string()
+            } else {
+                format!("{:.1}%", profile.edge_weight / total * 100.0)
+            },
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_profile_attributes_edge_weight() {
+        let data = vec![
+            record("Chad", "Enrollment", 2000, Some(1.0)),
+            record("Mali", "Enrollment", 2000, Some(3.0)),
+            record("Chad", "Teachers", 2000, None),
+            record("Chad", "Teachers", 2000, Some(2.0)),
+        ];
+        let (graph, profiles) = profile_series(&data);
+
+        // Contributions add up to the graph's total off-diagonal weight
+        let total: f64 = (0..2)
+            .flat_map(|i| (0..2).map(move |j| (i, j)))
+            .filter(|(i, j)| i != j)
+            .map(|(i, j)| graph.adjacency_matrix[i][j])
+            .sum();
+        let attributed: f64 = profiles.iter().map(|profile| profile.edge_weight).sum();
+        assert!((total - attributed).abs() < 1e-9);
+
+        assert_eq!(profiles[0].series, "Enrollment");
+        assert_eq!(profiles[0].records, 2);
+        assert_eq!(profiles[0].variance, Some(2.0));
+        assert!((profiles[0].edge_weight - 3.0).abs() < 1e-9);
+        assert_eq!(profiles[1].series, "Teachers");
+        assert_eq!(profiles[1].missing, 1);
+        assert_eq!(profiles[1].variance, None);
+    }
+}
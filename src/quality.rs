@@ -0,0 +1,172 @@
+use std::io;
+use std::str::FromStr;
+
+use crate::table::Table;
+
+pub const SELECTIONS: &[&str] = &["best", "elbow"];
+
+// How good a clustering is, and which number of clusters to pick. The
+// silhouette suits clusterings of feature vectors (k-means): for each
+// point, how much nearer it is to its own cluster than to the next nearest
+// one, from -1 to 1. Graph clusterings are scored by weighted modularity
+// (`louvain::modularity`) instead.
+
+// The mean silhouette of the points, with `distance(i, j)` between points
+// i and j. A point alone in its cluster scores 0; None for fewer than two
+// clusters, where the score is undefined.
+pub fn silhouette(assignments: &[usize], distance: impl Fn(usize, usize) -> f64) -> Option<f64> {
+    let clusters = assignments.iter().max().map_or(0, |&last| last + 1);
+    let mut sizes = vec![0usize; clusters];
+    for &cluster in assignments {
+        sizes[cluster] += 1;
+    }
+    if sizes.iter().filter(|&&size| size > 0).count() < 2 {
+        return None;
+    }
+    let mut total = 0.0;
+    for (i, &own) in assignments.iter().enumerate() {
+        if sizes[own] == 1 {
+            continue;
+        }
+        let mut sums = vec![0.0; clusters];
+        for (j, &cluster) in assignments.iter().enumerate() {
+            if i != j {
+                sums[cluster] += distance(i, j);
+            }
+        }
+        let within = sums[own] / (sizes[own] - 1) as f64;
+        let nearest = (0..clusters)
+            .filter(|&cluster| cluster != own && sizes[cluster] > 0)
+            .map(|cluster| sums[cluster] / sizes[cluster] as f64)
+            .fold(f64::INFINITY, f64::min);
+        let spread = within.max(nearest);
+        if spread > 0.0 {
+            total += (nearest - within) / spread;
+        }
+    }
+    Some(total / assignments.len() as f64)
+}
+
+// How a sweep over the number of clusters picks one.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Selection {
+    // The highest score (ties to the fewest clusters)
+    #[default]
+    Best,
+    // The knee of the curve: scaled to a unit square, the point furthest
+    // from the straight line between the first and the last, where adding
+    // clusters stops paying off
+    Elbow,
+}
+
+impl FromStr for Selection {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Selection> {
+        match value {
+            "best" => Ok(Selection::Best),
+            "elbow" => Ok(Selection::Elbow),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown selection `{}`; expected one of {}",
+                    other,
+                    SELECTIONS.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+impl Selection {
+    // The chosen number of clusters from (clusters, value) in increasing
+    // order of clusters; None without any finite value.
+    pub fn choose(&self, curve: &[(usize, f64)]) -> Option<usize> {
+        let finite: Vec<(usize, f64)> = curve
+            .iter()
+            .copied()
+            .filter(|(_, value)| value.is_finite())
+            .collect();
+        let (&(first_k, first), &(last_k, last)) = (finite.first()?, finite.last()?);
+        let best = finite
+            .iter()
+            .copied()
+            .reduce(|best, point| if point.1 > best.1 { point } else { best })?;
+        match self {
+            Selection::Best => Some(best.0),
+            Selection::Elbow if finite.len() < 3 || first == last => Some(first_k),
+            Selection::Elbow => {
+                let span = (last_k - first_k) as f64;
+                let (low, high) = finite.iter().fold(
+                    (f64::INFINITY, f64::NEG_INFINITY),
+                    |(low, high), &(_, v)| (low.min(v), high.max(v)),
+                );
+                let y = |value: f64| (value - low) / (high - low);
+                let distance = |&(k, value): &(usize, f64)| {
+                    let x = (k - first_k) as f64 / span;
+                    let line = y(first) + (y(last) - y(first)) * x;
+                    (y(value) - line).abs()
+                };
+                finite
+                    .iter()
+                    .copied()
+                    .reduce(|knee, point| {
+                        if distance(&point) > distance(&knee) {
+                            point
+                        } else {
+                            knee
+                        }
+                    })
+                    .map(|(k, _)| k)
+            }
+        }
+    }
+}
+
+// One row per number of clusters tried, the chosen one marked; NA where
+// the measure is undefined.
+pub fn sweep_table(measure: &str, curve: &[(usize, f64)], chosen: Option<usize>) -> Table {
+    let mut table = Table::new(&["clusters", measure, "chosen"]);
+    for &(k, value) in curve {
+        table.push_row(vec![
+            k.to_string(),
+            match value.is_finite() {
+                true => format!("{:.4}", value),
+                false => "NA".to_string(),
+            },
+            if chosen == Some(k) { "*" } else { "" }.to_string(),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_silhouette_and_selection() {
+        // Two tight groups far apart score near 1; mixing them scores below 0
+        let points: [f64; 4] = [0.0, 0.1, 10.0, 10.1];
+        let distance = |i: usize, j: usize| (points[i] - points[j]).abs();
+        let apart = silhouette(&[0, 0, 1, 1], distance).unwrap();
+        assert!(apart > 0.98, "{}", apart);
+        assert!(silhouette(&[0, 1, 0, 1], distance).unwrap() < 0.0);
+        assert_eq!(silhouette(&[0, 0, 0, 0], distance), None);
+        // Alone in a cluster counts as 0
+        let alone = silhouette(&[0, 0, 0, 1], distance).unwrap();
+        assert!(alone < apart);
+
+        let scores = [(2, 0.3), (3, 0.5), (4, 0.5), (5, f64::NAN)];
+        assert_eq!(Selection::Best.choose(&scores), Some(3));
+        // Inertia falling steeply to k = 3, then slowly
+        let inertia = [(1, 100.0), (2, 50.0), (3, 12.0), (4, 10.0), (5, 9.0)];
+        assert_eq!(Selection::Elbow.choose(&inertia), Some(3));
+        assert_eq!(Selection::Best.choose(&[]), None);
+        assert_eq!("knee".parse::<Selection>().ok(), None);
+
+        let table = sweep_table("silhouette", &scores, Some(3));
+        assert_eq!(table.headers[1], "silhouette");
+        assert_eq!(table.rows[1], ["3", "0.5000", "*"]);
+    }
+}
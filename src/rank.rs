@@ -0,0 +1,163 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::stats::{self, Ties};
+use crate::table::Table;
+use crate::EducationData;
+
+pub struct RankedCountry {
+    // 1 for the highest value; tied values share a rank as `Ties` says, by
+    // default the lowest they span ("1, 2, 2, 4")
+    pub rank: f64,
+    pub country: String,
+    pub value: f64,
+    // Share of countries below, counting ties as half (0-100)
+    pub percentile: f64,
+    // Change since the country's most recent earlier observation
+    pub delta: Option<f64>,
+}
+
+// The latest year in which any country reports the series.
+pub fn latest_year(data: &[EducationData], series: &str) -> Option<u32> {
+    data.iter()
+        .filter(|record| record.series == series && record.value.is_some())
+        .map(|record| record.year)
+        .max()
+}
+
+// Rank the countries reporting `series` in `year`, highest value first, ties
+// in name order. When a country has several records for a year, the last
+// one counts.
+pub fn rank_countries(
+    data: &[EducationData],
+    series: &str,
+    year: u32,
+    ties: Ties,
+) -> Vec<RankedCountry> {
+    let mut history: HashMap<&str, BTreeMap<u32, f64>> = HashMap::new();
+    for record in data.iter().filter(|record| record.series == series) {
+        if let Some(value) = record.value {
+            history
+                .entry(record.country_or_area.as_str())
+                .or_default()
+                .insert(record.year, value);
+        }
+    }
+
+    let mut current: Vec<(&str, f64, Option<f64>)> = history
+        .iter()
+        .filter_map(|(&country, values)| {
+            let value = *values.get(&year)?;
+            let previous = values.range(..year).next_back().map(|(_, &value)| value);
+            Some((country, value, previous.map(|previous| value - previous)))
+        })
+        .collect();
+    current.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    let count = current.len() as f64;
+    let negated: Vec<f64> = current.iter().map(|&(_, value, _)| -value).collect();
+    let ranks = stats::ranks(&negated, ties);
+    current
+        .iter()
+        .zip(ranks)
+        .map(|(&(country, value, delta), rank)| {
+            let above = current.iter().filter(|other| other.1 > value).count();
+            let tied = current.iter().filter(|other| other.1 == value).count();
+            let below = current.len() - above - tied;
+            RankedCountry {
+                rank,
+                country: country.to_string(),
+                value,
+                percentile: (below as f64 + 0.5 * tied as f64) / count * 100.0,
+                delta,
+            }
+        })
+        .collect()
+}
+
+// `clusters` maps country names to a cluster label; the column is only
+// included when a clustering was given.
+pub fn rank_table(ranked: &[RankedCountry], clusters: Option<&HashMap<String, String>>) -> Table {
+    let mut headers = vec!["rank", "country", "value", "percentile", "delta"];
+    if clusters.is_some() {
+        headers.push("cluster");
+    }
+    let mut table = Table::new(&headers);
+    for entry in ranked {
+        let mut row = vec![
+            // Whole ranks print as such; only average ties have halves
+            entry.rank.to_string(),
+            entry.country.clone(),
+            format!("{:.4}", entry.value),
+            format!("{:.1}", entry.percentile),
+            entry
+                .delta
+                .map_or_else(|| "-".to_string(), |delta| format!("{:+.4}", delta)),
+        ];
+        if let Some(clusters) = clusters {
+            row.push(
+                clusters
+                    .get(&entry.country)
+                    .cloned()
+                    .unwrap_or_else(|| "-".to_string()),
+            );
+        }
+        table.push_row(row);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    const FEMALE: &str = "Gross enrollment ratio - Primary (female)";
+
+    #[test]
+    fn test_rank_with_ties_percentiles_and_deltas() {
+        let series = "Gross enrollment ratio - Primary (female)";
+        let data = vec![
+            record("Mali", FEMALE, 2010, Some(60.0)),
+            record("Mali", FEMALE, 2015, Some(80.0)),
+            record("Chad", FEMALE, 2015, Some(80.0)),
+            record("Niger", FEMALE, 2005, Some(40.0)),
+            record("Niger", FEMALE, 2015, Some(50.0)),
+            record("Togo", FEMALE, 2015, None),
+            record("Togo", FEMALE, 2020, Some(90.0)),
+        ];
+        assert_eq!(latest_year(&data, series), Some(2020));
+
+        let ranked = rank_countries(&data, series, 2015, Ties::Min);
+        let rows: Vec<(f64, &str, f64)> = ranked
+            .iter()
+            .map(|entry| {
+                (
+                    entry.rank,
+                    entry.country.as_str(),
+                    (entry.percentile * 10.0).round() / 10.0,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (1.0, "Chad", 66.7),
+                (1.0, "Mali", 66.7),
+                (3.0, "Niger", 16.7)
+            ]
+        );
+        let ranks = |ties| -> Vec<f64> {
+            rank_countries(&data, series, 2015, ties)
+                .iter()
+                .map(|entry| entry.rank)
+                .collect()
+        };
+        assert_eq!(ranks(Ties::Average), [1.5, 1.5, 3.0]);
+        assert_eq!(ranks(Ties::Max), [2.0, 2.0, 3.0]);
+        assert_eq!(ranks(Ties::Dense), [1.0, 1.0, 2.0]);
+        assert_eq!(rank_table(&ranked, None).rows[2][0], "3");
+        assert_eq!(ranked[0].delta, None);
+        assert_eq!(ranked[1].delta, Some(20.0));
+        assert_eq!(ranked[2].delta, Some(10.0));
+    }
+}
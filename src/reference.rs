@@ -0,0 +1,451 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cli::BIN_NAME;
+
+// Reference tables for normalizing and grouping countries: UN M49 codes,
+// ISO 3166-1 alpha-3 codes, the M49 region hierarchy, the other names the
+// UN tables use for them and the states that have since dissolved. A copy
+// ships with the binary; `reference --install DIR` puts newer tables in the
+// user's data directory, which then take precedence (aliases.tsv and
+// dissolved.tsv are optional there).
+const BUNDLED_COUNTRIES: &str = include_str!("../data/reference/countries.tsv");
+const BUNDLED_REGIONS: &str = include_str!("../data/reference/regions.tsv");
+const BUNDLED_ALIASES: &str = include_str!("../data/reference/aliases.tsv");
+const BUNDLED_DISSOLVED: &str = include_str!("../data/reference/dissolved.tsv");
+const COUNTRIES_FILE: &str = "countries.tsv";
+const REGIONS_FILE: &str = "regions.tsv";
+const ALIASES_FILE: &str = "aliases.tsv";
+const DISSOLVED_FILE: &str = "dissolved.tsv";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Country {
+    pub m49: u16,
+    pub iso3: String,
+    pub name: String,
+    // Innermost M49 region containing the country
+    pub region: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub m49: u16,
+    pub name: String,
+    pub parent: Option<u16>,
+}
+
+// Another name for the country or region with this M49 code
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alias {
+    pub m49: u16,
+    pub name: String,
+}
+
+// A state that no longer exists, e.g. Serbia and Montenegro
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormerState {
+    pub m49: u16,
+    pub name: String,
+    // The country that continues it
+    pub successor: u16,
+}
+
+// What a name in the data stands for: a country, a region or other grouping
+// whose figures aggregate countries, or a dissolved state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Place<'a> {
+    Country(&'a Country),
+    Aggregate(&'a Region),
+    Former(&'a FormerState),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Bundled,
+    Cache(PathBuf),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Bundled => f.write_str("bundled"),
+            Source::Cache(dir) => write!(f, "cache at {}", dir.display()),
+        }
+    }
+}
+
+pub struct ReferenceData {
+    pub countries: Vec<Country>,
+    pub regions: Vec<Region>,
+    pub aliases: Vec<Alias>,
+    pub dissolved: Vec<FormerState>,
+    pub source: Source,
+}
+
+impl ReferenceData {
+    // Prefer the cached tables; fall back to the bundled copy when there is
+    // no cache (or no data directory at all, e.g. in a sandbox).
+    pub fn load() -> io::Result<ReferenceData> {
+        match cache_dir() {
+            Some(dir) if dir.join(COUNTRIES_FILE).exists() && dir.join(REGIONS_FILE).exists() => {
+                ReferenceData::load_dir(&dir)
+            }
+            _ => Ok(ReferenceData::bundled()),
+        }
+    }
+
+    pub fn bundled() -> ReferenceData {
+        ReferenceData::parse(
+            BUNDLED_COUNTRIES,
+            BUNDLED_REGIONS,
+            BUNDLED_ALIASES,
+            BUNDLED_DISSOLVED,
+            Source::Bundled,
+        )
+        .expect("bundled reference data is valid")
+    }
+
+    fn load_dir(dir: &Path) -> io::Result<ReferenceData> {
+        let countries = fs::read_to_string(dir.join(COUNTRIES_FILE))?;
+        let regions = fs::read_to_string(dir.join(REGIONS_FILE))?;
+        let aliases = read_optional(dir, ALIASES_FILE, BUNDLED_ALIASES)?;
+        let dissolved = read_optional(dir, DISSOLVED_FILE, BUNDLED_DISSOLVED)?;
+        ReferenceData::parse(
+            &countries,
+            &regions,
+            &aliases,
+            &dissolved,
+            Source::Cache(dir.to_path_buf()),
+        )
+    }
+
+    fn parse(
+        countries: &str,
+        regions: &str,
+        aliases: &str,
+        dissolved: &str,
+        source: Source,
+    ) -> io::Result<ReferenceData> {
+        let countries = parse_table(
+            countries,
+            COUNTRIES_FILE,
+            &["m49", "iso3", "name", "region"],
+        )?
+        .into_iter()
+        .map(|(line, fields)| {
+            Ok(Country {
+                m49: parse_code(fields[0], COUNTRIES_FILE, line)?,
+                iso3: fields[1].to_string(),
+                name: fields[2].to_string(),
+                region: parse_code(fields[3], COUNTRIES_FILE, line)?,
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+        let regions = parse_table(regions, REGIONS_FILE, &["m49", "name", "parent"])?
+            .into_iter()
+            .map(|(line, fields)| {
+                Ok(Region {
+                    m49: parse_code(fields[0], REGIONS_FILE, line)?,
+                    name: fields[1].to_string(),
+                    parent: match fields[2] {
+                        "" => None,
+                        code => Some(parse_code(code, REGIONS_FILE, line)?),
+                    },
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let aliases = parse_table(aliases, ALIASES_FILE, &["m49", "name"])?
+            .into_iter()
+            .map(|(line, fields)| {
+                Ok(Alias {
+                    m49: parse_code(fields[0], ALIASES_FILE, line)?,
+                    name: fields[1].to_string(),
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let dissolved = parse_table(dissolved, DISSOLVED_FILE, &["m49", "name", "successor"])?
+            .into_iter()
+            .map(|(line, fields)| {
+                Ok(FormerState {
+                    m49: parse_code(fields[0], DISSOLVED_FILE, line)?,
+                    name: fields[1].to_string(),
+                    successor: parse_code(fields[2], DISSOLVED_FILE, line)?,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(ReferenceData {
+            countries,
+            regions,
+            aliases,
+            dissolved,
+            source,
+        })
+    }
+
+    // Find a country by M49 code, ISO alpha-3 code or name (case-insensitive).
+    pub fn country(&self, query: &str) -> Option<&Country> {
+        let query = query.trim();
+        if let Ok(code) = query.parse::<u16>() {
+            return self.countries.iter().find(|country| country.m49 == code);
+        }
+        self.countries.iter().find(|country| {
+            country.iso3.eq_ignore_ascii_case(query) || country.name.eq_ignore_ascii_case(query)
+        })
+    }
+
+    // What a country name as given in the data stands for: a country by
+    // name or ISO alpha-3 code, a region or dissolved state by name, or any
+    // of them by an alias (all case-insensitive). None for names the tables
+    // do not know.
+    pub fn place(&self, name: &str) -> Option<Place<'_>> {
+        let name = name.trim();
+        let by_code = |code: u16| {
+            self.countries
+                .iter()
+                .find(|country| country.m49 == code)
+                .map(Place::Country)
+                .or_else(|| {
+                    self.regions
+                        .iter()
+                        .find(|region| region.m49 == code)
+                        .map(Place::Aggregate)
+                })
+                .or_else(|| {
+                    self.dissolved
+                        .iter()
+                        .find(|state| state.m49 == code)
+                        .map(Place::Former)
+                })
+        };
+        if let Some(country) = self.countries.iter().find(|country| {
+            country.name.eq_ignore_ascii_case(name) || country.iso3.eq_ignore_ascii_case(name)
+        }) {
+            return Some(Place::Country(country));
+        }
+        if let Some(region) = self
+            .regions
+            .iter()
+            .find(|region| region.name.eq_ignore_ascii_case(name))
+        {
+            return Some(Place::Aggregate(region));
+        }
+        if let Some(state) = self
+            .dissolved
+            .iter()
+            .find(|state| state.name.eq_ignore_ascii_case(name))
+        {
+            return Some(Place::Former(state));
+        }
+        self.aliases
+            .iter()
+            .filter(|alias| alias.name.eq_ignore_ascii_case(name))
+            .find_map(|alias| by_code(alias.m49))
+    }
+
+    // The country that continues a dissolved state, if the tables have it.
+    pub fn successor(&self, state: &FormerState) -> Option<&Country> {
+        self.countries
+            .iter()
+            .find(|country| country.m49 == state.successor)
+    }
+
+    // The region and its ancestors, innermost first.
+    pub fn region_path(&self, m49: u16) -> Vec<&Region> {
+        let mut path = Vec::new();
+        let mut next = Some(m49);
+        while let Some(code) = next {
+            match self.regions.iter().find(|region| region.m49 == code) {
+                // Guard against cycles in hand-edited tables
+                Some(region) if !path.contains(&region) => {
+                    path.push(region);
+                    next = region.parent;
+                }
+                _ => break,
+            }
+        }
+        path
+    }
+}
+
+// `$XDG_DATA_HOME/ds210/reference`, defaulting to `~/.local/share`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+    Some(data_home.join(BIN_NAME).join("reference"))
+}
+
+// Validate the tables in `from` and copy them into the cache, returning the
+// cache directory.
+pub fn install(from: &Path) -> io::Result<PathBuf> {
+    let dir = cache_dir().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "no data directory: set XDG_DATA_HOME or HOME",
+        )
+    })?;
+    install_into(from, &dir)?;
+    Ok(dir)
+}
+
+fn install_into(from: &Path, dir: &Path) -> io::Result<()> {
+    ReferenceData::load_dir(from)?;
+    fs::create_dir_all(dir)?;
+    for file in [COUNTRIES_FILE, REGIONS_FILE] {
+        fs::copy(from.join(file), dir.join(file))?;
+    }
+    for file in [ALIASES_FILE, DISSOLVED_FILE] {
+        if from.join(file).exists() {
+            fs::copy(from.join(file), dir.join(file))?;
+        }
+    }
+    Ok(())
+}
+
+// A table the cache may leave out, taken from the bundled copy then.
+fn read_optional(dir: &Path, file: &str, bundled: &str) -> io::Result<String> {
+    match fs::read_to_string(dir.join(file)) {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(bundled.to_string()),
+        text => text,
+    }
+}
+
+// Tab-separated rows after a header line naming the expected columns; `#`
+// lines are comments. Returns each row with its 1-based line number.
+fn parse_table<'a>(
+    text: &'a str,
+    file: &str,
+    columns: &[&str],
+) -> io::Result<Vec<(usize, Vec<&'a str>)>> {
+    let mut rows = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .filter(|(_, line)| !line.starts_with('#') && !line.trim().is_empty());
+
+    match rows.next() {
+        Some((_, header)) if header.split('\t').eq(columns.iter().copied()) => {}
+        _ => {
+            return Err(invalid_data(format!(
+                "{} must start with the header {}",
+                file,
+                columns.join("\\t")
+            )))
+        }
+    }
+
+    rows.map(|(line, text)| {
+        let fields: Vec<&str> = text.split('\t').map(str::trim).collect();
+        if fields.len() == columns.len() {
+            Ok((line, fields))
+        } else {
+            Err(invalid_data(format!(
+                "{} line {}: expected {} columns, found {}",
+                file,
+                line,
+                columns.len(),
+                fields.len()
+            )))
+        }
+    })
+    .collect()
+}
+
+fn parse_code(field: &str, file: &str, line: usize) -> io::Result<u16> {
+    field.parse().map_err(|_| {
+        invalid_data(format!(
+            "{} line {}: invalid M49 code {:?}",
+            file, line, field
+        ))
+    })
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_lookup_and_regions() {
+        let reference = ReferenceData::bundled();
+        let chad = reference.country("tcd").unwrap();
+        assert_eq!(chad.m49, 148);
+        assert_eq!(reference.country("148"), Some(chad));
+        assert_eq!(reference.country("CHAD"), Some(chad));
+        assert!(reference.country("Atlantis").is_none());
+
+        // Names in the UN tables, countries and aggregates alike
+        assert_eq!(reference.place("Chad"), Some(Place::Country(chad)));
+        for aggregate in ["Africa", "Latin America & the Caribbean", "LDC§", "SIDS"] {
+            assert!(
+                matches!(reference.place(aggregate), Some(Place::Aggregate(_))),
+                "{}",
+                aggregate
+            );
+        }
+        assert_eq!(reference.place("Atlantis"), None);
+        let Some(Place::Former(sudan)) = reference.place("Sudan [former]") else {
+            panic!("Sudan [former] is a dissolved state");
+        };
+        assert_eq!(reference.successor(sudan).unwrap().iso3, "SDN");
+        for state in &reference.dissolved {
+            assert!(reference.successor(state).is_some(), "{}", state.name);
+        }
+
+        let names: Vec<&str> = reference
+            .region_path(chad.region)
+            .iter()
+            .map(|region| region.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            ["Middle Africa", "Sub-Saharan Africa", "Africa", "World"]
+        );
+
+        // Every country's region is in the region table
+        for country in &reference.countries {
+            assert!(
+                !reference.region_path(country.region).is_empty(),
+                "{}",
+                country.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_install_validates_and_copies() {
+        let root = env::temp_dir().join(format!("ds210-{}-reference", std::process::id()));
+        let (from, cache) = (root.join("from"), root.join("cache"));
+        fs::create_dir_all(&from).unwrap();
+        fs::write(from.join(REGIONS_FILE), "m49\tname\tparent\n001\tWorld\t\n").unwrap();
+
+        // A malformed table is rejected before anything is copied
+        fs::write(
+            from.join(COUNTRIES_FILE),
+            "m49\tiso3\tname\tregion\nxx\tTCD\tChad\t001\n",
+        )
+        .unwrap();
+        assert!(install_into(&from, &cache).is_err());
+        assert!(!cache.exists());
+
+        fs::write(
+            from.join(COUNTRIES_FILE),
+            "m49\tiso3\tname\tregion\n148\tTCD\tChad\t001\n",
+        )
+        .unwrap();
+        install_into(&from, &cache).unwrap();
+        let reference = ReferenceData::load_dir(&cache).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(reference.countries.len(), 1);
+        assert_eq!(reference.source, Source::Cache(cache));
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::mem::size_of;
+
+use crate::engine::AnalysisResult;
+use crate::{EducationData, Graph};
+
+// Named datasets held side by side in server mode, each with its graph and
+// clustering. Entries are charged an estimate of the memory they hold, and
+// when a budget is set the least recently used ones are evicted to stay
+// under it.
+#[derive(Default)]
+pub struct Registry {
+    entries: BTreeMap<String, Entry>,
+    budget: Option<usize>,
+    // Bumped on every insert and lookup, for least-recently-used eviction
+    clock: u64,
+}
+
+pub struct Entry {
+    pub data: Vec<EducationData>,
+    pub graph: Graph,
+    pub result: AnalysisResult,
+    pub bytes: usize,
+    last_used: u64,
+}
+
+impl Registry {
+    pub fn with_budget(budget: usize) -> Registry {
+        Registry {
+            budget: Some(budget),
+            ..Registry::default()
+        }
+    }
+
+    pub fn budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    pub fn used(&self) -> usize {
+        self.entries.values().map(|entry| entry.bytes).sum()
+    }
+
+    // Add or replace `name`, returning the names evicted to make room. An
+    // entry larger than the whole budget is refused rather than evicting
+    // everything and still not fitting.
+    pub fn insert(
+        &mut self,
+        name: &str,
+        data: Vec<EducationData>,
+        graph: Graph,
+        result: AnalysisResult,
+    ) -> io::Result<Vec<String>> {
+        validate_name(name)?;
+        let bytes = data_bytes(&data) + graph_bytes(&graph) + result_bytes(&result);
+        if let Some(budget) = self.budget.filter(|&budget| bytes > budget) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "dataset {} needs about {} bytes, over the {} byte budget",
+                    name, bytes, budget
+                ),
+            ));
+        }
+        self.entries.remove(name);
+        let mut evicted = Vec::new();
+        if let Some(budget) = self.budget {
+            while self.used() + bytes > budget {
+                let Some(oldest) = self
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(name, _)| name.clone())
+                else {
+                    break;
+                };
+                self.entries.remove(&oldest);
+                evicted.push(oldest);
+            }
+        }
+        self.clock += 1;
+        self.entries.insert(
+            name.to_string(),
+            Entry {
+                data,
+                graph,
+                result,
+                bytes,
+                last_used: self.clock,
+            },
+        );
+        Ok(evicted)
+    }
+
+    // The entry under `name`, counting as a use for eviction.
+    pub fn get(&mut self, name: &str) -> io::Result<&Entry> {
+        self.clock += 1;
+        let clock = self.clock;
+        let entry = self.entries.get_mut(name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no dataset named {}", name),
+            )
+        })?;
+        entry.last_used = clock;
+        Ok(entry)
+    }
+
+    // Entries by name, without touching their recency.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Entry)> {
+        self.entries
+            .iter()
+            .map(|(name, entry)| (name.as_str(), entry))
+    }
+}
+
+// Names appear in URL paths, so keep them to a path-safe alphabet.
+pub fn validate_name(name: &str) -> io::Result<()> {
+    let safe = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+    if name.is_empty() || name.len() > 64 || !name.chars().all(safe) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "dataset name {:?} must be 1-64 letters, digits, '-', '_' or '.'",
+                name
+            ),
+        ));
+    }
+    Ok(())
+}
+
+// Heap estimates: struct sizes plus the capacity of what they own.
+fn data_bytes(data: &[EducationData]) -> usize {
+    data.iter()
+        .map(|record| {
+            size_of::<EducationData>()
+                + record.country_or_area.capacity()
+                + record.indicator.capacity()
+                + record.series.capacity()
+        })
+        .sum()
+}
+
+fn graph_bytes(graph: &Graph) -> usize {
+    let names: usize = graph
+        .nodes
+        .iter()
+        .map(|name| size_of::<String>() + name.capacity())
+        .sum();
+    let rows: usize = graph
+        .adjacency_matrix
+        .iter()
+        .map(|row| size_of::<Vec<f64>>() + row.capacity() * size_of::<f64>())
+        .sum();
+    names + rows
+}
+
+fn result_bytes(result: &AnalysisResult) -> usize {
+    result
+        .clusters
+        .iter()
+        .map(|cluster| {
+            cluster.label.capacity()
+                + cluster
+                    .members
+                    .iter()
+                    .map(|member| size_of::<String>() + member.capacity())
+                    .sum::<usize>()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::ResultCluster;
+
+    fn graph(countries: &[&str]) -> Graph {
+        Graph {
+            nodes: countries.iter().map(|name| name.to_string()).collect(),
+            adjacency_matrix: vec![vec![0.0; countries.len()]; countries.len()],
+        }
+    }
+
+    fn result() -> AnalysisResult {
+        AnalysisResult {
+            graph_id: 0,
+            clusters: vec![ResultCluster {
+                label: "Chad".to_string(),
+                members: vec!["Chad".to_string()],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_least_recently_used_eviction() {
+        let bytes = graph_bytes(&graph(&["Chad", "Mali"])) + result_bytes(&result());
+        let mut registry = Registry::with_budget(bytes * 2);
+        for name in ["a", "b"] {
+            let evicted = registry
+                .insert(name, Vec::new(), graph(&["Chad", "Mali"]), result())
+                .unwrap();
+            assert!(evicted.is_empty());
+        }
+        assert_eq!(registry.used(), bytes * 2);
+
+        // Using "a" makes "b" the one to go
+        registry.get("a").unwrap();
+        let evicted = registry
+            .insert("c", Vec::new(), graph(&["Chad", "Mali"]), result())
+            .unwrap();
+        assert_eq!(evicted, ["b"]);
+        let names: Vec<&str> = registry.entries().map(|(name, _)| name).collect();
+        assert_eq!(names, ["a", "c"]);
+        assert_eq!(
+            registry.get("b").err().map(|error| error.kind()),
+            Some(io::ErrorKind::NotFound)
+        );
+
+        // Too big for the whole budget, or unsafe in a URL
+        let big = graph(&["Chad", "Mali", "Niger", "Togo", "Benin", "Ghana"]);
+        assert!(registry.insert("d", Vec::new(), big, result()).is_err());
+        assert!(registry
+            .insert("a/b", Vec::new(), graph(&[]), result())
+            .is_err());
+        assert_eq!(registry.entries().count(), 2);
+    }
+}
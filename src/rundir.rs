@@ -0,0 +1,127 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::manifest::utc_timestamp;
+
+// A directory of its own for each `run --out-dir`, holding everything the
+// run writes (the report and its manifest, the graph figure, the run's
+// history entry) under a results root, instead of files scattered over the
+// working directory. Directories are named by run ID: the start time in
+// UTC unless one is given, so they sort in the order they ran, with a
+// counter added when two runs start in the same second.
+pub struct RunDir {
+    pub id: String,
+    pub path: PathBuf,
+}
+
+impl RunDir {
+    // Create the run's directory under `root`, creating `root` as needed.
+    // A given ID that is already taken is an error rather than mixing two
+    // runs' files.
+    pub fn create(root: &Path, id: Option<&str>, now: SystemTime) -> io::Result<RunDir> {
+        fs::create_dir_all(root)?;
+        if let Some(id) = id {
+            check_id(id)?;
+            let path = root.join(id);
+            return match fs::create_dir(&path) {
+                Ok(()) => Ok(RunDir {
+                    id: id.to_string(),
+                    path,
+                }),
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => Err(io::Error::new(
+                    error.kind(),
+                    format!("run directory {} already exists", path.display()),
+                )),
+                Err(error) => Err(error),
+            };
+        }
+        let stamp: String = utc_timestamp(now)
+            .chars()
+            .filter(|c| !matches!(c, '-' | ':'))
+            .collect();
+        for attempt in 1.. {
+            let id = match attempt {
+                1 => stamp.clone(),
+                n => format!("{}-{}", stamp, n),
+            };
+            let path = root.join(&id);
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(RunDir { id, path }),
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(error) => return Err(error),
+            }
+        }
+        unreachable!("run IDs are unbounded")
+    }
+
+    // The path of a file in the run's directory, as the text outputs take it.
+    pub fn file(&self, name: &str) -> String {
+        self.path.join(name).to_string_lossy().into_owned()
+    }
+}
+
+// The report's file name for a `--format`.
+pub fn report_file(format: &str) -> &'static str {
+    match format {
+        "html" => "report.html",
+        "json" => "report.json",
+        "csv" => "report.csv",
+        "dot" => "report.dot",
+        "gexf" => "report.gexf",
+        _ => "report.txt",
+    }
+}
+
+// IDs name a single directory: letters, digits, `.`, `_` and `-`, not
+// starting with a dot.
+fn check_id(id: &str) -> io::Result<()> {
+    let valid = !id.is_empty()
+        && !id.starts_with('.')
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "run ID {:?} must be letters, digits, `.`, `_` or `-`, not starting with `.`",
+                id
+            ),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_run_directories_are_named_and_unique() {
+        let root = std::env::temp_dir().join(format!("ds210-runs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let first = RunDir::create(&root, None, now).unwrap();
+        assert_eq!(first.id, "20231114T221320Z");
+        assert!(first.path.is_dir());
+        // The same second again gets a counter
+        let second = RunDir::create(&root, None, now).unwrap();
+        assert_eq!(second.id, "20231114T221320Z-2");
+        assert_eq!(
+            second.file("report.txt"),
+            root.join("20231114T221320Z-2/report.txt").to_string_lossy()
+        );
+
+        let named = RunDir::create(&root, Some("baseline"), now).unwrap();
+        assert_eq!(named.path, root.join("baseline"));
+        assert!(RunDir::create(&root, Some("baseline"), now).is_err());
+        assert!(RunDir::create(&root, Some("../elsewhere"), now).is_err());
+        assert_eq!(report_file("html"), "report.html");
+        let _ = fs::remove_dir_all(&root);
+    }
+}
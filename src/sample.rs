@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+
+use crate::random::Rng;
+use crate::EducationData;
+
+// A random share of the observations for quick, approximate runs while
+// parameters are being tried out. The sample is stratified by country:
+// each keeps the same share of its own records (at least one), so a
+// country that reports little is not lost to one that reports a lot.
+// Records keep their order, as some graphs depend on it.
+
+// Keep `fraction`, in (0, 1], of each country's records; returns how many
+// were kept.
+pub fn sample_by_country(data: &mut Vec<EducationData>, fraction: f64, rng: &mut Rng) -> usize {
+    let mut rows: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (index, record) in data.iter().enumerate() {
+        rows.entry(record.country_or_area.as_str())
+            .or_default()
+            .push(index);
+    }
+    let mut keep = vec![false; data.len()];
+    for mut indices in rows.into_values() {
+        rng.shuffle(&mut indices);
+        let count = ((indices.len() as f64 * fraction).ceil() as usize).clamp(1, indices.len());
+        for index in &indices[..count] {
+            keep[*index] = true;
+        }
+    }
+    let mut kept = keep.into_iter();
+    data.retain(|_| kept.next().unwrap_or(false));
+    data.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    fn observations() -> Vec<EducationData> {
+        let mut data: Vec<EducationData> = (0..20)
+            .map(|year| record("Chad", "primary", year, 1.0))
+            .collect();
+        data.extend((0..4).map(|year| record("Mali", "primary", year, 1.0)));
+        data.push(record("Peru", "primary", 0, 1.0));
+        data
+    }
+
+    #[test]
+    fn test_each_country_keeps_its_share() {
+        let mut sampled = observations();
+        assert_eq!(sample_by_country(&mut sampled, 0.25, &mut Rng::new(7)), 7);
+        let count = |country: &str| {
+            sampled
+                .iter()
+                .filter(|record| record.country_or_area == country)
+                .count()
+        };
+        assert_eq!((count("Chad"), count("Mali"), count("Peru")), (5, 1, 1));
+        // In their original order, and the same for the same seed
+        let years = |data: &[EducationData]| data.iter().map(|r| r.year).collect::<Vec<_>>();
+        assert!(years(&sampled[..5])
+            .windows(2)
+            .all(|pair| pair[0] < pair[1]));
+        let mut again = observations();
+        sample_by_country(&mut again, 0.25, &mut Rng::new(7));
+        assert_eq!(years(&again), years(&sampled));
+
+        let mut all = observations();
+        assert_eq!(sample_by_country(&mut all, 1.0, &mut Rng::new(0)), 25);
+    }
+}
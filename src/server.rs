@@ -0,0 +1,790 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use crate::auth::ApiKeys;
+use crate::console::note;
+use crate::engine::{country_count, Engine};
+use crate::jobs::{JobQueue, JobSpec};
+use crate::json::Json;
+
+// `ds210 serve`: the analysis engine as a JSON API over HTTP/1.1. Request
+// fields are query parameters and every call names what it made by a
+// handle the next call refers to, so clients can reuse a loaded dataset or
+// graph:
+//
+//   POST /v1/LoadDataset?path=P[&where=EXPR]
+//        -> {dataset_id, observations, countries}; P is a dataset artifact,
+//           observation store, CSV path or URL readable by the server
+//   POST /v1/BuildGraph?dataset_id=N   -> {graph_id, nodes}
+//   POST /v1/Cluster?graph_id=N        -> {result_id, clusters}
+//   GET  /v1/GetResult?result_id=N
+//        -> {result_id, graph_id, clusters: [{label, members}]}, as
+//           MessagePack with `Accept: application/msgpack`
+//   GET  /v1/FindSimilar?graph_id=N&country=C[&k=10]
+//        -> {graph_id, country, neighbors: [{country, weight}]}, most
+//           heavily linked first
+//
+// Besides them are the named-dataset routes under `/datasets`, background
+// jobs under `/jobs` and Prometheus `/metrics`. One request per connection,
+// served by a fixed pool of connection threads. With API keys configured,
+// every route needs `Authorization: Bearer <key>`.
+
+const MAX_BODY: u64 = 1 << 20;
+// Longest request or header line, and most header lines, read
+const MAX_LINE: usize = 8 << 10;
+const MAX_HEADERS: usize = 100;
+const TIMEOUT: Duration = Duration::from_secs(30);
+const MSGPACK: &str = "application/msgpack";
+
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    // Read one request; None when the client closed the connection first.
+    pub fn read(mut reader: impl BufRead) -> io::Result<Option<Request>> {
+        let request_line = read_line(&mut reader, || {
+            invalid_data(format!("request line longer than {} bytes", MAX_LINE))
+        })?;
+        if request_line.is_empty() {
+            return Ok(None);
+        }
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return Err(invalid_data(format!(
+                "malformed request line {:?}",
+                request_line.trim()
+            )));
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+        let mut headers = Vec::new();
+        loop {
+            let line = read_line(&mut reader, || {
+                headers_too_large(format!("header line longer than {} bytes", MAX_LINE))
+            })?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if headers.len() == MAX_HEADERS {
+                return Err(headers_too_large(format!(
+                    "more than {} header lines",
+                    MAX_HEADERS
+                )));
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+        let mut request = Request {
+            method: method.to_string(),
+            path: percent_decode(path),
+            query: query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (percent_decode(name), percent_decode(value))
+                })
+                .collect(),
+            headers,
+            body: Vec::new(),
+        };
+
+        let length = match request.header("content-length") {
+            Some(length) => length
+                .parse::<u64>()
+                .map_err(|_| invalid_data(format!("bad Content-Length {:?}", length)))?,
+            None => 0,
+        };
+        if length > MAX_BODY {
+            return Err(invalid_data(format!(
+                "request body of {} bytes exceeds the {} byte limit",
+                length, MAX_BODY
+            )));
+        }
+        reader.take(length).read_to_end(&mut request.body)?;
+        Ok(Some(request))
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn required(&self, name: &str) -> io::Result<&str> {
+        self.param(name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("missing query parameter {}", name),
+            )
+        })
+    }
+
+    fn id(&self, name: &str) -> io::Result<u64> {
+        let raw = self.required(name)?;
+        raw.parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} must be a numeric id, got {:?}", name, raw),
+            )
+        })
+    }
+}
+
+// One line of at most MAX_LINE bytes, empty at the end of the input; a
+// longer line is the error `too_long` makes.
+fn read_line(
+    reader: &mut impl BufRead,
+    too_long: impl FnOnce() -> io::Error,
+) -> io::Result<String> {
+    let mut line = String::new();
+    reader.take(MAX_LINE as u64).read_line(&mut line)?;
+    if line.len() == MAX_LINE && !line.ends_with('\n') {
+        return Err(too_long());
+    }
+    Ok(line)
+}
+
+// Headers over the limits, which are answered with 431 rather than 400.
+#[derive(Debug)]
+struct HeadersTooLarge(String);
+
+impl fmt::Display for HeadersTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for HeadersTooLarge {}
+
+fn headers_too_large(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, HeadersTooLarge(message))
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Reply {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Reply {
+    pub fn json(status: u16, body: &Json) -> Reply {
+        Reply {
+            status,
+            content_type: "application/json",
+            body: (body.to_pretty_string() + "\n").into_bytes(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Reply {
+        Reply::json(status, &Json::object().with("error", message))
+    }
+
+    // Map engine errors onto HTTP statuses.
+    fn from_error(error: &io::Error) -> Reply {
+        let status = match error.kind() {
+            io::ErrorKind::NotFound => 404,
+            io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => 400,
+            _ => 500,
+        };
+        Reply::error(status, &error.to_string())
+    }
+
+    // A request that could not be read.
+    fn unreadable(error: &io::Error) -> Reply {
+        let too_large = error
+            .get_ref()
+            .is_some_and(|inner| inner.is::<HeadersTooLarge>());
+        Reply::error(if too_large { 431 } else { 400 }, &error.to_string())
+    }
+
+    pub fn write_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+        if self.status == 401 {
+            write!(writer, "WWW-Authenticate: Bearer realm=\"ds210\"\r\n")?;
+        }
+        write!(writer, "\r\n")?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+// Everything a connection needs, shared across connection threads.
+pub struct Service {
+    pub engine: Arc<Mutex<Engine>>,
+    pub keys: ApiKeys,
+    pub jobs: Arc<JobQueue>,
+}
+
+impl Service {
+    fn engine(&self) -> MutexGuard<'_, Engine> {
+        self.engine
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// Route an authorized request, counting it in the engine's metrics either
+// way. Job routes never wait on the engine.
+pub fn handle(service: &Service, request: &Request) -> Reply {
+    let reply = if !service.keys.allows(request) {
+        Reply::error(401, "a valid API key is required")
+    } else if request.path == "/jobs" || job_id(&request.path).is_some() {
+        jobs(&service.jobs, request)
+    } else {
+        route(&mut service.engine(), request)
+    };
+    service
+        .engine()
+        .metrics
+        .record_request(route_label(&request.path), reply.status);
+    reply
+}
+
+const ROUTES: &[&str] = &[
+    "/metrics",
+    "/datasets",
+    "/jobs",
+    "/v1/LoadDataset",
+    "/v1/BuildGraph",
+    "/v1/Cluster",
+    "/v1/GetResult",
+    "/v1/FindSimilar",
+];
+
+// Dataset names collapse into the route template, and unknown paths share
+// one label, so neither can blow up the metrics' cardinality.
+fn route_label(path: &str) -> &str {
+    if ROUTES.contains(&path) {
+        path
+    } else if dataset_name(path).is_some() {
+        "/datasets/{name}/clusters"
+    } else if job_id(path).is_some() {
+        "/jobs/{id}"
+    } else {
+        "other"
+    }
+}
+
+fn dataset_name(path: &str) -> Option<&str> {
+    path.strip_prefix("/datasets/")?
+        .strip_suffix("/clusters")
+        .filter(|name| !name.is_empty() && !name.contains('/'))
+}
+
+fn job_id(path: &str) -> Option<&str> {
+    path.strip_prefix("/jobs/")
+        .filter(|id| !id.is_empty() && !id.contains('/'))
+}
+
+fn route(engine: &mut Engine, request: &Request) -> Reply {
+    if request.path == "/datasets" || dataset_name(&request.path).is_some() {
+        return datasets(engine, request);
+    }
+    let expected = match request.path.as_str() {
+        "/v1/LoadDataset" | "/v1/BuildGraph" | "/v1/Cluster" => "POST",
+        "/v1/GetResult" | "/v1/FindSimilar" | "/metrics" => "GET",
+        _ => return Reply::error(404, &format!("no route for {}", request.path)),
+    };
+    if request.method != expected {
+        return Reply::error(
+            405,
+            &format!(
+                "{} takes {}, not {}",
+                request.path, expected, request.method
+            ),
+        );
+    }
+    if request.path == "/metrics" {
+        return Reply {
+            status: 200,
+            content_type: "text/plain; version=0.0.4",
+            body: engine.metrics_text().into_bytes(),
+        };
+    }
+
+    let outcome = match request.path.as_str() {
+        "/v1/LoadDataset" => request.required("path").and_then(|path| {
+            let (id, observations, countries) =
+                engine.load_dataset(path, request.param("where"))?;
+            Ok(Json::object()
+                .with("dataset_id", id)
+                .with("observations", observations)
+                .with("countries", countries))
+        }),
+        "/v1/BuildGraph" => request.id("dataset_id").and_then(|dataset_id| {
+            let (id, nodes) = engine.build_graph(dataset_id)?;
+            Ok(Json::object().with("graph_id", id).with("nodes", nodes))
+        }),
+        "/v1/Cluster" => request.id("graph_id").and_then(|graph_id| {
+            let (id, clusters) = engine.cluster(graph_id)?;
+            Ok(Json::object()
+                .with("result_id", id)
+                .with("clusters", clusters))
+        }),
+        "/v1/FindSimilar" => request.id("graph_id").and_then(|graph_id| {
+            let country = request.required("country")?;
+            let k = match request.param("k") {
+                Some(raw) => raw.parse().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("k must be a count, got {:?}", raw),
+                    )
+                })?,
+                None => 10,
+            };
+            let neighbors = engine
+                .similar(graph_id, country, k)?
+                .into_iter()
+                .map(|neighbor| {
+                    Json::object()
+                        .with("country", neighbor.country)
+                        .with("weight", neighbor.score)
+                })
+                .collect();
+            Ok(Json::object()
+                .with("graph_id", graph_id)
+                .with("country", country)
+                .with("neighbors", Json::Array(neighbors)))
+        }),
+        _ => match request
+            .id("result_id")
+            .and_then(|id| Ok((id, engine.result(id)?)))
+        {
+            // Results can be large; clients may ask for the compact encoding
+            Ok((id, result)) if accepts_msgpack(request) => {
+                return Reply {
+                    status: 200,
+                    content_type: MSGPACK,
+                    body: result.to_msgpack(id).encode(),
+                }
+            }
+            outcome => outcome.map(|(id, result)| result.to_json(id)),
+        },
+    };
+    match outcome {
+        Ok(body) => Reply::json(200, &body),
+        Err(error) => Reply::from_error(&error),
+    }
+}
+
+// The named-dataset registry: POST /datasets loads, builds and clusters one,
+// GET /datasets lists them with their memory, and
+// GET /datasets/{name}/clusters fetches a clustering.
+fn datasets(engine: &mut Engine, request: &Request) -> Reply {
+    let outcome = match (request.method.as_str(), dataset_name(&request.path)) {
+        ("POST", None) => request.required("name").and_then(|name| {
+            let path = request.required("path")?;
+            let evicted = engine.register(name, path, request.param("where"))?;
+            let entry = engine.registry.get(name)?;
+            Ok(Json::object()
+                .with("name", name)
+                .with("observations", entry.data.len())
+                .with("countries", country_count(&entry.data))
+                .with("nodes", entry.graph.nodes.len())
+                .with("clusters", entry.result.clusters.len())
+                .with("bytes", entry.bytes)
+                .with(
+                    "evicted",
+                    Json::Array(evicted.into_iter().map(Json::from).collect()),
+                ))
+        }),
+        ("GET", None) => {
+            let datasets = engine
+                .registry
+                .entries()
+                .map(|(name, entry)| {
+                    Json::object()
+                        .with("name", name)
+                        .with("nodes", entry.graph.nodes.len())
+                        .with("bytes", entry.bytes)
+                })
+                .collect();
+            Ok(Json::object()
+                .with("used_bytes", engine.registry.used())
+                .with("budget_bytes", engine.registry.budget())
+                .with("datasets", Json::Array(datasets)))
+        }
+        ("GET", Some(name)) => engine.registry.get(name).map(|entry| {
+            Json::object()
+                .with("name", name)
+                .with("clusters", entry.result.clusters_json())
+        }),
+        (method, name) => {
+            let allowed = if name.is_some() { "GET" } else { "GET or POST" };
+            return Reply::error(
+                405,
+                &format!("{} takes {}, not {}", request.path, allowed, method),
+            );
+        }
+    };
+    match outcome {
+        Ok(body) => Reply::json(200, &body),
+        Err(error) => Reply::from_error(&error),
+    }
+}
+
+// POST /jobs queues an analysis and answers 202 with its id at once;
+// GET /jobs/{id} reports its state and, once done, its clusters.
+fn jobs(jobs: &JobQueue, request: &Request) -> Reply {
+    let outcome = match (request.method.as_str(), job_id(&request.path)) {
+        ("POST", None) => request.required("path").and_then(|path| {
+            let id = jobs.submit(JobSpec {
+                path: path.to_string(),
+                filter: request.param("where").map(str::to_string),
+                name: request.param("name").map(str::to_string),
+                notify: request.param("notify").map(str::to_string),
+            })?;
+            Ok(Reply::json(
+                202,
+                &Json::object().with("job_id", id).with("state", "queued"),
+            ))
+        }),
+        ("GET", Some(id)) => id
+            .parse()
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("job id must be numeric, got {:?}", id),
+                )
+            })
+            .and_then(|id| jobs.to_json(id))
+            .map(|body| Reply::json(200, &body)),
+        (method, id) => {
+            let allowed = if id.is_some() { "GET" } else { "POST" };
+            return Reply::error(
+                405,
+                &format!("{} takes {}, not {}", request.path, allowed, method),
+            );
+        }
+    };
+    outcome.unwrap_or_else(|error| Reply::from_error(&error))
+}
+
+fn accepts_msgpack(request: &Request) -> bool {
+    request.header("accept").is_some_and(|accept| {
+        accept
+            .split(',')
+            .any(|range| range.split(';').next().unwrap_or("").trim() == MSGPACK)
+    })
+}
+
+// Accept connections until the process is stopped, serving `connections`
+// of them at a time on as many threads with as many more waiting. Beyond
+// those, a connection is answered 503 straight away.
+pub fn serve(listener: TcpListener, service: Arc<Service>, connections: usize) -> io::Result<()> {
+    let (waiting, next) = mpsc::sync_channel::<TcpStream>(connections);
+    let next = Arc::new(Mutex::new(next));
+    for _ in 0..connections {
+        let (service, next) = (Arc::clone(&service), Arc::clone(&next));
+        thread::spawn(move || loop {
+            let stream = next
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .recv();
+            let Ok(stream) = stream else {
+                return;
+            };
+            if let Err(error) = serve_connection(stream, &service) {
+                note!("Connection failed: {}", error);
+            }
+        });
+    }
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                note!("Accept failed: {}", error);
+                continue;
+            }
+        };
+        if let Err(TrySendError::Full(mut stream)) = waiting.try_send(stream) {
+            // A client that does not read its answer cannot hold up the others
+            let turned_away = stream
+                .set_write_timeout(Some(Duration::from_secs(1)))
+                .and_then(|_| Reply::error(503, "too many connections").write_to(&mut stream));
+            if let Err(error) = turned_away {
+                note!("Connection failed: {}", error);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn serve_connection(stream: TcpStream, service: &Service) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let reply = match Request::read(BufReader::new(stream)) {
+        Ok(Some(request)) => handle(service, &request),
+        Ok(None) => return Ok(()),
+        Err(error) => Reply::unreadable(&error),
+    };
+    reply.write_to(&mut writer)
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let hex = |byte: u8| (byte as char).to_digit(16).map(|digit| digit as u8);
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = match bytes.get(index..index + 3) {
+            Some([b'%', high, low]) => hex(*high).zip(hex(*low)),
+            _ => None,
+        };
+        match (escaped, bytes[index]) {
+            (Some((high, low)), _) => {
+                decoded.push(high << 4 | low);
+                index += 3;
+                continue;
+            }
+            (None, b'+') => decoded.push(b' '),
+            (None, byte) => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn open_service() -> Service {
+        Service {
+            engine: Arc::default(),
+            keys: ApiKeys::default(),
+            jobs: Arc::default(),
+        }
+    }
+
+    // Handle a request with no headers or body.
+    fn call(service: &Service, method: &str, target: &str) -> Reply {
+        let raw = format!("{} {} HTTP/1.1\r\n\r\n", method, target);
+        handle(service, &Request::read(Cursor::new(raw)).unwrap().unwrap())
+    }
+
+    #[test]
+    fn test_request_parsing() {
+        let raw =
+            "POST /v1/LoadDataset?path=%2Ftmp%2Fmissing.csv&where=year+%3E%3D+2015 HTTP/1.1\r\n\
+                   Host: localhost\r\nContent-Length: 2\r\n\r\nok";
+        let request = Request::read(Cursor::new(raw)).unwrap().unwrap();
+        assert_eq!(request.path, "/v1/LoadDataset");
+        assert_eq!(request.param("path"), Some("/tmp/missing.csv"));
+        assert_eq!(request.param("where"), Some("year >= 2015"));
+        assert_eq!(request.header("host"), Some("localhost"));
+        assert_eq!(request.body, b"ok");
+        assert!(Request::read(Cursor::new("")).unwrap().is_none());
+
+        let raw = "GET /v1/GetResult?result_id=1 HTTP/1.1\r\n\
+                   Accept: application/json;q=0.5, application/msgpack\r\n\r\n";
+        assert!(accepts_msgpack(
+            &Request::read(Cursor::new(raw)).unwrap().unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_routing() {
+        let service = open_service();
+        let get = |target: &str, method: &str| call(&service, method, target);
+        assert_eq!(get("/v1/GetResult?result_id=7", "GET").status, 404);
+        assert_eq!(get("/v1/GetResult?result_id=x", "GET").status, 400);
+        assert_eq!(get("/v1/Cluster?graph_id=1", "GET").status, 405);
+        assert_eq!(
+            get("/v1/FindSimilar?graph_id=1&country=Chad", "GET").status,
+            404
+        );
+        assert_eq!(get("/v1/FindSimilar?graph_id=1", "POST").status, 405);
+        assert_eq!(get("/nowhere", "GET").status, 404);
+
+        let mut written = Vec::new();
+        get("/v1/BuildGraph", "POST")
+            .write_to(&mut written)
+            .unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(written.contains("missing query parameter dataset_id"));
+    }
+
+    #[test]
+    fn test_request_limits() {
+        let refused =
+            |raw: String| Reply::unreadable(&Request::read(Cursor::new(raw)).unwrap_err());
+        let long = "x".repeat(MAX_LINE);
+        assert_eq!(
+            refused(format!("GET /{} HTTP/1.1\r\n\r\n", long)).status,
+            400
+        );
+        assert_eq!(
+            refused(format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", long)).status,
+            431
+        );
+        let many = "X-Header: 1\r\n".repeat(MAX_HEADERS + 1);
+        assert_eq!(
+            refused(format!("GET / HTTP/1.1\r\n{}\r\n", many)).status,
+            431
+        );
+        // Up to the limits a request is read as usual
+        let most = "X-Header: 1\r\n".repeat(MAX_HEADERS);
+        let raw = format!("GET / HTTP/1.1\r\n{}\r\n", most);
+        let request = Request::read(Cursor::new(raw)).unwrap().unwrap();
+        assert_eq!(request.headers.len(), MAX_HEADERS);
+        let mut written = Vec::new();
+        Reply::unreadable(&headers_too_large("too many".to_string()))
+            .write_to(&mut written)
+            .unwrap();
+        assert!(written.starts_with(b"HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+    }
+
+    #[test]
+    fn test_connections_beyond_the_pool_are_turned_away() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let service = Arc::new(open_service());
+        thread::spawn(move || serve(listener, service, 1));
+
+        // One connection is served and one waits, both silent; by the third
+        // at the latest, connections are answered 503
+        let mut held = Vec::new();
+        let turned_away = (0..3).any(|_| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_millis(500)))
+                .unwrap();
+            let mut reply = String::new();
+            let answered = stream.read_to_string(&mut reply).is_ok();
+            held.push(stream);
+            answered && reply.starts_with("HTTP/1.1 503 Service Unavailable\r\n")
+        });
+        assert!(turned_away);
+    }
+
+    #[test]
+    fn test_metrics_count_requests_by_route() {
+        let service = open_service();
+        assert_eq!(call(&service, "GET", "/nowhere").status, 404);
+        assert_eq!(
+            call(&service, "GET", "/v1/GetResult?result_id=7").status,
+            404
+        );
+        assert_eq!(
+            call(&service, "GET", "/datasets/unesco/clusters").status,
+            404
+        );
+
+        // Unknown paths share one route label, and named routes keep
+        // their pattern rather than the name
+        let reply = call(&service, "GET", "/metrics");
+        assert_eq!(reply.content_type, "text/plain; version=0.0.4");
+        let metrics = String::from_utf8(reply.body).unwrap();
+        assert!(metrics.contains("ds210_requests_total{route=\"other\",status=\"404\"} 1\n"));
+        assert!(metrics.contains("ds210_result_lookups_total{outcome=\"miss\"} 1\n"));
+        assert!(metrics.contains(
+            "ds210_requests_total{route=\"/datasets/{name}/clusters\",status=\"404\"} 1\n"
+        ));
+    }
+
+    #[test]
+    fn test_dataset_routes() {
+        let service = open_service();
+        assert_eq!(call(&service, "GET", "/datasets").status, 200);
+        assert_eq!(
+            call(&service, "GET", "/datasets/unesco/clusters").status,
+            404
+        );
+        assert_eq!(
+            call(&service, "POST", "/datasets/unesco/clusters").status,
+            405
+        );
+        // Names are single path segments
+        assert_eq!(
+            call(&service, "POST", "/datasets?name=a%2Fb&path=x.csv").status,
+            400
+        );
+    }
+
+    #[test]
+    fn test_api_keys_guard_every_route() {
+        let mut service = open_service();
+        assert_eq!(call(&service, "GET", "/metrics").status, 200);
+
+        // With keys configured, requests without one are turned away
+        service.keys = ApiKeys::parse("letmein\n").unwrap();
+        let mut written = Vec::new();
+        call(&service, "GET", "/metrics")
+            .write_to(&mut written)
+            .unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        assert!(written.contains("WWW-Authenticate: Bearer"));
+        assert_eq!(call(&service, "POST", "/jobs?path=x.csv").status, 401);
+        let raw = "GET /metrics HTTP/1.1\r\nAuthorization: Bearer letmein\r\n\r\n";
+        let reply = handle(&service, &Request::read(Cursor::new(raw)).unwrap().unwrap());
+        assert_eq!(reply.status, 200);
+    }
+
+    #[test]
+    fn test_job_routes() {
+        let service = open_service();
+        assert_eq!(call(&service, "POST", "/jobs?path=x.csv").status, 202);
+        assert_eq!(call(&service, "GET", "/jobs/1").status, 200);
+        assert_eq!(call(&service, "GET", "/jobs/2").status, 404);
+        assert_eq!(call(&service, "GET", "/jobs/x").status, 400);
+        // A bad filter is refused before the job is queued
+        assert_eq!(
+            call(&service, "POST", "/jobs?path=x.csv&where=year+%3E").status,
+            400
+        );
+    }
+}
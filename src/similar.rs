@@ -0,0 +1,125 @@
+use crate::features::{self, FeatureMatrix};
+use crate::table::Table;
+use crate::Graph;
+
+// The countries most like one country, for a quick look around without
+// clustering: by edge weight in a graph (the mean of both directions,
+// heaviest first), or by distance between the countries' feature rows
+// (`features::distance`, nearest first). Ties go in name order; a country
+// sharing no series with the one asked about has no distance and is left
+// out.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Neighbor {
+    pub country: String,
+    // The edge weight, or the feature-space distance
+    pub score: f64,
+}
+
+// The `k` countries `node` is most heavily linked to.
+pub fn by_weight(graph: &Graph, node: usize, k: usize) -> Vec<Neighbor> {
+    let matrix = &graph.adjacency_matrix;
+    let mut neighbors: Vec<Neighbor> = (0..graph.nodes.len())
+        .filter(|&other| other != node)
+        .map(|other| Neighbor {
+            country: graph.nodes[other].clone(),
+            score: (matrix[node][other] + matrix[other][node]) / 2.0,
+        })
+        .filter(|neighbor| neighbor.score.is_finite())
+        .collect();
+    neighbors.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.country.cmp(&b.country))
+    });
+    neighbors.truncate(k);
+    neighbors
+}
+
+// The `k` countries whose rows are nearest `row`'s.
+pub fn by_distance(features: &FeatureMatrix, row: usize, k: usize) -> Vec<Neighbor> {
+    let mut neighbors: Vec<Neighbor> = (0..features.countries.len())
+        .filter(|&other| other != row)
+        .map(|other| Neighbor {
+            country: features.countries[other].clone(),
+            score: features::distance(&features.values[row], &features.values[other]),
+        })
+        .filter(|neighbor| neighbor.score.is_finite())
+        .collect();
+    neighbors.sort_by(|a, b| {
+        a.score
+            .total_cmp(&b.score)
+            .then_with(|| a.country.cmp(&b.country))
+    });
+    neighbors.truncate(k);
+    neighbors
+}
+
+// One row per neighbor, the score under `score` ("weight" or "distance").
+pub fn neighbors_table(neighbors: &[Neighbor], score: &str) -> Table {
+    let mut table = Table::new(&["rank", "country", score]);
+    for (rank, neighbor) in neighbors.iter().enumerate() {
+        table.push_row(vec![
+            (rank + 1).to_string(),
+            neighbor.country.clone(),
+            format!("{:.4}", neighbor.score),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nearest_by_weight_and_distance() {
+        let graph = Graph {
+            nodes: ["Chad", "India", "Indonesia", "Peru"]
+                .map(str::to_string)
+                .to_vec(),
+            adjacency_matrix: vec![
+                vec![1.0, 0.2, 0.4, 0.1],
+                vec![0.2, 1.0, 0.8, 0.6],
+                vec![0.4, 0.6, 1.0, 0.5],
+                vec![0.1, 0.6, 0.5, 1.0],
+            ],
+        };
+        let nearest = by_weight(&graph, 2, 2);
+        // India's 0.8 and 0.6 average to 0.7; the diagonal never counts
+        assert_eq!(
+            nearest,
+            [
+                Neighbor {
+                    country: "India".to_string(),
+                    score: 0.7
+                },
+                Neighbor {
+                    country: "Peru".to_string(),
+                    score: 0.5
+                },
+            ]
+        );
+        assert_eq!(by_weight(&graph, 0, 10).len(), 3);
+
+        let features = FeatureMatrix {
+            countries: ["Chad", "India", "Indonesia", "Peru"]
+                .map(str::to_string)
+                .to_vec(),
+            series: vec!["primary".to_string(), "tertiary".to_string()],
+            values: vec![
+                vec![10.0, f64::NAN],
+                vec![90.0, 20.0],
+                vec![95.0, 25.0],
+                vec![f64::NAN, 40.0],
+            ],
+        };
+        let near: Vec<String> = by_distance(&features, 2, 5)
+            .into_iter()
+            .map(|neighbor| neighbor.country)
+            .collect();
+        assert_eq!(near, ["India", "Peru", "Chad"]);
+        let table = neighbors_table(&nearest, "weight");
+        assert_eq!(table.rows[0], ["1", "India", "0.7000"]);
+    }
+}
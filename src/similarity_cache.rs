@@ -0,0 +1,137 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::artifact;
+use crate::console::note;
+use crate::features::FeatureMatrix;
+use crate::graph::{similarity_graph_of, SimilarityMetric};
+use crate::hash::{to_hex, Sha256};
+use crate::parallel::Parallelism;
+use crate::Graph;
+
+// Similarity graphs kept on disk between runs, so that trying another
+// clustering algorithm or stopping rule does not pay for the pairwise
+// similarities again. A graph is filed under the SHA-256 of the metric and
+// the feature matrix it was built from, which already reflects every
+// filter and transform applied before it; any change to either is a new
+// file, and stale files are never read. Graph policies (`--min-weight`
+// and the like) are applied afterwards and are not part of the key.
+
+// Part of every key, so a change to what a cached graph holds leaves the
+// old files behind instead of misreading them
+const FORMAT: &str = "ds210 similarity graph 1";
+
+pub struct Cached {
+    pub graph: Graph,
+    pub path: PathBuf,
+    // Read from the cache rather than computed
+    pub reused: bool,
+}
+
+pub fn cache_key(features: &FeatureMatrix, metric: SimilarityMetric) -> String {
+    let mut hasher = Sha256::new();
+    let mut text = |text: &str| {
+        hasher.update(&(text.len() as u64).to_le_bytes());
+        hasher.update(text.as_bytes());
+    };
+    text(FORMAT);
+    text(&format!("{:?}", metric));
+    for name in features.countries.iter().chain(&features.series) {
+        text(name);
+    }
+    hasher.update(&(features.countries.len() as u64).to_le_bytes());
+    for value in features.values.iter().flatten() {
+        hasher.update(&value.to_bits().to_le_bytes());
+    }
+    to_hex(&hasher.finalize())
+}
+
+// The similarity graph of `features` from `dir`, or computed and saved
+// there. A cached file that cannot be read is computed again and replaced.
+pub fn cached_similarity_graph(
+    dir: &Path,
+    features: FeatureMatrix,
+    metric: SimilarityMetric,
+    parallelism: Parallelism,
+) -> io::Result<Cached> {
+    let path = dir.join(format!("{}.graph", cache_key(&features, metric)));
+    let location = path.to_string_lossy().into_owned();
+    if path.exists() {
+        match artifact::load_graph(&location) {
+            Ok(graph) => {
+                return Ok(Cached {
+                    graph,
+                    path,
+                    reused: true,
+                })
+            }
+            Err(error) => note!("Warning: rebuilding {}: {}", location, error),
+        }
+    }
+    let graph = similarity_graph_of(features, metric, parallelism);
+    fs::create_dir_all(dir)?;
+    // Written aside and renamed, so a run cut short leaves no partial file
+    let partial = path.with_extension("partial");
+    artifact::save_graph(&partial.to_string_lossy(), &graph)?;
+    fs::rename(&partial, &path)?;
+    Ok(Cached {
+        graph,
+        path,
+        reused: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+    use crate::features::feature_matrix;
+
+    #[test]
+    fn test_second_lookup_reuses_the_graph() {
+        let mut data = vec![
+            record("Chad", "primary", 2015, 1.0),
+            record("Chad", "tertiary", 2015, 2.0),
+            record("Mali", "primary", 2015, 2.0),
+            record("Mali", "tertiary", 2015, 1.0),
+        ];
+        let dir = std::env::temp_dir().join(format!("ds210-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let sequential = Parallelism::sequential();
+        let cosine = SimilarityMetric::Cosine;
+
+        let built =
+            cached_similarity_graph(&dir, feature_matrix(&data, None), cosine, sequential).unwrap();
+        assert!(!built.reused);
+        let again =
+            cached_similarity_graph(&dir, feature_matrix(&data, None), cosine, sequential).unwrap();
+        assert!(again.reused);
+        assert_eq!(again.path, built.path);
+        assert_eq!(again.graph.nodes, built.graph.nodes);
+        assert_eq!(again.graph.adjacency_matrix, built.graph.adjacency_matrix);
+
+        // Another metric or another value is another key
+        let euclidean = SimilarityMetric::Euclidean;
+        assert!(
+            !cached_similarity_graph(&dir, feature_matrix(&data, None), euclidean, sequential)
+                .unwrap()
+                .reused
+        );
+        data[0].value = Some(1.5);
+        assert!(
+            !cached_similarity_graph(&dir, feature_matrix(&data, None), cosine, sequential)
+                .unwrap()
+                .reused
+        );
+
+        // A damaged file is rebuilt
+        fs::write(&built.path, b"not a graph").unwrap();
+        data[0].value = Some(1.0);
+        let rebuilt =
+            cached_similarity_graph(&dir, feature_matrix(&data, None), cosine, sequential).unwrap();
+        assert!(!rebuilt.reused);
+        assert_eq!(rebuilt.graph.adjacency_matrix, built.graph.adjacency_matrix);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
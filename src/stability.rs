@@ -0,0 +1,251 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::str::FromStr;
+
+use crate::cluster::{Algorithm, Stop};
+use crate::graph::{self, construct_value_graph};
+use crate::parallel::Parallelism;
+use crate::random::Rng;
+use crate::table::Table;
+use crate::{EducationData, Graph, SimilarityMetric};
+
+pub const SPLITS: &[&str] = &["parity", "random"];
+
+// How stable a clustering is: the observations are split in two, each half
+// is built into a graph and clustered on its own, and the two clusterings
+// are compared by the adjusted Rand index over the countries in both. An
+// index near 1 means the groups do not hinge on which observations went
+// into them; near 0, they agree no better than chance.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Split {
+    // Even years against odd years
+    #[default]
+    Parity,
+    // Each country's records shuffled and halved, so every country is in
+    // both halves
+    Random,
+}
+
+impl FromStr for Split {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Split> {
+        match value {
+            "parity" => Ok(Split::Parity),
+            "random" => Ok(Split::Random),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown split `{}`; expected one of {}",
+                    other,
+                    SPLITS.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+impl Split {
+    // The two halves, records in their original order.
+    pub fn split(&self, data: Vec<EducationData>, rng: &mut Rng) -> [Vec<EducationData>; 2] {
+        let first: Vec<bool> = match self {
+            Split::Parity => data.iter().map(|record| record.year % 2 == 0).collect(),
+            Split::Random => {
+                let mut rows: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+                for (index, record) in data.iter().enumerate() {
+                    rows.entry(record.country_or_area.as_str())
+                        .or_default()
+                        .push(index);
+                }
+                let mut first = vec![false; data.len()];
+                for mut indices in rows.into_values() {
+                    rng.shuffle(&mut indices);
+                    for &index in &indices[..indices.len().div_ceil(2)] {
+                        first[index] = true;
+                    }
+                }
+                first
+            }
+        };
+        let mut halves = [Vec::new(), Vec::new()];
+        for (record, first) in data.into_iter().zip(first) {
+            halves[usize::from(!first)].push(record);
+        }
+        halves
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Options {
+    // The value graph (`--value-graph`) when None
+    pub metric: Option<SimilarityMetric>,
+    pub algorithm: Algorithm,
+    pub stop: Stop,
+    pub parallelism: Parallelism,
+}
+
+pub struct Half {
+    pub observations: usize,
+    pub countries: usize,
+    pub clusters: usize,
+}
+
+pub struct Stability {
+    pub halves: [Half; 2],
+    // Countries clustered in both halves
+    pub compared: usize,
+    // None with fewer than two countries to compare
+    pub ari: Option<f64>,
+}
+
+pub fn split_stability(halves: [Vec<EducationData>; 2], options: Options) -> Stability {
+    let clustered = halves.map(|data| {
+        let graph = match options.metric {
+            Some(metric) => {
+                graph::construct_similarity_graph_with(&data, metric, options.parallelism)
+            }
+            None => construct_value_graph(&data),
+        };
+        let clusters = options
+            .algorithm
+            .cluster(&graph, None, options.stop, options.parallelism);
+        (data.len(), graph, clusters)
+    });
+    let cluster_of = |graph: &Graph, clusters: &[Vec<usize>]| {
+        let mut cluster_of = HashMap::new();
+        for (index, members) in clusters.iter().enumerate() {
+            for &node in members {
+                cluster_of.insert(graph.nodes[node].clone(), index);
+            }
+        }
+        cluster_of
+    };
+    let first = cluster_of(&clustered[0].1, &clustered[0].2);
+    let second = cluster_of(&clustered[1].1, &clustered[1].2);
+    let pairs: Vec<(usize, usize)> = first
+        .iter()
+        .filter_map(|(country, &a)| Some((a, *second.get(country)?)))
+        .collect();
+    let [a, b] = clustered.map(|(observations, graph, clusters)| Half {
+        observations,
+        countries: graph.nodes.len(),
+        clusters: clusters.len(),
+    });
+    Stability {
+        halves: [a, b],
+        compared: pairs.len(),
+        ari: adjusted_rand_index(&pairs),
+    }
+}
+
+// The adjusted Rand index of two labellings, given as (first, second) per
+// item; 1 when both put every item alone, or all together.
+pub fn adjusted_rand_index(pairs: &[(usize, usize)]) -> Option<f64> {
+    if pairs.len() < 2 {
+        return None;
+    }
+    let choose2 = |n: usize| (n * n.saturating_sub(1)) as f64 / 2.0;
+    let mut cells: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut rows: HashMap<usize, usize> = HashMap::new();
+    let mut columns: HashMap<usize, usize> = HashMap::new();
+    for &(a, b) in pairs {
+        *cells.entry((a, b)).or_default() += 1;
+        *rows.entry(a).or_default() += 1;
+        *columns.entry(b).or_default() += 1;
+    }
+    let index: f64 = cells.values().map(|&n| choose2(n)).sum();
+    let row_pairs: f64 = rows.values().map(|&n| choose2(n)).sum();
+    let column_pairs: f64 = columns.values().map(|&n| choose2(n)).sum();
+    let expected = row_pairs * column_pairs / choose2(pairs.len());
+    let max = (row_pairs + column_pairs) / 2.0;
+    if max == expected {
+        return Some(1.0);
+    }
+    Some((index - expected) / (max - expected))
+}
+
+pub fn stability_table(split: &str, stability: &Stability) -> Table {
+    let mut table = Table::new(&[
+        "split",
+        "half",
+        "observations",
+        "countries",
+        "clusters",
+        "compared",
+        "ari",
+    ]);
+    let ari = stability
+        .ari
+        .map_or_else(|| "NA".to_string(), |ari| format!("{:.4}", ari));
+    for (half, summary) in stability.halves.iter().enumerate() {
+        table.push_row(vec![
+            split.to_string(),
+            (half + 1).to_string(),
+            summary.observations.to_string(),
+            summary.countries.to_string(),
+            summary.clusters.to_string(),
+            stability.compared.to_string(),
+            ari.clone(),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    fn observations(years: std::ops::Range<u32>) -> Vec<EducationData> {
+        let mut data = Vec::new();
+        for year in years {
+            for (country, value) in [("Chad", 10.0), ("Mali", 11.0), ("Peru", 90.0)] {
+                data.push(record(country, "primary", year, value));
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_split_halves_and_agreement() {
+        let close = |a: Option<f64>, b: f64| (a.unwrap() - b).abs() < 1e-12;
+        // The same partition under other names, and one that disagrees
+        assert!(close(
+            adjusted_rand_index(&[(0, 5), (0, 5), (1, 7), (1, 7)]),
+            1.0
+        ));
+        assert!(close(
+            adjusted_rand_index(&[(0, 0), (0, 1), (1, 0), (1, 1)]),
+            -0.5
+        ));
+        assert!(close(adjusted_rand_index(&[(0, 0), (0, 0)]), 1.0));
+        assert_eq!(adjusted_rand_index(&[(0, 0)]), None);
+
+        let [even, odd] = Split::Parity.split(observations(2010..2015), &mut Rng::new(0));
+        assert!(even.iter().all(|record| record.year % 2 == 0));
+        assert_eq!((even.len(), odd.len()), (9, 6));
+        // Every country is in both random halves, the odd record in the first
+        let [first, second] = Split::Random.split(even, &mut Rng::new(3));
+        assert_eq!((first.len(), second.len()), (6, 3));
+        let mut countries: Vec<&str> = second
+            .iter()
+            .map(|record| record.country_or_area.as_str())
+            .collect();
+        countries.sort_unstable();
+        assert_eq!(countries, ["Chad", "Mali", "Peru"]);
+
+        // Both halves of a steady dataset cluster alike
+        let options = Options {
+            stop: Stop::Clusters(2),
+            ..Options::default()
+        };
+        let halves = Split::Random.split(observations(2010..2014), &mut Rng::new(1));
+        let stability = split_stability(halves, options);
+        assert_eq!(stability.compared, 3);
+        assert_eq!(stability.halves[0].clusters, 2);
+        assert!(close(stability.ari, 1.0));
+        let table = stability_table("random", &stability);
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(table.rows[1][6], "1.0000");
+    }
+}
@@ -0,0 +1,449 @@
+// Small statistics helpers shared by the analysis subcommands.
+
+use std::io;
+use std::str::FromStr;
+
+pub const TIES: &[&str] = &["average", "min", "max", "dense"];
+
+// Compensated (Kahan-Babuska-Neumaier) summation: the rounding error of each
+// addition is carried separately and added back at the end, so summing many
+// values of mixed magnitude loses no more precision than a single add.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct KahanSum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl KahanSum {
+    pub fn add(&mut self, value: f64) {
+        let total = self.sum + value;
+        if self.sum.abs() >= value.abs() {
+            self.compensation += (self.sum - total) + value;
+        } else {
+            self.compensation += (value - total) + self.sum;
+        }
+        self.sum = total;
+    }
+
+    pub fn value(&self) -> f64 {
+        // Once the running sum has overflowed the compensation is NaN (inf - inf)
+        if self.sum.is_finite() {
+            self.sum + self.compensation
+        } else {
+            self.sum
+        }
+    }
+}
+
+pub fn sum(values: impl IntoIterator<Item = f64>) -> f64 {
+    let mut total = KahanSum::default();
+    for value in values {
+        total.add(value);
+    }
+    total.value()
+}
+
+pub fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(sum(values.iter().copied()) / values.len() as f64)
+    }
+}
+
+// Population standard deviation.
+pub fn std_dev(values: &[f64]) -> Option<f64> {
+    let mean = mean(values)?;
+    let variance = sum(values.iter().map(|value| (value - mean).powi(2))) / values.len() as f64;
+    Some(variance.sqrt())
+}
+
+// Quantile of sorted values by linear interpolation between order
+// statistics (the default of R and NumPy).
+pub fn quantile(sorted: &[f64], q: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let position = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+    let fraction = position - lower as f64;
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * fraction)
+}
+
+// Ordinary least-squares line through `(x, y)` points.
+#[derive(Debug, PartialEq)]
+pub struct LinearFit {
+    pub slope: f64,
+    pub intercept: f64,
+    // 1 when y is constant, since the line then explains it exactly
+    pub r_squared: f64,
+    pub points: usize,
+}
+
+// Needs at least two points with distinct x values.
+pub fn linear_fit(points: &[(f64, f64)]) -> Option<LinearFit> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|&(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|&(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points
+        .iter()
+        .map(|&(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let spread_x: f64 = points.iter().map(|&(x, _)| (x - mean_x).powi(2)).sum();
+    let spread_y: f64 = points.iter().map(|&(_, y)| (y - mean_y).powi(2)).sum();
+    if spread_x == 0.0 {
+        return None;
+    }
+    let slope = covariance / spread_x;
+    Some(LinearFit {
+        slope,
+        intercept: mean_y - slope * mean_x,
+        r_squared: if spread_y == 0.0 {
+            1.0
+        } else {
+            covariance * covariance / (spread_x * spread_y)
+        },
+        points: points.len(),
+    })
+}
+
+// Pearson correlation of paired values; undefined for fewer than two pairs
+// or when either side is constant.
+pub fn pearson(pairs: &[(f64, f64)]) -> Option<f64> {
+    if pairs.len() < 2 {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mean_x = pairs.iter().map(|&(x, _)| x).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|&(_, y)| y).sum::<f64>() / n;
+    let (mut covariance, mut spread_x, mut spread_y) = (0.0, 0.0, 0.0);
+    for &(x, y) in pairs {
+        covariance += (x - mean_x) * (y - mean_y);
+        spread_x += (x - mean_x).powi(2);
+        spread_y += (y - mean_y).powi(2);
+    }
+    if spread_x == 0.0 || spread_y == 0.0 {
+        None
+    } else {
+        Some((covariance / (spread_x * spread_y).sqrt()).clamp(-1.0, 1.0))
+    }
+}
+
+// How tied values share ranks. Say four values rank 1, 2, 3, 4 with the
+// middle two equal:
+//   average  both take the mean of the ranks they span: 1, 2.5, 2.5, 4
+//   min      both take the lowest (competition ranking): 1, 2, 2, 4
+//   max      both take the highest: 1, 3, 3, 4
+//   dense    both take the next rank free, leaving no gaps: 1, 2, 2, 3
+// Education indicators tie often (many countries at a 100% rate), so the
+// choice moves ranks and rank correlations noticeably.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Ties {
+    #[default]
+    Average,
+    Min,
+    Max,
+    Dense,
+}
+
+impl FromStr for Ties {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Ties> {
+        match value {
+            "average" => Ok(Ties::Average),
+            "min" => Ok(Ties::Min),
+            "max" => Ok(Ties::Max),
+            "dense" => Ok(Ties::Dense),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown tie handling `{}`; expected one of {}",
+                    other,
+                    TIES.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+// The rank of each value, 1 for the smallest, in the order given.
+pub fn ranks(values: &[f64], ties: Ties) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let mut ranks = vec![0.0; values.len()];
+    let (mut start, mut distinct) = (0, 0);
+    while start < order.len() {
+        let value = values[order[start]];
+        let end = start
+            + order[start..]
+                .iter()
+                .take_while(|&&i| values[i] == value)
+                .count();
+        distinct += 1;
+        let rank = match ties {
+            Ties::Average => (start + 1 + end) as f64 / 2.0,
+            Ties::Min => (start + 1) as f64,
+            Ties::Max => end as f64,
+            Ties::Dense => distinct as f64,
+        };
+        for &index in &order[start..end] {
+            ranks[index] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+// Spearman's rank correlation: Pearson's on the ranks of each side.
+pub fn spearman(pairs: &[(f64, f64)], ties: Ties) -> Option<f64> {
+    let xs: Vec<f64> = pairs.iter().map(|&(x, _)| x).collect();
+    let ys: Vec<f64> = pairs.iter().map(|&(_, y)| y).collect();
+    let ranked: Vec<(f64, f64)> = ranks(&xs, ties).into_iter().zip(ranks(&ys, ties)).collect();
+    pearson(&ranked)
+}
+
+// Least squares for `y ~ rows`, where each row holds one observation's
+// regressors (include a 1 for an intercept). Returns the coefficients and
+// the residual sum of squares, or None when the design is singular.
+pub fn least_squares(rows: &[Vec<f64>], y: &[f64]) -> Option<(Vec<f64>, f64)> {
+    let width = rows.first()?.len();
+    // Normal equations X'X b = X'y
+    let mut xtx = vec![vec![0.0; width]; width];
+    let mut xty = vec![0.0; width];
+    for (row, &target) in rows.iter().zip(y) {
+        for i in 0..width {
+            xty[i] += row[i] * target;
+            for j in 0..width {
+                xtx[i][j] += row[i] * row[j];
+            }
+        }
+    }
+    let coefficients = solve(xtx, xty)?;
+    let rss = rows
+        .iter()
+        .zip(y)
+        .map(|(row, &target)| {
+            let fitted: f64 = row.iter().zip(&coefficients).map(|(x, b)| x * b).sum();
+            (target - fitted).powi(2)
+        })
+        .sum();
+    Some((coefficients, rss))
+}
+
+// Gaussian elimination with partial pivoting.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    let scale = a
+        .iter()
+        .flatten()
+        .fold(0.0f64, |max, value| max.max(value.abs()));
+    for column in 0..n {
+        let pivot =
+            (column..n).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+        if a[pivot][column].abs() <= 1e-12 * scale.max(1.0) {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        for row in column + 1..n {
+            let factor = a[row][column] / a[column][column];
+            let (upper, lower) = a.split_at_mut(row);
+            for (target, source) in lower[0][column..].iter_mut().zip(&upper[column][column..]) {
+                *target -= factor * source;
+            }
+            b[row] -= factor * b[column];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let known: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - known) / a[row][row];
+    }
+    Some(x)
+}
+
+// P(F > f) for an F distribution with (d1, d2) degrees of freedom.
+pub fn f_survival(f: f64, d1: f64, d2: f64) -> f64 {
+    if f <= 0.0 {
+        return 1.0;
+    }
+    regularized_beta(d2 / (d2 + d1 * f), d2 / 2.0, d1 / 2.0)
+}
+
+// I_x(a, b) by the continued fraction of Numerical Recipes (betacf).
+fn regularized_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The fraction converges quickly only on this side of the mean
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(x, a, b) / a
+    } else {
+        1.0 - front * beta_fraction(1.0 - x, b, a) / b
+    }
+}
+
+fn beta_fraction(x: f64, a: f64, b: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    d = 1.0 / if d.abs() < TINY { TINY } else { d };
+    let mut h = d;
+    for m in 1..200 {
+        let m = f64::from(m);
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            d = 1.0 / if d.abs() < TINY { TINY } else { d };
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-14 {
+            break;
+        }
+    }
+    h
+}
+
+// Lanczos approximation (g = 7, n = 9).
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| {
+            sum + c / (x + i as f64 + 1.0)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+// Benjamini-Hochberg adjusted p-values (false discovery rate).
+pub fn benjamini_hochberg(p_values: &[f64]) -> Vec<f64> {
+    let n = p_values.len();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&a, &b| p_values[b].total_cmp(&p_values[a]));
+    let mut adjusted = vec![0.0; n];
+    let mut running = 1.0f64;
+    for (position, &index) in order.iter().enumerate() {
+        let rank = (n - position) as f64;
+        running = running.min(p_values[index] * n as f64 / rank);
+        adjusted[index] = running;
+    }
+    adjusted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_fit() {
+        let fit = linear_fit(&[(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)]).unwrap();
+        assert_eq!((fit.slope, fit.intercept, fit.r_squared), (2.0, 1.0, 1.0));
+        assert!(linear_fit(&[(1.0, 1.0), (1.0, 2.0)]).is_none());
+        assert_eq!(std_dev(&[2.0, 4.0]), Some(1.0));
+        assert_eq!(quantile(&[1.0, 2.0, 3.0, 4.0], 0.5), Some(2.5));
+        assert_eq!(quantile(&[1.0, 2.0, 3.0, 4.0], 0.25), Some(1.75));
+        assert_eq!(pearson(&[(0.0, 3.0), (1.0, 2.0), (2.0, 1.0)]), Some(-1.0));
+        assert_eq!(pearson(&[(0.0, 3.0), (1.0, 3.0)]), None);
+    }
+
+    #[test]
+    fn test_tied_ranks() {
+        let values = [3.0, 1.0, 2.0, 2.0, 100.0, 100.0, 100.0];
+        assert_eq!(
+            ranks(&values, Ties::Average),
+            [4.0, 1.0, 2.5, 2.5, 6.0, 6.0, 6.0]
+        );
+        assert_eq!(
+            ranks(&values, Ties::Min),
+            [4.0, 1.0, 2.0, 2.0, 5.0, 5.0, 5.0]
+        );
+        assert_eq!(
+            ranks(&values, Ties::Max),
+            [4.0, 1.0, 3.0, 3.0, 7.0, 7.0, 7.0]
+        );
+        assert_eq!(
+            ranks(&values, Ties::Dense),
+            [3.0, 1.0, 2.0, 2.0, 4.0, 4.0, 4.0]
+        );
+        assert!("first".parse::<Ties>().is_err());
+
+        // Monotone but not linear: Spearman sees a perfect correlation
+        let pairs = [(1.0, 1.0), (2.0, 8.0), (3.0, 27.0), (4.0, 64.0)];
+        assert_eq!(spearman(&pairs, Ties::Average), Some(1.0));
+        assert!(pearson(&pairs).unwrap() < 1.0);
+        // Ties on one side: the strategies disagree, average matching the
+        // textbook value, the Pearson correlation of mid-ranks
+        let pairs = [(1.0, 100.0), (2.0, 100.0), (3.0, 90.0), (4.0, 80.0)];
+        let average = spearman(&pairs, Ties::Average).unwrap();
+        assert!((average - -0.9486832980505138).abs() < 1e-12, "{}", average);
+        assert_ne!(spearman(&pairs, Ties::Dense), Some(average));
+        // Nothing to rank against a constant side
+        assert_eq!(spearman(&[(1.0, 5.0), (2.0, 5.0)], Ties::Min), None);
+    }
+
+    #[test]
+    fn test_compensated_sum() {
+        // Naive summation drops every 1.0 against 1e16
+        let values = [1e16, 1.0, 1.0, 1.0, 1.0, -1e16];
+        assert_eq!(values.iter().sum::<f64>(), 0.0);
+        assert_eq!(sum(values), 4.0);
+        assert_eq!(sum(vec![0.1; 10]), 1.0);
+        assert_eq!(mean(&[1e308, 1e308]), Some(f64::INFINITY));
+        assert_eq!(sum([f64::MAX, f64::MAX, -f64::MAX]), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_least_squares_and_f_test() {
+        let rows: Vec<Vec<f64>> = [(1.0, 0.0), (2.0, 1.0), (3.0, 1.0), (4.0, 3.0)]
+            .iter()
+            .map(|&(a, b)| vec![1.0, a, b])
+            .collect();
+        let y: Vec<f64> = rows.iter().map(|row| 2.0 + 3.0 * row[1] - row[2]).collect();
+        let (coefficients, rss) = least_squares(&rows, &y).unwrap();
+        for (found, expected) in coefficients.iter().zip([2.0, 3.0, -1.0]) {
+            assert!((found - expected).abs() < 1e-9);
+        }
+        assert!(rss < 1e-12);
+        assert!(least_squares(&[vec![1.0, 2.0], vec![1.0, 2.0]], &[1.0, 2.0]).is_none());
+
+        // F(1, 10) critical value at 5% is 4.965
+        assert!((f_survival(4.965, 1.0, 10.0) - 0.05).abs() < 1e-3);
+        assert_eq!(f_survival(0.0, 1.0, 10.0), 1.0);
+
+        let adjusted = benjamini_hochberg(&[0.01, 0.04, 0.03, 0.5]);
+        assert_eq!(adjusted, [0.04, 0.04 * 4.0 / 3.0, 0.04 * 4.0 / 3.0, 0.5]);
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use crate::artifact;
+use crate::EducationData;
+
+// Observations are identified by (country or area, year, series).
+pub type Key = (String, u32, String);
+
+// An on-disk observation store that grows as new files are ingested, so the
+// original CSVs need not be kept. The log is append-only; replaying it with
+// last-write-wins gives the current observations, sorted by key.
+pub struct Store {
+    path: String,
+    observations: BTreeMap<Key, EducationData>,
+    // Log records superseded by later ones, reclaimable by `compact`
+    superseded: usize,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct IngestSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+impl Store {
+    // Open an existing store, or start an empty one that is created on the
+    // first ingest.
+    pub fn open(path: &str) -> io::Result<Store> {
+        let mut store = Store {
+            path: path.to_string(),
+            observations: BTreeMap::new(),
+            superseded: 0,
+        };
+        if std::path::Path::new(path).exists() {
+            for record in artifact::read_store(path)? {
+                if store.observations.insert(key(&record), record).is_some() {
+                    store.superseded += 1;
+                }
+            }
+        }
+        Ok(store)
+    }
+
+    // Upsert records by key. Only new or changed observations are appended
+    // to the log; within `records`, the last one for a key wins.
+    pub fn ingest(&mut self, records: Vec<EducationData>) -> io::Result<IngestSummary> {
+        let mut incoming: BTreeMap<Key, EducationData> = BTreeMap::new();
+        for record in records {
+            incoming.insert(key(&record), record);
+        }
+
+        let mut summary = IngestSummary::default();
+        let mut changed = Vec::new();
+        for (key, record) in incoming {
+            match self.observations.get(&key) {
+                Some(existing) if same_observation(existing, &record) => summary.unchanged += 1,
+                Some(_) => {
+                    summary.updated += 1;
+                    changed.push((key, record));
+                }
+                None => {
+                    summary.added += 1;
+                    changed.push((key, record));
+                }
+            }
+        }
+
+        let to_append: Vec<&EducationData> = changed.iter().map(|(_, record)| record).collect();
+        if !to_append.is_empty() || !std::path::Path::new(&self.path).exists() {
+            artifact::append_store(&self.path, &to_append)?;
+        }
+        self.superseded += summary.updated;
+        self.observations.extend(changed);
+        Ok(summary)
+    }
+
+    // Rewrite the log with only the current observations.
+    pub fn compact(&mut self) -> io::Result<usize> {
+        let records: Vec<&EducationData> = self.observations.values().collect();
+        artifact::rewrite_store(&self.path, &records)?;
+        Ok(std::mem::take(&mut self.superseded))
+    }
+
+    pub fn len(&self) -> usize {
+        self.observations.len()
+    }
+
+    pub fn into_observations(self) -> Vec<EducationData> {
+        self.observations.into_values().collect()
+    }
+}
+
+pub fn key(record: &EducationData) -> Key {
+    (
+        record.country_or_area.clone(),
+        record.year,
+        record.series.clone(),
+    )
+}
+
+fn same_observation(a: &EducationData, b: &EducationData) -> bool {
+    a.indicator == b.indicator && a.value.map(f64::to_bits) == b.value.map(f64::to_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    const FEMALE: &str = "Gross enrollment ratio - Primary (female)";
+
+    #[test]
+    fn test_ingest_upserts_and_compacts() {
+        let path = std::env::temp_dir()
+            .join(format!("ds210-{}-store.bin", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+
+        let mut store = Store::open(&path).unwrap();
+        let summary = store
+            .ingest(vec![
+                record("Mali", FEMALE, 2015, Some(70.0)),
+                record("Chad", FEMALE, 2015, None),
+            ])
+            .unwrap();
+        assert_eq!(summary.added, 2);
+
+        // A revised value updates, a repeated one is left alone
+        let summary = Store::open(&path)
+            .unwrap()
+            .ingest(vec![
+                record("Chad", FEMALE, 2015, Some(80.5)),
+                record("Mali", FEMALE, 2015, Some(70.0)),
+                record("Mali", FEMALE, 2020, Some(75.0)),
+            ])
+            .unwrap();
+        assert_eq!(
+            summary,
+            IngestSummary {
+                added: 1,
+                updated: 1,
+                unchanged: 1,
+            }
+        );
+
+        let mut store = Store::open(&path).unwrap();
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.compact().unwrap(), 1);
+        assert_eq!(artifact::read_store(&path).unwrap().len(), 3);
+
+        let observations = Store::open(&path).unwrap().into_observations();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(observations[0].country_or_area, "Chad");
+        assert_eq!(observations[0].value, Some(80.5));
+        assert_eq!(observations[2].year, 2020);
+    }
+}
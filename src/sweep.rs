@@ -0,0 +1,440 @@
+use std::io;
+
+use crate::cancel::RunStatus;
+use crate::cluster::{Algorithm, Stop};
+use crate::config::{Config, Value};
+use crate::features::{self, FeatureMatrix};
+use crate::graph::construct_similarity_graph_with;
+use crate::louvain;
+use crate::matrix::{self, MatrixBackend};
+use crate::observer::{Control, Iteration, IterationObserver};
+use crate::parallel::Parallelism;
+use crate::quality;
+use crate::stats::KahanSum;
+use crate::symmetry::Pruning;
+use crate::table::Table;
+use crate::{EducationData, Graph, SimilarityMetric};
+
+// Pipeline parameters a sweep grid may vary. Stages register their knobs
+// here (and in `SweepOptions::set`) as they become tunable.
+pub const PARAMETERS: &[&str] = &["threshold", "k", "metric"];
+
+// Quality metrics collected for every combination, usable as `rank_by`.
+pub const METRICS: &[&str] = &[
+    "clusters",
+    "coverage",
+    "intra_weight",
+    "modularity",
+    "silhouette",
+];
+
+const DEFAULT_RANK_BY: &str = "intra_weight";
+
+// Resolved pipeline settings for one grid combination.
+#[derive(Clone, Debug, Default)]
+pub struct SweepOptions {
+    // Edges lighter than this are dropped before clustering
+    pub threshold: f64,
+    // Merge down to this many clusters, or by the default stopping rule
+    pub k: Option<usize>,
+    // Compare the countries' series under this metric
+    pub metric: SimilarityMetric,
+}
+
+impl SweepOptions {
+    fn set(&mut self, name: &str, value: &Value) -> io::Result<()> {
+        match name {
+            "threshold" => {
+                self.threshold = value.as_f64().ok_or_else(|| {
+                    invalid_data(format!("sweep parameter `{}` must be a number", name))
+                })?
+            }
+            "k" => {
+                let k = value
+                    .as_f64()
+                    .filter(|&k| k >= 1.0 && k.fract() == 0.0)
+                    .ok_or_else(|| {
+                        invalid_data(format!(
+                            "sweep parameter `{}` must be a whole number of at least 1",
+                            name
+                        ))
+                    })?;
+                self.k = Some(k as usize);
+            }
+            "metric" => {
+                let metric = value.as_str().ok_or_else(|| {
+                    invalid_data(format!("sweep parameter `{}` must be a string", name))
+                })?;
+                self.metric = metric.parse()?;
+            }
+            other => unreachable!("parameter {} is validated by SweepPlan", other),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QualityMetrics {
+    pub clusters: usize,
+    // Fraction of nodes that were assigned to some cluster
+    pub coverage: f64,
+    // Fraction of the total edge weight that falls inside clusters
+    pub intra_weight: f64,
+    // Weighted modularity of the clusters in the graph
+    pub modularity: f64,
+    // Mean silhouette of the clustered countries in feature space, by
+    // `features::distance`; NaN for fewer than two clusters
+    pub silhouette: f64,
+}
+
+impl QualityMetrics {
+    pub fn compute(
+        graph: &Graph,
+        clusters: &[Vec<usize>],
+        features: &FeatureMatrix,
+    ) -> QualityMetrics {
+        let node_count = graph.nodes.len();
+        let mut membership = vec![None; node_count];
+        for (cluster_index, cluster) in clusters.iter().enumerate() {
+            for &node_index in cluster {
+                membership[node_index] = Some(cluster_index);
+            }
+        }
+
+        let mut total_weight = KahanSum::default();
+        let mut intra_weight = KahanSum::default();
+        let matrix = matrix::storage(&graph.adjacency_matrix);
+        for i in 0..matrix.dim() {
+            for (j, weight) in matrix.row_entries(i) {
+                if i == j {
+                    continue;
+                }
+                total_weight.add(weight);
+                if membership[i].is_some() && membership[i] == membership[j] {
+                    intra_weight.add(weight);
+                }
+            }
+        }
+
+        let assigned = membership
+            .iter()
+            .filter(|cluster| cluster.is_some())
+            .count();
+        QualityMetrics {
+            clusters: clusters.len(),
+            coverage: if node_count == 0 {
+                0.0
+            } else {
+                assigned as f64 / node_count as f64
+            },
+            intra_weight: if total_weight.value() == 0.0 {
+                0.0
+            } else {
+                intra_weight.value() / total_weight.value()
+            },
+            modularity: louvain::modularity(graph, clusters),
+            silhouette: silhouette(graph, clusters, features),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> f64 {
+        match name {
+            "clusters" => self.clusters as f64,
+            "coverage" => self.coverage,
+            "intra_weight" => self.intra_weight,
+            "modularity" => self.modularity,
+            "silhouette" => self.silhouette,
+            other => unreachable!("metric {} is validated by SweepPlan", other),
+        }
+    }
+}
+
+// The grid of parameter values to try and the metric used to rank results.
+pub struct SweepPlan {
+    pub grid: Vec<(String, Vec<Value>)>,
+    pub rank_by: String,
+}
+
+impl SweepPlan {
+    // Read the `[grid]` table and `rank_by` key of a sweep config.
+    pub fn from_config(config: &Config) -> io::Result<SweepPlan> {
+        let grid_table = config
+            .get("grid")
+            .and_then(Value::as_table)
+            .ok_or_else(|| invalid_data("sweep config needs a [grid] table".to_string()))?;
+
+        let mut grid = Vec::new();
+        for (name, values) in grid_table {
+            if !PARAMETERS.contains(&name.as_str()) {
+                return Err(invalid_data(format!(
+                    "unknown sweep parameter `{}`; supported: {}",
+                    name,
+                    PARAMETERS.join(", ")
+                )));
+            }
+            let values = values.to_list();
+            if values.is_empty() {
+                return Err(invalid_data(format!(
+                    "sweep parameter `{}` has no values",
+                    name
+                )));
+            }
+            grid.push((name.clone(), values));
+        }
+
+        let rank_by = config.get_str("rank_by")?.unwrap_or(DEFAULT_RANK_BY);
+        if !METRICS.contains(&rank_by) {
+            return Err(invalid_data(format!(
+                "unknown rank_by metric `{}`; supported: {}",
+                rank_by,
+                METRICS.join(", ")
+            )));
+        }
+
+        Ok(SweepPlan {
+            grid,
+            rank_by: rank_by.to_string(),
+        })
+    }
+
+    // Cartesian product of the grid, in declaration order.
+    pub fn combinations(&self) -> Vec<Vec<(String, Value)>> {
+        let mut combinations = vec![Vec::new()];
+        for (name, values) in &self.grid {
+            let mut expanded = Vec::with_capacity(combinations.len() * values.len());
+            for combination in &combinations {
+                for value in values {
+                    let mut next = combination.clone();
+                    next.push((name.clone(), value.clone()));
+                    expanded.push(next);
+                }
+            }
+            combinations = expanded;
+        }
+        combinations
+    }
+}
+
+pub struct SweepRun {
+    pub parameters: Vec<(String, Value)>,
+    pub metrics: QualityMetrics,
+}
+
+// Run the graph and clustering stages once per grid combination and return
+// the runs ranked best-first by the plan's metric. The observer hears about
+// each finished combination, with the ranking metric as objective; when it
+// asks to stop, the combinations finished so far are ranked and returned
+// along with the reason. Each combination's graph is built and clustered
+// over `parallelism`.
+pub fn run_sweep(
+    data: &[EducationData],
+    plan: &SweepPlan,
+    parallelism: Parallelism,
+    observer: &mut dyn IterationObserver,
+) -> io::Result<(Vec<SweepRun>, RunStatus)> {
+    let combinations = plan.combinations();
+    let total = combinations.len();
+    let features = features::feature_matrix(data, None);
+    let mut runs = Vec::new();
+    let mut status = RunStatus::Completed;
+    for parameters in combinations {
+        let mut options = SweepOptions::default();
+        for (name, value) in &parameters {
+            options.set(name, value)?;
+        }
+
+        let mut graph = construct_similarity_graph_with(data, options.metric, parallelism);
+        graph.prune(Pruning::Threshold(options.threshold));
+        let stop = options.k.map_or(Stop::Auto, Stop::Clusters);
+        let clusters = Algorithm::Agglomerative.cluster(&graph, None, stop, parallelism);
+        let metrics = QualityMetrics::compute(&graph, &clusters, &features);
+        let objective = metrics.get(&plan.rank_by);
+        runs.push(SweepRun {
+            parameters,
+            metrics,
+        });
+
+        let control = observer.on_iteration(&Iteration {
+            stage: "sweep",
+            iteration: runs.len(),
+            total: Some(total),
+            objective,
+        });
+        if let Control::Stop(reason) = control {
+            if runs.len() < total {
+                status = reason;
+            }
+            break;
+        }
+    }
+
+    // Stable sort, so ties keep their grid order; an undefined score ranks
+    // last
+    let score = |run: &SweepRun| {
+        let value = run.metrics.get(&plan.rank_by);
+        if value.is_nan() {
+            f64::NEG_INFINITY
+        } else {
+            value
+        }
+    };
+    runs.sort_by(|a, b| score(b).total_cmp(&score(a)));
+    Ok((runs, status))
+}
+
+pub fn results_table(plan: &SweepPlan, runs: &[SweepRun]) -> Table {
+    let mut headers = vec!["rank"];
+    headers.extend(plan.grid.iter().map(|(name, _)| name.as_str()));
+    headers.extend(METRICS);
+
+    let mut table = Table::new(&headers);
+    for (rank, run) in runs.iter().enumerate() {
+        let mut row = vec![(rank + 1).to_string()];
+        row.extend(run.parameters.iter().map(|(_, value)| value.to_string()));
+        row.push(run.metrics.clusters.to_string());
+        row.push(format!("{:.4}", run.metrics.coverage));
+        row.push(format!("{:.4}", run.metrics.intra_weight));
+        row.push(format!("{:.4}", run.metrics.modularity));
+        row.push(match run.metrics.silhouette.is_nan() {
+            true => "NA".to_string(),
+            false => format!("{:.4}", run.metrics.silhouette),
+        });
+        table.push_row(row);
+    }
+    table
+}
+
+// The silhouette of the clustered nodes over their countries' feature rows;
+// nodes without a row are left out.
+fn silhouette(graph: &Graph, clusters: &[Vec<usize>], features: &FeatureMatrix) -> f64 {
+    let mut rows = Vec::new();
+    let mut assignments = Vec::new();
+    for (cluster, members) in clusters.iter().enumerate() {
+        for &node in members {
+            let row = features
+                .countries
+                .iter()
+                .position(|country| *country == graph.nodes[node]);
+            if let Some(row) = row {
+                rows.push(row);
+                assignments.push(cluster);
+            }
+        }
+    }
+    quality::silhouette(&assignments, |i, j| {
+        features::distance(&features.values[rows[i]], &features.values[rows[j]])
+    })
+    .unwrap_or(f64::NAN)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancel::CancelToken;
+    use crate::data::record;
+    use crate::observer::Unobserved;
+
+    #[test]
+    fn test_plan_expands_grid() {
+        let config = Config::parse("[grid]\nthreshold = [0.0, 1.0, 2.5]\n").unwrap();
+        let plan = SweepPlan::from_config(&config).unwrap();
+
+        assert_eq!(plan.rank_by, DEFAULT_RANK_BY);
+        let combinations = plan.combinations();
+        assert_eq!(combinations.len(), 3);
+        assert_eq!(
+            combinations[2],
+            vec![("threshold".to_string(), Value::Float(2.5))]
+        );
+    }
+
+    #[test]
+    fn test_plan_rejects_unknown_names() {
+        let config = Config::parse("[grid]\nbogus = [1, 2]\n").unwrap();
+        assert!(SweepPlan::from_config(&config).is_err());
+
+        let config = Config::parse("rank_by = \"bogus\"\n[grid]\nthreshold = 1\n").unwrap();
+        assert!(SweepPlan::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_cancelled_sweep_returns_partial_results() {
+        let config = Config::parse("[grid]\nthreshold = [0.0, 1.0, 2.0]\n").unwrap();
+        let plan = SweepPlan::from_config(&config).unwrap();
+        let mut cancel = CancelToken::new().with_timeout(std::time::Duration::ZERO);
+
+        // The expired token stops the sweep after the first combination
+        let (runs, status) = run_sweep(&[], &plan, Parallelism::sequential(), &mut cancel).unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(status, RunStatus::TimedOut);
+    }
+
+    #[test]
+    fn test_sweep_varies_k_and_metric() {
+        let data = [
+            record("Chad", "primary", 2015, 40.0),
+            record("Chad", "secondary", 2015, 10.0),
+            record("Mali", "primary", 2015, 44.0),
+            record("Mali", "secondary", 2015, 12.0),
+            record("Peru", "primary", 2015, 10.0),
+            record("Peru", "secondary", 2015, 90.0),
+        ];
+        let config = Config::parse(
+            "rank_by = \"clusters\"\n[grid]\nk = [1, 2]\nmetric = [\"cosine\", \"euclidean\"]\n",
+        )
+        .unwrap();
+        let plan = SweepPlan::from_config(&config).unwrap();
+        let (runs, _) =
+            run_sweep(&data, &plan, Parallelism::sequential(), &mut Unobserved).unwrap();
+        assert_eq!(runs.len(), 4);
+        // Best-first by cluster count, grid order among ties
+        let ranked: Vec<(String, usize)> = runs
+            .iter()
+            .map(|run| (run.parameters[1].1.to_string(), run.metrics.clusters))
+            .collect();
+        assert_eq!(ranked[0].1, 2);
+        assert_eq!(ranked[3].1, 1);
+        assert!(ranked[0].0.contains("cosine"), "{:?}", ranked);
+
+        let config = Config::parse("[grid]\nmetric = \"hamming\"\n").unwrap();
+        let plan = SweepPlan::from_config(&config).unwrap();
+        assert!(run_sweep(&data, &plan, Parallelism::sequential(), &mut Unobserved).is_err());
+        let config = Config::parse("[grid]\nk = 0\n").unwrap();
+        let plan = SweepPlan::from_config(&config).unwrap();
+        assert!(run_sweep(&data, &plan, Parallelism::sequential(), &mut Unobserved).is_err());
+    }
+
+    #[test]
+    fn test_quality_metrics() {
+        let graph = Graph {
+            nodes: vec!["A".to_string(), "B".to_string(), "C".to_string()],
+            adjacency_matrix: vec![
+                vec![0.0, 3.0, 1.0],
+                vec![3.0, 0.0, 0.0],
+                vec![1.0, 0.0, 0.0],
+            ],
+        };
+        let features = FeatureMatrix {
+            countries: vec!["A".to_string(), "B".to_string(), "C".to_string()],
+            series: vec!["primary".to_string()],
+            values: vec![vec![0.0], vec![1.0], vec![10.0]],
+        };
+        let metrics = QualityMetrics::compute(&graph, &[vec![0, 1]], &features);
+
+        assert_eq!(metrics.clusters, 1);
+        assert!((metrics.coverage - 2.0 / 3.0).abs() < 1e-12);
+        assert!((metrics.intra_weight - 0.75).abs() < 1e-12);
+        assert!(metrics.silhouette.is_nan());
+
+        // Degrees 4, 3 and 1 of the 8: Q = 6/8 - (7/8)^2 - (1/8)^2
+        let metrics = QualityMetrics::compute(&graph, &[vec![0, 1], vec![2]], &features);
+        assert!((metrics.modularity + 0.03125).abs() < 1e-12);
+        // A and B are 1 apart and 10 and 9 from C, which is alone
+        let expected = (0.9 + 8.0 / 9.0) / 3.0;
+        assert!((metrics.silhouette - expected).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,241 @@
+use std::io;
+use std::str::FromStr;
+
+use crate::Graph;
+
+pub const SYMMETRIES: &[&str] = &["none", "average", "max"];
+
+// The graph builder fills each country's row with its own records' weight,
+// so the matrix is directed (row i to column j need not equal j to i) and
+// every node has a self-loop on the diagonal. Algorithms that assume an
+// undirected simple graph can ask for both to be normalized away once the
+// graph is built.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Symmetry {
+    // Leave both directions as built
+    #[default]
+    None,
+    // Both directions take the mean of the two weights
+    Average,
+    // Both directions take the larger of the two weights
+    Max,
+}
+
+impl FromStr for Symmetry {
+    type Err = io::Error;
+
+    fn from_str(value: &str) -> io::Result<Symmetry> {
+        match value {
+            "none" => Ok(Symmetry::None),
+            "average" => Ok(Symmetry::Average),
+            "max" => Ok(Symmetry::Max),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown symmetry `{}`; expected one of {}",
+                    other,
+                    SYMMETRIES.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+// Ways to thin out the dense matrix the builder makes, where every pair is
+// linked and clustering has little to go on. The diagonal is left alone.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pruning {
+    // Drop edges lighter than this
+    Threshold(f64),
+    // Keep each node's k heaviest links, a pair weighing the mean of its two
+    // directions; a pair either end keeps stays in both directions, so a
+    // node can end up with more than k. Ties go to the earlier node.
+    TopK(usize),
+}
+
+impl Graph {
+    pub fn prune(&mut self, pruning: Pruning) {
+        let matrix = &mut self.adjacency_matrix;
+        let size = matrix.len();
+        match pruning {
+            Pruning::Threshold(min_weight) => {
+                for (i, row) in matrix.iter_mut().enumerate() {
+                    for (j, weight) in row.iter_mut().enumerate() {
+                        if i != j && *weight < min_weight {
+                            *weight = 0.0;
+                        }
+                    }
+                }
+            }
+            Pruning::TopK(k) => {
+                let pair = |i: usize, j: usize| (matrix[i][j] + matrix[j][i]) / 2.0;
+                let mut keep = vec![vec![false; size]; size];
+                for i in 0..size {
+                    let mut links: Vec<usize> = (0..size)
+                        .filter(|&j| j != i && (matrix[i][j] != 0.0 || matrix[j][i] != 0.0))
+                        .collect();
+                    links.sort_by(|&a, &b| pair(i, b).total_cmp(&pair(i, a)).then(a.cmp(&b)));
+                    for &j in links.iter().take(k) {
+                        keep[i][j] = true;
+                        keep[j][i] = true;
+                    }
+                }
+                for (i, row) in matrix.iter_mut().enumerate() {
+                    for (j, weight) in row.iter_mut().enumerate() {
+                        if i != j && !keep[i][j] {
+                            *weight = 0.0;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GraphPolicy {
+    pub self_loops: bool,
+    pub symmetry: Symmetry,
+    // Edges lighter than this are dropped (after symmetrizing)
+    pub min_weight: Option<f64>,
+    // Then all but each node's strongest links (`Pruning::TopK`)
+    pub top_k: Option<usize>,
+}
+
+// As built: self-loops kept, directions left alone, every edge kept.
+impl Default for GraphPolicy {
+    fn default() -> GraphPolicy {
+        GraphPolicy {
+            self_loops: true,
+            symmetry: Symmetry::None,
+            min_weight: None,
+            top_k: None,
+        }
+    }
+}
+
+impl GraphPolicy {
+    pub fn apply(&self, graph: &mut Graph) {
+        if self.symmetry != Symmetry::None {
+            let matrix = &mut graph.adjacency_matrix;
+            let size = matrix.len();
+            let pairs = (0..size).flat_map(|i| (i + 1..size).map(move |j| (i, j)));
+            for (i, j) in pairs {
+                let (forward, backward) = (matrix[i][j], matrix[j][i]);
+                let weight = match self.symmetry {
+                    Symmetry::Average => (forward + backward) / 2.0,
+                    Symmetry::Max => forward.max(backward),
+                    Symmetry::None => unreachable!(),
+                };
+                matrix[i][j] = weight;
+                matrix[j][i] = weight;
+            }
+        }
+        if let Some(min_weight) = self.min_weight {
+            graph.prune(Pruning::Threshold(min_weight));
+        }
+        if let Some(k) = self.top_k {
+            graph.prune(Pruning::TopK(k));
+        }
+        if !self.self_loops {
+            for (i, row) in graph.adjacency_matrix.iter_mut().enumerate() {
+                row[i] = 0.0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> Graph {
+        Graph {
+            nodes: vec!["Chad".to_string(), "Mali".to_string(), "Niger".to_string()],
+            adjacency_matrix: vec![
+                vec![5.0, 1.0, 0.0],
+                vec![3.0, 6.0, 2.0],
+                vec![4.0, 2.0, 7.0],
+            ],
+        }
+    }
+
+    #[test]
+    fn test_symmetry_and_self_loops() {
+        let mut as_built = graph();
+        GraphPolicy::default().apply(&mut as_built);
+        assert_eq!(as_built.adjacency_matrix, graph().adjacency_matrix);
+
+        let mut averaged = graph();
+        let policy = GraphPolicy {
+            self_loops: false,
+            symmetry: Symmetry::Average,
+            min_weight: None,
+            top_k: None,
+        };
+        policy.apply(&mut averaged);
+        assert_eq!(
+            averaged.adjacency_matrix,
+            vec![
+                vec![0.0, 2.0, 2.0],
+                vec![2.0, 0.0, 2.0],
+                vec![2.0, 2.0, 0.0],
+            ]
+        );
+
+        let mut maxed = graph();
+        let policy = GraphPolicy {
+            symmetry: "max".parse().unwrap(),
+            ..GraphPolicy::default()
+        };
+        policy.apply(&mut maxed);
+        assert_eq!(
+            maxed.adjacency_matrix,
+            vec![
+                vec![5.0, 3.0, 4.0],
+                vec![3.0, 6.0, 2.0],
+                vec![4.0, 2.0, 7.0],
+            ]
+        );
+        assert!("mean".parse::<Symmetry>().is_err());
+
+        // Light edges go, the diagonal stays
+        let mut pruned = graph();
+        let policy = GraphPolicy {
+            min_weight: Some(3.0),
+            ..GraphPolicy::default()
+        };
+        policy.apply(&mut pruned);
+        assert_eq!(
+            pruned.adjacency_matrix,
+            vec![
+                vec![5.0, 0.0, 0.0],
+                vec![3.0, 6.0, 0.0],
+                vec![4.0, 0.0, 7.0],
+            ]
+        );
+
+        // Every pair averages 2, so each node keeps its earliest link: Chad
+        // keeps Mali, Mali and Niger keep Chad, and Mali-Niger goes
+        let mut nearest = graph();
+        nearest.prune(Pruning::TopK(1));
+        assert_eq!(
+            nearest.adjacency_matrix,
+            vec![
+                vec![5.0, 1.0, 0.0],
+                vec![3.0, 6.0, 0.0],
+                vec![4.0, 0.0, 7.0],
+            ]
+        );
+        let mut heavier = graph();
+        heavier.adjacency_matrix[1][2] = 10.0;
+        heavier.prune(Pruning::TopK(1));
+        // Mali-Niger now averages 6: Mali and Niger keep it, Chad keeps Mali
+        assert_eq!(heavier.adjacency_matrix[1][2], 10.0);
+        assert_eq!(heavier.adjacency_matrix[2][0], 0.0);
+        assert_eq!(heavier.adjacency_matrix[0][1], 1.0);
+        let mut all = graph();
+        all.prune(Pruning::TopK(2));
+        assert_eq!(all.adjacency_matrix, graph().adjacency_matrix);
+    }
+}
@@ -0,0 +1,108 @@
+use std::io::{self, Write};
+
+// A rectangular table of already-formatted cells that can be printed either
+// as aligned text for the terminal or as CSV for spreadsheets and pandas.
+pub struct Table {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Table {
+        Table {
+            headers: headers.iter().map(|header| header.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push_row(&mut self, row: Vec<String>) {
+        debug_assert_eq!(row.len(), self.headers.len());
+        self.rows.push(row);
+    }
+
+    pub fn write_text(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let mut widths: Vec<usize> = self.headers.iter().map(|header| header.len()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let format_row = |cells: &[String]| {
+            let padded: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            padded.join("  ").trim_end().to_string()
+        };
+
+        writeln!(writer, "{}", format_row(&self.headers))?;
+        let rule: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+        writeln!(writer, "{}", rule.join("  "))?;
+        for row in &self.rows {
+            writeln!(writer, "{}", format_row(row))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_csv(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_csv_record(writer, &self.headers)?;
+        for row in &self.rows {
+            write_csv_record(writer, row)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn write_csv_record(writer: &mut dyn Write, fields: &[String]) -> io::Result<()> {
+    let escaped: Vec<String> = fields.iter().map(|field| csv_escape(field)).collect();
+    writeln!(writer, "{}", escaped.join(","))
+}
+
+// Quote a field when it contains a delimiter, quote or line break (RFC 4180).
+pub fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Table {
+        let mut table = Table::new(&["country", "value"]);
+        table.push_row(vec!["Tanzania, Mainland".to_string(), "1.5".to_string()]);
+        table.push_row(vec!["Chad".to_string(), "12.25".to_string()]);
+        table
+    }
+
+    #[test]
+    fn test_write_text_aligns_columns() {
+        let mut buffer = Vec::new();
+        sample().write_text(&mut buffer).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+
+        let expected = "country             value\n\
+                        ------------------  -----\n\
+                        Tanzania, Mainland  1.5\n\
+                        Chad                12.25\n";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_write_csv_quotes_fields() {
+        let mut buffer = Vec::new();
+        sample().write_csv(&mut buffer).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+
+        assert_eq!(
+            output,
+            "country,value\n\"Tanzania, Mainland\",1.5\nChad,12.25\n"
+        );
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
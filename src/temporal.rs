@@ -0,0 +1,285 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+
+use crate::cluster::{Algorithm, Stop};
+use crate::graph;
+use crate::labels;
+use crate::matrix::{self, MatrixBackend};
+use crate::parallel::Parallelism;
+use crate::table::Table;
+use crate::{EducationData, Graph, SimilarityMetric};
+
+// Graphs over time. The value graph folds every year into one weight; here
+// the observations are cut into buckets of years instead, and each bucket
+// gets a similarity graph of its own (each series' latest value in the
+// bucket, as `features::feature_matrix` takes it), clustered on its own.
+// Consecutive snapshots are then compared for each country in both:
+//
+//   weight_change    mean absolute change of its edge weights to the other
+//                    countries in both (the mean of both directions)
+//   cluster_changed  share of those countries it went from sharing a
+//                    cluster with to not, or back
+
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    pub metric: SimilarityMetric,
+    // Years per snapshot, counted from the earliest year
+    pub bucket: u32,
+    pub algorithm: Algorithm,
+    pub stop: Stop,
+    pub parallelism: Parallelism,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            metric: SimilarityMetric::Cosine,
+            bucket: 1,
+            algorithm: Algorithm::default(),
+            stop: Stop::Auto,
+            parallelism: Parallelism::default(),
+        }
+    }
+}
+
+pub struct Snapshot {
+    pub years: RangeInclusive<u32>,
+    pub graph: Graph,
+    pub clusters: Vec<Vec<usize>>,
+    // Each cluster's label, as in the cluster report
+    pub labels: Vec<String>,
+}
+
+impl Snapshot {
+    // "2010", or "2010..2014" for a bucket of several years
+    pub fn period(&self) -> String {
+        match (self.years.start(), self.years.end()) {
+            (start, end) if start == end => start.to_string(),
+            (start, end) => format!("{}..{}", start, end),
+        }
+    }
+}
+
+// One snapshot per bucket with any observations, oldest first.
+pub fn snapshots(data: Vec<EducationData>, options: Options) -> Vec<Snapshot> {
+    let bucket = options.bucket.max(1);
+    let Some(first) = data.iter().map(|record| record.year).min() else {
+        return Vec::new();
+    };
+    let mut buckets: BTreeMap<u32, Vec<EducationData>> = BTreeMap::new();
+    for record in data {
+        buckets
+            .entry((record.year - first) / bucket)
+            .or_default()
+            .push(record);
+    }
+    buckets
+        .into_iter()
+        .map(|(index, records)| {
+            let start = first + index * bucket;
+            let graph = graph::construct_similarity_graph_with(
+                &records,
+                options.metric,
+                options.parallelism,
+            );
+            let clusters =
+                options
+                    .algorithm
+                    .cluster(&graph, None, options.stop, options.parallelism);
+            let labels = labels::cluster_labels(&graph, &clusters);
+            Snapshot {
+                years: start..=start.saturating_add(bucket - 1),
+                graph,
+                clusters,
+                labels,
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub country: String,
+    pub from: String,
+    pub to: String,
+    // The labels of its cluster in either snapshot
+    pub cluster_from: String,
+    pub cluster_to: String,
+    // None without another country in both snapshots
+    pub weight_change: Option<f64>,
+    pub cluster_changed: Option<f64>,
+}
+
+// Every country's change between consecutive snapshots, by period and then
+// country.
+pub fn changes(snapshots: &[Snapshot]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for pair in snapshots.windows(2) {
+        let [before, after] = [&pair[0], &pair[1]].map(|snapshot| {
+            let mut cluster_of = vec![usize::MAX; snapshot.graph.nodes.len()];
+            for (index, members) in snapshot.clusters.iter().enumerate() {
+                for &node in members {
+                    cluster_of[node] = index;
+                }
+            }
+            let nodes: HashMap<&str, usize> = snapshot
+                .graph
+                .nodes
+                .iter()
+                .enumerate()
+                .map(|(index, node)| (node.as_str(), index))
+                .collect();
+            (snapshot, cluster_of, nodes)
+        });
+        // Countries in both, as (before, after) node indices in name order
+        let mut shared: Vec<(&str, usize, usize)> = before
+            .2
+            .iter()
+            .filter_map(|(&name, &a)| Some((name, a, *after.2.get(name)?)))
+            .collect();
+        shared.sort_unstable();
+        let weight = |snapshot: &Snapshot, i: usize, j: usize| {
+            let matrix = &snapshot.graph.adjacency_matrix;
+            (matrix[i][j] + matrix[j][i]) / 2.0
+        };
+        let label = |(snapshot, cluster_of, _): &(&Snapshot, Vec<usize>, _), node: usize| {
+            snapshot
+                .labels
+                .get(cluster_of[node])
+                .cloned()
+                .unwrap_or_default()
+        };
+        for &(country, a, b) in &shared {
+            let others: Vec<(usize, usize)> = shared
+                .iter()
+                .filter(|other| other.0 != country)
+                .map(|&(_, c, d)| (c, d))
+                .collect();
+            let count = others.len() as f64;
+            let weight_change = others
+                .iter()
+                .map(|&(c, d)| (weight(after.0, b, d) - weight(before.0, a, c)).abs())
+                .sum::<f64>()
+                / count;
+            let together = |cluster_of: &[usize], node: usize, other: usize| {
+                cluster_of[node] != usize::MAX && cluster_of[node] == cluster_of[other]
+            };
+            let flipped = others
+                .iter()
+                .filter(|&&(c, d)| together(&before.1, a, c) != together(&after.1, b, d))
+                .count();
+            let defined = !others.is_empty();
+            changes.push(Change {
+                country: country.to_string(),
+                from: before.0.period(),
+                to: after.0.period(),
+                cluster_from: label(&before, a),
+                cluster_to: label(&after, b),
+                weight_change: defined.then_some(weight_change),
+                cluster_changed: defined.then(|| flipped as f64 / count),
+            });
+        }
+    }
+    changes
+}
+
+// One row per snapshot: its size and clustering.
+pub fn snapshots_table(snapshots: &[Snapshot]) -> Table {
+    let mut table = Table::new(&["period", "countries", "edges", "clusters"]);
+    for snapshot in snapshots {
+        table.push_row(vec![
+            snapshot.period(),
+            snapshot.graph.nodes.len().to_string(),
+            matrix::storage(&snapshot.graph.adjacency_matrix)
+                .edge_weights()
+                .len()
+                .to_string(),
+            snapshot.clusters.len().to_string(),
+        ]);
+    }
+    table
+}
+
+pub fn changes_table(changes: &[Change]) -> Table {
+    let score =
+        |value: Option<f64>| value.map_or_else(|| "NA".to_string(), |v| format!("{:.4}", v));
+    let mut table = Table::new(&[
+        "country",
+        "from",
+        "to",
+        "cluster_from",
+        "cluster_to",
+        "weight_change",
+        "cluster_changed",
+    ]);
+    for change in changes {
+        table.push_row(vec![
+            change.country.clone(),
+            change.from.clone(),
+            change.to.clone(),
+            change.cluster_from.clone(),
+            change.cluster_to.clone(),
+            score(change.weight_change),
+            score(change.cluster_changed),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    fn observations() -> Vec<EducationData> {
+        let mut data = Vec::new();
+        for year in [2010, 2011, 2013] {
+            // Mali moves from Chad's side to Peru's after 2011
+            let mali = if year < 2013 { (1.0, 9.0) } else { (9.0, 1.0) };
+            for (country, (primary, tertiary)) in
+                [("Chad", (1.0, 10.0)), ("Mali", mali), ("Peru", (10.0, 1.0))]
+            {
+                data.push(record(country, "primary", year, primary));
+                data.push(record(country, "tertiary", year, tertiary));
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_snapshots_per_bucket_and_changes() {
+        let options = Options {
+            stop: Stop::Clusters(2),
+            parallelism: Parallelism::sequential(),
+            ..Options::default()
+        };
+        let yearly = snapshots(observations(), options);
+        let periods: Vec<String> = yearly.iter().map(Snapshot::period).collect();
+        assert_eq!(periods, ["2010", "2011", "2013"]);
+        let pairs = Options {
+            bucket: 2,
+            ..options
+        };
+        let buckets = snapshots(observations(), pairs);
+        let periods: Vec<String> = buckets.iter().map(Snapshot::period).collect();
+        assert_eq!(periods, ["2010..2011", "2012..2013"]);
+
+        let found = changes(&yearly);
+        assert_eq!(found.len(), 6);
+        // Nothing moves from 2010 to 2011; Mali changes sides by 2013
+        assert!(found[..3]
+            .iter()
+            .all(|change| change.cluster_changed == Some(0.0)));
+        let mali = &found[4];
+        assert_eq!(
+            (mali.country.as_str(), mali.from.as_str()),
+            ("Mali", "2011")
+        );
+        assert_eq!(mali.cluster_changed, Some(1.0));
+        assert!(mali.weight_change.unwrap() > found[3].weight_change.unwrap());
+
+        let table = changes_table(&found);
+        assert_eq!(table.rows[4][..3], ["Mali", "2011", "2013"]);
+        assert_eq!(snapshots_table(&yearly).rows[0][1], "3");
+    }
+}
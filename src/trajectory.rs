@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+use crate::labels;
+use crate::stats::{mean, std_dev};
+use crate::table::Table;
+use crate::{EducationData, Graph};
+
+// How each cluster moved over the years: for every series and year, the
+// mean (and spread) of the values its members report. The table is tidy,
+// one row per cluster, series and year, so the groups' trends can be
+// plotted side by side elsewhere. A country with several records of a
+// series in a year counts once, with its last; years in which no member
+// reports a series are left out rather than written as gaps.
+
+pub struct Point {
+    pub cluster: usize,
+    pub series: String,
+    pub year: u32,
+    pub mean: f64,
+    // Population standard deviation over the reporting members
+    pub std_dev: f64,
+    pub countries: usize,
+}
+
+// By cluster, then series, then year.
+pub fn cluster_trajectories(
+    data: &[EducationData],
+    graph: &Graph,
+    clusters: &[Vec<usize>],
+) -> Vec<Point> {
+    let mut cluster_of = BTreeMap::new();
+    for (index, members) in clusters.iter().enumerate() {
+        for &node in members {
+            cluster_of.insert(graph.nodes[node].as_str(), index);
+        }
+    }
+    let mut values: BTreeMap<(&str, &str, u32), f64> = BTreeMap::new();
+    for record in data {
+        if let Some(value) = record.value {
+            if cluster_of.contains_key(record.country_or_area.as_str()) {
+                values.insert(
+                    (
+                        record.country_or_area.as_str(),
+                        record.series.as_str(),
+                        record.year,
+                    ),
+                    value,
+                );
+            }
+        }
+    }
+    let mut groups: BTreeMap<(usize, &str, u32), Vec<f64>> = BTreeMap::new();
+    for ((country, series, year), value) in values {
+        groups
+            .entry((cluster_of[country], series, year))
+            .or_default()
+            .push(value);
+    }
+    groups
+        .into_iter()
+        .map(|((cluster, series, year), values)| Point {
+            cluster,
+            series: series.to_string(),
+            year,
+            mean: mean(&values).unwrap_or(f64::NAN),
+            std_dev: std_dev(&values).unwrap_or(f64::NAN),
+            countries: values.len(),
+        })
+        .collect()
+}
+
+pub fn trajectory_table(graph: &Graph, clusters: &[Vec<usize>], points: &[Point]) -> Table {
+    let labels = labels::cluster_labels(graph, clusters);
+    let mut table = Table::new(&[
+        "cluster",
+        "label",
+        "series",
+        "year",
+        "mean",
+        "std_dev",
+        "countries",
+    ]);
+    for point in points {
+        table.push_row(vec![
+            point.cluster.to_string(),
+            labels[point.cluster].clone(),
+            point.series.clone(),
+            point.year.to_string(),
+            format!("{:.4}", point.mean),
+            format!("{:.4}", point.std_dev),
+            point.countries.to_string(),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_mean_per_cluster_series_and_year() {
+        let graph = Graph {
+            nodes: vec!["Chad".to_string(), "Mali".to_string(), "Peru".to_string()],
+            adjacency_matrix: vec![vec![0.0; 3]; 3],
+        };
+        let clusters = vec![vec![0, 1], vec![2]];
+        let data = vec![
+            record("Chad", "primary", 2010, Some(10.0)),
+            record("Mali", "primary", 2010, Some(20.0)),
+            record("Chad", "primary", 2015, Some(30.0)),
+            record("Mali", "primary", 2015, None),
+            record("Peru", "primary", 2015, Some(5.0)),
+            // Not in the clustering
+            record("Togo", "primary", 2010, Some(99.0)),
+        ];
+        let points = cluster_trajectories(&data, &graph, &clusters);
+        let summary: Vec<(usize, u32, f64, usize)> = points
+            .iter()
+            .map(|point| (point.cluster, point.year, point.mean, point.countries))
+            .collect();
+        assert_eq!(
+            summary,
+            [(0, 2010, 15.0, 2), (0, 2015, 30.0, 1), (1, 2015, 5.0, 1)]
+        );
+        assert_eq!(points[0].std_dev, 5.0);
+
+        let table = trajectory_table(&graph, &clusters, &points);
+        assert_eq!(table.headers[4], "mean");
+        assert_eq!(table.rows[0][2], "primary");
+        assert_eq!(table.rows[0][4], "15.0000");
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use crate::config::Config;
+use crate::EducationData;
+
+// Named value transforms, applied to series values before the graph is
+// built so that, say, enrolment counts spanning five orders of magnitude
+// are compared on a log scale. A transform returns None for a value outside
+// its domain (the log of 0, the logit of 100%), which is then missing.
+// Programs embedding the crate can register their own next to the built-in
+// ones and name them in the same config files.
+pub type TransformFn = Box<dyn Fn(f64) -> Option<f64> + Send + Sync>;
+
+pub struct Transforms {
+    functions: BTreeMap<String, TransformFn>,
+}
+
+// The built-in transforms:
+//   log       natural log of a positive value
+//   sqrt      square root of a non-negative value
+//   logit     log-odds of a percentage strictly between 0 and 100
+//   per-1000  the value divided by 1000, e.g. persons to thousands
+impl Default for Transforms {
+    fn default() -> Transforms {
+        let mut transforms = Transforms::empty();
+        transforms.register("log", |value| (value > 0.0).then(|| value.ln()));
+        transforms.register("sqrt", |value| (value >= 0.0).then(|| value.sqrt()));
+        transforms.register("logit", |value| {
+            let share = value / 100.0;
+            (share > 0.0 && share < 1.0).then(|| (share / (1.0 - share)).ln())
+        });
+        transforms.register("per-1000", |value| Some(value / 1000.0));
+        transforms
+    }
+}
+
+impl Transforms {
+    pub fn empty() -> Transforms {
+        Transforms {
+            functions: BTreeMap::new(),
+        }
+    }
+
+    // Register `transform` under `name`, replacing any transform of that name.
+    pub fn register(
+        &mut self,
+        name: &str,
+        transform: impl Fn(f64) -> Option<f64> + Send + Sync + 'static,
+    ) {
+        self.functions.insert(name.to_string(), Box::new(transform));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TransformFn> {
+        self.functions.get(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.functions.keys().map(String::as_str).collect()
+    }
+}
+
+// The transforms of one `[transform.<group>]` table: the series it covers
+// and the transforms applied to their values, in order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeriesGroup {
+    pub name: String,
+    // Exact series names, or prefixes ending in `*`
+    pub series: Vec<String>,
+    pub transforms: Vec<String>,
+}
+
+impl SeriesGroup {
+    pub fn covers(&self, series: &str) -> bool {
+        self.series
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => series.starts_with(prefix),
+                None => series == pattern,
+            })
+    }
+}
+
+// Every series group of a config file:
+//
+//     [transform.enrolment]
+//     series = ["Enrolment in primary*", "Enrolment in secondary*"]
+//     transform = ["log"]
+//
+// A series in several groups takes each group's transforms in turn, groups
+// in name order; series in none are left as they are.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransformPlan {
+    pub groups: Vec<SeriesGroup>,
+}
+
+impl TransformPlan {
+    pub fn load(path: &str, transforms: &Transforms) -> io::Result<TransformPlan> {
+        TransformPlan::from_config(&Config::load(path)?, transforms)
+    }
+
+    // Read the `[transform]` tables of a config, refusing transform names
+    // that are not registered.
+    pub fn from_config(config: &Config, transforms: &Transforms) -> io::Result<TransformPlan> {
+        let tables = match config.get("transform") {
+            None => return Ok(TransformPlan::default()),
+            Some(value) => value
+                .as_table()
+                .ok_or_else(|| invalid_data("`transform` must be a table of series groups"))?,
+        };
+        let mut groups = Vec::new();
+        for (name, table) in tables {
+            let table = table
+                .as_table()
+                .ok_or_else(|| invalid_data(&format!("`transform.{}` must be a table", name)))?;
+            let strings = |key: &str| -> io::Result<Vec<String>> {
+                let value = table.get(key).ok_or_else(|| {
+                    invalid_data(&format!("`transform.{}` needs a `{}` list", name, key))
+                })?;
+                value
+                    .to_list()
+                    .iter()
+                    .map(|item| item.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| {
+                        invalid_data(&format!("`transform.{}.{}` must be strings", name, key))
+                    })
+            };
+            let group = SeriesGroup {
+                name: name.clone(),
+                series: strings("series")?,
+                transforms: strings("transform")?,
+            };
+            if let Some(unknown) = group
+                .transforms
+                .iter()
+                .find(|transform| transforms.get(transform).is_none())
+            {
+                return Err(invalid_data(&format!(
+                    "`transform.{}`: unknown transform `{}`; expected one of {}",
+                    name,
+                    unknown,
+                    transforms.names().join(", ")
+                )));
+            }
+            if let Some(key) = table
+                .keys()
+                .find(|key| *key != "series" && *key != "transform")
+            {
+                return Err(invalid_data(&format!(
+                    "`transform.{}`: unknown key `{}`",
+                    name, key
+                )));
+            }
+            groups.push(group);
+        }
+        Ok(TransformPlan { groups })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    // Transform every value of the covered series in place; returns how many
+    // values fell outside a transform's domain and are now missing.
+    pub fn apply(&self, transforms: &Transforms, data: &mut [EducationData]) -> usize {
+        let mut dropped = 0;
+        let mut steps: BTreeMap<String, Vec<&TransformFn>> = BTreeMap::new();
+        for record in data {
+            let steps = steps.entry(record.series.clone()).or_insert_with(|| {
+                self.groups
+                    .iter()
+                    .filter(|group| group.covers(&record.series))
+                    .flat_map(|group| &group.transforms)
+                    .filter_map(|name| transforms.get(name))
+                    .collect()
+            });
+            if let Some(value) = record.value {
+                record.value = steps
+                    .iter()
+                    .try_fold(value, |value, transform| transform(value));
+                if record.value.is_none() {
+                    dropped += 1;
+                }
+            }
+        }
+        dropped
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::record;
+
+    #[test]
+    fn test_groups_transform_their_series() {
+        let config = Config::parse(
+            r#"
+            [transform.counts]
+            series = ["Enrolment in*"]
+            transform = ["per-1000", "log"]
+
+            [transform.rates]
+            series = "Literacy rate"
+            transform = ["logit"]
+            "#,
+        )
+        .unwrap();
+        let mut transforms = Transforms::default();
+        let plan = TransformPlan::from_config(&config, &transforms).unwrap();
+        assert_eq!(plan.groups.len(), 2);
+
+        let mut data = vec![
+            record(
+                "Chad",
+                "Enrolment in primary",
+                2015,
+                1000.0 * std::f64::consts::E,
+            ),
+            record("Chad", "Enrolment in primary", 2015, 0.0),
+            record("Chad", "Literacy rate", 2015, 50.0),
+            record("Chad", "Literacy rate", 2015, 100.0),
+            record("Chad", "Pupil-teacher ratio", 2015, 40.0),
+        ];
+        assert_eq!(plan.apply(&transforms, &mut data), 2);
+        let values: Vec<Option<f64>> = data.iter().map(|record| record.value).collect();
+        assert!((values[0].unwrap() - 1.0).abs() < 1e-12);
+        assert_eq!(values[1..], [None, Some(0.0), None, Some(40.0)]);
+
+        // Registered transforms are named like the built-in ones
+        transforms.register("double", |value| Some(value * 2.0));
+        let config = Config::parse("[transform.all]\nseries = \"*\"\ntransform = \"double\"\n");
+        let plan = TransformPlan::from_config(&config.unwrap(), &transforms).unwrap();
+        let mut data = vec![record("Chad", "Pupil-teacher ratio", 2015, 40.0)];
+        plan.apply(&transforms, &mut data);
+        assert_eq!(data[0].value, Some(80.0));
+
+        let bogus = Config::parse("[transform.all]\nseries = \"*\"\ntransform = \"cube\"\n");
+        let error = TransformPlan::from_config(&bogus.unwrap(), &Transforms::default());
+        assert!(error
+            .unwrap_err()
+            .to_string()
+            .contains("unknown transform `cube`"));
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::io;
+
+use crate::EducationData;
+
+// LOWESS-style smoothing: each point is replaced by a local linear fit over
+// the nearest `span` fraction of the series, weighted by the tricube kernel.
+// The fitted values are the trend; what is left over is treated as noise.
+pub fn lowess(xs: &[f64], ys: &[f64], span: f64) -> Vec<f64> {
+    let n = xs.len();
+    if n < 3 {
+        return ys.to_vec();
+    }
+    let neighbours = ((span * n as f64).ceil() as usize).clamp(2, n);
+
+    (0..n)
+        .map(|i| {
+            let mut distances: Vec<f64> = xs.iter().map(|x| (x - xs[i]).abs()).collect();
+            distances.sort_by(f64::total_cmp);
+            let radius = distances[neighbours - 1];
+
+            let weights: Vec<f64> = xs
+                .iter()
+                .map(|x| {
+                    let distance = (x - xs[i]).abs();
+                    if radius == 0.0 {
+                        if distance == 0.0 {
+                            1.0
+                        } else {
+                            0.0
+                        }
+                    } else if distance < radius {
+                        (1.0 - (distance / radius).powi(3)).powi(3)
+                    } else {
+                        0.0
+                    }
+                })
+                .collect();
+            local_linear(xs, ys, &weights, xs[i])
+        })
+        .collect()
+}
+
+// Weighted least-squares line evaluated at `at`; falls back to the weighted
+// mean when the weighted points do not pin down a slope.
+fn local_linear(xs: &[f64], ys: &[f64], weights: &[f64], at: f64) -> f64 {
+    let total: f64 = weights.iter().sum();
+    if total == 0.0 {
+        return ys[xs.iter().position(|&x| x == at).unwrap_or(0)];
+    }
+    let mean_x = xs.iter().zip(weights).map(|(x, w)| x * w).sum::<f64>() / total;
+    let mean_y = ys.iter().zip(weights).map(|(y, w)| y * w).sum::<f64>() / total;
+    let mut covariance = 0.0;
+    let mut spread = 0.0;
+    for ((x, y), w) in xs.iter().zip(ys).zip(weights) {
+        covariance += w * (x - mean_x) * (y - mean_y);
+        spread += w * (x - mean_x).powi(2);
+    }
+    if spread <= f64::EPSILON * total {
+        mean_y
+    } else {
+        mean_y + covariance / spread * (at - mean_x)
+    }
+}
+
+// Replace every observed value with the trend of its (country, series)
+// history. Missing values stay missing and do not influence the trend.
+pub fn smooth_observations(data: &mut [EducationData], span: f64) {
+    let mut histories: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    for (index, record) in data.iter().enumerate() {
+        if record.value.is_some() {
+            histories
+                .entry((record.country_or_area.clone(), record.series.clone()))
+                .or_default()
+                .push(index);
+        }
+    }
+
+    for indices in histories.values() {
+        let xs: Vec<f64> = indices
+            .iter()
+            .map(|&index| f64::from(data[index].year))
+            .collect();
+        let ys: Vec<f64> = indices
+            .iter()
+            .map(|&index| data[index].value.expect("observed"))
+            .collect();
+        for (&index, trend) in indices.iter().zip(lowess(&xs, &ys, span)) {
+            data[index].value = Some(trend);
+        }
+    }
+}
+
+pub fn parse_span(raw: &str) -> io::Result<f64> {
+    match raw.parse::<f64>() {
+        Ok(span) if span > 0.0 && span <= 1.0 => Ok(span),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "--trend must be a fraction of the series in (0, 1], got {}",
+                raw
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lowess_keeps_lines_and_damps_noise() {
+        let xs: Vec<f64> = (0..7).map(f64::from).collect();
+        let line: Vec<f64> = xs.iter().map(|x| 2.0 * x + 1.0).collect();
+        for (smoothed, expected) in lowess(&xs, &line, 0.5).iter().zip(&line) {
+            assert!((smoothed - expected).abs() < 1e-9);
+        }
+
+        // A lone spike is mostly absorbed into its neighbourhood
+        let spiky = [10.0, 10.0, 10.0, 40.0, 10.0, 10.0, 10.0];
+        let smoothed = lowess(&xs, &spiky, 0.6);
+        assert!(smoothed[3] < 25.0);
+        assert!(smoothed[0] < 15.0);
+        assert!(parse_span("0").is_err() && parse_span("0.5").is_ok());
+    }
+}
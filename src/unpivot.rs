@@ -0,0 +1,187 @@
+use std::io::{self, BufRead};
+
+use crate::csv::{parse_number, split_record, Layout, Records};
+use crate::source::DataSource;
+use crate::EducationData;
+
+// Recognizes year columns in a wide (one column per year) table. In the
+// pattern `#` matches one digit, which together form the year, `*` matches
+// any run of characters and everything else matches itself, e.g. `####`
+// for "2015" or `YR####*` for "YR2015 [YR2015]".
+pub struct YearPattern {
+    pattern: Vec<char>,
+}
+
+impl YearPattern {
+    pub fn parse(pattern: &str) -> io::Result<YearPattern> {
+        if !pattern.contains('#') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "year column pattern {:?} needs `#` digit placeholders",
+                    pattern
+                ),
+            ));
+        }
+        Ok(YearPattern {
+            pattern: pattern.chars().collect(),
+        })
+    }
+
+    // The year a column header stands for, if it matches.
+    pub fn year(&self, header: &str) -> Option<u32> {
+        let header: Vec<char> = header.trim().chars().collect();
+        let mut digits = String::new();
+        if matches(&self.pattern, &header, &mut digits) {
+            digits.parse().ok()
+        } else {
+            None
+        }
+    }
+}
+
+fn matches(pattern: &[char], text: &[char], digits: &mut String) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| {
+            let mut attempt = digits.clone();
+            let found = matches(rest, &text[skip..], &mut attempt);
+            if found {
+                *digits = attempt;
+            }
+            found
+        }),
+        Some((&expected, rest)) => match text.split_first() {
+            Some((&c, text_rest)) if expected == '#' && c.is_ascii_digit() => {
+                digits.push(c);
+                matches(rest, text_rest, digits)
+            }
+            Some((&c, text_rest)) if expected != '#' && c == expected => {
+                matches(rest, text_rest, digits)
+            }
+            _ => false,
+        },
+    }
+}
+
+// Identify the non-year columns by their headers.
+struct IdColumns {
+    country: usize,
+    series: Option<usize>,
+    indicator: Option<usize>,
+}
+
+impl IdColumns {
+    fn detect(headers: &[String], is_year: &[bool]) -> IdColumns {
+        let find = |needles: &[&str]| {
+            headers.iter().enumerate().position(|(index, header)| {
+                let header = header.to_lowercase();
+                !is_year[index] && needles.iter().any(|needle| header.contains(needle))
+            })
+        };
+        IdColumns {
+            // Fall back to the first column, where tables usually name the area
+            country: find(&["country", "area", "region"]).unwrap_or(0),
+            series: find(&["series", "indicator name", "variable"]),
+            indicator: find(&["indicator code", "code", "table"]),
+        }
+    }
+}
+
+// Melt a wide file, one row per country (and series) with one column per
+// year, into long records. Every year cell becomes a record; empty cells are
+// kept as missing values.
+pub fn load_wide(
+    source: &dyn DataSource,
+    layout: &Layout,
+    pattern: &YearPattern,
+) -> io::Result<Vec<EducationData>> {
+    let mut lines = Records::new(source.open()?.lines());
+    let headers = layout.read_headers(&mut lines)?;
+    if headers.is_empty() {
+        return Ok(Vec::new());
+    }
+    let years: Vec<Option<u32>> = headers.iter().map(|header| pattern.year(header)).collect();
+    if years.iter().all(Option::is_none) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "no column header of {} matches the year pattern",
+                source.location()
+            ),
+        ));
+    }
+    let is_year: Vec<bool> = years.iter().map(Option::is_some).collect();
+    let columns = IdColumns::detect(&headers, &is_year);
+
+    let mut data = Vec::new();
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = split_record(&line);
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|index| fields.get(index))
+                .map_or_else(String::new, |field| field.trim().to_string())
+        };
+
+        for (index, year) in years.iter().enumerate() {
+            if let Some(year) = *year {
+                data.push(EducationData {
+                    country_or_area: field(Some(columns.country)),
+                    year,
+                    indicator: field(columns.indicator),
+                    series: field(columns.series),
+                    value: fields.get(index).and_then(|cell| parse_number(cell)),
+                });
+            }
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source;
+
+    #[test]
+    fn test_year_pattern() {
+        let plain = YearPattern::parse("####").unwrap();
+        assert_eq!(plain.year("2015"), Some(2015));
+        assert_eq!(plain.year("Country"), None);
+        assert_eq!(plain.year("20155"), None);
+
+        let world_bank = YearPattern::parse("YR####*").unwrap();
+        assert_eq!(world_bank.year("YR2010 [YR2010]"), Some(2010));
+        assert!(YearPattern::parse("year").is_err());
+    }
+
+    #[test]
+    fn test_load_wide_melts_year_columns() {
+        let path = std::env::temp_dir().join(format!("ds210-{}-wide.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "Country Name,Series,2010,2015\n\
+             Chad,\"Enrollment, primary\",\"1,200\",\n\
+             Mali,\"Enrollment, primary\",900,950.5\n",
+        )
+        .unwrap();
+        let pattern = YearPattern::parse("####").unwrap();
+        let source = source::open_location(&path.to_string_lossy()).unwrap();
+        let data = load_wide(source.as_ref(), &Layout::default(), &pattern).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(data.len(), 4);
+        assert_eq!(data[0].country_or_area, "Chad");
+        assert_eq!(data[0].series, "Enrollment, primary");
+        assert_eq!((data[0].year, data[0].value), (2010, Some(1200.0)));
+        assert_eq!((data[1].year, data[1].value), (2015, None));
+        assert_eq!(
+            (data[3].country_or_area.as_str(), data[3].value),
+            ("Mali", Some(950.5))
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::io::{self, Write};
+
+use crate::table::Table;
+
+// How a graph's edge weights are spread, to help choose `--min-weight`: a
+// histogram of the weights off the diagonal, and a suggested threshold at
+// the knee of the weights sorted heaviest first. Scaled to a unit square,
+// the sorted weights run from (0, 1) to (1, 0); the knee is the weight
+// furthest from the straight line between the two, where a long tail of
+// weak links sets in (or, for weights that stay high and then fall away,
+// where they fall). Keeping the weights from the knee up drops the tail
+// beyond it.
+
+// The widest bar `write_histogram` draws, in characters.
+const BAR_WIDTH: usize = 40;
+
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    // Equal-width bins from `min` to `max`, the last taking in `max`
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    pub fn new(weights: &[f64], bins: usize) -> Histogram {
+        let min = weights.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = weights.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let bins = bins.max(1);
+        let mut counts = vec![0; bins];
+        if !weights.is_empty() {
+            let width = (max - min) / bins as f64;
+            for &weight in weights {
+                let bin = if width > 0.0 {
+                    ((weight - min) / width) as usize
+                } else {
+                    0
+                };
+                counts[bin.min(bins - 1)] += 1;
+            }
+        }
+        Histogram { min, max, counts }
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    // The start and end of each bin.
+    pub fn bounds(&self) -> Vec<(f64, f64)> {
+        let width = (self.max - self.min) / self.counts.len() as f64;
+        (0..self.counts.len())
+            .map(|bin| {
+                let start = self.min + width * bin as f64;
+                (start, start + width)
+            })
+            .collect()
+    }
+
+    pub fn table(&self) -> Table {
+        let mut table = Table::new(&["bin_start", "bin_end", "count", "share"]);
+        let total = self.total().max(1) as f64;
+        for ((start, end), &count) in self.bounds().into_iter().zip(&self.counts) {
+            table.push_row(vec![
+                format!("{:.6}", start),
+                format!("{:.6}", end),
+                count.to_string(),
+                format!("{:.4}", count as f64 / total),
+            ]);
+        }
+        table
+    }
+}
+
+// The knee of the weights; None for fewer than three, or all alike.
+pub fn suggest_threshold(weights: &[f64]) -> Option<f64> {
+    let mut sorted = weights.to_vec();
+    sorted.sort_by(|a, b| b.total_cmp(a));
+    let (max, min) = (*sorted.first()?, *sorted.last()?);
+    if sorted.len() < 3 || max <= min {
+        return None;
+    }
+    let last = (sorted.len() - 1) as f64;
+    let (knee, distance) = sorted
+        .iter()
+        .enumerate()
+        .map(|(rank, &weight)| {
+            let x = rank as f64 / last;
+            let y = (weight - min) / (max - min);
+            (weight, (1.0 - x - y).abs())
+        })
+        .fold(
+            (max, 0.0),
+            |best, point| if point.1 > best.1 { point } else { best },
+        );
+    (distance > 0.0).then_some(knee)
+}
+
+// The histogram with a bar per bin, and the suggestion if there is one.
+pub fn write_histogram(
+    writer: &mut dyn Write,
+    histogram: &Histogram,
+    weights: &[f64],
+) -> io::Result<()> {
+    if weights.is_empty() {
+        return writeln!(writer, "No edges off the diagonal");
+    }
+    writeln!(
+        writer,
+        "Edge weights: {} edges from {:.4} to {:.4}",
+        weights.len(),
+        histogram.min,
+        histogram.max
+    )?;
+    let tallest = histogram.counts.iter().copied().max().unwrap_or(0).max(1);
+    for ((start, end), &count) in histogram.bounds().into_iter().zip(&histogram.counts) {
+        let bar = "#".repeat((count * BAR_WIDTH).div_ceil(tallest));
+        writeln!(
+            writer,
+            "  {:>10.4} - {:<10.4} {:>6}  {}",
+            start, end, count, bar
+        )?;
+    }
+    match suggest_threshold(weights) {
+        Some(threshold) => {
+            let kept = weights
+                .iter()
+                .filter(|&&weight| weight >= threshold)
+                .count();
+            writeln!(
+                writer,
+                "Suggested --min-weight {:.4} (the knee of the sorted weights), keeping {} of {} edges",
+                threshold,
+                kept,
+                weights.len()
+            )
+        }
+        None => writeln!(writer, "Too few distinct weights to suggest a --min-weight"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_and_knee() {
+        // A few strong links, then a long tail of weak ones
+        let mut weights = vec![0.9, 0.85, 0.8];
+        weights.extend((0..20).map(|i| 0.1 + 0.001 * i as f64));
+        let histogram = Histogram::new(&weights, 4);
+        assert_eq!(histogram.counts, [20, 0, 0, 3]);
+        assert_eq!(histogram.total(), 23);
+        assert!((histogram.bounds()[1].0 - 0.3).abs() < 1e-12);
+        let table = histogram.table();
+        assert_eq!(table.rows[0][2], "20");
+
+        // The knee falls where the tail starts
+        let threshold = suggest_threshold(&weights).unwrap();
+        assert!(threshold > 0.1 && threshold <= 0.8, "{}", threshold);
+        assert_eq!(suggest_threshold(&[1.0, 1.0, 1.0]), None);
+
+        let mut output = Vec::new();
+        write_histogram(&mut output, &histogram, &weights).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("Edge weights: 23 edges from 0.1000 to 0.9000\n"));
+        assert!(output.contains("Suggested --min-weight"), "{}", output);
+    }
+}
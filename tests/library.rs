@@ -0,0 +1,63 @@
+// The pipeline driven through the library API, as another crate would use
+// it, rather than through the binary.
+
+use ds210::cancel::RunStatus;
+use ds210::cluster::{Algorithm, Stop};
+use ds210::source;
+use ds210::{
+    cluster_graph, construct_graph, load_and_preprocess_data, print_clusters, Clustering, Control,
+    Iteration, IterationObserver, Parallelism,
+};
+
+#[test]
+fn test_pipeline_through_the_library() {
+    let input = source::open_location(source::DEMO_LOCATION).unwrap();
+    let data = load_and_preprocess_data(input.as_ref(), &Default::default()).unwrap();
+    assert_eq!(data.len(), 120);
+    assert!(data.iter().all(|record| record.value.is_some()));
+
+    let graph = construct_graph(&data);
+    assert_eq!(graph.nodes.len(), 10);
+    assert_eq!(graph.nodes[0], "Chad");
+    assert!(graph.adjacency_matrix.iter().all(|row| row.len() == 10));
+
+    let warm_start = vec![vec![0, 1, 2], vec![3, 4, 5, 6], vec![7, 8, 9]];
+    let clusters = cluster_graph(&graph, Some(&warm_start));
+    assert!(clusters.validate(&graph).is_ok());
+    let mut report = Vec::new();
+    print_clusters(&mut report, &clusters, &graph).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    assert!(report.starts_with("Cluster 0 ("));
+    assert!(report.contains("  - Sweden\n"));
+}
+
+// An observer of another crate's own, ending agglomeration after one merge.
+struct StopAfterFirst {
+    seen: Vec<usize>,
+}
+
+impl IterationObserver for StopAfterFirst {
+    fn on_iteration(&mut self, iteration: &Iteration) -> Control {
+        self.seen.push(iteration.iteration);
+        Control::Stop(RunStatus::Interrupted)
+    }
+}
+
+#[test]
+fn test_observing_clustering_through_the_library() {
+    let input = source::open_location(source::DEMO_LOCATION).unwrap();
+    let data = load_and_preprocess_data(input.as_ref(), &Default::default()).unwrap();
+    let graph = construct_graph(&data);
+
+    let mut observer = StopAfterFirst { seen: Vec::new() };
+    let clusters = Algorithm::Agglomerative.cluster_observed(
+        &graph,
+        None,
+        Stop::Clusters(1),
+        Parallelism::default(),
+        &mut observer,
+    );
+    assert_eq!(observer.seen, [1]);
+    // Ten singletons, one merge in
+    assert_eq!(clusters.len(), 9);
+}
@@ -0,0 +1,480 @@
+// End-to-end runs of the `ds210` binary: load -> build -> cluster -> export
+// against the fixture CSVs in tests/fixtures (one of them cut from the UN
+// SYB education file), checking the JSON reports and summaries and the
+// manifests written along the way.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use ds210::json::Json;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/education.csv");
+const TITLED: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/titled.csv");
+// The rows of SYB66_309_202310_Education.csv for 2010 and 2015 for five
+// countries and the world total, title and header rows included
+const SYB: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/SYB66_309_202310_Education.csv"
+);
+
+// A scratch directory per test, removed when the test is done.
+struct Workdir(PathBuf);
+
+impl Workdir {
+    fn new(name: &str) -> Workdir {
+        let path = std::env::temp_dir().join(format!("ds210-it-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Workdir(path)
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.0.join(name).to_str().unwrap().to_string()
+    }
+
+    fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.0.join(name)).unwrap()
+    }
+}
+
+impl Drop for Workdir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn ds210(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ds210"))
+        .args(args)
+        .env_remove("DS210_NOTIFY_URL")
+        .env_remove("DS210_DONE_MARKER")
+        .output()
+        .unwrap()
+}
+
+// Run a command that must succeed, returning its stdout.
+fn ok(args: &[&str]) -> String {
+    let output = ds210(args);
+    assert!(
+        output.status.success(),
+        "ds210 {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// Run a command that must succeed and print JSON, returning the document.
+fn json(args: &[&str]) -> Json {
+    Json::parse(&ok(args)).unwrap()
+}
+
+// A number field of a JSON object.
+fn number(document: &Json, key: &str) -> f64 {
+    document
+        .get(key)
+        .and_then(Json::as_f64)
+        .unwrap_or_else(|| panic!("no {} in {:?}", key, document))
+}
+
+// The weight of the edge from `source` to `target` in a `--format json`
+// report; absent edges weigh 0.
+fn weight(report: &Json, source: &str, target: &str) -> f64 {
+    let edges = report.get("edges").and_then(Json::as_array).unwrap();
+    edges
+        .iter()
+        .find(|edge| {
+            edge.get("source").and_then(Json::as_str) == Some(source)
+                && edge.get("target").and_then(Json::as_str) == Some(target)
+        })
+        .map_or(0.0, |edge| number(edge, "weight"))
+}
+
+fn names(list: &Json) -> Vec<String> {
+    list.as_array()
+        .unwrap()
+        .iter()
+        .map(|name| name.as_str().unwrap().to_string())
+        .collect()
+}
+
+// Each cluster's members in a `--format json` report.
+fn members(report: &Json) -> Vec<Vec<String>> {
+    let clusters = report.get("clusters").and_then(Json::as_array).unwrap();
+    clusters
+        .iter()
+        .map(|cluster| names(cluster.get("members").unwrap()))
+        .collect()
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!(
+        (actual - expected).abs() < 1e-6,
+        "expected {}, got {}",
+        expected,
+        actual
+    );
+}
+
+// A clustering artifact in the on-disk format (magic, version, then each
+// cluster's member names), for warm-starting `cluster`.
+fn write_clustering(path: &str, clusters: &[&[&str]]) {
+    let mut bytes = b"DS210CLU".to_vec();
+    bytes.extend(2u32.to_le_bytes());
+    bytes.extend((clusters.len() as u64).to_le_bytes());
+    for cluster in clusters {
+        bytes.extend((cluster.len() as u64).to_le_bytes());
+        for name in *cluster {
+            bytes.extend((name.len() as u64).to_le_bytes());
+            bytes.extend(name.as_bytes());
+        }
+    }
+    fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn test_staged_pipeline() {
+    let dir = Workdir::new("staged");
+    let (dataset, graph) = (dir.path("education.bin"), dir.path("graph.bin"));
+    let (warm, clusters) = (dir.path("warm.bin"), dir.path("clusters.bin"));
+    let report = dir.path("report.json");
+
+    // Progress goes to stderr, leaving stdout for reports
+    let loaded = ds210(&["load", "--input", FIXTURE, "--save", &dataset]);
+    assert!(loaded.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&loaded.stderr).contains("Loaded 5 records"));
+    ok(&[
+        "build",
+        "--from",
+        &dataset,
+        "--value-graph",
+        "--save",
+        &graph,
+    ]);
+    write_clustering(&warm, &[&["Chad", "Mali"], &["Niger"]]);
+    ok(&[
+        "cluster", "--graph", &graph, "--init", &warm, "--save", &clusters,
+    ]);
+    ok(&[
+        "export",
+        "--graph",
+        &graph,
+        "--clusters",
+        &clusters,
+        "--format",
+        "json",
+        "--output",
+        &report,
+    ]);
+
+    // In the value graph each record adds its value across its country's
+    // row; the missing value adds nothing
+    let report = Json::parse(&dir.read("report.json")).unwrap();
+    let countries = ["Chad", "Mali", "Niger"];
+    assert_eq!(names(report.get("nodes").unwrap()), countries);
+    let expected = [[15.0, 5.0, 5.0], [20.0, 20.0, 0.0], [30.0, 30.0, 30.0]];
+    for (source, expected) in countries.iter().zip(&expected) {
+        for (target, &expected) in countries.iter().zip(expected) {
+            assert_close(weight(&report, source, target), expected);
+        }
+    }
+    assert_eq!(members(&report), [vec!["Chad", "Mali"], vec!["Niger"]]);
+
+    let analyze = ["analyze", "--graph", &graph, "--clusters", &clusters];
+    let stats = json(&[&analyze[..], &["--format", "json"]].concat());
+    assert_close(number(&stats, "nodes"), 3.0);
+    assert_close(number(&stats, "edges"), 5.0);
+    assert_close(number(&stats, "density"), 5.0 / 6.0);
+    let weights = stats.get("edge_weight").unwrap();
+    assert_close(number(weights, "min"), 5.0);
+    assert_close(number(weights, "mean"), 90.0 / 5.0);
+    assert_close(number(weights, "max"), 30.0);
+    assert_eq!(
+        stats
+            .get("clusters")
+            .and_then(Json::as_array)
+            .unwrap()
+            .len(),
+        2
+    );
+
+    // Without a warm start, average linkage pairs Chad with Niger
+    ok(&[
+        "cluster", "--graph", &graph, "--cutoff", "16", "--save", &clusters,
+    ]);
+    let stats = json(&[&analyze[..], &["--format", "json"]].concat());
+    assert_eq!(
+        stats
+            .get("clusters")
+            .and_then(Json::as_array)
+            .unwrap()
+            .len(),
+        2
+    );
+    ok(&[
+        "cluster",
+        "--graph",
+        &graph,
+        "--clusters",
+        "1",
+        "--save",
+        &clusters,
+    ]);
+    let stats = json(&[&analyze[..], &["--format", "json"]].concat());
+    assert_eq!(
+        stats
+            .get("clusters")
+            .and_then(Json::as_array)
+            .unwrap()
+            .len(),
+        1
+    );
+    assert_close(number(&stats, "modularity"), 0.0);
+
+    // Louvain settles on its own number of communities
+    ok(&[
+        "cluster", "--graph", &graph, "--algo", "louvain", "--save", &clusters,
+    ]);
+    let stats = json(&[&analyze[..], &["--format", "json"]].concat());
+    assert!(number(&stats, "modularity") >= 0.0);
+
+    // The text summary, written to a file instead
+    let summary = dir.path("summary.txt");
+    let printed = ok(&[
+        "analyze",
+        "--graph",
+        &graph,
+        "--clusters",
+        &clusters,
+        "--output",
+        &summary,
+    ]);
+    assert!(printed.is_empty());
+    assert_eq!(dir.read("summary.txt"), ok(&analyze));
+}
+
+#[test]
+fn test_manifests_trace_each_stage() {
+    let dir = Workdir::new("manifests");
+    let (dataset, graph) = (dir.path("education.bin"), dir.path("graph.bin"));
+    let (clusters, report) = (dir.path("clusters.bin"), dir.path("report.txt"));
+    ok(&["load", "--input", FIXTURE, "--save", &dataset]);
+    ok(&["build", "--from", &dataset, "--save", &graph]);
+    ok(&["cluster", "--graph", &graph, "--save", &clusters]);
+    ok(&[
+        "export",
+        "--graph",
+        &graph,
+        "--clusters",
+        &clusters,
+        "--output",
+        &report,
+    ]);
+
+    let manifest = dir.read("report.txt.manifest.json");
+    assert!(manifest.starts_with('{') && manifest.trim_end().ends_with('}'));
+    assert!(manifest.contains("\"command\": \"export\""));
+    assert!(manifest.contains("\"status\": \"completed\""));
+    assert!(manifest.contains(&format!("\"path\": \"{}\"", report)));
+
+    // The lineage lists every step, oldest first, ending with this one
+    let lineage = &manifest[manifest.find("\"lineage\"").unwrap()..];
+    let steps: Vec<usize> = ["load", "build", "cluster", "export"]
+        .iter()
+        .map(|command| {
+            lineage
+                .find(&format!("\"command\": \"{}\"", command))
+                .unwrap_or_else(|| panic!("no {} step in {}", command, lineage))
+        })
+        .collect();
+    assert!(
+        steps.windows(2).all(|pair| pair[0] < pair[1]),
+        "{:?}",
+        steps
+    );
+    assert!(lineage.contains(&format!("\"path\": \"{}\"", FIXTURE)));
+}
+
+#[test]
+fn test_run_in_one_go() {
+    let dir = Workdir::new("run");
+    let report = dir.path("report.json");
+    ok(&[
+        "run",
+        "--input",
+        TITLED,
+        "--skip-rows",
+        "1",
+        "--value-graph",
+        "--format",
+        "json",
+        "--output",
+        &report,
+    ]);
+    let report = Json::parse(&dir.read("report.json")).unwrap();
+    assert_eq!(names(report.get("nodes").unwrap()), ["Chad", "Mali"]);
+    assert_close(weight(&report, "Chad", "Chad"), 10.0);
+    assert_close(weight(&report, "Mali", "Mali"), 20.0);
+
+    // The built-in sample: ten countries, every edge between them named
+    let report = json(&["run", "--demo", "--format", "json"]);
+    let countries = names(report.get("nodes").unwrap());
+    assert_eq!(countries.len(), 10);
+    let edges = report.get("edges").and_then(Json::as_array).unwrap();
+    assert!(edges.iter().all(|edge| {
+        let source = edge.get("source").and_then(Json::as_str).unwrap();
+        countries.iter().any(|country| country == source)
+    }));
+    let clustered: usize = members(&report).iter().map(Vec::len).sum();
+    assert_eq!(clustered, 10);
+    // The report traces the similarity of every merge that got it there
+    let merges = report
+        .get("convergence")
+        .unwrap()
+        .get("agglomerative")
+        .unwrap();
+    let merged = merges.get("iterations").and_then(Json::as_f64).unwrap() as usize;
+    assert_eq!(merged + members(&report).len(), 10);
+    let modularity = number(&report, "modularity");
+    assert!((-0.5..=1.0).contains(&modularity), "{}", modularity);
+
+    // Light edges can be dropped and the report written as a page
+    let report = json(&[
+        "run",
+        "--input",
+        FIXTURE,
+        "--value-graph",
+        "--min-weight",
+        "10",
+        "--algo",
+        "passthrough",
+        "--format",
+        "json",
+    ]);
+    assert_close(weight(&report, "Chad", "Mali"), 0.0);
+    assert_close(weight(&report, "Mali", "Chad"), 20.0);
+    // Keeping only those pairs while building gives the same report
+    let pruned = ok(&["run", "--demo", "--min-weight", "0.9", "--format", "json"]);
+    let sparse = ok(&[
+        "run",
+        "--demo",
+        "--min-weight",
+        "0.9",
+        "--sparse",
+        "--format",
+        "json",
+    ]);
+    assert_eq!(sparse, pruned);
+    let output = ds210(&["run", "--demo", "--sparse"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--min-weight"));
+    let page = ok(&["run", "--demo", "--format", "html"]);
+    assert!(page.starts_with("<!DOCTYPE html>"), "{}", &page[..40]);
+    // The similarity graph is symmetric, so its edges are undirected
+    let dot = ok(&["run", "--demo", "--format", "dot"]);
+    assert!(dot.starts_with("graph countries {"), "{}", dot);
+    assert!(dot.contains("\"Chad\" [fillcolor="));
+
+    // There is no default input any more
+    let output = ds210(&["run"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--input"));
+}
+
+#[test]
+fn test_syb_extract() {
+    // The title row is passed over and the columns found by name; the
+    // world total loads like any other row until aggregates are excluded
+    let dir = Workdir::new("syb");
+    let dataset = dir.path("education.bin");
+    let loaded = ds210(&[
+        "load",
+        "--input",
+        SYB,
+        "--skip-rows",
+        "1",
+        "--save",
+        &dataset,
+    ]);
+    assert!(String::from_utf8_lossy(&loaded.stderr).contains("Loaded 108 records"));
+    let report = json(&[
+        "run",
+        "--input",
+        SYB,
+        "--skip-rows",
+        "1",
+        "--format",
+        "json",
+    ]);
+    let countries = names(report.get("nodes").unwrap());
+    assert_eq!(countries.len(), 6);
+    assert!(countries.contains(&"Total, all countries or areas".to_string()));
+
+    // Compared on their latest enrolment ratios, the Sahel countries and
+    // the European ones fall apart
+    let report = json(&[
+        "run",
+        "--input",
+        SYB,
+        "--skip-rows",
+        "1",
+        "--aggregates",
+        "exclude",
+        "--similarity",
+        "cosine",
+        "--clusters",
+        "2",
+        "--format",
+        "json",
+    ]);
+    assert_eq!(
+        names(report.get("nodes").unwrap()),
+        ["Chad", "France", "Mali", "Niger", "Sweden"]
+    );
+    assert_eq!(
+        members(&report),
+        [vec!["Chad", "Mali", "Niger"], vec!["France", "Sweden"]]
+    );
+    let (near, far) = (
+        weight(&report, "France", "Sweden"),
+        weight(&report, "Chad", "Sweden"),
+    );
+    assert!(near <= 1.0 && far < near, "{} {}", near, far);
+    assert_close(weight(&report, "Sweden", "France"), near);
+}
+
+#[test]
+fn test_filters_and_errors() {
+    let dir = Workdir::new("errors");
+    let graph = dir.path("graph.bin");
+    ok(&[
+        "build",
+        "--from",
+        FIXTURE,
+        "--where",
+        "year >= 2010",
+        "--value-graph",
+        "--save",
+        &graph,
+    ]);
+    let stats = json(&["analyze", "--graph", &graph, "--format", "json"]);
+    assert_close(number(&stats, "nodes"), 2.0);
+    assert_close(number(&stats, "edges"), 0.0);
+
+    // A dataset is not a graph, and a missing input is reported
+    let output = ds210(&["analyze", "--graph", FIXTURE]);
+    assert!(!output.status.success());
+    let output = ds210(&["load", "--input", "/nonexistent.csv", "--save", &graph]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Error: "));
+    assert!(Path::new(&graph).exists());
+
+    // A clustering naming a country twice is refused rather than exported
+    let clusters = dir.path("clusters.bin");
+    write_clustering(&clusters, &[&["Chad"], &["Mali", "Chad"]]);
+    let output = ds210(&["export", "--graph", &graph, "--clusters", &clusters]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("node Chad appears in cluster 0 and again in cluster 1"));
+}